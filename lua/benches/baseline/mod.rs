@@ -0,0 +1,142 @@
+// 基准对比用：改为 Lua 式的表之前的实现（数组部分加按插入顺序排列的节点与 HashMap 索引），只保留基准测试用到的操作
+
+use lua::value::Value;
+use std::collections::HashMap;
+
+/// 原来的表：数组部分与哈希部分
+/// - `array`: 键为 1..=n 的连续整数部分
+/// - `nodes`: 哈希部分的键值对，按插入顺序排列；值为 nil 的是已删除的键，保留位置使遍历（next）不受删除影响
+/// - `map`: 哈希部分的键在 nodes 中的位置
+/// - `ndead`: nodes 中已删除的键的个数，过多时在插入新键前整理
+pub struct Table {
+    array: Vec<Value>,
+    nodes: Vec<(Value, Value)>,
+    map: HashMap<Value, usize>,
+    ndead: usize,
+}
+
+impl Table {
+    /// 创建新表，预分配数组部分与哈希部分
+    pub fn new(narray: usize, nmap: usize) -> Self {
+        Table {
+            array: Vec::with_capacity(narray),
+            nodes: Vec::with_capacity(nmap),
+            map: HashMap::with_capacity(nmap),
+            ndead: 0,
+        }
+    }
+
+    /// 按键读取：整数键 1..=len 走数组部分，其余走哈希部分
+    pub fn get(&self, key: &Value) -> Value {
+        if let Value::Integer(i) = key {
+            if *i >= 1 && (*i as usize) <= self.array.len() {
+                return self.array[*i as usize - 1].clone();
+            }
+        }
+        match self.map.get(key) {
+            Some(&i) => self.nodes[i].1.clone(),
+            None => Value::Nil,
+        }
+    }
+
+    /// 按键写入：紧接数组末尾的整数键追加到数组部分，赋值 nil 即删除
+    pub fn set(&mut self, key: Value, value: Value) {
+        if let Value::Integer(i) = key {
+            if i >= 1 && (i as usize) <= self.array.len() {
+                self.array[i as usize - 1] = value;
+                return;
+            }
+            if i as usize == self.array.len() + 1 && value != Value::Nil {
+                self.array.push(value);
+                // 哈希部分中紧随其后的整数键迁移到数组部分
+                while let Some(v) = self.take(&Value::Integer(self.array.len() as i64 + 1)) {
+                    self.array.push(v);
+                }
+                return;
+            }
+        }
+        match self.map.get(&key) {
+            Some(&i) => {
+                let old = std::mem::replace(&mut self.nodes[i].1, value);
+                match (old == Value::Nil, self.nodes[i].1 == Value::Nil) {
+                    (false, true) => self.ndead += 1,
+                    (true, false) => self.ndead -= 1,
+                    _ => (),
+                }
+            }
+            None if value == Value::Nil => (),
+            None => {
+                if self.ndead > 0 && self.ndead * 2 >= self.nodes.len() {
+                    self.compact();
+                }
+                self.map.insert(key.clone(), self.nodes.len());
+                self.nodes.push((key, value));
+            }
+        }
+    }
+
+    /// 从哈希部分删除键，返回原来的值
+    fn take(&mut self, key: &Value) -> Option<Value> {
+        let &i = self.map.get(key)?;
+        let old = std::mem::replace(&mut self.nodes[i].1, Value::Nil);
+        if old == Value::Nil {
+            return None;
+        }
+        self.ndead += 1;
+        Some(old)
+    }
+
+    /// 整理哈希部分：去掉已删除的键（插入新键时才进行，遍历中插入新键的行为本来就是未定义的）
+    fn compact(&mut self) {
+        self.nodes.retain(|(_, v)| *v != Value::Nil);
+        self.map.clear();
+        for (i, (k, _)) in self.nodes.iter().enumerate() {
+            self.map.insert(k.clone(), i);
+        }
+        self.ndead = 0;
+    }
+
+    /// 遍历：返回 key 之后的下一个键值对，key 为 nil 时返回第一个，遍历结束返回 Ok(None)
+    /// 先按顺序遍历数组部分，再按插入顺序遍历哈希部分；key 不在表中时返回错误信息
+    pub fn next(&self, key: &Value) -> Result<Option<(Value, Value)>, &'static str> {
+        // 确定从数组部分还是哈希部分的哪个位置开始查找
+        let (array_from, node_from) = match key {
+            Value::Nil => (0, 0),
+            Value::Integer(i) if *i >= 1 && (*i as usize) <= self.array.len() => (*i as usize, 0),
+            _ => match self.map.get(key) {
+                Some(&i) => (self.array.len(), i + 1),
+                None => return Err("invalid key to 'next'"),
+            },
+        };
+        for (i, v) in self.array.iter().enumerate().skip(array_from) {
+            if *v != Value::Nil {
+                return Ok(Some((Value::Integer(i as i64 + 1), v.clone())));
+            }
+        }
+        Ok(self.nodes[node_from..].iter().find(|(_, v)| *v != Value::Nil).cloned())
+    }
+
+    /// 取长度（边界）：返回某个 n，使 t[n] 非 nil 且 t[n+1] 为 nil（表为空时为 0）
+    pub fn border(&self) -> usize {
+        let n = self.array.len();
+        if n > 0 && self.array[n - 1] == Value::Nil {
+            // 数组部分末尾是 nil：二分查找其中的边界
+            let (mut lo, mut hi) = (0, n);
+            while hi - lo > 1 {
+                let mid = (lo + hi) / 2;
+                if self.array[mid - 1] == Value::Nil {
+                    hi = mid;
+                } else {
+                    lo = mid;
+                }
+            }
+            return lo;
+        }
+        // 数组部分已满：继续在哈希部分中查找后续的整数键
+        let mut n = n;
+        while self.get(&Value::Integer(n as i64 + 1)) != Value::Nil {
+            n += 1;
+        }
+        n
+    }
+}
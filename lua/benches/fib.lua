@@ -0,0 +1,10 @@
+-- 递归调用：朴素的斐波那契数列
+local function fib(n)
+    return n < 2 and n or fib(n - 1) + fib(n - 2)
+end
+
+local s = 0
+for i = 1, 3 do
+    s = s + fib(27)
+end
+print(s)
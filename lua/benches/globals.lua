@@ -0,0 +1,7 @@
+-- 全局变量的读写：循环中每次迭代读两个、写一个全局变量
+count = 0
+step = 1
+for _ = 1, 3000000 do
+    count = count + step
+end
+print(count)
@@ -0,0 +1,45 @@
+// 解释器基准测试：cargo bench -p lua
+// 依次运行 benches 目录下的 Lua 脚本，每个脚本运行数次，打印最短与平均用时
+// - fib.lua：函数调用与整数运算
+// - nbody.lua：浮点运算、表字段访问与数值 for 循环
+// - table_churn.lua：表的创建、整数键读写与全局变量访问
+// - globals.lua：全局变量的读写
+// 可以在命令行中给出脚本名的一部分，只运行匹配的脚本
+
+use lua::vm::ExeState;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// 每个脚本的运行次数
+const RUNS: u32 = 5;
+
+const SCRIPTS: &[&str] = &["fib", "nbody", "table_churn", "globals"];
+
+/// 运行一次脚本，返回用时（不含创建虚拟机）
+fn run_once(path: &Path) -> Duration {
+    let mut state = ExeState::new();
+    let input = BufReader::new(File::open(path).unwrap());
+    let start = Instant::now();
+    let main = state.load(input, &path.display().to_string()).unwrap();
+    state.call(main, Vec::new()).unwrap();
+    start.elapsed()
+}
+
+fn main() {
+    // cargo bench 会传入 --bench 等参数
+    let filters: Vec<String> = env::args().skip(1).filter(|a| !a.starts_with("--")).collect();
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("benches");
+    for name in SCRIPTS {
+        if !filters.is_empty() && !filters.iter().any(|f| name.contains(f.as_str())) {
+            continue;
+        }
+        let path = dir.join(format!("{name}.lua"));
+        let times: Vec<Duration> = (0..RUNS).map(|_| run_once(&path)).collect();
+        let min = times.iter().min().unwrap();
+        let mean = times.iter().sum::<Duration>() / RUNS;
+        eprintln!("{name:<12} min {:>9.2?}  mean {:>9.2?}", min, mean);
+    }
+}
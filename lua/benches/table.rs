@@ -0,0 +1,209 @@
+// 表的基准测试：cargo bench -p lua --bench table
+// 比较 Lua 式的表（lua::value::Table）与原来的数组加 HashMap 的实现（baseline::Table），
+// 每个场景运行数次，打印两者的最短与平均用时
+// 可以在命令行中给出场景名的一部分，只运行匹配的场景
+//
+// 换成 Lua 式的表时的结果（release，5 次中最短用时，N = 200000）：
+//   场景       lua       baseline
+//   append     9.07ms    6.57ms     连续追加整数键：多出按 2 的幂重新计算数组部分大小的开销
+//   hinted     8.20ms   15.50ms     预先给出数组大小
+//   reverse   26.57ms   90.14ms     整数键倒序写入，先进入哈希部分再在扩容时移入数组部分
+//   sparse    70.42ms   99.75ms     稀疏的整数键
+//   strings   40.03ms   55.12ms     字符串键
+//   churn    106.76ms  124.71ms     反复插入、删除
+//   traverse 110.11ms  124.82ms     用 next 遍历
+// 只有连续追加变慢；其余场景，尤其是非顺序写入整数键，都明显变快
+
+mod baseline;
+
+use lua::value::{Table, Value};
+use std::env;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// 每个场景的运行次数
+const RUNS: u32 = 5;
+/// 每个场景操作的键数
+const N: i64 = 200_000;
+
+/// 两种表共同的操作
+trait Bench {
+    const NAME: &'static str;
+    fn new(narray: usize, nmap: usize) -> Self;
+    fn get(&self, key: &Value) -> Value;
+    fn set(&mut self, key: Value, value: Value);
+    fn next(&self, key: &Value) -> Option<(Value, Value)>;
+    fn border(&self) -> usize;
+}
+
+impl Bench for Table {
+    const NAME: &'static str = "lua";
+    fn new(narray: usize, nmap: usize) -> Self {
+        Table::new(narray, nmap)
+    }
+    fn get(&self, key: &Value) -> Value {
+        Table::get(self, key)
+    }
+    fn set(&mut self, key: Value, value: Value) {
+        Table::set(self, key, value)
+    }
+    fn next(&self, key: &Value) -> Option<(Value, Value)> {
+        Table::next(self, key).unwrap()
+    }
+    fn border(&self) -> usize {
+        Table::border(self)
+    }
+}
+
+impl Bench for baseline::Table {
+    const NAME: &'static str = "baseline";
+    fn new(narray: usize, nmap: usize) -> Self {
+        baseline::Table::new(narray, nmap)
+    }
+    fn get(&self, key: &Value) -> Value {
+        baseline::Table::get(self, key)
+    }
+    fn set(&mut self, key: Value, value: Value) {
+        baseline::Table::set(self, key, value)
+    }
+    fn next(&self, key: &Value) -> Option<(Value, Value)> {
+        baseline::Table::next(self, key).unwrap()
+    }
+    fn border(&self) -> usize {
+        baseline::Table::border(self)
+    }
+}
+
+/// 依次追加整数键 1..=N，再逐个读取
+fn append<T: Bench>() {
+    let mut t = T::new(0, 0);
+    for i in 1..=N {
+        t.set(Value::Integer(i), Value::Integer(i));
+    }
+    for i in 1..=N {
+        black_box(t.get(&Value::Integer(i)));
+    }
+    assert_eq!(t.border(), N as usize);
+}
+
+/// 按构造器给出的大小提示预先分配后填充
+fn hinted<T: Bench>() {
+    for _ in 0..N / 100 {
+        let mut t = T::new(50, 50);
+        for i in 1..=50 {
+            t.set(Value::Integer(i), Value::Integer(i));
+            t.set(Value::Integer(-i), Value::Integer(i));
+        }
+        black_box(&t);
+    }
+}
+
+/// 倒序填充整数键：先进入哈希部分，数组部分靠 rehash 才能用上
+fn reverse<T: Bench>() {
+    let mut t = T::new(0, 0);
+    for i in (1..=N).rev() {
+        t.set(Value::Integer(i), Value::Integer(i));
+    }
+    let mut sum = 0;
+    for i in 1..=N {
+        if let Value::Integer(v) = t.get(&Value::Integer(i)) {
+            sum += v;
+        }
+    }
+    assert_eq!(sum, N * (N + 1) / 2);
+}
+
+/// 稀疏的整数键
+fn sparse<T: Bench>() {
+    let mut t = T::new(0, 0);
+    for i in 0..N {
+        t.set(Value::Integer(i * 7919), Value::Integer(i));
+    }
+    for i in 0..N {
+        black_box(t.get(&Value::Integer(i * 7919)));
+    }
+}
+
+/// 字符串键的插入与读取
+fn strings<T: Bench>() {
+    let keys: Vec<Value> = (0..N / 4).map(|i| Value::from(format!("key{i}"))).collect();
+    let mut t = T::new(0, 0);
+    for (i, k) in keys.iter().enumerate() {
+        t.set(k.clone(), Value::Integer(i as i64));
+    }
+    for _ in 0..4 {
+        for k in &keys {
+            black_box(t.get(k));
+        }
+    }
+}
+
+/// 反复插入与删除：一个滑动窗口中的键
+fn churn<T: Bench>() {
+    let mut t = T::new(0, 0);
+    for i in 0..N {
+        t.set(Value::from(format!("k{}", i % 1000)), Value::Integer(i));
+        t.set(Value::Integer(i + 1_000_000), Value::Integer(i));
+        if i >= 100 {
+            t.set(Value::Integer(i - 100 + 1_000_000), Value::Nil);
+        }
+    }
+    black_box(&t);
+}
+
+/// 用 next 遍历整张表
+fn traverse<T: Bench>() {
+    let mut t = T::new(0, 0);
+    for i in 1..=N / 2 {
+        t.set(Value::Integer(i), Value::Integer(i));
+        t.set(Value::Float(i as f64 + 0.5), Value::Integer(i));
+    }
+    for _ in 0..4 {
+        let mut key = Value::Nil;
+        let mut count = 0;
+        while let Some((k, _)) = t.next(&key) {
+            key = k;
+            count += 1;
+        }
+        assert_eq!(count, N);
+    }
+}
+
+/// 场景名与两种表的实现
+type Case = (&'static str, fn(), fn());
+
+const CASES: &[Case] = &[
+    ("append", append::<Table>, append::<baseline::Table>),
+    ("hinted", hinted::<Table>, hinted::<baseline::Table>),
+    ("reverse", reverse::<Table>, reverse::<baseline::Table>),
+    ("sparse", sparse::<Table>, sparse::<baseline::Table>),
+    ("strings", strings::<Table>, strings::<baseline::Table>),
+    ("churn", churn::<Table>, churn::<baseline::Table>),
+    ("traverse", traverse::<Table>, traverse::<baseline::Table>),
+];
+
+/// 运行数次，打印最短与平均用时
+fn measure(name: &str, imp: &str, f: fn()) {
+    let times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect();
+    let min = times.iter().min().unwrap();
+    let mean = times.iter().sum::<Duration>() / RUNS;
+    eprintln!("{name:<10} {imp:<9} min {:>9.2?}  mean {:>9.2?}", min, mean);
+}
+
+fn main() {
+    // cargo bench 会传入 --bench 等参数
+    let filters: Vec<String> = env::args().skip(1).filter(|a| !a.starts_with("--")).collect();
+    for (name, lua, base) in CASES {
+        if !filters.is_empty() && !filters.iter().any(|f| name.contains(f.as_str())) {
+            continue;
+        }
+        measure(name, <Table as Bench>::NAME, *lua);
+        measure(name, <baseline::Table as Bench>::NAME, *base);
+    }
+}
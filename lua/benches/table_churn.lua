@@ -0,0 +1,23 @@
+-- 表的创建、整数键读写与全局变量访问
+N = 200
+
+local function fill(n)
+    local t = {}
+    for i = 1, n do
+        t[i] = i
+    end
+    return t
+end
+
+local sum = 0
+for _ = 1, 2000 do
+    local t = fill(N)
+    for i = 1, N do
+        t[i] = t[i] * 2
+    end
+    sum = sum + t[1] + t[N] + #t
+    local p = { x = 1, y = 2 }
+    p.x = p.x + p.y
+    sum = sum + p.x
+end
+print(sum)
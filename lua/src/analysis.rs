@@ -0,0 +1,556 @@
+// 源代码的语义索引，供语言服务器等编辑器工具使用
+// 在 AST 上按作用域解析变量名，记录：
+// - 定义：局部变量、参数、局部函数、循环变量，以及在本文件中赋值的全局变量，含名字的位置与可见范围
+// - 引用：每个变量名表达式及其对应的定义（全局变量没有局部定义时指向本文件中第一次赋值处）
+// - require("name") 调用中的模块名与字面量的推断类型
+// - 大纲：局部变量与函数组成的树
+// AST 中的名字没有单独的位置，定义处的名字在所在语句的范围内按词查找（跳过注释）
+
+use crate::ast::{self, BinOp, Block, Exp, ExpKind, FieldKind, FuncBody, Stat, StatKind, UnOp};
+use crate::lex::{ParseError, Span};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// 定义的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefKind {
+    Local,
+    Param,
+    LocalFunction,
+    Loop,
+    Global,
+}
+
+/// 变量的定义
+/// - `span`: 名字在源代码中的范围
+/// - `scope`: 可见范围的起止字节偏移，全局变量为整个文件
+/// - `ty`: 由初始值推断的类型
+/// - `detail`: 函数的签名，如 "function f(a, b)"
+/// - `require`: 初始值为 require("name") 时的模块名
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefKind,
+    pub span: Span,
+    pub scope: (usize, usize),
+    pub ty: Option<&'static str>,
+    pub detail: Option<String>,
+    pub require: Option<String>,
+}
+
+/// 变量名表达式，def 为其定义在 Index::defs 中的位置，找不到定义时为 None
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub span: Span,
+    pub def: Option<usize>,
+}
+
+/// 大纲中的一项：局部变量或函数，children 为函数体中的项
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub is_function: bool,
+    pub span: Span,
+    pub name_span: Span,
+    pub children: Vec<Symbol>,
+}
+
+/// 一个文件的语义索引
+/// - `requires`: require 的模块名与其字面量参数的范围
+/// - `literals`: 字面量以及结果类型确定的运算（如连接、比较）的范围与类型
+#[derive(Debug, Default)]
+pub struct Index {
+    pub defs: Vec<Definition>,
+    pub refs: Vec<Reference>,
+    pub requires: Vec<(String, Span)>,
+    pub literals: Vec<(Span, &'static str)>,
+    pub symbols: Vec<Symbol>,
+}
+
+impl Index {
+    /// 位于 offset 处的变量引用
+    pub fn reference_at(&self, offset: usize) -> Option<&Reference> {
+        self.refs.iter().find(|r| contains(r.span, offset))
+    }
+
+    /// 名字位于 offset 处的定义
+    pub fn definition_at(&self, offset: usize) -> Option<(usize, &Definition)> {
+        self.defs.iter().enumerate().find(|(_, d)| contains(d.span, offset))
+    }
+
+    /// offset 处可见的变量定义，同名的只保留最内层的
+    pub fn visible_at(&self, offset: usize) -> Vec<&Definition> {
+        let mut visible: Vec<&Definition> = Vec::new();
+        for def in self.defs.iter().filter(|d| d.scope.0 <= offset && offset <= d.scope.1) {
+            match visible.iter_mut().find(|d| d.name == def.name) {
+                // 后声明的局部定义在内层或遮蔽前面的同名定义，全局定义总是被局部定义遮蔽
+                Some(d) if def.kind != DefKind::Global => *d = def,
+                Some(_) => (),
+                None => visible.push(def),
+            }
+        }
+        visible
+    }
+
+    /// 位于 offset 处的 require 模块名
+    pub fn require_at(&self, offset: usize) -> Option<&(String, Span)> {
+        self.requires.iter().find(|(_, span)| contains(*span, offset))
+    }
+
+    /// 包含 offset 的最内层的字面量
+    pub fn literal_at(&self, offset: usize) -> Option<&(Span, &'static str)> {
+        self.literals
+            .iter()
+            .filter(|(span, _)| contains(*span, offset))
+            .min_by_key(|(span, _)| span.end - span.start)
+    }
+}
+
+/// offset 是否在范围内（包括紧接在末尾之后的位置，即光标在名字末尾时）
+fn contains(span: Span, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+/// 解析源代码并建立索引；有语法错误时返回错误
+pub fn index(source: &str) -> Result<Index, ParseError> {
+    let (block, comments) = ast::parse_with_comments(Cursor::new(source.as_bytes()))?;
+    let mut indexer = Indexer {
+        source: source.as_bytes(),
+        comments,
+        index: Index::default(),
+        scopes: Vec::new(),
+        globals: HashMap::new(),
+        symbols: vec![Vec::new()],
+    };
+    indexer.scoped(source.len(), |ix| ix.block(&block));
+    let mut index = indexer.index;
+    // 没有局部定义的名字指向本文件中的全局定义
+    let globals = indexer.globals;
+    for r in index.refs.iter_mut().filter(|r| r.def.is_none()) {
+        r.def = globals.get(&r.name).copied();
+    }
+    index.symbols = indexer.symbols.pop().unwrap();
+    Ok(index)
+}
+
+/// 建立索引的状态
+/// - `scopes`: 由外向内的各层作用域的结束位置与其中的定义
+/// - `globals`: 全局变量名到其第一次赋值处的定义
+/// - `symbols`: 由外向内的各层函数中已收集的大纲项
+struct Indexer<'a> {
+    source: &'a [u8],
+    comments: Vec<Span>,
+    index: Index,
+    scopes: Vec<(usize, Vec<usize>)>,
+    globals: HashMap<String, usize>,
+    symbols: Vec<Vec<Symbol>>,
+}
+
+impl Indexer<'_> {
+    /// 在结束于 end 的新作用域中执行 f
+    fn scoped(&mut self, end: usize, f: impl FnOnce(&mut Self)) {
+        self.scopes.push((end, Vec::new()));
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|(_, defs)| defs.iter().rev().copied().find(|&d| self.index.defs[d].name == name))
+    }
+
+    /// 从 from 开始查找作为独立单词出现的 name，跳过注释；找不到时为 from 处的空范围
+    fn name_span(&self, name: &str, from: usize) -> Span {
+        let s = self.source;
+        let is_word = |c: u8| c == b'_' || c.is_ascii_alphanumeric();
+        let mut i = from;
+        while let Some(pos) = find_bytes(&s[i.min(s.len())..], name.as_bytes()) {
+            let start = i + pos;
+            let end = start + name.len();
+            let in_comment = self.comments.iter().any(|c| c.start <= start && start < c.end);
+            let joined = (start > 0 && is_word(s[start - 1])) || s.get(end).is_some_and(|&c| is_word(c));
+            if !in_comment && !joined {
+                return self.span(start, end);
+            }
+            i = start + 1;
+        }
+        self.span(from, from)
+    }
+
+    /// 字节范围对应的 Span
+    fn span(&self, start: usize, end: usize) -> Span {
+        let line_of = |pos: usize| {
+            1 + self.source[..pos.min(self.source.len())]
+                .iter()
+                .filter(|&&c| c == b'\n')
+                .count()
+        };
+        Span {
+            start,
+            end,
+            line: line_of(start),
+            end_line: line_of(end),
+        }
+    }
+
+    /// 在当前作用域中声明局部变量，可见范围从 visible 开始；名字从 from 开始查找
+    fn declare(&mut self, name: &str, kind: DefKind, from: usize, visible: usize) -> usize {
+        let span = self.name_span(name, from);
+        let (end, _) = *self.scopes.last().unwrap();
+        self.index.defs.push(Definition {
+            name: name.to_string(),
+            kind,
+            span,
+            scope: (visible, end),
+            ty: None,
+            detail: None,
+            require: None,
+        });
+        let d = self.index.defs.len() - 1;
+        self.scopes.last_mut().unwrap().1.push(d);
+        d
+    }
+
+    /// 给名为 name 的变量赋值：没有局部定义且是第一次赋值的全局变量记为全局定义，返回新的定义
+    fn assign(&mut self, name: &str, span: Span, value: Option<&Exp>) -> Option<usize> {
+        if let Some(d) = self.find(name) {
+            self.reference(name, span, Some(d));
+            return None;
+        }
+        if self.globals.contains_key(name) {
+            self.reference(name, span, None);
+            return None;
+        }
+        self.index.defs.push(Definition {
+            name: name.to_string(),
+            kind: DefKind::Global,
+            span,
+            scope: (0, self.source.len()),
+            ty: value.and_then(|v| self.type_of(v)),
+            detail: None,
+            require: value.and_then(require_name),
+        });
+        let d = self.index.defs.len() - 1;
+        self.globals.insert(name.to_string(), d);
+        Some(d)
+    }
+
+    fn reference(&mut self, name: &str, span: Span, def: Option<usize>) {
+        self.index.refs.push(Reference {
+            name: name.to_string(),
+            span,
+            def,
+        });
+    }
+
+    /// 在大纲的当前层加入一项
+    fn symbol(&mut self, name: String, is_function: bool, span: Span, name_span: Span, children: Vec<Symbol>) {
+        self.symbols.last_mut().unwrap().push(Symbol {
+            name,
+            is_function,
+            span,
+            name_span,
+            children,
+        });
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stat in &block.stats {
+            self.stat(stat);
+        }
+    }
+
+    fn stat(&mut self, stat: &Stat) {
+        let span = stat.span;
+        match &stat.kind {
+            StatKind::Local(vars, exps) => {
+                // 初始值中的函数的大纲项归在变量名下
+                let mut bodies = Vec::new();
+                for exp in exps {
+                    match &exp.kind {
+                        ExpKind::Function(func) => {
+                            self.index.literals.push((exp.span, "function"));
+                            bodies.push(Some(self.function(func, false)));
+                        }
+                        _ => {
+                            self.exp(exp);
+                            bodies.push(None);
+                        }
+                    }
+                }
+                // 名字在 local 关键字之后依次出现
+                let mut from = span.start + "local".len();
+                for (i, (name, _)) in vars.iter().enumerate() {
+                    let d = self.declare(name, DefKind::Local, from, span.end);
+                    from = self.index.defs[d].span.end;
+                    if let Some(v) = exps.get(i) {
+                        let ty = self.type_of(v);
+                        let def = &mut self.index.defs[d];
+                        def.ty = ty;
+                        def.require = require_name(v);
+                        if let ExpKind::Function(func) = &v.kind {
+                            def.detail = Some(signature(&format!("local function {name}"), func, false));
+                        }
+                    }
+                    let name_span = self.index.defs[d].span;
+                    match bodies.get_mut(i).and_then(Option::take) {
+                        Some(children) => self.symbol(name.clone(), true, span, name_span, children),
+                        None => self.symbol(name.clone(), false, span, name_span, Vec::new()),
+                    }
+                }
+            }
+            StatKind::LocalFunction(name, func) => {
+                // 函数体中可以递归调用自身
+                let d = self.declare(name, DefKind::LocalFunction, func.span.start, func.span.start);
+                self.index.defs[d].ty = Some("function");
+                self.index.defs[d].detail = Some(signature(&format!("local function {name}"), func, false));
+                let name_span = self.index.defs[d].span;
+                let children = self.function(func, false);
+                self.symbol(name.clone(), true, span, name_span, children);
+            }
+            StatKind::Function(fname, func) => {
+                let first = &fname.path[0];
+                let first_span = self.name_span(first, span.start + "function".len());
+                let full = match &fname.method {
+                    Some(m) => format!("{}:{m}", fname.path.join(".")),
+                    None => fname.path.join("."),
+                };
+                if fname.path.len() == 1 && fname.method.is_none() {
+                    if let Some(d) = self.assign(first, first_span, None) {
+                        self.index.defs[d].ty = Some("function");
+                        self.index.defs[d].detail = Some(signature(&format!("function {full}"), func, false));
+                    }
+                } else {
+                    let def = self.find(first);
+                    self.reference(first, first_span, def);
+                }
+                let children = self.function(func, fname.method.is_some());
+                // 函数名从第一段到参数列表之前
+                let paren = func.span.start
+                    + self.source[func.span.start..]
+                        .iter()
+                        .position(|&c| c == b'(')
+                        .unwrap_or(0);
+                let name_span = self.trim_name(self.span(first_span.start, paren.max(first_span.end)));
+                self.symbol(full, true, span, name_span, children);
+            }
+            StatKind::Assign(vars, exps) => {
+                self.exps(exps);
+                for (i, var) in vars.iter().enumerate() {
+                    match &var.kind {
+                        ExpKind::Name(name) => {
+                            self.assign(name, var.span, exps.get(i));
+                        }
+                        _ => self.exp(var),
+                    }
+                }
+            }
+            StatKind::Call(call) => self.exp(call),
+            StatKind::Do(block) => self.scoped(span.end, |ix| ix.block(block)),
+            StatKind::While(cond, block) => {
+                self.exp(cond);
+                self.scoped(span.end, |ix| ix.block(block));
+            }
+            StatKind::Repeat(block, cond) => {
+                self.scoped(span.end, |ix| {
+                    ix.block(block);
+                    ix.exp(cond);
+                });
+            }
+            StatKind::If(conds, else_block) => {
+                for (cond, block) in conds {
+                    self.exp(cond);
+                    self.scoped(block.span.end, |ix| ix.block(block));
+                }
+                if let Some(block) = else_block {
+                    self.scoped(block.span.end, |ix| ix.block(block));
+                }
+            }
+            StatKind::NumericFor {
+                var,
+                start,
+                limit,
+                step,
+                body,
+            } => {
+                self.exp(start);
+                self.exp(limit);
+                if let Some(step) = step {
+                    self.exp(step);
+                }
+                self.scoped(span.end, |ix| {
+                    let d = ix.declare(var, DefKind::Loop, span.start + "for".len(), body.span.start);
+                    ix.index.defs[d].ty = Some("number");
+                    ix.block(body);
+                });
+            }
+            StatKind::GenericFor { vars, exps, body } => {
+                self.exps(exps);
+                self.scoped(span.end, |ix| {
+                    let mut from = span.start + "for".len();
+                    for var in vars {
+                        let d = ix.declare(var, DefKind::Loop, from, body.span.start);
+                        from = ix.index.defs[d].span.end;
+                    }
+                    ix.block(body);
+                });
+            }
+            StatKind::Return(exps) => self.exps(exps),
+            StatKind::Break | StatKind::Goto(_) | StatKind::Label(_) => (),
+        }
+    }
+
+    /// 去掉范围末尾的空白
+    fn trim_name(&self, mut span: Span) -> Span {
+        while span.end > span.start && self.source[span.end - 1].is_ascii_whitespace() {
+            span.end -= 1;
+        }
+        span
+    }
+
+    /// 函数体：参数与函数体中的局部变量在同一个作用域，返回函数体中的大纲项
+    fn function(&mut self, func: &FuncBody, has_self: bool) -> Vec<Symbol> {
+        self.symbols.push(Vec::new());
+        self.scoped(func.span.end, |ix| {
+            let start = func.span.start;
+            if has_self {
+                ix.declare("self", DefKind::Param, start, start);
+            }
+            // 参数在左括号之后
+            let mut from = start + ix.source[start..].iter().position(|&c| c == b'(').unwrap_or(0);
+            for param in &func.params {
+                let d = ix.declare(param, DefKind::Param, from, start);
+                from = ix.index.defs[d].span.end;
+            }
+            ix.block(&func.body);
+        });
+        self.symbols.pop().unwrap()
+    }
+
+    fn exps(&mut self, exps: &[Exp]) {
+        for exp in exps {
+            self.exp(exp);
+        }
+    }
+
+    fn exp(&mut self, exp: &Exp) {
+        if let Some(ty) = literal_type(exp) {
+            self.index.literals.push((exp.span, ty));
+        }
+        match &exp.kind {
+            ExpKind::Nil
+            | ExpKind::True
+            | ExpKind::False
+            | ExpKind::Dots
+            | ExpKind::Integer(_)
+            | ExpKind::Float(_)
+            | ExpKind::String(_) => (),
+            ExpKind::Function(func) => {
+                // 匿名函数不进入大纲
+                self.function(func, false);
+            }
+            ExpKind::Table(fields) => {
+                for field in fields {
+                    match &field.kind {
+                        FieldKind::Positional(v) | FieldKind::Named(_, v) => self.exp(v),
+                        FieldKind::Keyed(k, v) => {
+                            self.exp(k);
+                            self.exp(v);
+                        }
+                    }
+                }
+            }
+            ExpKind::Binary(_, left, right) => {
+                self.exp(left);
+                self.exp(right);
+            }
+            ExpKind::Unary(_, operand) | ExpKind::Paren(operand) | ExpKind::Field(operand, _) => self.exp(operand),
+            ExpKind::Name(name) => {
+                let def = self.find(name);
+                self.reference(name, exp.span, def);
+            }
+            ExpKind::Index(t, key) => {
+                self.exp(t);
+                self.exp(key);
+            }
+            ExpKind::Call(func, args) => {
+                if let Some(module) = require_name(exp) {
+                    self.index.requires.push((module, args[0].span));
+                }
+                self.exp(func);
+                self.exps(args);
+            }
+            ExpKind::Method(obj, _, args) => {
+                self.exp(obj);
+                self.exps(args);
+            }
+        }
+    }
+
+    /// 表达式的推断类型：字面量、结果类型确定的运算，以及有推断类型的局部变量
+    fn type_of(&self, exp: &Exp) -> Option<&'static str> {
+        match &exp.kind {
+            ExpKind::Name(name) => self.find(name).and_then(|d| self.index.defs[d].ty),
+            ExpKind::Paren(e) => self.type_of(e),
+            _ => literal_type(exp),
+        }
+    }
+}
+
+/// 不依赖变量的表达式类型
+fn literal_type(exp: &Exp) -> Option<&'static str> {
+    let ty = match &exp.kind {
+        ExpKind::Nil => "nil",
+        ExpKind::True | ExpKind::False => "boolean",
+        ExpKind::Integer(_) => "integer",
+        ExpKind::Float(_) => "number",
+        ExpKind::String(_) => "string",
+        ExpKind::Table(_) => "table",
+        ExpKind::Function(_) => "function",
+        ExpKind::Binary(BinOp::Concat, ..) => "string",
+        ExpKind::Binary(
+            BinOp::Equal | BinOp::NotEq | BinOp::Less | BinOp::LesEq | BinOp::Greater | BinOp::GreEq,
+            ..,
+        ) => "boolean",
+        ExpKind::Unary(UnOp::Not, _) => "boolean",
+        ExpKind::Unary(UnOp::Len, _) => "integer",
+        _ => return None,
+    };
+    Some(ty)
+}
+
+/// require("name") 或 require "name" 调用中的模块名
+fn require_name(exp: &Exp) -> Option<String> {
+    match &exp.kind {
+        ExpKind::Call(func, args) if args.len() == 1 && matches!(&func.kind, ExpKind::Name(n) if n == "require") => {
+            match &args[0].kind {
+                ExpKind::String(s) => Some(String::from_utf8_lossy(s).into_owned()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// 函数签名，如 "function f(a, b, ...)"
+fn signature(prefix: &str, func: &FuncBody, has_self: bool) -> String {
+    let mut params: Vec<&str> = Vec::new();
+    if has_self {
+        params.push("self");
+    }
+    params.extend(func.params.iter().map(String::as_str));
+    if func.is_vararg {
+        params.push("...");
+    }
+    format!("{prefix}({})", params.join(", "))
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return None;
+    }
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
@@ -0,0 +1,227 @@
+// Lua 运算的数值部分
+// 算术、位运算与比较在操作数为数字（或可转换为数字的字符串）时的结果；
+// 操作数不是数字时返回 None，由虚拟机继续查找元方法或报错
+
+use crate::value::{str_to_number, Value};
+use std::cmp::Ordering;
+
+/// 二元算术与位运算
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Idiv,
+    Mod,
+    Pow,
+    BitAnd,
+    BitXor,
+    BitOr,
+    ShiftL,
+    ShiftR,
+}
+
+impl ArithOp {
+    /// 对应的元方法名
+    pub fn event(self) -> &'static str {
+        match self {
+            ArithOp::Add => "__add",
+            ArithOp::Sub => "__sub",
+            ArithOp::Mul => "__mul",
+            ArithOp::Div => "__div",
+            ArithOp::Idiv => "__idiv",
+            ArithOp::Mod => "__mod",
+            ArithOp::Pow => "__pow",
+            ArithOp::BitAnd => "__band",
+            ArithOp::BitXor => "__bxor",
+            ArithOp::BitOr => "__bor",
+            ArithOp::ShiftL => "__shl",
+            ArithOp::ShiftR => "__shr",
+        }
+    }
+
+    /// 是否为位运算（操作数须能表示为整数）
+    pub fn is_bitwise(self) -> bool {
+        matches!(
+            self,
+            ArithOp::BitAnd | ArithOp::BitXor | ArithOp::BitOr | ArithOp::ShiftL | ArithOp::ShiftR
+        )
+    }
+}
+
+/// 把操作数转为数字：数字原样返回，字符串按 Lua 规则解析
+pub fn to_arith_number(v: &Value) -> Option<Value> {
+    match v {
+        Value::Integer(_) | Value::Float(_) => Some(v.clone()),
+        _ => v.as_str().and_then(str_to_number),
+    }
+}
+
+/// 数值运算：操作数不是数字（位运算时不能表示为整数）返回 Ok(None)
+/// 整数除以 0 取整或取模时返回错误信息，由虚拟机加上出错位置
+pub fn arith(op: ArithOp, a: &Value, b: &Value) -> Result<Option<Value>, &'static str> {
+    let (Some(a), Some(b)) = (to_arith_number(a), to_arith_number(b)) else {
+        return Ok(None);
+    };
+    if op.is_bitwise() {
+        let (Some(x), Some(y)) = (a.to_integer(), b.to_integer()) else {
+            return Ok(None);
+        };
+        let r = match op {
+            ArithOp::BitAnd => x & y,
+            ArithOp::BitXor => x ^ y,
+            ArithOp::BitOr => x | y,
+            ArithOp::ShiftL => shift_left(x, y),
+            _ => shift_left(x, y.wrapping_neg()),
+        };
+        return Ok(Some(Value::Integer(r)));
+    }
+
+    if let (Value::Integer(x), Value::Integer(y)) = (&a, &b) {
+        let (x, y) = (*x, *y);
+        let r = match op {
+            ArithOp::Add => x.wrapping_add(y),
+            ArithOp::Sub => x.wrapping_sub(y),
+            ArithOp::Mul => x.wrapping_mul(y),
+            ArithOp::Idiv => {
+                if y == 0 {
+                    return Err("attempt to perform 'n//0'");
+                }
+                let q = x.wrapping_div(y);
+                // 向负无穷取整
+                if x.wrapping_rem(y) != 0 && (x ^ y) < 0 {
+                    q - 1
+                } else {
+                    q
+                }
+            }
+            ArithOp::Mod => {
+                if y == 0 {
+                    return Err("attempt to perform 'n%0'");
+                }
+                let r = x.wrapping_rem(y);
+                // 结果与除数同号
+                if r != 0 && (r ^ y) < 0 {
+                    r + y
+                } else {
+                    r
+                }
+            }
+            // Div 与 Pow 总是得到浮点数
+            _ => return Ok(Some(Value::Float(float_arith(op, x as f64, y as f64)))),
+        };
+        return Ok(Some(Value::Integer(r)));
+    }
+
+    let x = a.to_number().unwrap();
+    let y = b.to_number().unwrap();
+    Ok(Some(Value::Float(float_arith(op, x, y))))
+}
+
+/// 浮点数运算
+fn float_arith(op: ArithOp, x: f64, y: f64) -> f64 {
+    match op {
+        ArithOp::Add => x + y,
+        ArithOp::Sub => x - y,
+        ArithOp::Mul => x * y,
+        ArithOp::Div => x / y,
+        ArithOp::Idiv => (x / y).floor(),
+        ArithOp::Mod => {
+            let m = x % y;
+            // 与 C 实现一致：结果与除数同号
+            if (m > 0.0 && y < 0.0) || (m < 0.0 && y > 0.0) {
+                m + y
+            } else {
+                m
+            }
+        }
+        ArithOp::Pow => x.powf(y),
+        _ => unreachable!("bitwise operation on floats"),
+    }
+}
+
+/// 逻辑左移，n 为负时右移，移出 64 位时为 0
+fn shift_left(x: i64, n: i64) -> i64 {
+    if n <= -64 || n >= 64 {
+        0
+    } else if n >= 0 {
+        ((x as u64) << n) as i64
+    } else {
+        ((x as u64) >> -n) as i64
+    }
+}
+
+/// 取负：数字直接计算，字符串先转换
+pub fn neg(v: &Value) -> Option<Value> {
+    match to_arith_number(v)? {
+        Value::Integer(i) => Some(Value::Integer(i.wrapping_neg())),
+        Value::Float(f) => Some(Value::Float(-f)),
+        _ => None,
+    }
+}
+
+/// 按位取反
+pub fn bit_not(v: &Value) -> Option<Value> {
+    to_arith_number(v)?.to_integer().map(|i| Value::Integer(!i))
+}
+
+/// 整数与浮点数的精确比较，不经过可能丢失精度的 i64 -> f64 转换；f 为 NaN 时返回 None
+fn cmp_int_float(i: i64, f: f64) -> Option<Ordering> {
+    if f.is_nan() {
+        return None;
+    }
+    // 2^63 及以上、-2^63 以下的浮点数超出整数范围
+    if f >= -(i64::MIN as f64) {
+        return Some(Ordering::Less);
+    }
+    if f < i64::MIN as f64 {
+        return Some(Ordering::Greater);
+    }
+    // 范围内的 floor(f) 可以精确表示为整数，相等时再看 f 是否有小数部分
+    let fi = f.floor() as i64;
+    Some(i.cmp(&fi).then(if f > fi as f64 { Ordering::Less } else { Ordering::Equal }))
+}
+
+/// 两个数字的大小关系，有 NaN 时返回 None
+fn cmp_numbers(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Integer(x), Value::Integer(y)) => Some(x.cmp(y)),
+        (Value::Float(x), Value::Float(y)) => x.partial_cmp(y),
+        (Value::Integer(x), Value::Float(y)) => cmp_int_float(*x, *y),
+        (Value::Float(x), Value::Integer(y)) => cmp_int_float(*y, *x).map(Ordering::reverse),
+        _ => None,
+    }
+}
+
+/// 原始相等（不调用元方法）：整数与浮点数按数学上的值比较
+pub fn raw_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Integer(x), Value::Float(y)) | (Value::Float(y), Value::Integer(x)) => {
+            cmp_int_float(*x, *y) == Some(Ordering::Equal)
+        }
+        _ => a == b,
+    }
+}
+
+/// a < b：数字之间或字符串之间比较，其他类型返回 None
+pub fn less_than(a: &Value, b: &Value) -> Option<bool> {
+    match (a, b) {
+        (Value::Integer(x), Value::Integer(y)) => Some(x < y),
+        (Value::Integer(_) | Value::Float(_), Value::Integer(_) | Value::Float(_)) => {
+            Some(cmp_numbers(a, b) == Some(Ordering::Less))
+        }
+        _ => Some(a.as_bytes()? < b.as_bytes()?),
+    }
+}
+
+/// a <= b：数字之间或字符串之间比较，其他类型返回 None
+pub fn less_equal(a: &Value, b: &Value) -> Option<bool> {
+    match (a, b) {
+        (Value::Integer(x), Value::Integer(y)) => Some(x <= y),
+        (Value::Integer(_) | Value::Float(_), Value::Integer(_) | Value::Float(_)) => {
+            Some(matches!(cmp_numbers(a, b), Some(Ordering::Less | Ordering::Equal)))
+        }
+        _ => Some(a.as_bytes()? <= b.as_bytes()?),
+    }
+}
@@ -0,0 +1,877 @@
+// Lua 抽象语法树（AST）模块
+// 把 Token 流解析为完整的语法树，覆盖 Lua 5.4 的全部语法构造：
+// - 语句：局部变量（含属性）、局部函数、函数定义、赋值、函数调用、do/while/repeat/if、
+//   数值与泛型 for、return、break、goto 与标签
+// - 表达式：常量、变量名、字段与索引、函数调用与方法调用、匿名函数、表构造器、一元与二元运算、括号
+// 每个语句、表达式与表字段都记录其在源代码中的范围（Span），供格式化、静态检查等工具使用；
+// 括号表达式与语法糖之外的书写形式都原样保留，按源代码顺序打印即可得到等价的程序
+
+use crate::lex::{Lex, ParseError, Span, Token};
+use std::io::{Read, Seek};
+
+/// 局部变量的属性：local x <const>、local x <close>
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Attrib {
+    Plain,
+    /// 只读变量
+    Const,
+    /// 待关闭变量（同样只读）：离开作用域时调用其值的 __close 元方法
+    Close,
+}
+
+/// 代码块：语句序列，return 只能是最后一条
+/// 范围从第一条语句到最后一条语句，空代码块为其后的 Token 处的空范围；主代码块的范围延伸到文件尾
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub stats: Vec<Stat>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stat {
+    pub kind: StatKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatKind {
+    /// local name [attrib], ... [= explist]
+    Local(Vec<(String, Attrib)>, Vec<Exp>),
+    /// local function name body
+    LocalFunction(String, FuncBody),
+    /// function a.b.c:m body
+    Function(FuncName, FuncBody),
+    /// varlist = explist，变量为 Name、Index 或 Field 表达式
+    Assign(Vec<Exp>, Vec<Exp>),
+    /// 函数调用语句，表达式为 Call 或 Method
+    Call(Exp),
+    Do(Block),
+    While(Exp, Block),
+    Repeat(Block, Exp),
+    /// if 与各 elseif 的（条件, 代码块），以及可选的 else 代码块
+    If(Vec<(Exp, Block)>, Option<Block>),
+    /// for name = start, limit [, step] do block end
+    NumericFor {
+        var: String,
+        start: Exp,
+        limit: Exp,
+        step: Option<Exp>,
+        body: Block,
+    },
+    /// for namelist in explist do block end
+    GenericFor {
+        vars: Vec<String>,
+        exps: Vec<Exp>,
+        body: Block,
+    },
+    Return(Vec<Exp>),
+    Break,
+    Goto(String),
+    Label(String),
+}
+
+/// function 语句的函数名：path 为点号分隔的各段，method 为冒号之后的方法名
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncName {
+    pub path: Vec<String>,
+    pub method: Option<String>,
+}
+
+/// 函数体：参数列表与代码块，span 从函数名（匿名函数为 function 关键字）到 end
+/// 方法定义隐含的 self 参数不在 params 中
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncBody {
+    pub params: Vec<String>,
+    pub is_vararg: bool,
+    pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exp {
+    pub kind: ExpKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpKind {
+    Nil,
+    True,
+    False,
+    /// 可变参数 ...
+    Dots,
+    Integer(i64),
+    Float(f64),
+    String(Vec<u8>),
+    Function(Box<FuncBody>),
+    Table(Vec<Field>),
+    Binary(BinOp, Box<Exp>, Box<Exp>),
+    Unary(UnOp, Box<Exp>),
+    Name(String),
+    /// t[k]
+    Index(Box<Exp>, Box<Exp>),
+    /// t.name
+    Field(Box<Exp>, String),
+    /// f(args)，f "str" 与 f {table} 同样表示为只有一个参数的调用
+    Call(Box<Exp>, Vec<Exp>),
+    /// obj:name(args)
+    Method(Box<Exp>, String, Vec<Exp>),
+    /// (exp)：只保留第一个值
+    Paren(Box<Exp>),
+}
+
+/// 表构造器中的一项
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub kind: FieldKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldKind {
+    /// exp：数组项
+    Positional(Exp),
+    /// name = exp
+    Named(String, Exp),
+    /// [exp] = exp
+    Keyed(Exp, Exp),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Idiv,
+    Mod,
+    Pow,
+    Concat,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftL,
+    ShiftR,
+    Equal,
+    NotEq,
+    Less,
+    LesEq,
+    Greater,
+    GreEq,
+    And,
+    Or,
+}
+
+impl BinOp {
+    fn from_token(t: &Token) -> Option<Self> {
+        let op = match t {
+            Token::Add => BinOp::Add,
+            Token::Sub => BinOp::Sub,
+            Token::Mul => BinOp::Mul,
+            Token::Div => BinOp::Div,
+            Token::Idiv => BinOp::Idiv,
+            Token::Mod => BinOp::Mod,
+            Token::Pow => BinOp::Pow,
+            Token::Concat => BinOp::Concat,
+            Token::BitAnd => BinOp::BitAnd,
+            Token::BitOr => BinOp::BitOr,
+            Token::BitXor => BinOp::BitXor,
+            Token::ShiftL => BinOp::ShiftL,
+            Token::ShiftR => BinOp::ShiftR,
+            Token::Equal => BinOp::Equal,
+            Token::NotEq => BinOp::NotEq,
+            Token::Less => BinOp::Less,
+            Token::LesEq => BinOp::LesEq,
+            Token::Greater => BinOp::Greater,
+            Token::GreEq => BinOp::GreEq,
+            Token::And => BinOp::And,
+            Token::Or => BinOp::Or,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// （左, 右）优先级，右结合的运算符右优先级较低
+    pub fn priority(self) -> (i32, i32) {
+        match self {
+            BinOp::Or => (1, 1),
+            BinOp::And => (2, 2),
+            BinOp::Equal | BinOp::NotEq | BinOp::Less | BinOp::LesEq | BinOp::Greater | BinOp::GreEq => (3, 3),
+            BinOp::BitOr => (4, 4),
+            BinOp::BitXor => (5, 5),
+            BinOp::BitAnd => (6, 6),
+            BinOp::ShiftL | BinOp::ShiftR => (7, 7),
+            BinOp::Concat => (9, 8),
+            BinOp::Add | BinOp::Sub => (10, 10),
+            BinOp::Mul | BinOp::Div | BinOp::Idiv | BinOp::Mod => (11, 11),
+            BinOp::Pow => (14, 13),
+        }
+    }
+
+    /// 运算符在源代码中的写法
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Idiv => "//",
+            BinOp::Mod => "%",
+            BinOp::Pow => "^",
+            BinOp::Concat => "..",
+            BinOp::BitAnd => "&",
+            BinOp::BitOr => "|",
+            BinOp::BitXor => "~",
+            BinOp::ShiftL => "<<",
+            BinOp::ShiftR => ">>",
+            BinOp::Equal => "==",
+            BinOp::NotEq => "~=",
+            BinOp::Less => "<",
+            BinOp::LesEq => "<=",
+            BinOp::Greater => ">",
+            BinOp::GreEq => ">=",
+            BinOp::And => "and",
+            BinOp::Or => "or",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnOp {
+    /// -
+    Neg,
+    Not,
+    /// ~
+    BitNot,
+    /// #
+    Len,
+}
+
+impl UnOp {
+    /// 一元运算符的优先级
+    pub const PRIORITY: i32 = 12;
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnOp::Neg => "-",
+            UnOp::Not => "not",
+            UnOp::BitNot => "~",
+            UnOp::Len => "#",
+        }
+    }
+}
+
+/// 解析整个代码块
+pub fn parse<R: Read + Seek>(input: R) -> Result<Block, ParseError> {
+    Parser::new(Lex::new(input)).chunk()
+}
+
+/// 解析整个代码块，同时返回源代码中所有注释的范围
+pub fn parse_with_comments<R: Read + Seek>(input: R) -> Result<(Block, Vec<Span>), ParseError> {
+    let mut lex = Lex::new(input);
+    lex.keep_comments();
+    let mut parser = Parser::new(lex);
+    let block = parser.chunk()?;
+    Ok((block, parser.lex.take_comments()))
+}
+
+/// 递归下降的语法分析器
+/// - `last`: 最近取出的 Token 的范围，用作各节点的结束位置
+/// - `vararg`: 正在解析的各层函数是否为可变参数函数
+struct Parser<R> {
+    lex: Lex<R>,
+    last: Span,
+    vararg: Vec<bool>,
+}
+
+/// 结束代码块的 Token
+fn block_follow(t: &Token) -> bool {
+    matches!(t, Token::End | Token::Eos | Token::Else | Token::Elseif | Token::Until)
+}
+
+impl<R: Read + Seek> Parser<R> {
+    fn new(lex: Lex<R>) -> Self {
+        Parser {
+            lex,
+            last: Span::default(),
+            vararg: vec![true],
+        }
+    }
+
+    /// 主代码块：读到文件尾为止，其范围延伸到文件尾
+    fn chunk(&mut self) -> Result<Block, ParseError> {
+        let mut block = self.block()?;
+        match self.next()? {
+            Token::Eos => (),
+            t => return Err(self.error(format!("'<eof>' expected near {t}"))),
+        }
+        block.span.end = self.last.end;
+        block.span.end_line = self.last.end_line;
+        Ok(block)
+    }
+
+    fn next(&mut self) -> Result<Token, ParseError> {
+        let t = self.lex.next()?;
+        self.last = self.lex.token_span();
+        Ok(t)
+    }
+
+    fn peek(&mut self) -> Result<&Token, ParseError> {
+        self.lex.peek()
+    }
+
+    /// 下一个 Token 的范围，作为节点的开始位置
+    fn start(&mut self) -> Result<Span, ParseError> {
+        self.lex.peek()?;
+        Ok(self.lex.ahead_span())
+    }
+
+    /// 从 start 开始到最近取出的 Token 为止的范围
+    fn span_from(&self, start: Span) -> Span {
+        Span {
+            start: start.start,
+            line: start.line,
+            end: self.last.end,
+            end_line: self.last.end_line,
+        }
+    }
+
+    /// 下一个 Token 是 t 时取出它并返回 true
+    fn test_next(&mut self, t: Token) -> Result<bool, ParseError> {
+        if self.peek()? == &t {
+            self.next()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// 读取下一个 Token 并检查是否为期望的 Token
+    fn expect(&mut self, t: Token) -> Result<(), ParseError> {
+        let got = self.next()?;
+        if got != t {
+            return Err(self.error(format!("{t} expected near {got}")));
+        }
+        Ok(())
+    }
+
+    fn expect_name(&mut self) -> Result<String, ParseError> {
+        match self.next()? {
+            Token::Name(name) => Ok(name),
+            t => Err(self.error(format!("<name> expected near {t}"))),
+        }
+    }
+
+    fn expect_end(&mut self) -> Result<(), ParseError> {
+        match self.next()? {
+            Token::End => Ok(()),
+            t => Err(self.error(format!("'end' expected near {t}"))),
+        }
+    }
+
+    fn error(&self, msg: impl Into<String>) -> ParseError {
+        self.lex.error(msg)
+    }
+
+    /// 代码块：解析语句直到遇到块结束 Token（不取出该 Token）
+    fn block(&mut self) -> Result<Block, ParseError> {
+        let start = self.start()?;
+        let mut stats = Vec::new();
+        loop {
+            match self.peek()? {
+                Token::SemiColon => {
+                    self.next()?;
+                }
+                Token::Return => {
+                    stats.push(self.return_stat()?);
+                    break;
+                }
+                t if block_follow(t) => break,
+                _ => stats.push(self.statement()?),
+            }
+        }
+        let span = match (stats.first(), stats.last()) {
+            (Some(first), Some(last)) => Span {
+                start: first.span.start,
+                line: first.span.line,
+                end: last.span.end,
+                end_line: last.span.end_line,
+            },
+            _ => Span {
+                end: start.start,
+                end_line: start.line,
+                ..start
+            },
+        };
+        Ok(Block { stats, span })
+    }
+
+    /// return [explist] [';']，之后必须是代码块的结尾
+    fn return_stat(&mut self) -> Result<Stat, ParseError> {
+        let start = self.start()?;
+        self.next()?;
+        let t = self.peek()?;
+        let exps = if block_follow(t) || t == &Token::SemiColon {
+            Vec::new()
+        } else {
+            self.explist()?
+        };
+        let span = self.span_from(start);
+        self.test_next(Token::SemiColon)?;
+        if !block_follow(self.peek()?) {
+            let t = self.next()?;
+            return Err(self.error(format!("'end' expected near {t}")));
+        }
+        Ok(Stat {
+            kind: StatKind::Return(exps),
+            span,
+        })
+    }
+
+    fn statement(&mut self) -> Result<Stat, ParseError> {
+        let start = self.start()?;
+        let kind = match self.peek()? {
+            Token::Local => {
+                self.next()?;
+                if self.test_next(Token::Function)? {
+                    let name_start = self.start()?;
+                    let name = self.expect_name()?;
+                    let func = self.funcbody(name_start)?;
+                    StatKind::LocalFunction(name, func)
+                } else {
+                    self.local()?
+                }
+            }
+            Token::Function => {
+                self.next()?;
+                self.function_stat()?
+            }
+            Token::For => {
+                self.next()?;
+                self.for_stat()?
+            }
+            Token::Do => {
+                self.next()?;
+                let block = self.block()?;
+                self.expect_end()?;
+                StatKind::Do(block)
+            }
+            Token::While => {
+                self.next()?;
+                let cond = self.exp()?;
+                self.expect(Token::Do)?;
+                let block = self.block()?;
+                self.expect_end()?;
+                StatKind::While(cond, block)
+            }
+            Token::Repeat => {
+                self.next()?;
+                let block = self.block()?;
+                self.expect(Token::Until)?;
+                let cond = self.exp()?;
+                StatKind::Repeat(block, cond)
+            }
+            Token::If => {
+                self.next()?;
+                self.if_stat()?
+            }
+            Token::Break => {
+                self.next()?;
+                StatKind::Break
+            }
+            Token::Goto => {
+                self.next()?;
+                StatKind::Goto(self.expect_name()?)
+            }
+            Token::DoubColon => {
+                self.next()?;
+                let name = self.expect_name()?;
+                self.expect(Token::DoubColon)?;
+                StatKind::Label(name)
+            }
+            _ => self.exp_stat()?,
+        };
+        Ok(Stat {
+            kind,
+            span: self.span_from(start),
+        })
+    }
+
+    /// local name [attrib] {',' name [attrib]} ['=' explist]
+    fn local(&mut self) -> Result<StatKind, ParseError> {
+        let mut vars = Vec::new();
+        loop {
+            let name = self.expect_name()?;
+            let attrib = self.attrib()?;
+            if attrib == Attrib::Close && vars.iter().any(|(_, a)| *a == Attrib::Close) {
+                return Err(self.error("multiple to-be-closed variables in local list"));
+            }
+            vars.push((name, attrib));
+            if !self.test_next(Token::Comma)? {
+                break;
+            }
+        }
+        let exps = if self.test_next(Token::Assign)? { self.explist()? } else { Vec::new() };
+        Ok(StatKind::Local(vars, exps))
+    }
+
+    /// 局部变量的属性：attrib ::= ['<' Name '>']
+    fn attrib(&mut self) -> Result<Attrib, ParseError> {
+        if !self.test_next(Token::Less)? {
+            return Ok(Attrib::Plain);
+        }
+        let name = self.expect_name()?;
+        self.expect(Token::Greater)?;
+        match name.as_str() {
+            "const" => Ok(Attrib::Const),
+            "close" => Ok(Attrib::Close),
+            _ => Err(self.error(format!("unknown attribute '{name}'"))),
+        }
+    }
+
+    /// function name {'.' name} [':' name] body
+    fn function_stat(&mut self) -> Result<StatKind, ParseError> {
+        let start = self.start()?;
+        let mut path = vec![self.expect_name()?];
+        let mut method = None;
+        loop {
+            if self.test_next(Token::Dot)? {
+                path.push(self.expect_name()?);
+            } else if self.test_next(Token::Colon)? {
+                method = Some(self.expect_name()?);
+                break;
+            } else {
+                break;
+            }
+        }
+        let func = self.funcbody(start)?;
+        Ok(StatKind::Function(FuncName { path, method }, func))
+    }
+
+    /// 数值 for 或泛型 for（已读入 for）
+    fn for_stat(&mut self) -> Result<StatKind, ParseError> {
+        let var = self.expect_name()?;
+        if self.test_next(Token::Assign)? {
+            let start = self.exp()?;
+            self.expect(Token::Comma)?;
+            let limit = self.exp()?;
+            let step = if self.test_next(Token::Comma)? { Some(self.exp()?) } else { None };
+            self.expect(Token::Do)?;
+            let body = self.block()?;
+            self.expect_end()?;
+            return Ok(StatKind::NumericFor {
+                var,
+                start,
+                limit,
+                step,
+                body,
+            });
+        }
+        let mut vars = vec![var];
+        while self.test_next(Token::Comma)? {
+            vars.push(self.expect_name()?);
+        }
+        match self.next()? {
+            Token::In => (),
+            t => return Err(self.error(format!("'=' or 'in' expected near {t}"))),
+        }
+        let exps = self.explist()?;
+        self.expect(Token::Do)?;
+        let body = self.block()?;
+        self.expect_end()?;
+        Ok(StatKind::GenericFor { vars, exps, body })
+    }
+
+    /// if exp then block {elseif exp then block} [else block] end（已读入 if）
+    fn if_stat(&mut self) -> Result<StatKind, ParseError> {
+        let mut conds = Vec::new();
+        loop {
+            let cond = self.exp()?;
+            self.expect(Token::Then)?;
+            let block = self.block()?;
+            conds.push((cond, block));
+            match self.next()? {
+                Token::Elseif => (),
+                Token::Else => {
+                    let block = self.block()?;
+                    self.expect_end()?;
+                    return Ok(StatKind::If(conds, Some(block)));
+                }
+                Token::End => return Ok(StatKind::If(conds, None)),
+                t => return Err(self.error(format!("'end' expected near {t}"))),
+            }
+        }
+    }
+
+    /// 以前缀表达式开头的语句：赋值或函数调用
+    fn exp_stat(&mut self) -> Result<StatKind, ParseError> {
+        let first = self.suffixed_exp()?;
+        if matches!(self.peek()?, Token::Assign | Token::Comma) {
+            let mut vars = vec![first];
+            while self.test_next(Token::Comma)? {
+                vars.push(self.suffixed_exp()?);
+            }
+            self.expect(Token::Assign)?;
+            if vars.iter().any(|v| !matches!(v.kind, ExpKind::Name(_) | ExpKind::Index(..) | ExpKind::Field(..))) {
+                return Err(self.error("syntax error: cannot assign to this expression"));
+            }
+            let exps = self.explist()?;
+            return Ok(StatKind::Assign(vars, exps));
+        }
+        match first.kind {
+            ExpKind::Call(..) | ExpKind::Method(..) => Ok(StatKind::Call(first)),
+            _ => Err(self.error("syntax error: expected function call or assignment")),
+        }
+    }
+
+    fn explist(&mut self) -> Result<Vec<Exp>, ParseError> {
+        let mut exps = vec![self.exp()?];
+        while self.test_next(Token::Comma)? {
+            exps.push(self.exp()?);
+        }
+        Ok(exps)
+    }
+
+    /// 表达式解析：exp ::= (simpleexp | unop exp) {binop exp}
+    fn exp(&mut self) -> Result<Exp, ParseError> {
+        self.exp_limit(0)
+    }
+
+    /// 按运算符优先级解析表达式，只处理左优先级高于 limit 的二元运算符
+    fn exp_limit(&mut self, limit: i32) -> Result<Exp, ParseError> {
+        let start = self.start()?;
+        let unop = match self.peek()? {
+            Token::Not => Some(UnOp::Not),
+            Token::Sub => Some(UnOp::Neg),
+            Token::BitXor => Some(UnOp::BitNot),
+            Token::Len => Some(UnOp::Len),
+            _ => None,
+        };
+        let mut exp = match unop {
+            Some(op) => {
+                self.next()?;
+                let operand = self.exp_limit(UnOp::PRIORITY)?;
+                Exp {
+                    kind: ExpKind::Unary(op, Box::new(operand)),
+                    span: self.span_from(start),
+                }
+            }
+            None => self.simple_exp()?,
+        };
+        loop {
+            let Some(op) = BinOp::from_token(self.peek()?) else {
+                return Ok(exp);
+            };
+            let (left_pri, right_pri) = op.priority();
+            if left_pri <= limit {
+                return Ok(exp);
+            }
+            self.next()?;
+            let right = self.exp_limit(right_pri)?;
+            exp = Exp {
+                kind: ExpKind::Binary(op, Box::new(exp), Box::new(right)),
+                span: self.span_from(start),
+            };
+        }
+    }
+
+    /// 简单表达式：常量、...、匿名函数、表构造器或前缀表达式
+    fn simple_exp(&mut self) -> Result<Exp, ParseError> {
+        let start = self.start()?;
+        let kind = match self.peek()? {
+            Token::Nil => ExpKind::Nil,
+            Token::True => ExpKind::True,
+            Token::False => ExpKind::False,
+            Token::Integer(i) => ExpKind::Integer(*i),
+            Token::Float(f) => ExpKind::Float(*f),
+            Token::String(_) => {
+                let Token::String(s) = self.next()? else { unreachable!() };
+                return Ok(Exp {
+                    kind: ExpKind::String(s),
+                    span: self.span_from(start),
+                });
+            }
+            Token::Dots => {
+                if !self.vararg.last().unwrap() {
+                    return Err(self.error("cannot use '...' outside a vararg function"));
+                }
+                ExpKind::Dots
+            }
+            Token::Function => {
+                self.next()?;
+                let func = self.funcbody(start)?;
+                return Ok(Exp {
+                    kind: ExpKind::Function(Box::new(func)),
+                    span: self.span_from(start),
+                });
+            }
+            Token::CurlyL => return self.table_constructor(),
+            _ => return self.suffixed_exp(),
+        };
+        self.next()?;
+        Ok(Exp {
+            kind,
+            span: self.span_from(start),
+        })
+    }
+
+    /// 基本表达式：名字或括号表达式
+    fn primary_exp(&mut self) -> Result<Exp, ParseError> {
+        let start = self.start()?;
+        let kind = match self.next()? {
+            Token::Name(name) => ExpKind::Name(name),
+            Token::ParL => {
+                let exp = self.exp()?;
+                self.expect(Token::ParR)?;
+                ExpKind::Paren(Box::new(exp))
+            }
+            t => return Err(self.error(format!("unexpected symbol near {t}"))),
+        };
+        Ok(Exp {
+            kind,
+            span: self.span_from(start),
+        })
+    }
+
+    /// 前缀表达式及其后缀：.name、[exp]、(args)/"string"/{table}、:name(args)
+    fn suffixed_exp(&mut self) -> Result<Exp, ParseError> {
+        let start = self.start()?;
+        let mut exp = self.primary_exp()?;
+        loop {
+            let kind = match self.peek()? {
+                Token::Dot => {
+                    self.next()?;
+                    ExpKind::Field(Box::new(exp), self.expect_name()?)
+                }
+                Token::SqurL => {
+                    self.next()?;
+                    let key = self.exp()?;
+                    self.expect(Token::SqurR)?;
+                    ExpKind::Index(Box::new(exp), Box::new(key))
+                }
+                Token::Colon => {
+                    self.next()?;
+                    let name = self.expect_name()?;
+                    let args = self.call_args()?;
+                    ExpKind::Method(Box::new(exp), name, args)
+                }
+                Token::ParL | Token::String(_) | Token::CurlyL => {
+                    let args = self.call_args()?;
+                    ExpKind::Call(Box::new(exp), args)
+                }
+                _ => return Ok(exp),
+            };
+            exp = Exp {
+                kind,
+                span: self.span_from(start),
+            };
+        }
+    }
+
+    /// 函数调用参数：(explist)、"string" 或 {table}
+    fn call_args(&mut self) -> Result<Vec<Exp>, ParseError> {
+        let start = self.start()?;
+        match self.peek()? {
+            Token::String(_) => {
+                let Token::String(s) = self.next()? else { unreachable!() };
+                Ok(vec![Exp {
+                    kind: ExpKind::String(s),
+                    span: self.span_from(start),
+                }])
+            }
+            Token::CurlyL => Ok(vec![self.table_constructor()?]),
+            Token::ParL => {
+                self.next()?;
+                if self.test_next(Token::ParR)? {
+                    return Ok(Vec::new());
+                }
+                let args = self.explist()?;
+                self.expect(Token::ParR)?;
+                Ok(args)
+            }
+            _ => {
+                let t = self.next()?;
+                Err(self.error(format!("function arguments expected near {t}")))
+            }
+        }
+    }
+
+    /// 函数体：(params) block end，start 为函数名或 function 关键字的位置
+    fn funcbody(&mut self, start: Span) -> Result<FuncBody, ParseError> {
+        let mut params = Vec::new();
+        let mut is_vararg = false;
+        self.expect(Token::ParL)?;
+        if self.peek()? != &Token::ParR {
+            loop {
+                match self.next()? {
+                    Token::Name(name) => params.push(name),
+                    Token::Dots => {
+                        is_vararg = true;
+                        break;
+                    }
+                    t => return Err(self.error(format!("<name> expected near {t}"))),
+                }
+                if !self.test_next(Token::Comma)? {
+                    break;
+                }
+            }
+        }
+        self.expect(Token::ParR)?;
+        self.vararg.push(is_vararg);
+        let body = self.block()?;
+        self.vararg.pop();
+        self.expect_end()?;
+        Ok(FuncBody {
+            params,
+            is_vararg,
+            body,
+            span: self.span_from(start),
+        })
+    }
+
+    /// 表构造器：{ field {sep field} [sep] }，sep 为 ',' 或 ';'
+    fn table_constructor(&mut self) -> Result<Exp, ParseError> {
+        let start = self.start()?;
+        self.expect(Token::CurlyL)?;
+        let mut fields = Vec::new();
+        loop {
+            if self.test_next(Token::CurlyR)? {
+                break;
+            }
+            let field_start = self.start()?;
+            let kind = match self.peek()? {
+                Token::SqurL => {
+                    self.next()?;
+                    let key = self.exp()?;
+                    self.expect(Token::SqurR)?;
+                    self.expect(Token::Assign)?;
+                    FieldKind::Keyed(key, self.exp()?)
+                }
+                Token::Name(_) => {
+                    let exp = self.exp()?;
+                    match exp.kind {
+                        ExpKind::Name(name) if self.peek()? == &Token::Assign => {
+                            self.next()?;
+                            FieldKind::Named(name, self.exp()?)
+                        }
+                        kind => FieldKind::Positional(Exp { kind, ..exp }),
+                    }
+                }
+                _ => FieldKind::Positional(self.exp()?),
+            };
+            fields.push(Field {
+                kind,
+                span: self.span_from(field_start),
+            });
+            match self.next()? {
+                Token::Comma | Token::SemiColon => (),
+                Token::CurlyR => break,
+                t => return Err(self.error(format!("'}}' expected near {t}"))),
+            }
+        }
+        Ok(Exp {
+            kind: ExpKind::Table(fields),
+            span: self.span_from(start),
+        })
+    }
+}
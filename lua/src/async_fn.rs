@@ -0,0 +1,91 @@
+// 异步 Rust 函数：在 Lua 中调用时挂起正在执行的代码，等待 Future 完成后再继续
+// create_async_function 把 async 闭包包装成 Lua 函数值；调用它的 Lua 代码须由 call_async 执行，
+// call_async 返回的 Future 被轮询时执行 Lua 代码，遇到异步函数就挂起，其 Future 完成后放置返回值并继续执行
+// 经过 Rust 代码（pcall、元方法、call 等）的嵌套调用中不能挂起，此时调用异步函数会报错
+
+use crate::sync::{MaybeSend, MaybeSendFuture, Rc, RefCell};
+use crate::value::{RustClosure, Value};
+use crate::vm::{ExeState, LuaError};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+impl ExeState {
+    /// 创建异步函数：f 收到全部参数，返回的 Future 完成时得到函数的全部返回值
+    pub fn create_async_function<F, Fut>(&mut self, f: F) -> Value
+    where
+        F: Fn(Vec<Value>) -> Fut + MaybeSend + 'static,
+        Fut: Future<Output = Result<Vec<Value>, LuaError>> + MaybeSendFuture + 'static,
+    {
+        let func = move |state: &mut ExeState| -> Result<i32, LuaError> {
+            state.check_yieldable()?;
+            let args = (1..=state.get_top()).map(|i| state.arg(i)).collect();
+            state.set_pending(Box::pin(f(args)));
+            Ok(0)
+        };
+        let func: RustClosure = RefCell::new(Box::new(func));
+        Value::RustClosure(Rc::new(func))
+    }
+
+    /// 异步调用函数：返回的 Future 完成时得到函数的全部返回值
+    /// 执行中调用的异步函数未完成时 Future 返回 Poll::Pending；Future 未完成就被丢弃时放弃这次调用
+    pub fn call_async(&mut self, func: Value, args: Vec<Value>) -> CallAsync<'_> {
+        let (ifunc, nargs, depth) = self.push_call(func, args);
+        CallAsync {
+            state: self,
+            ifunc,
+            nargs,
+            depth,
+            started: false,
+            done: false,
+        }
+    }
+}
+
+/// call_async 返回的 Future
+pub struct CallAsync<'a> {
+    state: &'a mut ExeState,
+    ifunc: usize,
+    nargs: usize,
+    depth: usize,
+    started: bool,
+    done: bool,
+}
+
+impl Future for CallAsync<'_> {
+    type Output = Result<Vec<Value>, LuaError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        assert!(!this.done, "CallAsync polled after completion");
+        let state = &mut *this.state;
+        let mut result = Ok(());
+        if !this.started {
+            this.started = true;
+            state.set_async(true);
+            result = state.start_call(this.ifunc, this.nargs, this.depth);
+        }
+        // 依次等待挂起的异步函数，完成后继续执行，直到调用结束或遇到未完成的 Future
+        while result.is_ok() {
+            let Some(suspended) = state.suspended() else {
+                break;
+            };
+            match suspended.future.as_mut().poll(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(values) => result = state.resume_call(values, this.depth),
+            }
+        }
+        this.done = true;
+        state.set_async(false);
+        Poll::Ready(state.finish_call(this.ifunc, this.depth, result))
+    }
+}
+
+impl Drop for CallAsync<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.state.abandon_call(self.ifunc, self.depth);
+            self.state.set_async(false);
+        }
+    }
+}
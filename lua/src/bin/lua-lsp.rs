@@ -0,0 +1,16 @@
+// Lua 语言服务器：在标准输入输出上以 JSON-RPC 与编辑器通信
+// 补全的全局变量为解释器默认打开的库；嵌入解释器的宿主可以用注册了自己的函数的 ExeState 创建 lsp::Server
+
+use lua::lsp::Server;
+use lua::vm::ExeState;
+use std::io;
+use std::process;
+
+fn main() {
+    let state = ExeState::new();
+    let mut server = Server::new(&state);
+    if let Err(e) = server.run(io::stdin().lock(), io::stdout().lock()) {
+        eprintln!("lua-lsp: {e}");
+        process::exit(1);
+    }
+}
@@ -1,19 +1,32 @@
 // Lua 字节码（ByteCode）定义
 // 虚拟机执行的指令集，由解析器生成
-// 包括变量操作（加载、存储）、常数加载、函数调用等指令
+// 包括变量操作（加载、存储）、常数加载、表操作、函数调用与返回、运算与条件跳转等指令
+// 寄存器编号均相对于当前函数栈帧的基址
+// 指令以 32 位整数（Instruction）保存与执行，格式与 Lua 5.4 相同；ByteCode 是其类型化的视图，
+// 供解析器生成指令与反汇编使用：
+//   iABC:  C(8)  | B(8)  | k(1) | A(8) | Op(7)
+//   iABx:  Bx(17)               | A(8) | Op(7)
+//   iAsBx: sBx(17)              | A(8) | Op(7)
+//   isJ:   sJ(25)                      | Op(7)
+//   iAx:   Ax(25)                      | Op(7)
+// 操作码是 ByteCode 中各指令的序号，一经分配不再改变，新增的指令追加在最后
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ByteCode{
-    /// 从全局表读取变量到寄存器
-    GetGlobal(u8,u8),
-    /// 将寄存器值写回全局表
-    SetGlobal(u8,u8),
+    /// 从全局表读取变量到寄存器：(目标寄存器, 变量名常量)
+    GetGlobal(u8,u32),
+    /// 将寄存器值写回全局表：(变量名常量, 源寄存器)
+    SetGlobal(u32,u8),
     /// 从常数池设置全局变量
     SetGlobalConst(u8,u8),
     /// 用另一个全局变量的值设置全局变量
     SetGlobalGlobal(u8,u8),
+    /// 读取上值到寄存器：(目标寄存器, 上值索引)
+    GetUpvalue(u8,u8),
+    /// 将寄存器值写入上值：(上值索引, 源寄存器)
+    SetUpvalue(u8,u8),
     /// 将常数加载到寄存器
-    LoadConst(u8,u16),
+    LoadConst(u8,u32),
     /// 将 nil 加载到寄存器
     LoadNil(u8),
     /// 将布尔值加载到寄存器
@@ -22,15 +35,351 @@ pub enum ByteCode{
     LoadInt(u8,i16),
     /// 在寄存器间移动值
     Move(u8,u8),
-    /// 函数调用：(参数寄存器, 返回值数)
-    Call(u8,u8),
+    /// 函数调用：(函数寄存器, 参数个数+1, 期望返回值个数+1)
+    /// 参数位于函数寄存器之后，返回值从函数寄存器开始存放
+    /// 参数个数为 0 表示参数一直到栈顶（上一个多返回值调用或 ... 的结果）
+    /// 返回值个数为 0 表示保留全部返回值，并把栈顶设在最后一个返回值之后
+    Call(u8,u8,u8),
+    /// 函数返回：(第一个返回值寄存器, 返回值个数+1)，个数为 0 表示一直到栈顶
+    Return(u8,u8),
+    /// 加载可变参数 ...：(目标寄存器, 期望个数+1)，个数为 0 表示全部并设置栈顶
+    VarArgs(u8,u8),
+    /// 创建闭包：(目标寄存器, 子函数原型索引)
+    Closure(u8,u32),
+    /// 关闭寄存器及其以上被捕获的局部变量（离开作用域时）
+    Close(u8),
+    /// 读取表字段：(目标寄存器, 表寄存器, 字段名常量)
+    GetField(u8,u8,u8),
+    /// 读取表项：(目标寄存器, 表寄存器, 键寄存器)
+    GetTable(u8,u8,u8),
+    /// 读取整数键表项：(目标寄存器, 表寄存器, 整数键)，数组部分直接访问
+    GetInt(u8,u8,u8),
     // 创建新表：(目标寄存器, 数组部分大小, 哈希部分大小)
     NewTable(u8,u8,u8),
     // 表项设置：(表寄存器, 键寄存器, 值寄存器)[key]="vvv" key在栈上;
     SetTable(u8,u8,u8),
-    // 设置表字段：(表寄存器, 字段键寄存器, 字段值寄存器)x="hello", y="world" k是字符串常量; 
+    /// 设置整数键表项：(表寄存器, 整数键, 值寄存器)
+    SetInt(u8,u8,u8),
+    // 设置表字段：(表寄存器, 字段键寄存器, 字段值寄存器)x="hello", y="world" k是字符串常量;
     SetField(u8,u8,u8),
-    // 设置表数组部分：(表寄存器, 元素数量)
-    SetList(u8,u8),
+    // 设置表数组部分：(表寄存器, 元素数量, 批次)，数量为 0 表示一直到栈顶
+    // 第 n 批从数组下标 n*FIELDS_PER_FLUSH+1 开始写入；批次为 255 时真正的批次在其后的 ExtraArg 中
+    SetList(u8,u8,u8),
+    /// 方法调用准备：(目标寄存器, 对象寄存器, 方法名常量)
+    /// 把 对象[方法名] 放入目标寄存器，对象本身放入其后一个寄存器作为第一个参数
+    Self_(u8,u8,u8),
 
-}
\ No newline at end of file
+    /// 无条件跳转：相对于下一条指令的偏移，可以为负（循环、goto）
+    Jump(i32),
+    /// 条件跳转（and）：寄存器的值为假时跳过其后的若干条指令，保留该值作为结果
+    TestAndJump(u8,i32),
+    /// 条件跳转（or）：寄存器的值为真时跳过其后的若干条指令，保留该值作为结果
+    TestOrJump(u8,i32),
+
+    /// 数值 for 循环准备：(初值寄存器, 跳过的指令数)
+    /// 初值、终值、步长依次位于 A、A+1、A+2，循环变量位于 A+3；
+    /// 检查参数，整数循环时把迭代次数存入 A+1，不执行循环时跳到 ForLoop 之后
+    ForPrep(u8,u32),
+    /// 数值 for 循环迭代：(初值寄存器, 向后跳转的指令数)
+    /// 还有迭代时更新初值与循环变量并跳回循环体开头
+    ForLoop(u8,u32),
+    /// 泛型 for 循环调用迭代函数：(迭代函数寄存器, 循环变量个数)
+    /// 迭代函数、状态、控制变量、待关闭值依次位于 A 到 A+3，以 A+1、A+2 为参数调用 A，
+    /// 返回值调整为循环变量个数后放在 A+4 开始的循环变量中
+    TForCall(u8,u8),
+    /// 泛型 for 循环迭代：(迭代函数寄存器, 向后跳转的指令数)
+    /// 第一个循环变量 A+4 不为 nil 时把它存为控制变量 A+2 并跳回循环体开头
+    TForLoop(u8,u32),
+
+    // 一元运算：(目标寄存器, 操作数寄存器)
+    /// 取负 -
+    Neg(u8,u8),
+    /// 逻辑非 not
+    Not(u8,u8),
+    /// 按位取反 ~
+    BitNot(u8,u8),
+    /// 取长度 #
+    Len(u8,u8),
+
+    // 二元运算：(目标寄存器, 左操作数寄存器, 右操作数寄存器)
+    Add(u8,u8,u8),
+    Sub(u8,u8,u8),
+    Mul(u8,u8,u8),
+    Div(u8,u8,u8),
+    Idiv(u8,u8,u8),
+    Mod(u8,u8,u8),
+    Pow(u8,u8,u8),
+    BitAnd(u8,u8,u8),
+    BitXor(u8,u8,u8),
+    BitOr(u8,u8,u8),
+    ShiftL(u8,u8,u8),
+    ShiftR(u8,u8,u8),
+    /// 字符串连接 ..
+    Concat(u8,u8,u8),
+
+    // 比较运算，结果为布尔值；a > b 与 a >= b 交换操作数后使用 LesThan/LesEq
+    Equal(u8,u8,u8),
+    NotEq(u8,u8,u8),
+    LesThan(u8,u8,u8),
+    LesEq(u8,u8,u8),
+
+    /// 把寄存器标记为待关闭变量（local x <close>）：值须为 nil、false 或带有 __close 元方法
+    Tbc(u8),
+    /// 前一条指令的扩展参数，本身不执行
+    ExtraArg(u32),
+}
+
+/// 表构造器中每批写入数组部分的项数（SetList）
+pub const FIELDS_PER_FLUSH: usize = 50;
+
+/// 各字段的位数
+const SIZE_OP: u32 = 7;
+const SIZE_A: u32 = 8;
+const SIZE_B: u32 = 8;
+const SIZE_BX: u32 = 17;
+const SIZE_SJ: u32 = 25;
+
+/// 各字段的起始位
+const POS_A: u32 = SIZE_OP;
+const POS_K: u32 = POS_A + SIZE_A;
+const POS_B: u32 = POS_K + 1;
+const POS_C: u32 = POS_B + SIZE_B;
+const POS_BX: u32 = POS_K;
+const POS_SJ: u32 = POS_A;
+
+/// Bx 的最大值
+pub const MAX_BX: u32 = (1 << SIZE_BX) - 1;
+/// sBx 以加上此偏移后的无符号数保存，取值范围为 -OFFSET_SBX..=OFFSET_SBX+1
+pub const OFFSET_SBX: i32 = (MAX_BX >> 1) as i32;
+/// sJ 以加上此偏移后的无符号数保存，取值范围为 -OFFSET_SJ..=OFFSET_SJ+1
+pub const OFFSET_SJ: i32 = ((1 << SIZE_SJ) - 1) >> 1;
+/// B、C 字段（寄存器、常量索引等）的最大值
+pub const MAX_ARG_B: usize = (1 << SIZE_B) - 1;
+
+/// 取 x 的从 pos 开始的 size 位
+fn bits(x: u32, pos: u32, size: u32) -> u32 {
+    (x >> pos) & ((1 << size) - 1)
+}
+
+/// 编码后的 32 位指令
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Instruction(pub u32);
+
+impl Instruction {
+    /// iABC 格式
+    pub fn abc(op: u8, a: u8, b: u8, c: u8, k: bool) -> Self {
+        Instruction(
+            op as u32 | (a as u32) << POS_A | (k as u32) << POS_K | (b as u32) << POS_B | (c as u32) << POS_C,
+        )
+    }
+
+    /// iABx 格式，bx 不超过 MAX_BX
+    pub fn abx(op: u8, a: u8, bx: u32) -> Self {
+        debug_assert!(bx <= MAX_BX);
+        Instruction(op as u32 | (a as u32) << POS_A | bx << POS_BX)
+    }
+
+    /// iAsBx 格式，sbx 的范围与 17 位有符号数相当
+    pub fn asbx(op: u8, a: u8, sbx: i32) -> Self {
+        debug_assert!((-OFFSET_SBX..=OFFSET_SBX + 1).contains(&sbx));
+        Self::abx(op, a, (sbx + OFFSET_SBX) as u32)
+    }
+
+    /// isJ 格式，sj 的范围与 25 位有符号数相当（用于跳转距离较大的无条件跳转）
+    pub fn isj(op: u8, sj: i32) -> Self {
+        debug_assert!((-OFFSET_SJ..=OFFSET_SJ + 1).contains(&sj));
+        Instruction(op as u32 | ((sj + OFFSET_SJ) as u32) << POS_SJ)
+    }
+
+    /// iAx 格式，ax 不超过 25 位
+    pub fn iax(op: u8, ax: u32) -> Self {
+        debug_assert!(ax < 1 << SIZE_SJ);
+        Instruction(op as u32 | ax << POS_SJ)
+    }
+
+    pub fn op(self) -> u8 {
+        bits(self.0, 0, SIZE_OP) as u8
+    }
+    pub fn a(self) -> u8 {
+        bits(self.0, POS_A, SIZE_A) as u8
+    }
+    pub fn k(self) -> bool {
+        bits(self.0, POS_K, 1) != 0
+    }
+    pub fn b(self) -> u8 {
+        bits(self.0, POS_B, SIZE_B) as u8
+    }
+    pub fn c(self) -> u8 {
+        bits(self.0, POS_C, SIZE_B) as u8
+    }
+    pub fn bx(self) -> u32 {
+        bits(self.0, POS_BX, SIZE_BX)
+    }
+    pub fn sbx(self) -> i32 {
+        self.bx() as i32 - OFFSET_SBX
+    }
+    pub fn sj(self) -> i32 {
+        bits(self.0, POS_SJ, SIZE_SJ) as i32 - OFFSET_SJ
+    }
+    pub fn ax(self) -> u32 {
+        bits(self.0, POS_SJ, SIZE_SJ)
+    }
+
+    /// 解码为类型化的指令，虚拟机每执行一条指令都要解码一次
+    #[inline(always)]
+    pub fn decode(self) -> ByteCode {
+        match self.op() {
+            0 => ByteCode::GetGlobal(self.a(), self.bx()),
+            1 => ByteCode::SetGlobal(self.bx(), self.a()),
+            2 => ByteCode::SetGlobalConst(self.a(), self.b()),
+            3 => ByteCode::SetGlobalGlobal(self.a(), self.b()),
+            4 => ByteCode::GetUpvalue(self.a(), self.b()),
+            5 => ByteCode::SetUpvalue(self.a(), self.b()),
+            6 => ByteCode::LoadConst(self.a(), self.bx()),
+            7 => ByteCode::LoadNil(self.a()),
+            8 => ByteCode::LoadBool(self.a(), self.k()),
+            9 => ByteCode::LoadInt(self.a(), self.sbx() as i16),
+            10 => ByteCode::Move(self.a(), self.b()),
+            11 => ByteCode::Call(self.a(), self.b(), self.c()),
+            12 => ByteCode::Return(self.a(), self.b()),
+            13 => ByteCode::VarArgs(self.a(), self.b()),
+            14 => ByteCode::Closure(self.a(), self.bx()),
+            15 => ByteCode::Close(self.a()),
+            16 => ByteCode::GetField(self.a(), self.b(), self.c()),
+            17 => ByteCode::GetTable(self.a(), self.b(), self.c()),
+            18 => ByteCode::GetInt(self.a(), self.b(), self.c()),
+            19 => ByteCode::NewTable(self.a(), self.b(), self.c()),
+            20 => ByteCode::SetTable(self.a(), self.b(), self.c()),
+            21 => ByteCode::SetInt(self.a(), self.b(), self.c()),
+            22 => ByteCode::SetField(self.a(), self.b(), self.c()),
+            23 => ByteCode::SetList(self.a(), self.b(), self.c()),
+            24 => ByteCode::Self_(self.a(), self.b(), self.c()),
+            25 => ByteCode::Jump(self.sj()),
+            26 => ByteCode::TestAndJump(self.a(), self.sbx()),
+            27 => ByteCode::TestOrJump(self.a(), self.sbx()),
+            28 => ByteCode::ForPrep(self.a(), self.bx()),
+            29 => ByteCode::ForLoop(self.a(), self.bx()),
+            30 => ByteCode::TForCall(self.a(), self.c()),
+            31 => ByteCode::TForLoop(self.a(), self.bx()),
+            32 => ByteCode::Neg(self.a(), self.b()),
+            33 => ByteCode::Not(self.a(), self.b()),
+            34 => ByteCode::BitNot(self.a(), self.b()),
+            35 => ByteCode::Len(self.a(), self.b()),
+            36 => ByteCode::Add(self.a(), self.b(), self.c()),
+            37 => ByteCode::Sub(self.a(), self.b(), self.c()),
+            38 => ByteCode::Mul(self.a(), self.b(), self.c()),
+            39 => ByteCode::Div(self.a(), self.b(), self.c()),
+            40 => ByteCode::Idiv(self.a(), self.b(), self.c()),
+            41 => ByteCode::Mod(self.a(), self.b(), self.c()),
+            42 => ByteCode::Pow(self.a(), self.b(), self.c()),
+            43 => ByteCode::BitAnd(self.a(), self.b(), self.c()),
+            44 => ByteCode::BitXor(self.a(), self.b(), self.c()),
+            45 => ByteCode::BitOr(self.a(), self.b(), self.c()),
+            46 => ByteCode::ShiftL(self.a(), self.b(), self.c()),
+            47 => ByteCode::ShiftR(self.a(), self.b(), self.c()),
+            48 => ByteCode::Concat(self.a(), self.b(), self.c()),
+            49 => ByteCode::Equal(self.a(), self.b(), self.c()),
+            50 => ByteCode::NotEq(self.a(), self.b(), self.c()),
+            51 => ByteCode::LesThan(self.a(), self.b(), self.c()),
+            52 => ByteCode::LesEq(self.a(), self.b(), self.c()),
+            53 => ByteCode::Tbc(self.a()),
+            54 => ByteCode::ExtraArg(self.ax()),
+            op => panic!("invalid opcode {op}"),
+        }
+    }
+}
+
+impl std::fmt::Debug for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.decode())
+    }
+}
+
+impl ByteCode {
+    /// 参数能否放进指令的相应字段：Bx 不超过 MAX_BX，sBx、sJ 在各自的范围内
+    /// u8 参数总能放进 A、B、C 字段，由解析器保证其值不超过 255
+    pub fn fits(self) -> bool {
+        match self {
+            ByteCode::GetGlobal(_, bx)
+            | ByteCode::SetGlobal(bx, _)
+            | ByteCode::LoadConst(_, bx)
+            | ByteCode::Closure(_, bx)
+            | ByteCode::ForPrep(_, bx)
+            | ByteCode::ForLoop(_, bx)
+            | ByteCode::TForLoop(_, bx) => bx <= MAX_BX,
+            ByteCode::TestAndJump(_, sbx) | ByteCode::TestOrJump(_, sbx) => {
+                (-OFFSET_SBX..=OFFSET_SBX + 1).contains(&sbx)
+            }
+            ByteCode::Jump(sj) => (-OFFSET_SJ..=OFFSET_SJ + 1).contains(&sj),
+            ByteCode::ExtraArg(ax) => ax < 1 << SIZE_SJ,
+            _ => true,
+        }
+    }
+
+    /// 编码为 32 位指令
+    pub fn encode(self) -> Instruction {
+        match self {
+            ByteCode::GetGlobal(a, bx) => Instruction::abx(0, a, bx),
+            ByteCode::SetGlobal(bx, a) => Instruction::abx(1, a, bx),
+            ByteCode::SetGlobalConst(a, b) => Instruction::abc(2, a, b, 0, false),
+            ByteCode::SetGlobalGlobal(a, b) => Instruction::abc(3, a, b, 0, false),
+            ByteCode::GetUpvalue(a, b) => Instruction::abc(4, a, b, 0, false),
+            ByteCode::SetUpvalue(a, b) => Instruction::abc(5, a, b, 0, false),
+            ByteCode::LoadConst(a, bx) => Instruction::abx(6, a, bx),
+            ByteCode::LoadNil(a) => Instruction::abc(7, a, 0, 0, false),
+            ByteCode::LoadBool(a, k) => Instruction::abc(8, a, 0, 0, k),
+            ByteCode::LoadInt(a, sbx) => Instruction::asbx(9, a, sbx as i32),
+            ByteCode::Move(a, b) => Instruction::abc(10, a, b, 0, false),
+            ByteCode::Call(a, b, c) => Instruction::abc(11, a, b, c, false),
+            ByteCode::Return(a, b) => Instruction::abc(12, a, b, 0, false),
+            ByteCode::VarArgs(a, b) => Instruction::abc(13, a, b, 0, false),
+            ByteCode::Closure(a, bx) => Instruction::abx(14, a, bx),
+            ByteCode::Close(a) => Instruction::abc(15, a, 0, 0, false),
+            ByteCode::GetField(a, b, c) => Instruction::abc(16, a, b, c, false),
+            ByteCode::GetTable(a, b, c) => Instruction::abc(17, a, b, c, false),
+            ByteCode::GetInt(a, b, c) => Instruction::abc(18, a, b, c, false),
+            ByteCode::NewTable(a, b, c) => Instruction::abc(19, a, b, c, false),
+            ByteCode::SetTable(a, b, c) => Instruction::abc(20, a, b, c, false),
+            ByteCode::SetInt(a, b, c) => Instruction::abc(21, a, b, c, false),
+            ByteCode::SetField(a, b, c) => Instruction::abc(22, a, b, c, false),
+            ByteCode::SetList(a, b, c) => Instruction::abc(23, a, b, c, false),
+            ByteCode::Self_(a, b, c) => Instruction::abc(24, a, b, c, false),
+            ByteCode::Jump(sj) => Instruction::isj(25, sj),
+            ByteCode::TestAndJump(a, sbx) => Instruction::asbx(26, a, sbx),
+            ByteCode::TestOrJump(a, sbx) => Instruction::asbx(27, a, sbx),
+            ByteCode::ForPrep(a, bx) => Instruction::abx(28, a, bx),
+            ByteCode::ForLoop(a, bx) => Instruction::abx(29, a, bx),
+            ByteCode::TForCall(a, c) => Instruction::abc(30, a, 0, c, false),
+            ByteCode::TForLoop(a, bx) => Instruction::abx(31, a, bx),
+            ByteCode::Neg(a, b) => Instruction::abc(32, a, b, 0, false),
+            ByteCode::Not(a, b) => Instruction::abc(33, a, b, 0, false),
+            ByteCode::BitNot(a, b) => Instruction::abc(34, a, b, 0, false),
+            ByteCode::Len(a, b) => Instruction::abc(35, a, b, 0, false),
+            ByteCode::Add(a, b, c) => Instruction::abc(36, a, b, c, false),
+            ByteCode::Sub(a, b, c) => Instruction::abc(37, a, b, c, false),
+            ByteCode::Mul(a, b, c) => Instruction::abc(38, a, b, c, false),
+            ByteCode::Div(a, b, c) => Instruction::abc(39, a, b, c, false),
+            ByteCode::Idiv(a, b, c) => Instruction::abc(40, a, b, c, false),
+            ByteCode::Mod(a, b, c) => Instruction::abc(41, a, b, c, false),
+            ByteCode::Pow(a, b, c) => Instruction::abc(42, a, b, c, false),
+            ByteCode::BitAnd(a, b, c) => Instruction::abc(43, a, b, c, false),
+            ByteCode::BitXor(a, b, c) => Instruction::abc(44, a, b, c, false),
+            ByteCode::BitOr(a, b, c) => Instruction::abc(45, a, b, c, false),
+            ByteCode::ShiftL(a, b, c) => Instruction::abc(46, a, b, c, false),
+            ByteCode::ShiftR(a, b, c) => Instruction::abc(47, a, b, c, false),
+            ByteCode::Concat(a, b, c) => Instruction::abc(48, a, b, c, false),
+            ByteCode::Equal(a, b, c) => Instruction::abc(49, a, b, c, false),
+            ByteCode::NotEq(a, b, c) => Instruction::abc(50, a, b, c, false),
+            ByteCode::LesThan(a, b, c) => Instruction::abc(51, a, b, c, false),
+            ByteCode::LesEq(a, b, c) => Instruction::abc(52, a, b, c, false),
+            ByteCode::Tbc(a) => Instruction::abc(53, a, 0, 0, false),
+            ByteCode::ExtraArg(ax) => Instruction::iax(54, ax),
+        }
+    }
+}
+
+impl From<ByteCode> for Instruction {
+    fn from(code: ByteCode) -> Self {
+        code.encode()
+    }
+}
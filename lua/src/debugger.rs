@@ -0,0 +1,186 @@
+// lua --debug 使用的命令行调试器
+// 通过行钩子在每一行执行之前决定是否停下：
+// - 单步（step）在任意新的一行停下，包括进入被调用的函数
+// - 单步跳过（next）只在调用层数不超过开始时的层数时停下
+// - 继续（continue）只在断点处停下
+// 停下后读取并执行调试命令，直到恢复执行
+
+use lua::value::Value;
+use lua::vm::{ExeState, HookEvent, LuaError, MASK_LINE};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+const HELP: &str = "\
+commands:
+  s, step          execute until the next line, stepping into calls
+  n, next          execute until the next line in this function
+  c, continue      execute until a breakpoint
+  b, break N       set a breakpoint at line N
+  d, delete N      delete the breakpoint at line N
+  l, locals        show local variables of the current function
+  p, print NAME    show the value of a local or global variable
+  ls, list         show the source around the current line
+  bt, backtrace    show the call stack
+  q, quit          exit the program
+  h, help          show this help
+an empty line repeats the last command";
+
+/// 执行方式
+enum Mode {
+    Step,
+    /// 调用层数不超过该值时停下
+    Next(usize),
+    Continue,
+}
+
+/// 调试器状态
+/// - `file`: 被调试的脚本，断点与源代码显示只针对该文件
+/// - `lines`: 脚本的源代码行
+struct Debugger {
+    file: String,
+    lines: Vec<String>,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+    last_cmd: String,
+}
+
+/// 在 state 上安装调试器，从脚本的第一行开始单步执行
+pub fn attach(state: &mut ExeState, file: &str) {
+    let lines = fs::read_to_string(file)
+        .map(|s| s.lines().map(String::from).collect())
+        .unwrap_or_default();
+    let mut debugger = Debugger {
+        file: file.to_string(),
+        lines,
+        breakpoints: BTreeSet::new(),
+        mode: Mode::Step,
+        last_cmd: String::new(),
+    };
+    println!("debugging {file}, type 'h' for help");
+    state.set_hook(MASK_LINE, 0, move |state, event| match event {
+        HookEvent::Line(line) => debugger.on_line(state, line),
+        _ => Ok(()),
+    });
+}
+
+impl Debugger {
+    /// 行事件：判断是否停下，停下时显示当前行并进入命令循环
+    fn on_line(&mut self, state: &mut ExeState, line: usize) -> Result<(), LuaError> {
+        let src = state.get_info(0).map(|info| info.short_src).unwrap_or_default();
+        let at_breakpoint = src == self.file && self.breakpoints.contains(&line);
+        let stop = match self.mode {
+            Mode::Step => true,
+            Mode::Next(depth) => state.call_depth() <= depth,
+            Mode::Continue => false,
+        };
+        if !stop && !at_breakpoint {
+            return Ok(());
+        }
+        if at_breakpoint {
+            println!("breakpoint at {src}:{line}");
+        }
+        self.show_line(&src, line);
+        self.command_loop(state, &src, line);
+        Ok(())
+    }
+
+    /// 显示一行源代码，非被调试脚本的代码只显示位置
+    fn show_line(&self, src: &str, line: usize) {
+        match self.source_line(src, line) {
+            Some(text) => println!("{src}:{line}: {text}"),
+            None => println!("{src}:{line}"),
+        }
+    }
+
+    fn source_line(&self, src: &str, line: usize) -> Option<&str> {
+        if src != self.file {
+            return None;
+        }
+        self.lines.get(line.checked_sub(1)?).map(|s| s.as_str())
+    }
+
+    /// 读取并执行命令，直到遇到恢复执行的命令
+    fn command_loop(&mut self, state: &mut ExeState, src: &str, line: usize) {
+        let stdin = io::stdin();
+        loop {
+            print!("(ldb) ");
+            io::stdout().flush().ok();
+            let mut input = String::new();
+            if stdin.lock().read_line(&mut input).unwrap_or(0) == 0 {
+                // 输入结束时不再停下，执行到底
+                self.breakpoints.clear();
+                self.mode = Mode::Continue;
+                return;
+            }
+            let input = match input.trim() {
+                "" => self.last_cmd.clone(),
+                cmd => cmd.to_string(),
+            };
+            self.last_cmd = input.clone();
+            let mut words = input.split_whitespace();
+            let cmd = words.next().unwrap_or("");
+            let arg = words.next();
+            match cmd {
+                "s" | "step" => {
+                    self.mode = Mode::Step;
+                    return;
+                }
+                "n" | "next" => {
+                    self.mode = Mode::Next(state.call_depth());
+                    return;
+                }
+                "c" | "continue" => {
+                    self.mode = Mode::Continue;
+                    return;
+                }
+                "b" | "break" => match arg.and_then(|a| a.parse().ok()) {
+                    Some(n) => {
+                        self.breakpoints.insert(n);
+                        println!("breakpoint set at {}:{n}", self.file);
+                    }
+                    None => println!("usage: b LINE"),
+                },
+                "d" | "delete" => match arg.and_then(|a| a.parse::<usize>().ok()) {
+                    Some(n) if self.breakpoints.remove(&n) => println!("breakpoint at line {n} deleted"),
+                    Some(n) => println!("no breakpoint at line {n}"),
+                    None => println!("usage: d LINE"),
+                },
+                "l" | "locals" => {
+                    for (name, value) in locals(state) {
+                        println!("  {name} = {value}");
+                    }
+                }
+                "p" | "print" => match arg {
+                    Some(name) => {
+                        let value = locals(state)
+                            .into_iter()
+                            .rev()
+                            .find(|(n, _)| n == name)
+                            .map_or_else(|| state.get_global(name), |(_, v)| v);
+                        println!("{name} = {value}");
+                    }
+                    None => println!("usage: p NAME"),
+                },
+                "ls" | "list" => {
+                    for n in line.saturating_sub(5).max(1)..=line + 5 {
+                        if let Some(text) = self.source_line(src, n) {
+                            let mark = if n == line { "->" } else { "  " };
+                            println!("{mark}{n:4}  {text}");
+                        }
+                    }
+                }
+                "bt" | "backtrace" => println!("{}", state.traceback(0)),
+                "q" | "quit" => process::exit(0),
+                "h" | "help" => println!("{HELP}"),
+                _ => println!("unknown command '{cmd}', type 'h' for help"),
+            }
+        }
+    }
+}
+
+/// 当前函数中有效的局部变量，按声明顺序排列
+fn locals(state: &ExeState) -> Vec<(String, Value)> {
+    (1..).map_while(|n| state.get_local(0, n)).collect()
+}
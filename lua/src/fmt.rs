@@ -0,0 +1,464 @@
+// Lua 源代码格式化
+// 把源代码解析为 AST 后重新打印：
+// - 每级缩进 4 个空格，每条语句一行，二元运算符两侧、逗号之后加空格，去掉多余的分号
+// - 字符串与数字常量保留源代码中的写法，括号表达式原样保留，f "str" 与 f {t} 统一写成 f("str")、f({t})
+// - 源代码中跨行的表构造器每项一行并加上末尾的逗号，单行的表保持单行、不加末尾逗号
+// - 注释按所在位置保留：独占一行的注释放在其后的语句之前，行尾注释仍放在所在语句的行尾；
+//   语句之间的空行最多保留一行
+// 只依据源代码的换行决定布局，不按行宽折行，因此格式化的结果再次格式化保持不变
+
+use crate::ast::{self, Block, Exp, ExpKind, Field, FieldKind, FuncBody, Stat, StatKind};
+use crate::lex::{ParseError, Span};
+use std::io::Cursor;
+
+const INDENT: &str = "    ";
+
+/// 格式化一段 Lua 源代码，源代码有语法错误时返回错误
+pub fn format(source: &[u8]) -> Result<Vec<u8>, ParseError> {
+    let (block, comments) = ast::parse_with_comments(Cursor::new(source))?;
+    let mut f = Formatter {
+        src: source,
+        comments,
+        icomment: 0,
+        out: Vec::new(),
+        indent: 0,
+    };
+    f.list(&block.stats, source.len(), |s| s.span, Formatter::stat);
+    Ok(f.out)
+}
+
+/// 格式化状态
+/// - `src`: 源代码，用于取出常量与注释的原始写法
+/// - `comments`: 所有注释的范围，按位置排序
+/// - `icomment`: 第一个尚未输出的注释
+/// - `indent`: 当前的缩进级别
+struct Formatter<'a> {
+    src: &'a [u8],
+    comments: Vec<Span>,
+    icomment: usize,
+    out: Vec<u8>,
+    indent: usize,
+}
+
+/// 注释是否为行注释（而不是 --[[ ]] 形式的长注释），行注释之后必须换行
+fn is_line_comment(text: &[u8]) -> bool {
+    let rest = &text[2..];
+    let level = rest.iter().skip(1).take_while(|&&c| c == b'=').count();
+    !(rest.first() == Some(&b'[') && rest.get(level + 1) == Some(&b'['))
+}
+
+impl Formatter<'_> {
+    fn write(&mut self, s: &str) {
+        self.out.extend_from_slice(s.as_bytes());
+    }
+
+    /// 原样输出源代码中 span 范围的内容
+    fn write_raw(&mut self, span: Span) {
+        self.out.extend_from_slice(&self.src[span.start..span.end]);
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.out.extend_from_slice(INDENT.as_bytes());
+        }
+    }
+
+    /// 下一个尚未输出的注释
+    fn pending(&self) -> Option<Span> {
+        self.comments.get(self.icomment).copied()
+    }
+
+    /// 源代码中 offset 处所在的行号
+    fn line_of(&self, offset: usize) -> usize {
+        self.src[..offset].iter().filter(|&&c| c == b'\n').count() + 1
+    }
+
+    /// 从 from 开始跳过空白、注释与其他单词，找到关键字 kw 的位置，
+    /// 用于确定 AST 中没有记录的 else、elseif 等关键字的位置
+    fn find_keyword(&self, from: usize, kw: &str) -> usize {
+        let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_';
+        let mut pos = from;
+        while pos < self.src.len() {
+            if let Some(c) = self.comments.iter().find(|c| c.start == pos) {
+                pos = c.end;
+                continue;
+            }
+            let rest = &self.src[pos..];
+            let boundary = pos == 0 || !is_word(self.src[pos - 1]);
+            if boundary && rest.starts_with(kw.as_bytes()) && !rest.get(kw.len()).is_some_and(|&c| is_word(c)) {
+                return pos;
+            }
+            pos += 1;
+        }
+        pos
+    }
+
+    /// 如果与上一项之间有空行（prev 为上一项的结束行，None 表示列表开头），输出一个空行
+    fn blank_line(&mut self, prev: Option<usize>, line: usize) {
+        if prev.is_some_and(|p| line > p + 1) {
+            self.out.push(b'\n');
+        }
+    }
+
+    /// 输出起始位置在 limit 之前的注释，每个独占一行，返回最后一个注释的结束行
+    fn own_line_comments(&mut self, limit: usize, mut prev: Option<usize>) -> Option<usize> {
+        while let Some(c) = self.pending().filter(|c| c.start < limit) {
+            self.blank_line(prev, c.line);
+            self.write_indent();
+            self.write_raw(c);
+            self.out.push(b'\n');
+            self.icomment += 1;
+            prev = Some(c.end_line);
+        }
+        prev
+    }
+
+    /// 输出一项之后的行尾注释：起始位置在该项结束之前的（嵌在表达式内部的），
+    /// 以及与该项结束在同一行、位于下一项 next 之前的；返回最后输出内容的结束行
+    fn trailing_comments(&mut self, end: usize, end_line: usize, next: usize) -> usize {
+        let mut last = end_line;
+        let mut after_line_comment = false;
+        while let Some(c) = self.pending() {
+            if !(c.start < end || (c.line == end_line && c.start < next)) {
+                break;
+            }
+            if after_line_comment {
+                self.out.push(b'\n');
+                self.write_indent();
+            } else {
+                self.out.push(b' ');
+            }
+            self.write_raw(c);
+            after_line_comment = is_line_comment(&self.src[c.start..c.end]);
+            self.icomment += 1;
+            last = c.end_line;
+        }
+        last
+    }
+
+    /// 逐行输出语句或表字段，保留其间的注释与空行；close 为结束该列表的 Token（end、} 等）的位置
+    fn list<T>(&mut self, items: &[T], close: usize, span: impl Fn(&T) -> Span, print: impl Fn(&mut Self, &T)) {
+        let mut prev = None;
+        for (i, item) in items.iter().enumerate() {
+            let sp = span(item);
+            prev = self.own_line_comments(sp.start, prev);
+            self.blank_line(prev, sp.line);
+            self.write_indent();
+            print(self, item);
+            let next = items.get(i + 1).map_or(close, |it| span(it).start);
+            prev = Some(self.trailing_comments(sp.end, sp.end_line, next));
+            self.out.push(b'\n');
+        }
+        self.own_line_comments(close, prev);
+    }
+
+    /// 输出代码块的头部之后的部分：头部的行尾注释、缩进一级的代码块，以及结束的关键字（不含）之前的缩进
+    /// header_line 为头部结束的行，close 为结束代码块的关键字的位置
+    fn body(&mut self, block: &Block, header_line: usize, close: usize) {
+        let next = block.stats.first().map_or(close, |s| s.span.start);
+        self.trailing_comments(0, header_line, next);
+        self.out.push(b'\n');
+        self.indent += 1;
+        self.list(&block.stats, close, |s| s.span, Formatter::stat);
+        self.indent -= 1;
+        self.write_indent();
+    }
+
+    /// 以 end 结束的代码块：代码块为空且其中没有注释时与头部写在同一行
+    fn body_end(&mut self, block: &Block, header_line: usize, end: usize) {
+        if block.stats.is_empty() && self.pending().is_none_or(|c| c.start >= end) {
+            self.write(" end");
+            return;
+        }
+        self.body(block, header_line, end);
+        self.write("end");
+    }
+
+    fn stat(&mut self, stat: &Stat) {
+        // 以括号开头的语句前加分号，避免与上一行连成函数调用
+        if self.src[stat.span.start] == b'(' {
+            self.write(";");
+        }
+        let end = stat.span.end.saturating_sub("end".len());
+        match &stat.kind {
+            StatKind::Local(vars, exps) => {
+                self.write("local ");
+                for (i, (name, attrib)) in vars.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ");
+                    }
+                    self.write(name);
+                    match attrib {
+                        ast::Attrib::Plain => (),
+                        ast::Attrib::Const => self.write(" <const>"),
+                        ast::Attrib::Close => self.write(" <close>"),
+                    }
+                }
+                if !exps.is_empty() {
+                    self.write(" = ");
+                    self.exps(exps);
+                }
+            }
+            StatKind::LocalFunction(name, func) => {
+                self.write("local function ");
+                self.write(name);
+                self.funcbody(func);
+            }
+            StatKind::Function(name, func) => {
+                self.write("function ");
+                self.write(&name.path.join("."));
+                if let Some(method) = &name.method {
+                    self.write(":");
+                    self.write(method);
+                }
+                self.funcbody(func);
+            }
+            StatKind::Assign(vars, exps) => {
+                self.exps(vars);
+                self.write(" = ");
+                self.exps(exps);
+            }
+            StatKind::Call(call) => self.exp(call),
+            StatKind::Do(block) => {
+                self.write("do");
+                self.body_end(block, stat.span.line, end);
+            }
+            StatKind::While(cond, block) => {
+                self.write("while ");
+                self.exp(cond);
+                self.write(" do");
+                self.body_end(block, cond.span.end_line, end);
+            }
+            StatKind::Repeat(block, cond) => {
+                self.write("repeat");
+                let until = self.find_keyword(block.span.end.max(stat.span.start + "repeat".len()), "until");
+                self.body(block, stat.span.line, until);
+                self.write("until ");
+                self.exp(cond);
+            }
+            StatKind::If(conds, else_block) => {
+                for (i, (cond, block)) in conds.iter().enumerate() {
+                    self.write(if i == 0 { "if " } else { "elseif " });
+                    self.exp(cond);
+                    self.write(" then");
+                    let from = block.span.end.max(cond.span.end);
+                    let close = match (conds.get(i + 1), else_block) {
+                        (Some(_), _) => self.find_keyword(from, "elseif"),
+                        (None, Some(_)) => self.find_keyword(from, "else"),
+                        (None, None) => end,
+                    };
+                    self.body(block, cond.span.end_line, close);
+                }
+                if let Some(block) = else_block {
+                    let from = conds.last().map(|(c, b)| b.span.end.max(c.span.end)).unwrap();
+                    let line = self.line_of(self.find_keyword(from, "else"));
+                    self.write("else");
+                    self.body(block, line, end);
+                }
+                self.write("end");
+            }
+            StatKind::NumericFor {
+                var,
+                start,
+                limit,
+                step,
+                body,
+            } => {
+                self.write("for ");
+                self.write(var);
+                self.write(" = ");
+                self.exp(start);
+                self.write(", ");
+                self.exp(limit);
+                if let Some(step) = step {
+                    self.write(", ");
+                    self.exp(step);
+                }
+                self.write(" do");
+                let header_line = step.as_ref().unwrap_or(limit).span.end_line;
+                self.body_end(body, header_line, end);
+            }
+            StatKind::GenericFor { vars, exps, body } => {
+                self.write("for ");
+                self.write(&vars.join(", "));
+                self.write(" in ");
+                self.exps(exps);
+                self.write(" do");
+                let header_line = exps.last().unwrap().span.end_line;
+                self.body_end(body, header_line, end);
+            }
+            StatKind::Return(exps) => {
+                self.write("return");
+                if !exps.is_empty() {
+                    self.write(" ");
+                    self.exps(exps);
+                }
+            }
+            StatKind::Break => self.write("break"),
+            StatKind::Goto(name) => {
+                self.write("goto ");
+                self.write(name);
+            }
+            StatKind::Label(name) => {
+                self.write("::");
+                self.write(name);
+                self.write("::");
+            }
+        }
+    }
+
+    /// 参数列表与函数体
+    fn funcbody(&mut self, func: &FuncBody) {
+        self.write("(");
+        let mut params = func.params.clone();
+        if func.is_vararg {
+            params.push("...".to_string());
+        }
+        self.write(&params.join(", "));
+        self.write(")");
+        self.body_end(&func.body, func.span.line, func.span.end - "end".len());
+    }
+
+    fn exps(&mut self, exps: &[Exp]) {
+        for (i, exp) in exps.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.exp(exp);
+        }
+    }
+
+    /// 方括号中的表达式：以长字符串开头时加空格，避免 [[ 被当作长字符串的开始
+    fn bracketed(&mut self, exp: &Exp) {
+        let long = self.src[exp.span.start] == b'[';
+        self.write(if long { "[ " } else { "[" });
+        self.exp(exp);
+        self.write(if long { " ]" } else { "]" });
+    }
+
+    fn exp(&mut self, exp: &Exp) {
+        match &exp.kind {
+            ExpKind::Nil => self.write("nil"),
+            ExpKind::True => self.write("true"),
+            ExpKind::False => self.write("false"),
+            ExpKind::Dots => self.write("..."),
+            ExpKind::Integer(_) | ExpKind::Float(_) | ExpKind::String(_) => self.write_raw(exp.span),
+            ExpKind::Function(func) => {
+                self.write("function");
+                self.funcbody(func);
+            }
+            ExpKind::Table(fields) => self.table(fields, exp.span),
+            ExpKind::Binary(op, left, right) => {
+                self.exp(left);
+                self.write(" ");
+                self.write(op.symbol());
+                self.write(" ");
+                self.exp(right);
+            }
+            ExpKind::Unary(op, operand) => {
+                self.write(op.symbol());
+                // not 之后必须有空格；- -x 不能写成注释 --x
+                if *op == ast::UnOp::Not || self.src[operand.span.start] == b'-' {
+                    self.write(" ");
+                }
+                self.exp(operand);
+            }
+            ExpKind::Name(name) => self.write(name),
+            ExpKind::Index(t, key) => {
+                self.exp(t);
+                self.bracketed(key);
+            }
+            ExpKind::Field(t, name) => {
+                self.exp(t);
+                self.write(".");
+                self.write(name);
+            }
+            ExpKind::Call(func, args) => {
+                self.exp(func);
+                self.write("(");
+                self.exps(args);
+                self.write(")");
+            }
+            ExpKind::Method(obj, name, args) => {
+                self.exp(obj);
+                self.write(":");
+                self.write(name);
+                self.write("(");
+                self.exps(args);
+                self.write(")");
+            }
+            ExpKind::Paren(inner) => {
+                self.write("(");
+                self.exp(inner);
+                self.write(")");
+            }
+        }
+    }
+
+    /// 表构造器：源代码中写在一行的保持一行，否则每项一行并加上末尾的逗号
+    fn table(&mut self, fields: &[Field], span: Span) {
+        let close = span.end - 1;
+        let has_comment = self.pending().is_some_and(|c| c.start < close);
+        let multiline = span.line != span.end_line || fields.iter().any(|f| field_multiline(&f.kind));
+        if (!multiline || fields.is_empty()) && !has_comment {
+            self.write("{");
+            for (i, field) in fields.iter().enumerate() {
+                if i > 0 {
+                    self.write(", ");
+                }
+                self.field(field);
+            }
+            self.write("}");
+            return;
+        }
+        self.write("{");
+        let next = fields.first().map_or(close, |f| f.span.start);
+        self.trailing_comments(0, span.line, next);
+        self.out.push(b'\n');
+        self.indent += 1;
+        self.list(fields, close, |f| f.span, |f, field| {
+            f.field(field);
+            f.write(",");
+        });
+        self.indent -= 1;
+        self.write_indent();
+        self.write("}");
+    }
+
+    fn field(&mut self, field: &Field) {
+        match &field.kind {
+            FieldKind::Positional(exp) => self.exp(exp),
+            FieldKind::Named(name, exp) => {
+                self.write(name);
+                self.write(" = ");
+                self.exp(exp);
+            }
+            FieldKind::Keyed(key, exp) => {
+                self.bracketed(key);
+                self.write(" = ");
+                self.exp(exp);
+            }
+        }
+    }
+}
+
+/// 表达式格式化后是否跨行：包含非空的函数体，或者包含跨行的表构造器
+fn multiline(exp: &Exp) -> bool {
+    match &exp.kind {
+        ExpKind::Function(func) => !func.body.stats.is_empty(),
+        ExpKind::Table(fields) => {
+            !fields.is_empty() && (exp.span.line != exp.span.end_line || fields.iter().any(|f| field_multiline(&f.kind)))
+        }
+        ExpKind::Binary(_, left, right) | ExpKind::Index(left, right) => multiline(left) || multiline(right),
+        ExpKind::Unary(_, e) | ExpKind::Paren(e) | ExpKind::Field(e, _) => multiline(e),
+        ExpKind::Call(e, args) | ExpKind::Method(e, _, args) => multiline(e) || args.iter().any(multiline),
+        _ => false,
+    }
+}
+
+fn field_multiline(field: &FieldKind) -> bool {
+    match field {
+        FieldKind::Positional(v) | FieldKind::Named(_, v) => multiline(v),
+        FieldKind::Keyed(k, v) => multiline(k) || multiline(v),
+    }
+}
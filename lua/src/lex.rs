@@ -3,8 +3,8 @@
 // 支持识别 Lua 的所有关键字、操作符、常数（数字、字符串）与标识符
 // 使用单字符向前查看（lookahead）机制实现高效的多字符 Token 识别
 
-use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
 use std::mem;
 
 /// Lua Token 类型枚举
@@ -77,7 +77,7 @@ pub enum Token {
     // 常数类型
     Integer(i64),   // 整数常量
     Float(f64),     // 浮点数常量
-    String(String), // 字符串常量（已解析，不含引号）
+    String(Vec<u8>), // 字符串常量（已解析，不含引号），可以包含任意字节
 
     // 标识符（变量名或表键名）
     Name(String),
@@ -86,131 +86,365 @@ pub enum Token {
     Eos,
 }
 
+/// 按 Lua 源代码的写法显示 Token，用于语法错误信息，如 "unexpected symbol near '='"
+/// 与 C 实现一致，文件尾显示为不带引号的 <eof>
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Token::And => "and",
+            Token::Break => "break",
+            Token::Do => "do",
+            Token::Else => "else",
+            Token::Elseif => "elseif",
+            Token::End => "end",
+            Token::False => "false",
+            Token::For => "for",
+            Token::Function => "function",
+            Token::Goto => "goto",
+            Token::If => "if",
+            Token::In => "in",
+            Token::Local => "local",
+            Token::Nil => "nil",
+            Token::Not => "not",
+            Token::Or => "or",
+            Token::Repeat => "repeat",
+            Token::Return => "return",
+            Token::Then => "then",
+            Token::True => "true",
+            Token::Until => "until",
+            Token::While => "while",
+            Token::Add => "+",
+            Token::Sub => "-",
+            Token::Mul => "*",
+            Token::Div => "/",
+            Token::Mod => "%",
+            Token::Pow => "^",
+            Token::Len => "#",
+            Token::BitAnd => "&",
+            Token::BitXor => "~",
+            Token::BitOr => "|",
+            Token::ShiftL => "<<",
+            Token::ShiftR => ">>",
+            Token::Idiv => "//",
+            Token::Equal => "==",
+            Token::NotEq => "~=",
+            Token::LesEq => "<=",
+            Token::GreEq => ">=",
+            Token::Less => "<",
+            Token::Greater => ">",
+            Token::Assign => "=",
+            Token::ParL => "(",
+            Token::ParR => ")",
+            Token::CurlyL => "{",
+            Token::CurlyR => "}",
+            Token::SqurL => "[",
+            Token::SqurR => "]",
+            Token::DoubColon => "::",
+            Token::SemiColon => ";",
+            Token::Colon => ":",
+            Token::Comma => ",",
+            Token::Dot => ".",
+            Token::Concat => "..",
+            Token::Dots => "...",
+            Token::Integer(i) => return write!(f, "'{i}'"),
+            Token::Float(x) => return write!(f, "'{x:?}'"),
+            Token::String(b) => return write!(f, "'\"{}\"'", String::from_utf8_lossy(b)),
+            Token::Name(name) => name,
+            Token::Eos => return write!(f, "<eof>"),
+        };
+        write!(f, "'{s}'")
+    }
+}
+
+/// 词法/语法错误：出错的行号与错误信息
+/// span 为出错处的 Token（词法错误时为已读取的部分），由代码生成阶段报告的错误没有
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub line: usize,
+    pub msg: String,
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.msg)
+    }
+}
+
+/// 源代码中的一段范围：起止字节偏移 [start, end) 与起止行号
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub end_line: usize,
+}
+
 /// Lua 词法分析器结构体
 /// - `input`: 输入文件流，用于逐字符读取源代码
 /// - `ahead`: 预存的下一个 Token（向前查看机制），用于 `peek()` 和高效的 `next()` 实现
+/// - `line`: 当前行号（从 1 开始），用于错误信息
+/// - `last`: 最近读取的字符，回退时据此修正行号
+/// - `eof`: 最近一次读取到达了输入末尾（此时读到的字符记为 0，与源代码中的 NUL 字节区分），
+///   读到的字节已回退时也为真，不能再次回退
+/// - `io_error`: 读取或回退输入时出的错，视同到达输入末尾，由 do_next() 报告
+/// - `offset`: 已读取的字节数，即下一个字节的偏移
+/// - `span`: 正在读取的 Token 的范围
+/// - `ahead_span`: 预存 Token 的范围
+/// - `token_span`: 最近由 next() 取出的 Token 的范围，其结束行号用于生成字节码的行号表
+/// - `comments`: 开启 keep_comments() 后记录跳过的注释的范围
 #[derive(Debug)]
 pub struct Lex <R>{
     input: R,
     ahead: Token,
+    line: usize,
+    last: u8,
+    eof: bool,
+    io_error: Option<io::Error>,
+    offset: usize,
+    span: Span,
+    ahead_span: Span,
+    token_span: Span,
+    comments: Option<Vec<Span>>,
 }
 
-impl<R:Read> Lex<R> {
+impl<R: Read + Seek> Lex<R> {
     /// 创建新的词法分析器实例，以指定的文件为输入源
     pub fn new(input: R) -> Self {
         Lex {
             input,
             ahead: Token::Eos,
+            line: 1,
+            last: b'\0',
+            eof: true,
+            io_error: None,
+            offset: 0,
+            span: Span::default(),
+            ahead_span: Span::default(),
+            token_span: Span::default(),
+            comments: None,
+        }
+    }
+
+    /// 记录此后跳过的注释，供格式化等需要保留注释的工具使用
+    pub fn keep_comments(&mut self) {
+        self.comments = Some(Vec::new());
+    }
+
+    /// 取出已记录的注释的范围（不含行注释末尾的换行）
+    pub fn take_comments(&mut self) -> Vec<Span> {
+        self.comments.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// 当前行号
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// 最近取出的 Token 所在的行号（跨行的长字符串为其结束行）
+    pub fn token_line(&self) -> usize {
+        self.token_span.end_line
+    }
+
+    /// 最近取出的 Token 的范围
+    pub fn token_span(&self) -> Span {
+        self.token_span
+    }
+
+    /// 预存 Token 的范围，在 peek() 之后有效
+    pub fn ahead_span(&self) -> Span {
+        self.ahead_span
+    }
+
+    /// 构造当前位置的错误，位置为最近读取的 Token
+    pub fn error(&self, msg: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.line,
+            msg: msg.into(),
+            span: Some(Span {
+                start: self.span.start,
+                end: self.offset.max(self.span.start),
+                line: self.span.line,
+                end_line: self.line,
+            }),
         }
     }
 
     /// 获取下一个 Token
     /// 如果预存 Token（ahead）不为 Eos，则返回预存 Token 并清空预存区；
     /// 否则从文件中读取新 Token
-    pub fn next(&mut self) -> Token {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Token, ParseError> {
         if self.ahead == Token::Eos {
-            self.do_next()
+            let t = self.do_next()?;
+            self.token_span = self.span;
+            Ok(t)
         } else {
-            mem::replace(&mut self.ahead, Token::Eos)
+            self.token_span = self.ahead_span;
+            Ok(mem::replace(&mut self.ahead, Token::Eos))
         }
     }
 
     /// 查看下一个 Token 而不消费它（向前查看 / Lookahead）
     /// 将 Token 缓存在 ahead 中以供后续 next() 使用
-    pub fn peek(&mut self) -> &Token {
+    pub fn peek(&mut self) -> Result<&Token, ParseError> {
         if self.ahead == Token::Eos {
-            self.ahead = self.do_next();
+            self.ahead = self.do_next()?;
+            self.ahead_span = self.span;
+        }
+        Ok(&self.ahead)
+    }
+
+    /// 读取下一个 Token，读取输入出错时返回该错误
+    pub fn do_next(&mut self) -> Result<Token, ParseError> {
+        let t = self.read_token();
+        match self.io_error.take() {
+            Some(e) => Err(self.error(format!("cannot read source: {e}"))),
+            None => t,
         }
-        &self.ahead
     }
 
     /// 主词法分析函数：读取下一个字符并根据其类型分发到相应的处理函数
     /// - 跳过空白字符（递归调用自身）
     /// - 识别单字符 Token（操作符、括号等）
     /// - 调用专用函数处理多字符 Token（数字、字符串、标识符等）
-    /// 若遇到未知字符则 panic
-    pub fn do_next(&mut self) -> Token {
-        let ch = self.read_char();
-        match ch {
-            '\n' | '\r' | '\t' | ' ' => self.do_next(),
-            '+' => Token::Add,
-            '*' => Token::Mul,
-            '%' => Token::Mod,
-            '^' => Token::Pow,
-            '#' => Token::Len,
-            '&' => Token::BitAnd,
-            '|' => Token::BitOr,
-            '(' => Token::ParL,
-            ')' => Token::ParR,
-            '{' => Token::CurlyL,
-            '}' => Token::CurlyR,
-            '[' => Token::SqurL,
-            ']' => Token::SqurR,
-            ';' => Token::SemiColon,
-            ',' => Token::Comma,
-            '/' => self.check_ahead('/', Token::Idiv, Token::Div),
-            '=' => self.check_ahead('=', Token::Equal, Token::Assign),
-            '~' => self.check_ahead('=', Token::NotEq, Token::BitXor),
-            ':' => self.check_ahead(':', Token::DoubColon, Token::Colon),
-            '<' => self.check_ahead2('=', Token::LesEq, '<', Token::ShiftL, Token::Less),
-            '>' => self.check_ahead2('=', Token::GreEq, '>', Token::ShiftR, Token::Greater),
-            '\'' | '"' => self.read_string(ch),
-            '.' => match self.read_char() {
-                '.' => {
-                    if self.read_char() == '.' {
+    ///
+    /// 若遇到未知字符则返回错误
+    fn read_token(&mut self) -> Result<Token, ParseError> {
+        self.span.start = self.offset;
+        self.span.line = self.line;
+        let ch = self.read_byte();
+        let t = match ch {
+            b'\n' | b'\r' | b'\t' | b' ' | b'\x0b' | b'\x0c' => return self.read_token(),
+            b'+' => Token::Add,
+            b'*' => Token::Mul,
+            b'%' => Token::Mod,
+            b'^' => Token::Pow,
+            b'#' => Token::Len,
+            b'&' => Token::BitAnd,
+            b'|' => Token::BitOr,
+            b'(' => Token::ParL,
+            b')' => Token::ParR,
+            b'{' => Token::CurlyL,
+            b'}' => Token::CurlyR,
+            b'[' => match self.read_long_bracket_level() {
+                Some(level) => Token::String(self.read_long_string(level)?),
+                None => Token::SqurL,
+            },
+            b']' => Token::SqurR,
+            b';' => Token::SemiColon,
+            b',' => Token::Comma,
+            b'/' => self.check_ahead(b'/', Token::Idiv, Token::Div),
+            b'=' => self.check_ahead(b'=', Token::Equal, Token::Assign),
+            b'~' => self.check_ahead(b'=', Token::NotEq, Token::BitXor),
+            b':' => self.check_ahead(b':', Token::DoubColon, Token::Colon),
+            b'<' => self.check_ahead2(b'=', Token::LesEq, b'<', Token::ShiftL, Token::Less),
+            b'>' => self.check_ahead2(b'=', Token::GreEq, b'>', Token::ShiftR, Token::Greater),
+            b'\'' | b'"' => self.read_string(ch)?,
+            b'.' => match self.read_byte() {
+                b'.' => {
+                    if self.read_byte() == b'.' {
                         Token::Dots
                     } else {
-                        self.putback_char();
+                        self.putback_byte();
                         Token::Concat
                     }
                 }
-                '0'..='9' => {
-                    self.putback_char();
-                    self.read_number_fraction(0)
+                b'0'..=b'9' => {
+                    self.putback_byte();
+                    self.read_number(b'.')?
                 }
                 _ => {
-                    self.putback_char();
+                    self.putback_byte();
                     Token::Dot
                 }
             },
-            '-' => {
-                if self.read_char() == '-' {
-                    self.read_comment();
-                    self.do_next()
+            b'-' => {
+                if self.read_byte() == b'-' {
+                    let (start, line) = (self.offset - 2, self.line);
+                    self.read_comment()?;
+                    if let Some(comments) = &mut self.comments {
+                        let (end, end_line) = if self.last == b'\n' {
+                            (self.offset - 1, self.line - 1)
+                        } else {
+                            (self.offset, self.line)
+                        };
+                        comments.push(Span { start, end, line, end_line });
+                    }
+                    return self.read_token();
                 } else {
-                    self.putback_char();
+                    self.putback_byte();
                     Token::Sub
                 }
             }
-            '0'..='9' => self.read_number(ch),
-            'A'..='Z' | 'a'..='z' | '_' => self.read_name(ch),
-            '\0' => Token::Eos,
-            _ => panic!("invalid char {ch}"),
-        }
+            b'0'..=b'9' => self.read_number(ch)?,
+            b'A'..=b'Z' | b'a'..=b'z' | b'_' => self.read_name(ch),
+            b'\0' if self.eof => Token::Eos,
+            // 与 Lua 一样，控制字符显示为其编码
+            _ if ch.is_ascii_control() => return Err(self.error(format!("unexpected symbol near '<\\{ch}>'"))),
+            _ => return Err(self.error(format!("unexpected symbol near '{}'", ch as char))),
+        };
+        self.span.end = self.offset;
+        self.span.end_line = self.line;
+        Ok(t)
     }
 
-    /// 从输入流读取一个字节，转为 char；若到达文件尾返回 '\\0'
-    fn read_char(&mut self) -> char {
+    /// 从输入流读取一个字节；若到达文件尾或读取出错，返回 0 并设置 eof
+    fn read_byte(&mut self) -> u8 {
         let mut buf: [u8; 1] = [0];
-        if self.input.read(&mut buf).unwrap() == 0 {
-            '\0'
+        self.eof = loop {
+            match self.input.read(&mut buf) {
+                Ok(0) => break true,
+                Ok(_) => break false,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    self.io_error.get_or_insert(e);
+                    break true;
+                }
+            }
+        };
+        let ch = if self.eof {
+            b'\0'
         } else {
-            buf[0] as char
+            self.offset += 1;
+            buf[0]
+        };
+        if ch == b'\n' {
+            self.line += 1;
         }
+        self.last = ch;
+        ch
     }
 
     /// 将文件位置指针回退一个字节（用于实现 lookahead）
-    fn putback_char(&mut self) {
-        self.input.seek(SeekFrom::Current(-1)).unwrap();
+    fn putback_byte(&mut self) {
+        if self.eof {
+            // 文件尾没有实际读到字节，无需回退
+            return;
+        }
+        if self.last == b'\n' {
+            self.line -= 1;
+        }
+        self.last = b'\0';
+        self.eof = true;
+        self.offset -= 1;
+        self.seek_back(1);
+    }
+
+    /// 输入流回退 n 个字节，出错时记下错误
+    fn seek_back(&mut self, n: usize) {
+        if let Err(e) = self.input.seek(SeekFrom::Current(-(n as i64))) {
+            self.io_error.get_or_insert(e);
+        }
     }
 
     /// 向前查看 1 个字符：若匹配 ahead 则返回 long Token，否则回退并返回 short Token
     /// 用于识别两字符操作符（如 //, ==, <=, >=, ~=, ::）
-    fn check_ahead(&mut self, ahead: char, long: Token, short: Token) -> Token {
-        if self.read_char() == ahead {
+    fn check_ahead(&mut self, ahead: u8, long: Token, short: Token) -> Token {
+        if self.read_byte() == ahead {
             long
         } else {
-            self.putback_char();
+            self.putback_byte();
             short
         }
     }
@@ -220,19 +454,19 @@ impl<R:Read> Lex<R> {
     /// 用于识别三字符操作符（如 <<, >>, 以及 <= / <）
     fn check_ahead2(
         &mut self,
-        ahead1: char,
+        ahead1: u8,
         long1: Token,
-        ahead2: char,
+        ahead2: u8,
         long2: Token,
         short: Token,
     ) -> Token {
-        let ch = self.read_char();
+        let ch = self.read_byte();
         if ch == ahead1 {
             long1
         } else if ch == ahead2 {
             long2
         } else {
-            self.putback_char();
+            self.putback_byte();
             short
         }
     }
@@ -241,15 +475,14 @@ impl<R:Read> Lex<R> {
     /// 持续读取字母、数字、下划线直到遇到其他字符
     /// 然后通过关键字表匹配：若为关键字则返回对应 Token，否则返回 Name Token
     /// 性能考虑：当前用 match 字符串比较，TODO 建议用哈希表优化（参考注释）
-    fn read_name(&mut self, first: char) -> Token {
-        let mut s = first.to_string();
-        s.push(first);
+    fn read_name(&mut self, first: u8) -> Token {
+        let mut s = String::from(first as char);
         loop {
-            let ch = self.read_char();
-            if ch.is_ascii_alphanumeric() || ch == '_' {
-                s.push(ch);
+            let ch = self.read_byte();
+            if ch.is_ascii_alphanumeric() || ch == b'_' {
+                s.push(ch as char);
             } else {
-                self.putback_char();
+                self.putback_byte();
                 break;
             }
         }
@@ -284,101 +517,323 @@ impl<R:Read> Lex<R> {
     /// 读取并跳过注释
     /// Lua 支持两种注释形式：
     /// - 单行注释：-- 开头，读到行尾（'\\n' 或 '\\0'）
-    /// - 多行注释：--[[ 开头，需要找 ]] 结尾（当前 TODO 未实现）
-    fn read_comment(&mut self) {
-        match self.read_char() {
-            '[' => todo!("lex long comment"), // 多行注释：--[[...]] 尚未实现
-            _ => loop {
-                // 单行注释：读到换行或文件尾
-                let ch = self.read_char();
-                if ch == '\n' || ch == '\0' {
-                    break;
+    /// - 多行注释：--[[ 开头（中间可有若干 b'='），找到同级的 ]] 结尾
+    fn read_comment(&mut self) -> Result<(), ParseError> {
+        match self.read_byte() {
+            b'[' => {
+                if let Some(level) = self.read_long_bracket_level() {
+                    self.read_long_string(level)?;
+                } else {
+                    self.skip_line();
                 }
-            },
+            }
+            b'\n' => (),
+            b'\0' if self.eof => (),
+            _ => self.skip_line(),
+        }
+        Ok(())
+    }
+
+    /// 跳过当前行剩余部分
+    fn skip_line(&mut self) {
+        loop {
+            // 单行注释：读到换行或文件尾
+            let ch = self.read_byte();
+            if ch == b'\n' || self.eof {
+                break;
+            }
+        }
+    }
+
+    /// 在已读入 b'[' 后识别长括号的级别：`[[` 为 0 级，`[==[` 为 2 级
+    /// 若不是长括号则回退已读字符并返回 None
+    fn read_long_bracket_level(&mut self) -> Option<usize> {
+        let mut level = 0;
+        loop {
+            match self.read_byte() {
+                b'=' => level += 1,
+                b'[' => return Some(level),
+                _ => {
+                    // 回退 '[' 之后读到的字节，文件尾没有实际读到字节
+                    let back = if self.eof { level } else { level + 1 };
+                    if self.last == b'\n' {
+                        self.line -= 1;
+                    }
+                    self.offset -= back;
+                    self.seek_back(back);
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// 读取长括号内容直到同级的结束括号，如 `]==]`
+    /// 紧跟开括号的第一个换行会被忽略
+    fn read_long_string(&mut self, level: usize) -> Result<Vec<u8>, ParseError> {
+        let mut s = Vec::new();
+        let mut ch = self.read_byte();
+        if ch == b'\r' {
+            ch = self.read_byte();
+        }
+        if ch != b'\n' {
+            self.putback_byte();
+        }
+        loop {
+            match self.read_byte() {
+                b'\0' if self.eof => return Err(self.error("unfinished long string")),
+                b']' => {
+                    let mut n = 0;
+                    loop {
+                        match self.read_byte() {
+                            b'=' => n += 1,
+                            b']' if n == level => return Ok(s),
+                            _ => {
+                                self.putback_byte();
+                                break;
+                            }
+                        }
+                    }
+                    s.push(b']');
+                    s.extend(std::iter::repeat_n(b'=', n));
+                }
+                ch => s.push(ch),
+            }
         }
     }
 
     /// 读取字符串常量（由 quoto 字符：' 或 " 标记）
     /// 持续读取字符直到遇到结束引号，生成 String Token
-    /// 性能考虑：
-    /// - 当前不支持转义序列（\\t, \\n 等），有 TODO 标记
-    /// - 到达文件尾而未找到闭合引号时 panic
-    fn read_string(&mut self, quoto: char) -> Token {
-        let mut s = String::new();
+    /// 支持 Lua 的转义序列：\n \t \\ \" \ddd \xXX \z \u{XXX} 等
+    /// 到达文件尾或行尾而未找到闭合引号时返回错误
+    fn read_string(&mut self, quoto: u8) -> Result<Token, ParseError> {
+        let mut s = Vec::new();
         loop {
-            let ch = self.read_char();
+            let ch = self.read_byte();
             match ch {
-                '\0' => panic!("unexpected end of file"),
-                '\\' => todo!("escape"), // 转义序列尚未实现
+                b'\n' => return Err(self.error("unfinished string")),
+                b'\0' if self.eof => return Err(self.error("unfinished string")),
+                b'\\' => self.read_escape(&mut s)?,
                 ch if ch == quoto => break, // 遇到结束引号
                 ch => s.push(ch),
             }
         }
-        Token::String(s)
+        Ok(Token::String(s))
+    }
+
+    /// 读取十六进制数字，非法时返回错误
+    fn read_hex_digit(&mut self) -> Result<u32, ParseError> {
+        (self.read_byte() as char).to_digit(16).ok_or_else(|| self.error("hexadecimal digit expected"))
+    }
+
+    /// 读取反斜杠之后的转义序列并追加到 s
+    fn read_escape(&mut self, s: &mut Vec<u8>) -> Result<(), ParseError> {
+        match self.read_byte() {
+            b'n' => s.push(b'\n'),
+            b't' => s.push(b'\t'),
+            b'r' => s.push(b'\r'),
+            b'a' => s.push(b'\x07'),
+            b'b' => s.push(b'\x08'),
+            b'f' => s.push(b'\x0c'),
+            b'v' => s.push(b'\x0b'),
+            b'\\' => s.push(b'\\'),
+            b'"' => s.push(b'"'),
+            b'\'' => s.push(b'\''),
+            b'\n' => s.push(b'\n'),
+            b'x' => {
+                let mut n = 0;
+                for _ in 0..2 {
+                    n = n * 16 + self.read_hex_digit()?;
+                }
+                s.push(n as u8);
+            }
+            b'z' => loop {
+                // \z 跳过后续的空白字符（包括换行）
+                let ch = self.read_byte();
+                if !ch.is_ascii_whitespace() {
+                    self.putback_byte();
+                    break;
+                }
+            },
+            b'u' => {
+                if self.read_byte() != b'{' {
+                    return Err(self.error("missing b'{' in \\u{xxxx}"));
+                }
+                let mut n: u32 = 0;
+                loop {
+                    let ch = self.read_byte();
+                    if ch == b'}' {
+                        break;
+                    }
+                    let d = (ch as char).to_digit(16).ok_or_else(|| self.error("hexadecimal digit expected"))?;
+                    n = n.saturating_mul(16).saturating_add(d);
+                }
+                if n > 0x7FFF_FFFF {
+                    return Err(self.error("UTF-8 value too large"));
+                }
+                utf8_encode(n, s);
+            }
+            ch @ b'0'..=b'9' => {
+                // \ddd：最多 3 位十进制
+                let mut n = (ch as char).to_digit(10).unwrap();
+                for _ in 0..2 {
+                    match (self.read_byte() as char).to_digit(10) {
+                        Some(d) => n = n * 10 + d,
+                        None => {
+                            self.putback_byte();
+                            break;
+                        }
+                    }
+                }
+                if n > 255 {
+                    return Err(self.error("decimal escape too large"));
+                }
+                s.push(n as u8);
+            }
+            ch => return Err(self.error(format!("invalid escape sequence '\\{ch}'"))),
+        }
+        Ok(())
     }
 
     /// 读取数字常量（整数或浮点数）
-    /// first 为首字符（0-9）
+    /// first 为首字符（0-9，或以 b'.' 开头的小数如 .5）
     /// 处理流程：
     /// 1. 若以 0 开头且后跟 x/X，调用 read_heximal() 读十六进制
-    /// 2. 否则作为十进制读取，遇 '.' 转移到浮点处理，遇 'e'/'E' 转移到科学记数法处理
-    /// 性能考虑：科学记数法处理有 TODO（未实现）
-    fn read_number(&mut self, first: char) -> Token {
-        if first == '0' {
-            let second = self.read_char();
-            if second == 'x' || second == 'X' {
-                return self.read_heximal();
+    /// 2. 否则收集十进制数字、小数点与 e/E 指数，交给标准库解析（保证舍入精确）
+    ///
+    /// 没有小数点和指数的整数若溢出 i64，按 Lua 规则转为浮点数
+    fn read_number(&mut self, first: u8) -> Result<Token, ParseError> {
+        if first == b'0' {
+            let second = self.read_byte();
+            if second == b'x' || second == b'X' {
+                return self.read_heximal(second);
             }
-            self.putback_char();
+            self.putback_byte();
         }
-        // 十进制数
-        let mut n = char::to_digit(first, 10).unwrap() as i64;
+        let mut s = String::from(first as char);
+        let mut is_float = first == b'.';
         loop {
-            let ch = self.read_char();
-            if let Some(d) = char::to_digit(ch, 10) {
-                n = n * 10 + d as i64;
-            } else if ch == '.' {
-                return self.read_number_fraction(n);
-            } else if ch == 'e' || ch == 'E' {
-                return self.read_number_exponent(n);
-            } else {
-                self.putback_char();
-                break;
+            let ch = self.read_byte();
+            match ch {
+                b'0'..=b'9' => s.push(ch as char),
+                b'.' if !is_float => {
+                    is_float = true;
+                    s.push(ch as char);
+                }
+                b'e' | b'E' => {
+                    is_float = true;
+                    s.push(ch as char);
+                    let sign = self.read_byte();
+                    if sign == b'+' || sign == b'-' {
+                        s.push(sign as char);
+                    } else {
+                        self.putback_byte();
+                    }
+                }
+                _ => {
+                    self.putback_byte();
+                    break;
+                }
             }
         }
-        //check following
-        Token::Integer(n)
+        if !is_float {
+            if let Ok(i) = s.parse::<i64>() {
+                return Ok(Token::Integer(i));
+            }
+        }
+        match s.parse::<f64>() {
+            Ok(f) => Ok(Token::Float(f)),
+            Err(_) => Err(self.error(format!("malformed number near '{s}'"))),
+        }
     }
 
-    /// 读取科学记数法形式的浮点数（如 1e10, 2.5e-3）
-    /// i: 整数部分，尚未实现
-    fn read_number_exponent(&mut self, i: i64) -> Token {
-        todo!("lex number exponent")
+    /// 读取二进制指数部分的值（十六进制浮点数 p 之后），如 p-3
+    fn read_exponent(&mut self) -> Result<i32, ParseError> {
+        let neg = match self.read_byte() {
+            b'-' => true,
+            b'+' => false,
+            _ => {
+                self.putback_byte();
+                false
+            }
+        };
+        let mut exp: i32 = 0;
+        let mut ndigits = 0;
+        loop {
+            let ch = self.read_byte();
+            if let Some(d) = (ch as char).to_digit(10) {
+                exp = exp.saturating_mul(10).saturating_add(d as i32);
+                ndigits += 1;
+            } else {
+                self.putback_byte();
+                break;
+            }
+        }
+        if ndigits == 0 {
+            return Err(self.error("malformed number"));
+        }
+        Ok(if neg { -exp } else { exp })
     }
 
-    /// 读取浮点数的小数部分
-    /// i: 整数部分
-    /// 持续读取小数位数字，返回组合后的 Float Token
-    fn read_number_fraction(&mut self, i: i64) -> Token {
-        let mut n: i64 = 0;       // 小数部分数字
-        let mut x: f64 = 1.0;     // 小数位数（用于计算除数）
+    /// 读取十六进制数（0x 或 0X 开头，x 为读到的 x 或 X），至少要有一位十六进制数字
+    /// 整数按 Lua 规则在 64 位上回绕，带小数点或 p 指数时为浮点数
+    fn read_heximal(&mut self, x: u8) -> Result<Token, ParseError> {
+        let mut n: i64 = 0;
+        let mut f: f64 = 0.0;
+        let mut is_float = false;
+        let mut exp: i32 = 0;
+        // 已读取的文本，没有任何十六进制数字时用于错误信息，如 "malformed number near '0x'"
+        let mut text = format!("0{}", x as char);
+        let mut ndigits = 0;
         loop {
-            let ch = self.read_char();
-            if let Some(d) = char::to_digit(ch, 10) {
-                n = n * 10 + d as i64;
-                x *= 10.0;
+            let ch = self.read_byte();
+            if let Some(d) = (ch as char).to_digit(16) {
+                n = n.wrapping_mul(16).wrapping_add(d as i64);
+                f = f * 16.0 + d as f64;
+                if is_float {
+                    exp -= 4;
+                }
+                ndigits += 1;
+            } else if ch == b'.' && !is_float {
+                is_float = true;
+            } else if ch == b'p' || ch == b'P' {
+                if ndigits == 0 {
+                    return Err(self.error(format!("malformed number near '{text}{}'", ch as char)));
+                }
+                let e = self.read_exponent()?;
+                return Ok(Token::Float(f * 2f64.powi(exp.saturating_add(e))));
             } else {
-                self.putback_char();
+                self.putback_byte();
                 break;
             }
+            text.push(ch as char);
+        }
+        if ndigits == 0 {
+            return Err(self.error(format!("malformed number near '{text}'")));
         }
-        // 整数部分 + (小数部分 / 小数位数) = 完整浮点数
-        Token::Float(i as f64 + n as f64 / x)
+        Ok(if is_float {
+            Token::Float(f * 2f64.powi(exp))
+        } else {
+            Token::Integer(n)
+        })
     }
+}
 
-    /// 读取十六进制数（0x 或 0X 开头）
-    /// 当前尚未实现
-    fn read_heximal(&mut self) -> Token {
-        todo!()
+/// 按 UTF-8 的编码方式把 n（最大 0x7FFFFFFF，与 Lua 一样允许最长 6 个字节的扩展形式）追加到 buf
+pub fn utf8_encode(n: u32, buf: &mut Vec<u8>) {
+    if n < 0x80 {
+        buf.push(n as u8);
+        return;
+    }
+    // 从低位起每 6 位放入一个后续字节，直到剩余的位能放进首字节
+    let mut tail = Vec::new();
+    let mut n = n;
+    let mut first_max = 0x3f; // 首字节能容纳的最大值
+    while n > first_max {
+        tail.push(0x80 | (n & 0x3f) as u8);
+        n >>= 6;
+        first_max >>= 1;
     }
+    let prefix = !(first_max << 1) as u8 & 0xfe; // 首字节的前缀：后续字节数个 1，再跟一个 0
+    buf.push(prefix | n as u8);
+    buf.extend(tail.iter().rev());
 }
@@ -0,0 +1,31 @@
+// Lua 解释器库
+// 宿主程序通过 vm::ExeState 加载、执行 Lua 代码，并可注册 Rust 实现的函数与模块
+
+pub mod analysis;
+pub mod arith;
+pub mod ast;
+pub mod async_fn;
+pub mod bytecode;
+pub mod fmt;
+pub mod lex;
+pub mod lib_base;
+pub mod lib_debug;
+pub mod lib_io;
+#[cfg(feature = "serde")]
+pub mod lib_json;
+pub mod lib_os;
+pub mod lib_package;
+pub mod lib_string;
+pub mod lib_utf8;
+pub mod lint;
+#[cfg(feature = "serde")]
+pub mod lsp;
+pub mod parse;
+pub mod profile;
+pub mod sync;
+pub mod table;
+pub mod userdata;
+pub mod value;
+#[cfg(feature = "serde")]
+pub mod value_serde;
+pub mod vm;
@@ -0,0 +1,506 @@
+// Lua 基础库：直接注册为全局变量的函数
+// print/tostring/tonumber/type、表的遍历（next/pairs/ipairs）与原始访问（raw*）、
+// 元表、错误处理（assert/error/pcall/xpcall）、多值（select/unpack）与代码加载（load/dofile）
+
+use crate::arith;
+use crate::sync::{Rc, RefCell};
+use crate::value::{str_to_number, RustFunction, Table, Value};
+use crate::vm::{ExeState, LuaError};
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Write};
+
+/// unpack 最多返回的值的个数
+const MAX_UNPACK: i64 = 1_000_000;
+
+/// 值的默认字符串形式（不考虑元方法），字符串原样返回（保留其中不是 UTF-8 的字节）
+pub fn tostring_raw(v: &Value) -> Value {
+    match v.as_bytes() {
+        Some(_) => v.clone(),
+        None => Value::from(v.to_string()),
+    }
+}
+
+/// 按 tostring 的规则把值转为字符串：优先调用 __tostring 元方法，
+/// 其次元表中的 __name 作为类型名，如 "File: 0x5581..."
+pub fn tostring(state: &mut ExeState, v: &Value) -> Result<Value, LuaError> {
+    let mm = state.metamethod(v, "__tostring");
+    if mm != Value::Nil {
+        let r = state.call(mm, vec![v.clone()])?.into_iter().next().unwrap_or(Value::Nil);
+        return match r {
+            Value::Integer(_) | Value::Float(_) => Ok(tostring_raw(&r)),
+            r if r.as_bytes().is_some() => Ok(r),
+            _ => Err(state.error("'__tostring' must return a string")),
+        };
+    }
+    if let Some(name) = state.metamethod(v, "__name").as_str() {
+        if let Value::Table(_) | Value::UserData(_) = v {
+            let raw = tostring_raw(v);
+            let addr = raw.as_str().unwrap().split_once(": ").unwrap().1;
+            return Ok(Value::from(format!("{name}: {addr}")));
+        }
+    }
+    Ok(tostring_raw(v))
+}
+
+/// 检查第 i 个参数存在（可以是 nil）
+fn check_any(state: &ExeState, i: usize, fname: &str) -> Result<Value, LuaError> {
+    if i > state.get_top() {
+        return Err(state.arg_error(i, fname, "value expected"));
+    }
+    Ok(state.arg(i))
+}
+
+/// 检查第 i 个参数是表
+fn check_table(state: &ExeState, i: usize, fname: &str) -> Result<Rc<RefCell<Table>>, LuaError> {
+    match state.arg(i) {
+        Value::Table(t) => Ok(t),
+        _ => Err(state.arg_type_error(i, fname, "table")),
+    }
+}
+
+/// print(...)：输出所有参数的 tostring 结果，以制表符分隔，最后换行
+fn lib_print(state: &mut ExeState) -> Result<i32, LuaError> {
+    let mut line = Vec::new();
+    for i in 1..=state.get_top() {
+        if i > 1 {
+            line.push(b'\t');
+        }
+        let s = tostring(state, &state.arg(i))?;
+        line.extend_from_slice(s.as_bytes().unwrap());
+    }
+    line.push(b'\n');
+    let mut out = io::stdout().lock();
+    out.write_all(&line).and_then(|_| out.flush()).map_err(|e| state.error(e.to_string()))?;
+    Ok(0)
+}
+
+/// tostring(v)
+fn lib_tostring(state: &mut ExeState) -> Result<i32, LuaError> {
+    let v = check_any(state, 1, "tostring")?;
+    let s = tostring(state, &v)?;
+    state.push(s);
+    Ok(1)
+}
+
+/// 按进制 base（2 到 36）把字符串解析为整数，允许首尾空白与负号，溢出时回绕
+fn str_to_int_base(s: &str, base: u32) -> Option<i64> {
+    let s = s.trim();
+    let (neg, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    let mut n: i64 = 0;
+    for c in digits.chars() {
+        let d = c.to_digit(base)?;
+        n = n.wrapping_mul(base as i64).wrapping_add(d as i64);
+    }
+    Some(if neg { n.wrapping_neg() } else { n })
+}
+
+/// tonumber(v [, base])：数字原样返回，字符串按 Lua 的数字语法或指定进制解析，失败返回 nil
+fn lib_tonumber(state: &mut ExeState) -> Result<i32, LuaError> {
+    let r = match state.arg(2) {
+        Value::Nil => match check_any(state, 1, "tonumber")? {
+            v @ (Value::Integer(_) | Value::Float(_)) => v,
+            v => v.as_str().and_then(str_to_number).unwrap_or(Value::Nil),
+        },
+        _ => {
+            let base = state.check_integer(2, "tonumber")?;
+            let v = state.arg(1);
+            let Some(s) = v.as_bytes() else {
+                return Err(state.arg_type_error(1, "tonumber", "string"));
+            };
+            if !(2..=36).contains(&base) {
+                return Err(state.arg_error(2, "tonumber", "base out of range"));
+            }
+            std::str::from_utf8(s)
+                .ok()
+                .and_then(|s| str_to_int_base(s, base as u32))
+                .map_or(Value::Nil, Value::Integer)
+        }
+    };
+    state.push(r);
+    Ok(1)
+}
+
+/// type(v)
+fn lib_type(state: &mut ExeState) -> Result<i32, LuaError> {
+    let v = check_any(state, 1, "type")?;
+    state.push(v.type_name());
+    Ok(1)
+}
+
+/// next(t [, k])：返回表中 k 之后的下一个键值对，遍历结束时返回 nil
+fn lib_next(state: &mut ExeState) -> Result<i32, LuaError> {
+    let t = check_table(state, 1, "next")?;
+    let next = t.borrow().next(&state.arg(2));
+    match next {
+        Ok(Some((k, v))) => {
+            state.push(k);
+            state.push(v);
+            Ok(2)
+        }
+        Ok(None) => {
+            state.push(Value::Nil);
+            Ok(1)
+        }
+        Err(msg) => Err(LuaError::new(msg)),
+    }
+}
+
+/// pairs(t)：有 __pairs 元方法时返回其前三个返回值，否则返回 next, t, nil
+fn lib_pairs(state: &mut ExeState) -> Result<i32, LuaError> {
+    let t = check_any(state, 1, "pairs")?;
+    let mm = state.metamethod(&t, "__pairs");
+    if mm != Value::Nil {
+        let mut results = state.call(mm, vec![t])?;
+        results.resize(3, Value::Nil);
+        for v in results {
+            state.push(v);
+        }
+        return Ok(3);
+    }
+    if !matches!(t, Value::Table(_)) {
+        return Err(state.arg_type_error(1, "pairs", "table"));
+    }
+    state.push(Value::Function(lib_next));
+    state.push(t);
+    state.push(Value::Nil);
+    Ok(3)
+}
+
+/// ipairs 的迭代函数：返回 i+1, t[i+1]（考虑 __index），值为 nil 时结束
+fn ipairs_aux(state: &mut ExeState) -> Result<i32, LuaError> {
+    let i = state.check_integer(2, "ipairs")?.wrapping_add(1);
+    let v = state.index(&state.arg(1), &Value::Integer(i))?;
+    if v == Value::Nil {
+        state.push(Value::Nil);
+        return Ok(1);
+    }
+    state.push(Value::Integer(i));
+    state.push(v);
+    Ok(2)
+}
+
+/// ipairs(t)：返回 ipairs_aux, t, 0
+fn lib_ipairs(state: &mut ExeState) -> Result<i32, LuaError> {
+    let t = check_any(state, 1, "ipairs")?;
+    state.push(Value::Function(ipairs_aux));
+    state.push(t);
+    state.push(Value::Integer(0));
+    Ok(3)
+}
+
+/// rawget(t, k)
+fn lib_rawget(state: &mut ExeState) -> Result<i32, LuaError> {
+    let t = check_table(state, 1, "rawget")?;
+    let k = check_any(state, 2, "rawget")?;
+    let v = t.borrow().get(&k);
+    state.push(v);
+    Ok(1)
+}
+
+/// rawset(t, k, v)：返回 t
+fn lib_rawset(state: &mut ExeState) -> Result<i32, LuaError> {
+    let t = check_table(state, 1, "rawset")?;
+    let k = check_any(state, 2, "rawset")?;
+    let v = check_any(state, 3, "rawset")?;
+    match k {
+        Value::Nil => return Err(state.error("table index is nil")),
+        Value::Float(f) if f.is_nan() => return Err(state.error("table index is NaN")),
+        _ => t.borrow_mut().set(k, v),
+    }
+    state.push(Value::Table(t));
+    Ok(1)
+}
+
+/// rawequal(a, b)
+fn lib_rawequal(state: &mut ExeState) -> Result<i32, LuaError> {
+    let a = check_any(state, 1, "rawequal")?;
+    let b = check_any(state, 2, "rawequal")?;
+    state.push(arith::raw_equal(&a, &b));
+    Ok(1)
+}
+
+/// rawlen(v)：表的边界或字符串的字节数
+fn lib_rawlen(state: &mut ExeState) -> Result<i32, LuaError> {
+    let n = match state.arg(1) {
+        Value::Table(t) => t.borrow().border(),
+        v => match v.as_bytes() {
+            Some(b) => b.len(),
+            None => return Err(state.arg_error(1, "rawlen", "table or string expected")),
+        },
+    };
+    state.push(Value::Integer(n as i64));
+    Ok(1)
+}
+
+/// getmetatable(v)：元表中有 __metatable 字段时返回该字段
+fn lib_getmetatable(state: &mut ExeState) -> Result<i32, LuaError> {
+    let meta = match check_any(state, 1, "getmetatable")? {
+        Value::Table(t) => t.borrow().metatable.clone(),
+        Value::UserData(u) => u.metatable.clone(),
+        _ => None,
+    };
+    let r = match meta {
+        Some(m) => match m.borrow().get_str("__metatable") {
+            Value::Nil => Value::Table(m.clone()),
+            protected => protected,
+        },
+        None => Value::Nil,
+    };
+    state.push(r);
+    Ok(1)
+}
+
+/// setmetatable(t, mt)：mt 为 nil 时移除元表，返回 t
+fn lib_setmetatable(state: &mut ExeState) -> Result<i32, LuaError> {
+    let t = check_table(state, 1, "setmetatable")?;
+    let meta = match state.arg(2) {
+        Value::Nil => None,
+        Value::Table(m) => Some(m),
+        _ => return Err(state.arg_type_error(2, "setmetatable", "nil or table")),
+    };
+    let protected = match &t.borrow().metatable {
+        Some(m) => m.borrow().get_str("__metatable") != Value::Nil,
+        None => false,
+    };
+    if protected {
+        return Err(state.error("cannot change a protected metatable"));
+    }
+    t.borrow_mut().metatable = meta;
+    state.push(Value::Table(t));
+    Ok(1)
+}
+
+/// assert(v [, message, ...])：v 为真时返回全部参数，否则以 message（默认 "assertion failed!"）报错
+fn lib_assert(state: &mut ExeState) -> Result<i32, LuaError> {
+    let v = check_any(state, 1, "assert")?;
+    if v.is_truthy() {
+        return Ok(state.get_top() as i32);
+    }
+    if state.get_top() >= 2 {
+        return Err(LuaError::Runtime(state.arg(2)));
+    }
+    // 与 error 一样，由内置函数直接调用（如 pcall(assert, false)）时没有位置信息
+    if state.called_from_rust() {
+        return Err(LuaError::new("assertion failed!"));
+    }
+    Err(state.error("assertion failed!"))
+}
+
+/// error(message [, level])：字符串信息前加上第 level 层调用（默认 1，即调用 error 的函数）的位置
+fn lib_error(state: &mut ExeState) -> Result<i32, LuaError> {
+    let msg = state.arg(1);
+    let level = state.opt_integer(2, "error")?.unwrap_or(1);
+    // level 为 1 而调用者是内置函数（如 pcall(error, msg)）时没有位置信息
+    let located = level > 1 || (level == 1 && !state.called_from_rust());
+    if located && msg.as_bytes().is_some() {
+        let mut s = state.location(level as usize - 1).into_bytes();
+        s.extend_from_slice(msg.as_bytes().unwrap());
+        return Err(LuaError::Runtime(Value::from(s)));
+    }
+    Err(LuaError::Runtime(msg))
+}
+
+/// pcall(f, ...)：保护模式调用，成功返回 true 与 f 的返回值，出错返回 false 与错误值；中断不被捕获
+/// 调用期间去掉外层 xpcall 的消息处理函数，f 中的错误由这里捕获而不经过处理
+fn lib_pcall(state: &mut ExeState) -> Result<i32, LuaError> {
+    let f = check_any(state, 1, "pcall")?;
+    let args = (2..=state.get_top()).map(|i| state.arg(i)).collect();
+    let saved = state.set_msgh(None);
+    let result = state.call(f, args);
+    state.set_msgh(saved);
+    let results = match result {
+        Ok(rets) => [vec![Value::Boolean(true)], rets].concat(),
+        Err(LuaError::Runtime(e)) => vec![Value::Boolean(false), e],
+        Err(e) => return Err(e),
+    };
+    let n = results.len();
+    results.into_iter().for_each(|v| state.push(v));
+    Ok(n as i32)
+}
+
+/// xpcall(f, msgh, ...)：与 pcall 相同，但出错时返回 false 与 msgh(错误值) 的结果
+/// msgh 在出错处、调用栈展开之前调用，因此可以用 debug.traceback 取得出错处的回溯信息
+fn lib_xpcall(state: &mut ExeState) -> Result<i32, LuaError> {
+    let f = state.arg(1);
+    let handler = check_any(state, 2, "xpcall")?;
+    let args = (3..=state.get_top()).map(|i| state.arg(i)).collect();
+    let saved = state.set_msgh(Some(handler));
+    let result = state.call(f, args);
+    // f 不是 Lua 函数（如内置函数直接出错）时错误没有经过执行循环，处理函数还在，在这里调用
+    let result = result.map_err(|e| state.handle_error(e));
+    state.set_msgh(saved);
+    let results = match result {
+        Ok(rets) => [vec![Value::Boolean(true)], rets].concat(),
+        Err(LuaError::Runtime(e)) => vec![Value::Boolean(false), e],
+        Err(e) => return Err(e),
+    };
+    let n = results.len();
+    results.into_iter().for_each(|v| state.push(v));
+    Ok(n as i32)
+}
+
+/// select(n, ...)：返回第 n 个及之后的参数，n 为负时从末尾数起；select('#', ...) 返回参数个数
+fn lib_select(state: &mut ExeState) -> Result<i32, LuaError> {
+    let top = state.get_top() as i64;
+    if state.arg(1).as_bytes() == Some(b"#") {
+        state.push(Value::Integer(top - 1));
+        return Ok(1);
+    }
+    let n = state.check_integer(1, "select")?;
+    let n = if n < 0 { top + n } else { n.min(top) };
+    if n < 1 {
+        return Err(state.arg_error(1, "select", "index out of range"));
+    }
+    // 要返回的正是栈顶的最后 top - n 个参数
+    Ok((top - n) as i32)
+}
+
+/// unpack(t [, i [, j]])：返回 t[i], ..., t[j]，默认 i 为 1，j 为 #t
+fn lib_unpack(state: &mut ExeState) -> Result<i32, LuaError> {
+    let t = state.arg(1);
+    let i = state.opt_integer(2, "unpack")?.unwrap_or(1);
+    let j = match state.opt_integer(3, "unpack")? {
+        Some(j) => j,
+        None => match &t {
+            Value::Table(tb) => tb.borrow().border() as i64,
+            _ => return Err(state.arg_type_error(1, "unpack", "table")),
+        },
+    };
+    if i > j {
+        return Ok(0);
+    }
+    if j.wrapping_sub(i) >= MAX_UNPACK || j.checked_sub(i).is_none() {
+        return Err(state.error("too many results to unpack"));
+    }
+    for k in i..=j {
+        let v = state.index(&t, &Value::Integer(k))?;
+        state.push(v);
+    }
+    Ok((j - i + 1) as i32)
+}
+
+/// 代码块名的显示形式：以 '=' 或 '@' 开头时去掉该字符，否则显示为 [string "源代码的第一行..."]
+fn chunk_id(name: &str) -> String {
+    if let Some(rest) = name.strip_prefix('=').or_else(|| name.strip_prefix('@')) {
+        return rest.to_string();
+    }
+    // 与 C 实现的 LUA_IDSIZE 一致，最多保留 45 个字符
+    let first = name.lines().next().unwrap_or("");
+    let truncated: String = first.chars().take(45).collect();
+    if truncated.len() < name.len() {
+        format!("[string \"{truncated}...\"]")
+    } else {
+        format!("[string \"{truncated}\"]")
+    }
+}
+
+/// load(chunk [, chunkname [, mode]])：编译字符串或由函数分段返回的代码，
+/// 返回代码块函数，出错时返回 nil 与错误信息
+fn lib_load(state: &mut ExeState) -> Result<i32, LuaError> {
+    let chunk = state.arg(1);
+    let source = match chunk.as_bytes() {
+        Some(s) => s.to_vec(),
+        None if matches!(chunk, Value::Function(_) | Value::RustClosure(_) | Value::LuaFunction(_)) => {
+            let mut source = Vec::new();
+            loop {
+                let piece = state.call(chunk.clone(), Vec::new())?.into_iter().next().unwrap_or(Value::Nil);
+                match piece.as_bytes() {
+                    Some(b"") => break,
+                    Some(b) => source.extend_from_slice(b),
+                    None if piece == Value::Nil => break,
+                    None => return Err(state.error("reader function must return a string")),
+                }
+            }
+            source
+        }
+        None => return Err(state.arg_type_error(1, "load", "string")),
+    };
+    let name = match state.opt_string(2, "load")? {
+        Some(name) => name,
+        None if chunk.as_bytes().is_some() => String::from_utf8_lossy(&source).into_owned(),
+        None => "=(load)".to_string(),
+    };
+    if state.opt_string(3, "load")?.is_some_and(|mode| !mode.contains('t')) {
+        state.push(Value::Nil);
+        state.push("attempt to load a text chunk");
+        return Ok(2);
+    }
+    if state.arg(4) != Value::Nil {
+        return Err(state.arg_error(4, "load", "custom environments are not supported"));
+    }
+    match state.load(Cursor::new(source), &chunk_id(&name)) {
+        Ok(f) => {
+            state.push(f);
+            Ok(1)
+        }
+        Err(LuaError::Runtime(msg)) => {
+            state.push(Value::Nil);
+            state.push(msg);
+            Ok(2)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// dofile(filename)：加载并执行文件，返回其全部返回值
+fn lib_dofile(state: &mut ExeState) -> Result<i32, LuaError> {
+    let filename = state.check_string(1, "dofile")?;
+    let file = File::open(&filename).map_err(|e| state.error(format!("cannot open {filename}: {e}")))?;
+    let f = state.load(BufReader::new(file), &filename)?;
+    let results = state.call(f, Vec::new())?;
+    let n = results.len();
+    results.into_iter().for_each(|v| state.push(v));
+    Ok(n as i32)
+}
+
+/// collectgarbage([opt])：值由引用计数管理，没有单独的垃圾回收器，各选项只返回与 C 实现形式相同的结果
+fn lib_collectgarbage(state: &mut ExeState) -> Result<i32, LuaError> {
+    let opt = state.opt_string(1, "collectgarbage")?.unwrap_or_else(|| "collect".to_string());
+    let r = match opt.as_str() {
+        "count" => Value::Float(0.0),
+        "isrunning" => Value::Boolean(true),
+        "collect" | "step" | "stop" | "restart" | "incremental" | "generational" => Value::Integer(0),
+        _ => return Err(state.arg_error(1, "collectgarbage", &format!("invalid option '{opt}'"))),
+    };
+    state.push(r);
+    Ok(1)
+}
+
+/// 注册基础库函数为全局变量
+pub fn open_lib(state: &mut ExeState) {
+    let funcs: [(&str, RustFunction); 22] = [
+        ("print", lib_print),
+        ("tostring", lib_tostring),
+        ("tonumber", lib_tonumber),
+        ("type", lib_type),
+        ("next", lib_next),
+        ("pairs", lib_pairs),
+        ("ipairs", lib_ipairs),
+        ("rawget", lib_rawget),
+        ("rawset", lib_rawset),
+        ("rawequal", lib_rawequal),
+        ("rawlen", lib_rawlen),
+        ("getmetatable", lib_getmetatable),
+        ("setmetatable", lib_setmetatable),
+        ("assert", lib_assert),
+        ("error", lib_error),
+        ("pcall", lib_pcall),
+        ("xpcall", lib_xpcall),
+        ("select", lib_select),
+        ("unpack", lib_unpack),
+        ("load", lib_load),
+        ("dofile", lib_dofile),
+        ("collectgarbage", lib_collectgarbage),
+    ];
+    for (name, f) in funcs {
+        state.set_global(name, Value::Function(f));
+    }
+    state.set_global("_VERSION", Value::from("Lua 5.4"));
+    state.set_global("_G", Value::Table(state.globals()));
+}
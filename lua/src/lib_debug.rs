@@ -0,0 +1,160 @@
+// Lua debug 库
+// 调试接口的层级（level）与标准 Lua 一致：0 为 debug 库函数自身，1 为调用它的函数，依此类推；
+// 内置函数不占用调用栈帧，因此第 n 层（n >= 1）对应 ExeState 调试接口的第 n-1 层
+// sethook 设置的 Lua 钩子函数以 (事件名, 行号) 调用，事件名为 "call"、"return"、"line" 或 "count"
+
+use crate::value::{Table, Value};
+use crate::vm::{DebugInfo, ExeState, HookEvent, LuaError, MASK_CALL, MASK_COUNT, MASK_LINE, MASK_RETURN};
+
+/// debug.traceback([message [, level]])：在 message 之后附加调用栈回溯信息
+/// message 不是字符串也不是 nil 时原样返回
+fn debug_traceback(state: &mut ExeState) -> Result<i32, LuaError> {
+    let msg = state.arg(1);
+    if msg != Value::Nil && msg.as_bytes().is_none() && !matches!(msg, Value::Integer(_) | Value::Float(_)) {
+        state.push(msg);
+        return Ok(1);
+    }
+    let level = state.opt_integer(2, "traceback")?.unwrap_or(1).max(1) as usize;
+    let traceback = state.traceback(level - 1);
+    let s = match state.opt_string(1, "traceback")? {
+        Some(msg) => format!("{msg}\n{traceback}"),
+        None => traceback,
+    };
+    state.push(Value::from(s));
+    Ok(1)
+}
+
+/// debug.getinfo([level | f [, what]])：返回函数或调用层级的信息表，层级超出调用栈时返回 nil
+/// what 参数被接受但忽略，总是返回全部字段
+fn debug_getinfo(state: &mut ExeState) -> Result<i32, LuaError> {
+    let info = match state.arg(1) {
+        Value::Integer(_) | Value::Float(_) => {
+            let level = state.check_integer(1, "getinfo")?;
+            match level {
+                0 => Some(DebugInfo::of_function(&Value::Function(debug_getinfo))),
+                l if l > 0 => state.get_info(l as usize - 1),
+                _ => None,
+            }
+        }
+        f @ (Value::Function(_) | Value::RustClosure(_) | Value::LuaFunction(_)) => Some(DebugInfo::of_function(&f)),
+        _ => return Err(state.arg_error(1, "getinfo", "function or level expected")),
+    };
+    let Some(info) = info else {
+        state.push(Value::Nil);
+        return Ok(1);
+    };
+
+    let is_c = info.what == "C";
+    let line = |n: usize| Value::Integer(if is_c { -1 } else { n as i64 });
+    let mut t = Table::new(0, 10);
+    t.set_str("source", Value::from(info.source));
+    t.set_str("short_src", Value::from(info.short_src));
+    t.set_str("what", Value::from(info.what));
+    t.set_str("currentline", info.currentline.map_or(Value::Integer(-1), line));
+    t.set_str("linedefined", line(info.linedefined));
+    t.set_str("lastlinedefined", line(info.lastlinedefined));
+    t.set_str("nparams", Value::Integer(info.nparams as i64));
+    t.set_str("isvararg", Value::Boolean(info.is_vararg));
+    t.set_str("nups", Value::Integer(info.nups as i64));
+    t.set_str("func", info.func);
+    state.push(Value::from(t));
+    Ok(1)
+}
+
+/// debug.getlocal(level | f, n)：返回局部变量的名字与值
+/// 第一个参数为函数时只返回第 n 个参数的名字
+fn debug_getlocal(state: &mut ExeState) -> Result<i32, LuaError> {
+    let n = state.check_integer(2, "getlocal")?;
+    if let Value::LuaFunction(f) = state.arg(1) {
+        let name = (n >= 1 && n as usize <= f.proto.nparam)
+            .then(|| f.proto.locvars.get(n as usize - 1))
+            .flatten();
+        state.push(name.map_or(Value::Nil, |v| Value::from(v.name.as_str())));
+        return Ok(1);
+    }
+    let level = state.check_integer(1, "getlocal")?;
+    if level < 0 || (level as usize) > state.call_depth() {
+        return Err(state.arg_error(1, "getlocal", "level out of range"));
+    }
+    // 第 0 层是 getlocal 自身，没有局部变量
+    let local = match level {
+        0 => None,
+        l => state.get_local(l as usize - 1, n),
+    };
+    match local {
+        Some((name, value)) => {
+            state.push(Value::from(name));
+            state.push(value);
+            Ok(2)
+        }
+        None => {
+            state.push(Value::Nil);
+            Ok(1)
+        }
+    }
+}
+
+/// debug.sethook([f, mask [, count]])：设置钩子，mask 中的 'c'、'r'、'l' 分别表示调用、返回与行事件，
+/// count 大于 0 时每执行 count 条指令触发一次计数事件；不带参数时移除钩子
+fn debug_sethook(state: &mut ExeState) -> Result<i32, LuaError> {
+    let func = state.arg(1);
+    if func == Value::Nil {
+        state.remove_hook();
+        return Ok(0);
+    }
+    if func.type_name() != "function" {
+        return Err(state.arg_type_error(1, "sethook", "function"));
+    }
+    let smask = state.check_string(2, "sethook")?;
+    let count = state.opt_integer(3, "sethook")?.unwrap_or(0).max(0) as usize;
+    let mut mask = 0;
+    for (c, m) in [('c', MASK_CALL), ('r', MASK_RETURN), ('l', MASK_LINE)] {
+        if smask.contains(c) {
+            mask |= m;
+        }
+    }
+    if count > 0 {
+        mask |= MASK_COUNT;
+    }
+
+    let hook = func.clone();
+    let f = move |state: &mut ExeState, event: HookEvent| -> Result<(), LuaError> {
+        let args = match event {
+            HookEvent::Call => vec![Value::from("call")],
+            HookEvent::Return => vec![Value::from("return")],
+            HookEvent::Line(line) => vec![Value::from("line"), Value::Integer(line as i64)],
+            HookEvent::Count => vec![Value::from("count")],
+        };
+        state.call(hook.clone(), args).map(|_| ())
+    };
+    state.install_hook(mask, count, Box::new(f), Some(func));
+    Ok(0)
+}
+
+/// debug.gethook()：返回当前钩子函数、掩码字符串与计数；由宿主设置的钩子返回 "external hook"
+fn debug_gethook(state: &mut ExeState) -> Result<i32, LuaError> {
+    let Some((func, mask, count)) = state.hook_info() else {
+        state.push(Value::Nil);
+        return Ok(1);
+    };
+    let smask: String = [('c', MASK_CALL), ('r', MASK_RETURN), ('l', MASK_LINE)]
+        .into_iter()
+        .filter(|(_, m)| mask & m != 0)
+        .map(|(c, _)| c)
+        .collect();
+    state.push(func.unwrap_or_else(|| Value::from("external hook")));
+    state.push(Value::from(smask));
+    state.push(Value::Integer(count as i64));
+    Ok(3)
+}
+
+/// 创建 debug 库表
+pub fn open_lib() -> Value {
+    let mut lib = Table::new(0, 5);
+    lib.set_str("traceback", Value::Function(debug_traceback));
+    lib.set_str("getinfo", Value::Function(debug_getinfo));
+    lib.set_str("getlocal", Value::Function(debug_getlocal));
+    lib.set_str("sethook", Value::Function(debug_sethook));
+    lib.set_str("gethook", Value::Function(debug_gethook));
+    Value::from(lib)
+}
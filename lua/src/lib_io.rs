@@ -0,0 +1,481 @@
+// Lua io 标准库
+// 文件句柄以用户数据（UserData）的形式交给 Lua，方法通过元表的 __index 查找
+// 支持 io.open/read/write/lines/close 以及 io.stdin/stdout/stderr 标准句柄
+
+use crate::sync::{Rc, RefCell};
+use crate::value::{format_g14, AnyUserData, Table, Value};
+use crate::vm::{ExeState, LuaError};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+/// 文件句柄的内部状态
+enum LuaFile {
+    Stdin,
+    Stdout,
+    Stderr,
+    /// 普通文件：读取经过缓冲，写入前丢弃读缓冲以保持文件位置正确
+    File(BufReader<File>),
+    /// 已关闭的文件
+    Closed,
+}
+
+/// io.read 支持的读取格式
+enum ReadFormat {
+    /// "n"：读取一个数字
+    Number,
+    /// "l"：读取一行，不含换行符
+    Line,
+    /// "L"：读取一行，保留换行符
+    LineWithEol,
+    /// "a"：读取剩余全部内容
+    All,
+    /// 整数 n：最多读取 n 个字节
+    Count(usize),
+}
+
+impl LuaFile {
+    /// 以缓冲读取接口访问句柄，标准输出/错误不可读
+    fn with_reader<T>(&mut self, f: impl FnOnce(&mut dyn BufRead) -> io::Result<T>) -> io::Result<T> {
+        match self {
+            LuaFile::Stdin => f(&mut io::stdin().lock()),
+            LuaFile::File(r) => f(r),
+            _ => Err(io::Error::from_raw_os_error(libc::EBADF)),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            LuaFile::Stdout => io::stdout().write_all(buf),
+            LuaFile::Stderr => io::stderr().write_all(buf),
+            LuaFile::File(r) => {
+                // BufReader 的 seek 会丢弃缓冲并把底层文件定位到逻辑位置；
+                // clippy 建议的 stream_position 只计算位置，两者都不做
+                #[allow(clippy::seek_from_current)]
+                r.seek(SeekFrom::Current(0))?;
+                r.get_mut().write_all(buf)
+            }
+            _ => Err(io::Error::from_raw_os_error(libc::EBADF)),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            LuaFile::Stdout => io::stdout().flush(),
+            LuaFile::Stderr => io::stderr().flush(),
+            LuaFile::File(r) => r.get_mut().flush(),
+            _ => Ok(()),
+        }
+    }
+
+    /// 按单个格式读取，到达文件尾（读取失败）时返回 nil
+    fn read(&mut self, format: &ReadFormat) -> io::Result<Value> {
+        self.with_reader(|r| match format {
+            ReadFormat::Line | ReadFormat::LineWithEol => {
+                let mut buf = Vec::new();
+                if r.read_until(b'\n', &mut buf)? == 0 {
+                    return Ok(Value::Nil);
+                }
+                if matches!(format, ReadFormat::Line) && buf.last() == Some(&b'\n') {
+                    buf.pop();
+                }
+                Ok(Value::from(buf))
+            }
+            ReadFormat::All => {
+                let mut buf = Vec::new();
+                r.read_to_end(&mut buf)?;
+                Ok(Value::from(buf))
+            }
+            ReadFormat::Count(n) => {
+                let mut buf = Vec::new();
+                r.take(*n as u64).read_to_end(&mut buf)?;
+                // 读 0 个字节用于探测文件尾
+                if buf.is_empty() && (*n > 0 || r.fill_buf()?.is_empty()) {
+                    return Ok(Value::Nil);
+                }
+                Ok(Value::from(buf))
+            }
+            ReadFormat::Number => read_number(r),
+        })
+    }
+}
+
+/// 读取一个数字：跳过前导空白，读取最长的数字字符序列再解析
+fn read_number(r: &mut dyn BufRead) -> io::Result<Value> {
+    // 跳过空白
+    loop {
+        let buf = r.fill_buf()?;
+        match buf.first() {
+            Some(b) if b.is_ascii_whitespace() => r.consume(1),
+            _ => break,
+        }
+    }
+    let mut s = String::new();
+    loop {
+        let buf = r.fill_buf()?;
+        match buf.first() {
+            Some(&b) if b.is_ascii_hexdigit() || b"+-.xXpP".contains(&b) => {
+                // 符号只允许出现在开头或指数之后
+                if (b == b'+' || b == b'-') && !(s.is_empty() || s.ends_with(['e', 'E', 'p', 'P'])) {
+                    break;
+                }
+                s.push(b as char);
+                r.consume(1);
+            }
+            _ => break,
+        }
+        if s.len() > 200 {
+            break;
+        }
+    }
+    Ok(crate::value::str_to_number(&s).unwrap_or(Value::Nil))
+}
+
+/// 解析 io.read 的格式参数，兼容 Lua 5.1 的 "*l" 写法
+fn parse_format(state: &ExeState, i: usize, fname: &str) -> Result<ReadFormat, LuaError> {
+    if let Value::Integer(n) = state.arg(i) {
+        return Ok(ReadFormat::Count(n.max(0) as usize));
+    }
+    let fmt = state.check_string(i, fname)?;
+    match fmt.trim_start_matches('*').chars().next() {
+        Some('n') => Ok(ReadFormat::Number),
+        Some('l') => Ok(ReadFormat::Line),
+        Some('L') => Ok(ReadFormat::LineWithEol),
+        Some('a') => Ok(ReadFormat::All),
+        _ => Err(state.arg_error(i, fname, "invalid format")),
+    }
+}
+
+/// 把 io::Error 转成 Lua 惯用的失败返回值：nil, 错误信息, 错误码
+fn push_io_error(state: &mut ExeState, err: io::Error, filename: Option<&str>) -> i32 {
+    let msg = match filename {
+        Some(name) => format!("{name}: {}", os_error_message(&err)),
+        None => os_error_message(&err),
+    };
+    state.push(Value::Nil);
+    state.push(Value::from(msg));
+    state.push(Value::Integer(err.raw_os_error().unwrap_or(0) as i64));
+    3
+}
+
+/// 与 C 的 strerror 一致的错误描述（去掉 Rust 附加的 "(os error N)"）
+pub fn os_error_message(err: &io::Error) -> String {
+    let msg = err.to_string();
+    match msg.find(" (os error") {
+        Some(i) => msg[..i].to_string(),
+        None => msg,
+    }
+}
+
+/// 检查第 i 个参数是文件句柄，并以可变引用访问其内部状态
+fn with_file<T>(
+    state: &ExeState,
+    i: usize,
+    fname: &str,
+    f: impl FnOnce(&mut LuaFile) -> T,
+) -> Result<T, LuaError> {
+    if let Value::UserData(u) = state.arg(i) {
+        if let Some(file) = u.data.borrow_mut().downcast_mut::<LuaFile>() {
+            if matches!(file, LuaFile::Closed) {
+                return Err(state.error("attempt to use a closed file"));
+            }
+            return Ok(f(file));
+        }
+    }
+    Err(state.arg_type_error(i, fname, "FILE*"))
+}
+
+/// 从第 first 个参数开始按格式依次读取，遇到失败即停止，返回结果个数
+fn read_formats(state: &mut ExeState, file: &Value, first: usize, fname: &str) -> Result<i32, LuaError> {
+    let mut formats = Vec::new();
+    for i in first..=state.get_top() {
+        formats.push(parse_format(state, i, fname)?);
+    }
+    if formats.is_empty() {
+        formats.push(ReadFormat::Line);
+    }
+    let Value::UserData(u) = file else { unreachable!() };
+    let mut data = u.data.borrow_mut();
+    let lf = data.downcast_mut::<LuaFile>().unwrap();
+    let mut n = 0;
+    for format in &formats {
+        match lf.read(format) {
+            Ok(v) => {
+                let failed = v == Value::Nil;
+                state.push(v);
+                n += 1;
+                if failed {
+                    break;
+                }
+            }
+            Err(e) => {
+                drop(data);
+                return Ok(push_io_error(state, e, None));
+            }
+        }
+    }
+    Ok(n)
+}
+
+/// 依次写入第 first 个参数起的字符串或数字，成功时返回文件本身
+fn write_values(state: &mut ExeState, file: &Value, first: usize, fname: &str) -> Result<i32, LuaError> {
+    let mut buf = Vec::new();
+    for i in first..=state.get_top() {
+        match state.arg(i) {
+            Value::Integer(n) => buf.extend(n.to_string().into_bytes()),
+            Value::Float(n) => buf.extend(format_g14(n).into_bytes()),
+            v => match v.as_bytes() {
+                Some(b) => buf.extend_from_slice(b),
+                None => return Err(state.arg_type_error(i - first + 1, fname, "string")),
+            },
+        }
+    }
+    let Value::UserData(u) = file else { unreachable!() };
+    let result = u.data.borrow_mut().downcast_mut::<LuaFile>().unwrap().write_all(&buf);
+    match result {
+        Ok(()) => {
+            state.push(file.clone());
+            Ok(1)
+        }
+        Err(e) => Ok(push_io_error(state, e, None)),
+    }
+}
+
+/// 创建文件句柄的迭代器：每次调用按格式读取，第一个结果为 nil 时结束
+/// close_at_eof 为真时（io.lines(filename)）在文件尾自动关闭文件
+fn lines_iterator(file: Value, formats: Vec<Value>, close_at_eof: bool) -> Value {
+    let f = move |state: &mut ExeState| -> Result<i32, LuaError> {
+        let Value::UserData(u) = &file else { unreachable!() };
+        if matches!(u.data.borrow().downcast_ref::<LuaFile>(), Some(LuaFile::Closed)) {
+            return Err(state.error("file is already closed"));
+        }
+        // 把格式参数放回栈上，复用 read_formats 的解析逻辑
+        let first = state.get_top() + 1;
+        for v in &formats {
+            state.push(v.clone());
+        }
+        let n = read_formats(state, &file, first, "lines")?;
+        let results = state_results(state, n);
+        if results.first().is_none_or(|v| *v == Value::Nil) && close_at_eof {
+            *u.data.borrow_mut().downcast_mut::<LuaFile>().unwrap() = LuaFile::Closed;
+        }
+        for v in results {
+            state.push(v);
+        }
+        Ok(n)
+    };
+    Value::RustClosure(Rc::new(RefCell::new(Box::new(f))))
+}
+
+/// 取出栈顶的 n 个返回值
+fn state_results(state: &mut ExeState, n: i32) -> Vec<Value> {
+    (0..n).map(|_| state.pop()).collect::<Vec<_>>().into_iter().rev().collect()
+}
+
+/// 根据 Lua 的打开模式字符串设置 OpenOptions
+fn open_options(mode: &str) -> Option<OpenOptions> {
+    let mut opts = OpenOptions::new();
+    let mode = mode.trim_end_matches('b');
+    match mode {
+        "r" => opts.read(true),
+        "w" => opts.write(true).create(true).truncate(true),
+        "a" => opts.append(true).create(true),
+        "r+" => opts.read(true).write(true),
+        "w+" => opts.read(true).write(true).create(true).truncate(true),
+        "a+" => opts.read(true).append(true).create(true),
+        _ => return None,
+    };
+    Some(opts)
+}
+
+/// 文件句柄的元表：__index 指向方法表，__name 为 "FILE*"；每个解释器状态一张
+fn file_metatable() -> Rc<RefCell<Table>> {
+    let mut methods = Table::new(0, 8);
+    methods.set_str("read", Value::Function(file_read));
+    methods.set_str("write", Value::Function(file_write));
+    methods.set_str("lines", Value::Function(file_lines));
+    methods.set_str("close", Value::Function(io_close));
+    methods.set_str("flush", Value::Function(file_flush));
+    methods.set_str("seek", Value::Function(file_seek));
+    let mut meta = Table::new(0, 2);
+    meta.set_str("__index", Value::from(methods));
+    meta.set_str("__name", Value::from("FILE*"));
+    Rc::new(RefCell::new(meta))
+}
+
+fn new_file(state: &mut ExeState, file: LuaFile) -> Value {
+    let meta = state.metatable_of::<LuaFile>(file_metatable);
+    Value::UserData(AnyUserData::new(file, Some(meta)))
+}
+
+/// io.open(filename [, mode])
+fn io_open(state: &mut ExeState) -> Result<i32, LuaError> {
+    let name = state.check_string(1, "open")?;
+    let mode = state.opt_string(2, "open")?.unwrap_or_else(|| "r".to_string());
+    let Some(opts) = open_options(&mode) else {
+        return Err(state.arg_error(2, "open", "invalid mode"));
+    };
+    match opts.open(&name) {
+        Ok(f) => {
+            let file = new_file(state, LuaFile::File(BufReader::new(f)));
+            state.push(file);
+            Ok(1)
+        }
+        Err(e) => Ok(push_io_error(state, e, Some(&name))),
+    }
+}
+
+/// io.close([file])：不带参数时关闭默认输出
+fn io_close(state: &mut ExeState) -> Result<i32, LuaError> {
+    if state.get_top() == 0 {
+        let stdout = io_stdout(state);
+        state.push(stdout);
+    }
+    let standard = with_file(state, 1, "close", |f| {
+        if matches!(f, LuaFile::Stdin | LuaFile::Stdout | LuaFile::Stderr) {
+            return true;
+        }
+        let _ = f.flush();
+        *f = LuaFile::Closed;
+        false
+    })?;
+    if standard {
+        state.push(Value::Nil);
+        state.push(Value::from("cannot close standard file"));
+        return Ok(2);
+    }
+    state.push(Value::Boolean(true));
+    Ok(1)
+}
+
+/// io.read(...)：从标准输入读取
+fn io_read(state: &mut ExeState) -> Result<i32, LuaError> {
+    let stdin = io_field(state, "stdin");
+    read_formats(state, &stdin, 1, "read")
+}
+
+/// io.write(...)：写到标准输出
+fn io_write(state: &mut ExeState) -> Result<i32, LuaError> {
+    let stdout = io_stdout(state);
+    write_values(state, &stdout, 1, "write")
+}
+
+/// io.lines([filename, ...])：不带文件名时逐行读取标准输入
+fn io_lines(state: &mut ExeState) -> Result<i32, LuaError> {
+    let formats = (2..=state.get_top()).map(|i| state.arg(i)).collect();
+    let iter = match state.opt_string(1, "lines")? {
+        None => lines_iterator(io_field(state, "stdin"), formats, false),
+        Some(name) => match File::open(&name) {
+            Ok(f) => lines_iterator(new_file(state, LuaFile::File(BufReader::new(f))), formats, true),
+            Err(e) => return Err(state.error(format!("{name}: {}", os_error_message(&e)))),
+        },
+    };
+    state.push(iter);
+    Ok(1)
+}
+
+/// io.type(obj)：返回 "file"、"closed file" 或 nil
+fn io_type(state: &mut ExeState) -> Result<i32, LuaError> {
+    let t = match state.arg(1) {
+        Value::UserData(u) => match u.data.borrow().downcast_ref::<LuaFile>() {
+            Some(LuaFile::Closed) => Value::from("closed file"),
+            Some(_) => Value::from("file"),
+            None => Value::Nil,
+        },
+        _ => Value::Nil,
+    };
+    state.push(t);
+    Ok(1)
+}
+
+/// file:read(...)
+fn file_read(state: &mut ExeState) -> Result<i32, LuaError> {
+    with_file(state, 1, "read", |_| ())?;
+    let file = state.arg(1);
+    read_formats(state, &file, 2, "read")
+}
+
+/// file:write(...)
+fn file_write(state: &mut ExeState) -> Result<i32, LuaError> {
+    with_file(state, 1, "write", |_| ())?;
+    let file = state.arg(1);
+    write_values(state, &file, 2, "write")
+}
+
+/// file:lines(...)：不会在文件尾自动关闭文件
+fn file_lines(state: &mut ExeState) -> Result<i32, LuaError> {
+    with_file(state, 1, "lines", |_| ())?;
+    let formats = (2..=state.get_top()).map(|i| state.arg(i)).collect();
+    let iter = lines_iterator(state.arg(1), formats, false);
+    state.push(iter);
+    Ok(1)
+}
+
+/// file:flush()
+fn file_flush(state: &mut ExeState) -> Result<i32, LuaError> {
+    match with_file(state, 1, "flush", |f| f.flush())? {
+        Ok(()) => {
+            let file = state.arg(1);
+            state.push(file);
+            Ok(1)
+        }
+        Err(e) => Ok(push_io_error(state, e, None)),
+    }
+}
+
+/// file:seek([whence [, offset]])：返回相对文件头的新位置
+fn file_seek(state: &mut ExeState) -> Result<i32, LuaError> {
+    let whence = state.opt_string(2, "seek")?.unwrap_or_else(|| "cur".to_string());
+    let offset = state.opt_integer(3, "seek")?.unwrap_or(0);
+    let pos = match whence.as_str() {
+        "set" => SeekFrom::Start(offset.max(0) as u64),
+        "cur" => SeekFrom::Current(offset),
+        "end" => SeekFrom::End(offset),
+        _ => return Err(state.arg_error(2, "seek", &format!("invalid option '{whence}'"))),
+    };
+    let result = with_file(state, 1, "seek", |f| match f {
+        LuaFile::File(r) => r.seek(pos),
+        _ => Err(io::Error::from_raw_os_error(libc::ESPIPE)),
+    })?;
+    match result {
+        Ok(p) => {
+            state.push(Value::Integer(p as i64));
+            Ok(1)
+        }
+        Err(e) => Ok(push_io_error(state, e, None)),
+    }
+}
+
+/// 读取 io 表中的标准句柄；io 表被脚本替换时退回到新建的句柄
+fn io_field(state: &mut ExeState, name: &str) -> Value {
+    if let Value::Table(t) = state.get_global("io") {
+        let v = t.borrow().get_str(name);
+        if matches!(&v, Value::UserData(u) if u.data.borrow().is::<LuaFile>()) {
+            return v;
+        }
+    }
+    let file = match name {
+        "stdin" => LuaFile::Stdin,
+        "stderr" => LuaFile::Stderr,
+        _ => LuaFile::Stdout,
+    };
+    new_file(state, file)
+}
+
+fn io_stdout(state: &mut ExeState) -> Value {
+    io_field(state, "stdout")
+}
+
+/// 创建 io 库表
+pub fn open_lib(state: &mut ExeState) -> Value {
+    let mut io = Table::new(0, 16);
+    io.set_str("open", Value::Function(io_open));
+    io.set_str("close", Value::Function(io_close));
+    io.set_str("read", Value::Function(io_read));
+    io.set_str("write", Value::Function(io_write));
+    io.set_str("lines", Value::Function(io_lines));
+    io.set_str("type", Value::Function(io_type));
+    io.set_str("stdin", new_file(state, LuaFile::Stdin));
+    io.set_str("stdout", new_file(state, LuaFile::Stdout));
+    io.set_str("stderr", new_file(state, LuaFile::Stderr));
+    Value::from(io)
+}
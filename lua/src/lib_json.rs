@@ -0,0 +1,86 @@
+// json 模块（serde 特性），通过 require("json") 加载
+// - json.encode(value [, options])：编码为 JSON 字符串，options 表的字段：
+//   sort_keys（对象按键排序）、empty_as_array（空表编码为 []）、
+//   sparse_array（稀疏数组："map" 编码为对象，默认；"fill" 空缺处填 null；"error" 报错）、
+//   nan（NaN 与无穷大："error" 报错，默认；"null" 编码为 null）
+//   默认报错而不是像 SerializeOptions::default() 那样原样交给序列化器：serde_json 会把它们悄悄写成 null，
+//   JSON 又不能表示它们，由调用者明确选择 "null" 更不容易丢失数据
+// - json.decode(s [, options])：解码 JSON 字符串，options.null 为 null 解码得到的值（默认为 nil）
+// - json.null：表示 null 的值（空指针轻量用户数据），编码为 null，可用作 decode 的 null 选项
+
+use crate::sync::{Rc, RefCell};
+use crate::value::{Table, Value};
+use crate::value_serde::{NanMode, SerializeOptions, SparseArray, ValueSeed};
+use crate::vm::{ExeState, LuaError};
+use serde::de::DeserializeSeed;
+
+/// json.null
+const NULL: Value = Value::LightUserData(std::ptr::null_mut());
+
+/// 读取函数 fname 的选项表中的字符串选项，返回其在 choices 中的位置；缺省时为 0
+fn check_choice(
+    state: &ExeState,
+    fname: &str,
+    options: &Table,
+    name: &str,
+    choices: &[&str],
+) -> Result<usize, LuaError> {
+    match options.get_str(name) {
+        Value::Nil => Ok(0),
+        v => v
+            .as_str()
+            .and_then(|s| choices.iter().position(|c| *c == s))
+            .ok_or_else(|| state.arg_error(2, fname, &format!("invalid value for option '{name}'"))),
+    }
+}
+
+/// 读取第 i 个参数的选项表，没有时为空表
+fn opt_options(state: &ExeState, i: usize, fname: &str) -> Result<Rc<RefCell<Table>>, LuaError> {
+    match state.arg(i) {
+        Value::Nil => Ok(Rc::new(RefCell::new(Table::new(0, 0)))),
+        Value::Table(t) => Ok(t),
+        _ => Err(state.arg_type_error(i, fname, "table")),
+    }
+}
+
+fn json_encode(state: &mut ExeState) -> Result<i32, LuaError> {
+    if state.get_top() == 0 {
+        return Err(state.arg_error(1, "encode", "value expected"));
+    }
+    let opts = opt_options(state, 2, "encode")?;
+    let opts = opts.borrow();
+    let options = SerializeOptions {
+        empty_as_array: opts.get_str("empty_as_array").is_truthy(),
+        sparse_array: [SparseArray::Map, SparseArray::Fill, SparseArray::Error]
+            [check_choice(state, "encode", &opts, "sparse_array", &["map", "fill", "error"])?],
+        nan: [NanMode::Error, NanMode::Null][check_choice(state, "encode", &opts, "nan", &["error", "null"])?],
+        sort_keys: opts.get_str("sort_keys").is_truthy(),
+    };
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut out);
+    state.arg(1).serialize_with(&options, &mut serializer).map_err(|e| state.error(e.to_string()))?;
+    state.push(Value::from(out));
+    Ok(1)
+}
+
+fn json_decode(state: &mut ExeState) -> Result<i32, LuaError> {
+    let s = state.check_bytes(1, "decode")?;
+    let null = opt_options(state, 2, "decode")?.borrow().get_str("null");
+    let mut deserializer = serde_json::Deserializer::from_slice(&s);
+    let value = ValueSeed { null }
+        .deserialize(&mut deserializer)
+        .and_then(|v| deserializer.end().map(|_| v))
+        .map_err(|e| state.error(e.to_string()))?;
+    state.push(value);
+    Ok(1)
+}
+
+/// require("json") 的加载函数
+pub fn loader(state: &mut ExeState) -> Result<i32, LuaError> {
+    let mut json = Table::new(0, 3);
+    json.set_str("encode", Value::Function(json_encode));
+    json.set_str("decode", Value::Function(json_decode));
+    json.set_str("null", NULL);
+    state.push(Value::from(json));
+    Ok(1)
+}
@@ -0,0 +1,254 @@
+// Lua os 标准库
+// 时间相关函数（os.time/os.date/os.clock）通过 libc 调用系统的 mktime/strftime/clock，
+// 与 C 实现的 Lua 行为一致（包括本地时区）
+
+use crate::lib_io::os_error_message;
+use crate::value::{Table, Value};
+use crate::vm::{ExeState, LuaError};
+use std::ffi::{CStr, CString};
+use std::io::{self, Write};
+
+/// os.time([table])：不带参数返回当前时间戳，带表时按本地时间换算
+fn os_time(state: &mut ExeState) -> Result<i32, LuaError> {
+    let t = match state.arg(1) {
+        Value::Nil => unsafe { libc::time(std::ptr::null_mut()) },
+        Value::Table(t) => {
+            let t = t.borrow();
+            // 读取字段并减去 delta（年份从 1900 起、月份从 0 起），结果超出 int 范围时报错
+            let field = |key: &str, default: Option<i64>, delta: i64| -> Result<libc::c_int, LuaError> {
+                match t.get_str(key) {
+                    Value::Nil => default
+                        .map(|d| d as libc::c_int)
+                        .ok_or_else(|| state.error(format!("field '{key}' missing in date table"))),
+                    v => v
+                        .to_integer()
+                        .and_then(|n| n.checked_sub(delta))
+                        .and_then(|n| libc::c_int::try_from(n).ok())
+                        .ok_or_else(|| state.error(format!("field '{key}' is out-of-bound"))),
+                }
+            };
+            let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+            tm.tm_year = field("year", None, 1900)?;
+            tm.tm_mon = field("month", None, 1)?;
+            tm.tm_mday = field("day", None, 0)?;
+            tm.tm_hour = field("hour", Some(12), 0)?;
+            tm.tm_min = field("min", Some(0), 0)?;
+            tm.tm_sec = field("sec", Some(0), 0)?;
+            tm.tm_isdst = match t.get_str("isdst") {
+                Value::Nil => -1,
+                v => v.is_truthy() as libc::c_int,
+            };
+            let t = unsafe { libc::mktime(&mut tm) };
+            if t == -1 {
+                return Err(state.error(
+                    "time result cannot be represented in this installation",
+                ));
+            }
+            t
+        }
+        _ => return Err(state.arg_type_error(1, "time", "table")),
+    };
+    state.push(Value::Integer(t as i64));
+    Ok(1)
+}
+
+/// os.clock()：程序使用的 CPU 时间（秒）
+fn os_clock(state: &mut ExeState) -> Result<i32, LuaError> {
+    // 与 C 的 clock() 含义相同：进程消耗的 CPU 时间
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut ts) };
+    state.push(Value::Float(ts.tv_sec as f64 + ts.tv_nsec as f64 / 1e9));
+    Ok(1)
+}
+
+/// os.difftime(t2 [, t1])
+fn os_difftime(state: &mut ExeState) -> Result<i32, LuaError> {
+    let t2 = state.check_number(1, "difftime")?;
+    let t1 = match state.arg(2) {
+        Value::Nil => 0.0,
+        _ => state.check_number(2, "difftime")?,
+    };
+    state.push(Value::Float(t2 - t1));
+    Ok(1)
+}
+
+/// os.date([format [, time]])
+/// - 以 '!' 开头时按 UTC 格式化，否则按本地时间
+/// - "*t" 返回日期表，其余交给 strftime 处理（默认 "%c"）
+fn os_date(state: &mut ExeState) -> Result<i32, LuaError> {
+    let format = state.opt_string(1, "date")?.unwrap_or_else(|| "%c".to_string());
+    let t: libc::time_t = match state.opt_integer(2, "date")? {
+        Some(t) => t as libc::time_t,
+        None => unsafe { libc::time(std::ptr::null_mut()) },
+    };
+    let (utc, format) = match format.strip_prefix('!') {
+        Some(f) => (true, f),
+        None => (false, format.as_str()),
+    };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let ok = unsafe {
+        if utc {
+            !libc::gmtime_r(&t, &mut tm).is_null()
+        } else {
+            !libc::localtime_r(&t, &mut tm).is_null()
+        }
+    };
+    if !ok {
+        return Err(state.error(
+            "date result cannot be represented in this installation",
+        ));
+    }
+
+    if format.starts_with("*t") {
+        let mut date = Table::new(0, 9);
+        date.set_str("year", Value::Integer(tm.tm_year as i64 + 1900));
+        date.set_str("month", Value::Integer(tm.tm_mon as i64 + 1));
+        date.set_str("day", Value::Integer(tm.tm_mday as i64));
+        date.set_str("hour", Value::Integer(tm.tm_hour as i64));
+        date.set_str("min", Value::Integer(tm.tm_min as i64));
+        date.set_str("sec", Value::Integer(tm.tm_sec as i64));
+        date.set_str("wday", Value::Integer(tm.tm_wday as i64 + 1));
+        date.set_str("yday", Value::Integer(tm.tm_yday as i64 + 1));
+        date.set_str("isdst", Value::Boolean(tm.tm_isdst > 0));
+        state.push(Value::from(date));
+        return Ok(1);
+    }
+
+    let s = strftime(state, format, &tm)?;
+    state.push(Value::from(s));
+    Ok(1)
+}
+
+/// 逐个转换说明符调用 strftime，非法的说明符报错而不是交给 C 库
+fn strftime(state: &ExeState, format: &str, tm: &libc::tm) -> Result<String, LuaError> {
+    // C99 strftime 的合法转换说明符（含 E/O 修饰）
+    const VALID: &str = "aAbBcCdDeFgGhHIjmMnprRStTuUVwWxXyYzZ%";
+    const VALID_E: &str = "cCxXyY";
+    const VALID_O: &str = "deHImMSuUVwWy";
+
+    let mut out = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            out.push(ch);
+            continue;
+        }
+        let mut spec = String::from("%");
+        let conv = chars.next();
+        match conv {
+            Some(c @ ('E' | 'O')) => {
+                let valid = if c == 'E' { VALID_E } else { VALID_O };
+                match chars.next() {
+                    Some(d) if valid.contains(d) => {
+                        spec.push(c);
+                        spec.push(d);
+                    }
+                    d => return Err(invalid_conversion(state, c, d)),
+                }
+            }
+            Some(c) if VALID.contains(c) => spec.push(c),
+            c => return Err(invalid_conversion(state, '%', c)),
+        }
+        let cspec = CString::new(spec).unwrap();
+        let mut buf = [0u8; 256];
+        let n = unsafe {
+            libc::strftime(buf.as_mut_ptr() as *mut libc::c_char, buf.len(), cspec.as_ptr(), tm)
+        };
+        out.push_str(&String::from_utf8_lossy(&buf[..n]));
+    }
+    Ok(out)
+}
+
+fn invalid_conversion(state: &ExeState, c: char, d: Option<char>) -> LuaError {
+    let spec = match (c, d) {
+        ('%', Some(d)) => format!("%{d}"),
+        ('%', None) => "%".to_string(),
+        (c, Some(d)) => format!("%{c}{d}"),
+        (c, None) => format!("%{c}"),
+    };
+    state.arg_error(1, "date", &format!("invalid conversion specifier '{spec}'"))
+}
+
+/// os.getenv(varname)
+fn os_getenv(state: &mut ExeState) -> Result<i32, LuaError> {
+    let name = state.check_string(1, "getenv")?;
+    match std::env::var_os(&name) {
+        Some(v) => state.push(Value::from(v.to_string_lossy().into_owned())),
+        None => state.push(Value::Nil),
+    }
+    Ok(1)
+}
+
+/// 成功时返回 true，失败时返回 nil, 错误信息, 错误码
+fn push_result(state: &mut ExeState, result: io::Result<()>, filename: &str) -> i32 {
+    match result {
+        Ok(()) => {
+            state.push(Value::Boolean(true));
+            1
+        }
+        Err(e) => {
+            state.push(Value::Nil);
+            state.push(Value::from(format!("{filename}: {}", os_error_message(&e))));
+            state.push(Value::Integer(e.raw_os_error().unwrap_or(0) as i64));
+            3
+        }
+    }
+}
+
+/// os.remove(filename)：可删除文件或空目录
+fn os_remove(state: &mut ExeState) -> Result<i32, LuaError> {
+    let name = state.check_string(1, "remove")?;
+    let result = match std::fs::symlink_metadata(&name) {
+        Ok(m) if m.is_dir() => std::fs::remove_dir(&name),
+        _ => std::fs::remove_file(&name),
+    };
+    Ok(push_result(state, result, &name))
+}
+
+/// os.rename(oldname, newname)
+fn os_rename(state: &mut ExeState) -> Result<i32, LuaError> {
+    let from = state.check_string(1, "rename")?;
+    let to = state.check_string(2, "rename")?;
+    let result = std::fs::rename(&from, &to);
+    Ok(push_result(state, result, &from))
+}
+
+/// os.tmpname()：用 mkstemp 创建临时文件并返回其文件名
+fn os_tmpname(state: &mut ExeState) -> Result<i32, LuaError> {
+    let mut template = *b"/tmp/lua_XXXXXX\0";
+    let fd = unsafe { libc::mkstemp(template.as_mut_ptr() as *mut libc::c_char) };
+    if fd == -1 {
+        return Err(state.error("unable to generate a unique filename"));
+    }
+    unsafe { libc::close(fd) };
+    let name = CStr::from_bytes_with_nul(&template).unwrap();
+    state.push(Value::from(name.to_string_lossy().into_owned()));
+    Ok(1)
+}
+
+/// os.exit([code [, close]])：true 为成功，false 为失败，默认成功
+fn os_exit(state: &mut ExeState) -> Result<i32, LuaError> {
+    let code = match state.arg(1) {
+        Value::Nil | Value::Boolean(true) => 0,
+        Value::Boolean(false) => 1,
+        _ => state.check_integer(1, "exit")? as i32,
+    };
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    std::process::exit(code);
+}
+
+/// 创建 os 库表
+pub fn open_lib() -> Value {
+    let mut os = Table::new(0, 12);
+    os.set_str("time", Value::Function(os_time));
+    os.set_str("clock", Value::Function(os_clock));
+    os.set_str("difftime", Value::Function(os_difftime));
+    os.set_str("date", Value::Function(os_date));
+    os.set_str("getenv", Value::Function(os_getenv));
+    os.set_str("remove", Value::Function(os_remove));
+    os.set_str("rename", Value::Function(os_rename));
+    os.set_str("tmpname", Value::Function(os_tmpname));
+    os.set_str("exit", Value::Function(os_exit));
+    Value::from(os)
+}
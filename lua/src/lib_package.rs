@@ -0,0 +1,171 @@
+// Lua package 库与 require
+// require(name) 先查 package.loaded 缓存，再依次调用 package.searchers 中的搜索函数寻找加载函数：
+// - 第一个搜索函数查 package.preload（宿主通过 ExeState::register_module 注册的 Rust 模块放在这里）
+// - 第二个搜索函数按 package.path 中的模板查找 Lua 源文件
+// 找到的加载函数以 (模块名, 附加数据) 调用，返回值存入 package.loaded[name]
+
+use crate::sync::{Rc, RefCell};
+use crate::value::{Table, Value};
+use crate::vm::{ExeState, LuaError};
+use std::fs::File;
+use std::io::BufReader;
+
+/// package.path 的默认值
+const DEFAULT_PATH: &str = "./?.lua;./?/init.lua";
+
+/// 读取 package 表中的某个字段，要求为表
+fn package_table(state: &ExeState, field: &str) -> Result<Rc<RefCell<Table>>, LuaError> {
+    match state.package().borrow().get_str(field) {
+        Value::Table(t) => Ok(t),
+        _ => Err(state.error(format!("'package.{field}' must be a table"))),
+    }
+}
+
+/// require(name)：加载模块，返回模块值与加载器附加数据（如文件名）
+pub fn require(state: &mut ExeState) -> Result<i32, LuaError> {
+    let name = state.check_string(1, "require")?;
+    let loaded = package_table(state, "loaded")?;
+    let cached = loaded.borrow().get_str(&name);
+    if cached.is_truthy() {
+        state.push(cached);
+        return Ok(1);
+    }
+
+    let (loader, data) = find_loader(state, &name)?;
+    let result = state.call(loader, vec![Value::from(name.as_str()), data.clone()])?;
+    let mut loaded = loaded.borrow_mut();
+    if let Some(v) = result.into_iter().next().filter(|v| *v != Value::Nil) {
+        loaded.set_str(&name, v);
+    }
+    // 模块没有返回值也没有自行设置 package.loaded 时记为 true
+    let module = match loaded.get_str(&name) {
+        Value::Nil => {
+            loaded.set_str(&name, Value::Boolean(true));
+            Value::Boolean(true)
+        }
+        v => v,
+    };
+    state.push(module);
+    state.push(data);
+    Ok(2)
+}
+
+/// 依次调用 package.searchers 中的搜索函数，返回第一个找到的加载函数及其附加数据
+/// 搜索函数返回字符串表示未找到的原因，全部失败时汇总到错误信息中
+fn find_loader(state: &mut ExeState, name: &str) -> Result<(Value, Value), LuaError> {
+    let searchers = package_table(state, "searchers")?;
+    let mut msg = String::new();
+    for i in 1.. {
+        let searcher = searchers.borrow().get(&Value::Integer(i));
+        if searcher == Value::Nil {
+            break;
+        }
+        let mut results = state.call(searcher, vec![Value::from(name)])?.into_iter();
+        let loader = results.next().unwrap_or(Value::Nil);
+        if loader.type_name() == "function" {
+            return Ok((loader, results.next().unwrap_or(Value::Nil)));
+        }
+        if let Some(s) = loader.as_bytes() {
+            msg.push_str("\n\t");
+            msg.push_str(&String::from_utf8_lossy(s));
+        }
+    }
+    Err(state.error(format!("module '{name}' not found:{msg}")))
+}
+
+/// 搜索函数：在 package.preload 中查找
+fn searcher_preload(state: &mut ExeState) -> Result<i32, LuaError> {
+    let name = state.check_string(1, "searcher")?;
+    let preload = package_table(state, "preload")?;
+    let loader = preload.borrow().get_str(&name);
+    if loader == Value::Nil {
+        state.push(Value::from(format!("no field package.preload['{name}']")));
+        return Ok(1);
+    }
+    state.push(loader);
+    state.push(Value::from(":preload:"));
+    Ok(2)
+}
+
+/// 搜索函数：按 package.path 查找 Lua 源文件并编译为加载函数
+fn searcher_lua(state: &mut ExeState) -> Result<i32, LuaError> {
+    let name = state.check_string(1, "searcher")?;
+    let path = match state.package().borrow().get_str("path").as_str() {
+        Some(p) => p.to_string(),
+        None => return Err(state.error("'package.path' must be a string")),
+    };
+    let filename = match search_path(&name, &path, ".", "/") {
+        Ok(f) => f,
+        Err(msg) => {
+            state.push(Value::from(msg));
+            return Ok(1);
+        }
+    };
+    let loader = File::open(&filename)
+        .map_err(|e| LuaError::new(e.to_string()))
+        .and_then(|f| state.load(BufReader::new(f), &filename))
+        .map_err(|e| {
+            state.error(format!("error loading module '{name}' from file '{filename}':\n\t{e}"))
+        })?;
+    state.push(loader);
+    state.push(Value::from(filename));
+    Ok(2)
+}
+
+/// 在以 ';' 分隔的模板中把 '?' 替换为模块名（其中的 sep 先替换为 rep），返回第一个可读的文件
+/// 都不存在时返回逐个列出所尝试文件的信息
+fn search_path(name: &str, path: &str, sep: &str, rep: &str) -> Result<String, String> {
+    let name = if sep.is_empty() { name.to_string() } else { name.replace(sep, rep) };
+    let mut tried = Vec::new();
+    for template in path.split(';').filter(|t| !t.is_empty()) {
+        let filename = template.replace('?', &name);
+        if File::open(&filename).is_ok() {
+            return Ok(filename);
+        }
+        tried.push(format!("no file '{filename}'"));
+    }
+    Err(tried.join("\n\t"))
+}
+
+/// package.searchpath(name, path [, sep [, rep]])
+fn package_searchpath(state: &mut ExeState) -> Result<i32, LuaError> {
+    let name = state.check_string(1, "searchpath")?;
+    let path = state.check_string(2, "searchpath")?;
+    let sep = state.opt_string(3, "searchpath")?.unwrap_or_else(|| ".".to_string());
+    let rep = state.opt_string(4, "searchpath")?.unwrap_or_else(|| "/".to_string());
+    match search_path(&name, &path, &sep, &rep) {
+        Ok(filename) => {
+            state.push(Value::from(filename));
+            Ok(1)
+        }
+        Err(msg) => {
+            state.push(Value::Nil);
+            state.push(Value::from(msg));
+            Ok(2)
+        }
+    }
+}
+
+/// 初始的 package.path：取环境变量 LUA_PATH_5_4 或 LUA_PATH，其中的 ";;" 替换为默认路径
+fn init_path() -> String {
+    match std::env::var("LUA_PATH_5_4").or_else(|_| std::env::var("LUA_PATH")) {
+        Ok(path) => path.replacen(";;", &format!(";{DEFAULT_PATH};"), 1),
+        Err(_) => DEFAULT_PATH.to_string(),
+    }
+}
+
+/// 创建 package 库表
+pub fn open_lib() -> Rc<RefCell<Table>> {
+    let mut searchers = Table::new(2, 0);
+    searchers.set(Value::Integer(1), Value::Function(searcher_preload));
+    searchers.set(Value::Integer(2), Value::Function(searcher_lua));
+
+    let mut package = Table::new(0, 6);
+    package.set_str("path", Value::from(init_path()));
+    package.set_str("config", Value::from("/\n;\n?\n!\n-\n"));
+    package.set_str("loaded", Value::from(Table::new(0, 4)));
+    package.set_str("preload", Value::from(Table::new(0, 0)));
+    package.set_str("searchers", Value::from(searchers));
+    package.set_str("searchpath", Value::Function(package_searchpath));
+    Rc::new(RefCell::new(package))
+}
@@ -0,0 +1,365 @@
+// Lua string 标准库（目前只有二进制打包函数）
+// string.pack/unpack/packsize 使用 Lua 5.4 的格式语言，与 C 实现的行为一致：
+// - 字节序：< 小端、> 大端、= 本机（默认）；![n] 设置最大对齐（默认 1，不带 n 时为本机的 8）
+// - 整数：b/B(1) h/H(2) i/I[n](默认 4) l/L(8) j/J(8) T(8)，小写为有符号，n 的范围是 1..=16
+// - 浮点数：f(4) d/n(8)
+// - 字符串：s[n] 前缀长度（默认 8 字节）、z 以 '\0' 结尾、cn 定长
+// - 填充：x 一个零字节，Xop 按 op 的大小对齐，空格忽略
+// 需要对齐的选项按 min(大小, 最大对齐) 对齐，且对齐值必须是 2 的幂
+
+use crate::value::{Table, Value};
+use crate::vm::{ExeState, LuaError};
+
+/// 整数选项允许的最大字节数
+const MAX_INT_SIZE: usize = 16;
+
+/// Lua 整数的字节数
+const SZINT: usize = 8;
+
+/// 本机的最大对齐（! 不带数字时）
+const NATIVE_ALIGN: usize = 8;
+
+/// 格式中的一项
+#[derive(Clone, Copy, PartialEq)]
+enum KOption {
+    Int,
+    Uint,
+    Float,
+    Double,
+    Char,
+    String,
+    Zstr,
+    Padding,
+    PadAlign,
+    Nop,
+}
+
+/// 格式解析状态：剩余格式、字节序与最大对齐
+struct Header<'a> {
+    fmt: &'a [u8],
+    little: bool,
+    max_align: usize,
+}
+
+impl<'a> Header<'a> {
+    fn new(fmt: &'a [u8]) -> Self {
+        Header {
+            fmt,
+            little: cfg!(target_endian = "little"),
+            max_align: 1,
+        }
+    }
+
+    /// 读取可选的数字，没有时返回 default
+    fn num(&mut self, default: usize) -> usize {
+        if !self.fmt.first().is_some_and(u8::is_ascii_digit) {
+            return default;
+        }
+        let mut n = 0usize;
+        while let Some(&c) = self.fmt.first().filter(|c| c.is_ascii_digit()) {
+            if n > (i32::MAX as usize - 9) / 10 {
+                break;
+            }
+            n = n * 10 + (c - b'0') as usize;
+            self.fmt = &self.fmt[1..];
+        }
+        n
+    }
+
+    /// 读取整数大小，检查范围
+    fn num_limit(&mut self, state: &ExeState, default: usize) -> Result<usize, LuaError> {
+        let n = self.num(default);
+        if n == 0 || n > MAX_INT_SIZE {
+            return Err(state.error(format!("integral size ({n}) out of limits [1,{MAX_INT_SIZE}]")));
+        }
+        Ok(n)
+    }
+
+    /// 读取一个选项，返回 (选项, 大小)
+    fn option(&mut self, state: &ExeState) -> Result<(KOption, usize), LuaError> {
+        let c = self.fmt[0];
+        self.fmt = &self.fmt[1..];
+        let r = match c {
+            b'b' => (KOption::Int, 1),
+            b'B' => (KOption::Uint, 1),
+            b'h' => (KOption::Int, 2),
+            b'H' => (KOption::Uint, 2),
+            b'l' | b'j' => (KOption::Int, 8),
+            b'L' | b'J' | b'T' => (KOption::Uint, 8),
+            b'f' => (KOption::Float, 4),
+            b'd' | b'n' => (KOption::Double, 8),
+            b'i' => (KOption::Int, self.num_limit(state, 4)?),
+            b'I' => (KOption::Uint, self.num_limit(state, 4)?),
+            b's' => (KOption::String, self.num_limit(state, 8)?),
+            b'c' => match self.fmt.first() {
+                Some(c) if c.is_ascii_digit() => (KOption::Char, self.num(0)),
+                _ => return Err(state.error("missing size for format option 'c'")),
+            },
+            b'z' => (KOption::Zstr, 0),
+            b'x' => (KOption::Padding, 1),
+            b'X' => (KOption::PadAlign, 0),
+            b' ' => (KOption::Nop, 0),
+            b'<' => {
+                self.little = true;
+                (KOption::Nop, 0)
+            }
+            b'>' => {
+                self.little = false;
+                (KOption::Nop, 0)
+            }
+            b'=' => {
+                self.little = cfg!(target_endian = "little");
+                (KOption::Nop, 0)
+            }
+            b'!' => {
+                self.max_align = self.num_limit(state, NATIVE_ALIGN)?;
+                (KOption::Nop, 0)
+            }
+            c => return Err(state.error(format!("invalid format option '{}'", c as char))),
+        };
+        Ok(r)
+    }
+
+    /// 读取一个选项并计算在 total 字节之后需要的对齐填充，返回 (选项, 大小, 填充字节数)
+    fn details(&mut self, state: &ExeState, total: usize, fname: &str) -> Result<(KOption, usize, usize), LuaError> {
+        let (opt, size) = self.option(state)?;
+        let mut align = size;
+        if opt == KOption::PadAlign {
+            // X 按其后的选项对齐
+            let next = if self.fmt.is_empty() {
+                None
+            } else {
+                Some(self.option(state)?)
+            };
+            match next {
+                Some((next, size)) if next != KOption::Char && size != 0 => align = size,
+                _ => return Err(state.arg_error(1, fname, "invalid next option for option 'X'")),
+            }
+        }
+        if align <= 1 || opt == KOption::Char {
+            return Ok((opt, size, 0));
+        }
+        let align = align.min(self.max_align);
+        if !align.is_power_of_two() {
+            return Err(state.arg_error(1, fname, "format asks for alignment not power of 2"));
+        }
+        Ok((opt, size, (align - (total & (align - 1))) & (align - 1)))
+    }
+}
+
+/// 把整数 n 按字节序写成 size 个字节，超过 8 字节的部分按符号扩展
+fn pack_int(out: &mut Vec<u8>, n: u64, little: bool, size: usize, negative: bool) {
+    let mut bytes: Vec<u8> = (0..size)
+        .map(|i| match i < SZINT {
+            true => (n >> (i * 8)) as u8,
+            false if negative => 0xFF,
+            false => 0,
+        })
+        .collect();
+    if !little {
+        bytes.reverse();
+    }
+    out.extend(bytes);
+}
+
+/// 读取 size 个字节的整数；超过 8 字节时多出的字节必须是符号扩展
+fn unpack_int(state: &ExeState, s: &[u8], little: bool, size: usize, signed: bool) -> Result<i64, LuaError> {
+    let byte = |i: usize| if little { s[i] } else { s[size - 1 - i] };
+    let limit = size.min(SZINT);
+    let mut res = (0..limit).rev().fold(0u64, |res, i| (res << 8) | byte(i) as u64);
+    if size < SZINT {
+        if signed {
+            let mask = 1u64 << (size * 8 - 1);
+            res = (res ^ mask).wrapping_sub(mask);
+        }
+    } else if size > SZINT {
+        let mask = if !signed || (res as i64) >= 0 { 0 } else { 0xFF };
+        if (limit..size).any(|i| byte(i) != mask) {
+            return Err(state.error(format!("{size}-byte integer does not fit into Lua Integer")));
+        }
+    }
+    Ok(res as i64)
+}
+
+/// 按字节序写入浮点数的字节（bytes 为小端序）
+fn pack_bytes(out: &mut Vec<u8>, mut bytes: Vec<u8>, little: bool) {
+    if !little {
+        bytes.reverse();
+    }
+    out.extend(bytes);
+}
+
+/// string.pack(fmt, v1, v2, ...)：按格式把值打包为字符串
+fn str_pack(state: &mut ExeState) -> Result<i32, LuaError> {
+    let fmt = state.check_bytes(1, "pack")?;
+    let mut h = Header::new(&fmt);
+    let mut out = Vec::new();
+    let mut arg = 1;
+    while !h.fmt.is_empty() {
+        let (opt, size, ntoalign) = h.details(state, out.len(), "pack")?;
+        out.resize(out.len() + ntoalign, 0);
+        arg += 1;
+        match opt {
+            KOption::Int => {
+                let n = state.check_integer(arg, "pack")?;
+                if size < SZINT {
+                    let lim = 1i64 << (size * 8 - 1);
+                    if !(-lim..lim).contains(&n) {
+                        return Err(state.arg_error(arg, "pack", "integer overflow"));
+                    }
+                }
+                pack_int(&mut out, n as u64, h.little, size, n < 0);
+            }
+            KOption::Uint => {
+                let n = state.check_integer(arg, "pack")?;
+                if size < SZINT && (n as u64) >= 1u64 << (size * 8) {
+                    return Err(state.arg_error(arg, "pack", "unsigned overflow"));
+                }
+                pack_int(&mut out, n as u64, h.little, size, false);
+            }
+            KOption::Float => {
+                let f = state.check_number(arg, "pack")? as f32;
+                pack_bytes(&mut out, f.to_le_bytes().to_vec(), h.little);
+            }
+            KOption::Double => {
+                let f = state.check_number(arg, "pack")?;
+                pack_bytes(&mut out, f.to_le_bytes().to_vec(), h.little);
+            }
+            KOption::Char => {
+                let s = state.check_bytes(arg, "pack")?;
+                if s.len() > size {
+                    return Err(state.arg_error(arg, "pack", "string longer than given size"));
+                }
+                out.extend(&s);
+                out.resize(out.len() + size - s.len(), 0);
+            }
+            KOption::String => {
+                let s = state.check_bytes(arg, "pack")?;
+                if size < SZINT && (s.len() as u64) >= 1u64 << (size * 8) {
+                    return Err(state.arg_error(arg, "pack", "string length does not fit in given size"));
+                }
+                pack_int(&mut out, s.len() as u64, h.little, size, false);
+                out.extend(&s);
+            }
+            KOption::Zstr => {
+                let s = state.check_bytes(arg, "pack")?;
+                if s.contains(&0) {
+                    return Err(state.arg_error(arg, "pack", "string contains zeros"));
+                }
+                out.extend(&s);
+                out.push(0);
+            }
+            KOption::Padding => {
+                out.push(0);
+                arg -= 1;
+            }
+            KOption::PadAlign | KOption::Nop => arg -= 1,
+        }
+    }
+    state.push(Value::from(out));
+    Ok(1)
+}
+
+/// string.packsize(fmt)：按格式打包的结果的字节数，格式中不能有变长的 s 与 z
+fn str_packsize(state: &mut ExeState) -> Result<i32, LuaError> {
+    let fmt = state.check_bytes(1, "packsize")?;
+    let mut h = Header::new(&fmt);
+    let mut total = 0usize;
+    while !h.fmt.is_empty() {
+        let (opt, size, ntoalign) = h.details(state, total, "packsize")?;
+        if opt == KOption::String || opt == KOption::Zstr {
+            return Err(state.arg_error(1, "packsize", "variable-length format"));
+        }
+        let size = size + ntoalign;
+        if total > i64::MAX as usize - size {
+            return Err(state.arg_error(1, "packsize", "format result too large"));
+        }
+        total += size;
+    }
+    state.push(Value::Integer(total as i64));
+    Ok(1)
+}
+
+/// string.unpack(fmt, s [, pos])：从 s 的 pos 处（默认 1）按格式解包，返回各个值与之后的位置
+fn str_unpack(state: &mut ExeState) -> Result<i32, LuaError> {
+    let fmt = state.check_bytes(1, "unpack")?;
+    let data = state.check_bytes(2, "unpack")?;
+    let ld = data.len();
+    let pos = match state.opt_integer(3, "unpack")?.unwrap_or(1) {
+        p if p > 0 => p as u64 - 1,
+        0 => 0,
+        p if p.unsigned_abs() > ld as u64 => 0,
+        p => (ld as i64 + p) as u64,
+    };
+    if pos > ld as u64 {
+        return Err(state.arg_error(3, "unpack", "initial position out of string"));
+    }
+    let mut pos = pos as usize;
+    let mut h = Header::new(&fmt);
+    let mut results = Vec::new();
+    while !h.fmt.is_empty() {
+        let (opt, size, ntoalign) = h.details(state, pos, "unpack")?;
+        if ntoalign + size > ld - pos {
+            return Err(state.arg_error(2, "unpack", "data string too short"));
+        }
+        pos += ntoalign;
+        let s = &data[pos..];
+        match opt {
+            KOption::Int | KOption::Uint => {
+                let n = unpack_int(state, s, h.little, size, opt == KOption::Int)?;
+                results.push(Value::Integer(n));
+            }
+            KOption::Float => {
+                let mut b = [0; 4];
+                b.copy_from_slice(&s[..4]);
+                let f = if h.little {
+                    f32::from_le_bytes(b)
+                } else {
+                    f32::from_be_bytes(b)
+                };
+                results.push(Value::Float(f as f64));
+            }
+            KOption::Double => {
+                let mut b = [0; 8];
+                b.copy_from_slice(&s[..8]);
+                let f = if h.little {
+                    f64::from_le_bytes(b)
+                } else {
+                    f64::from_be_bytes(b)
+                };
+                results.push(Value::Float(f));
+            }
+            KOption::Char => results.push(Value::from(&s[..size])),
+            KOption::String => {
+                let len = unpack_int(state, s, h.little, size, false)? as u64;
+                if len > (ld - pos - size) as u64 {
+                    return Err(state.arg_error(2, "unpack", "data string too short"));
+                }
+                let len = len as usize;
+                results.push(Value::from(&s[size..size + len]));
+                pos += len;
+            }
+            KOption::Zstr => {
+                let Some(len) = s.iter().position(|&c| c == 0) else {
+                    return Err(state.arg_error(2, "unpack", "unfinished string for format 'z'"));
+                };
+                results.push(Value::from(&s[..len]));
+                pos += len + 1;
+            }
+            KOption::Padding | KOption::PadAlign | KOption::Nop => {}
+        }
+        pos += size;
+    }
+    let n = results.len() + 1;
+    results.into_iter().for_each(|v| state.push(v));
+    state.push(Value::Integer(pos as i64 + 1));
+    Ok(n as i32)
+}
+
+pub fn open_lib() -> Value {
+    let mut string = Table::new(0, 3);
+    string.set_str("pack", Value::Function(str_pack));
+    string.set_str("packsize", Value::Function(str_packsize));
+    string.set_str("unpack", Value::Function(str_unpack));
+    Value::from(string)
+}
@@ -0,0 +1,236 @@
+// Lua utf8 标准库
+// 字符串是任意字节序列，这里按 UTF-8 解释其中的字节；
+// 与 C 实现一致，默认严格模式拒绝代理项和大于 10FFFF 的码点，lax 参数放宽到 7FFFFFFF
+
+use crate::lex::utf8_encode;
+use crate::value::{Table, Value};
+use crate::vm::{ExeState, LuaError};
+
+/// 宽松模式下允许的最大码点
+const MAX_UTF: u32 = 0x7FFF_FFFF;
+
+/// Unicode 的最大码点
+const MAX_UNICODE: u32 = 0x10FFFF;
+
+/// 匹配恰好一个 UTF-8 字节序列的模式
+const CHAR_PATTERN: &[u8] = b"[\0-\x7F\xC2-\xFD][\x80-\xBF]*";
+
+const MSG_INVALID: &str = "invalid UTF-8 code";
+
+/// 是否为 UTF-8 的后续字节（10xxxxxx）；越界视为字符串结尾的 '\0'
+fn is_cont(s: &[u8], i: usize) -> bool {
+    s.get(i).is_some_and(|&c| c & 0xC0 == 0x80)
+}
+
+/// 相对位置转换为绝对位置：负数从末尾算起，过小时返回 0
+fn pos_relative(pos: i64, len: usize) -> i64 {
+    if pos >= 0 {
+        pos
+    } else if pos.unsigned_abs() > len as u64 {
+        0
+    } else {
+        len as i64 + pos + 1
+    }
+}
+
+/// 解码 s[i..] 开头的一个字符，返回 (码点, 字节数)；不是合法序列时返回 None
+fn decode(s: &[u8], i: usize, strict: bool) -> Option<(u32, usize)> {
+    // 各长度序列能表示的最小码点，小于它的是过长编码
+    const LIMITS: [u32; 6] = [u32::MAX, 0x80, 0x800, 0x10000, 0x200000, 0x4000000];
+    let mut c = *s.get(i)? as u32;
+    let mut res = 0u32;
+    let mut count = 0;
+    if c < 0x80 {
+        res = c;
+    } else {
+        while c & 0x40 != 0 {
+            count += 1;
+            if !is_cont(s, i + count) || count > 5 {
+                return None;
+            }
+            res = (res << 6) | (s[i + count] & 0x3F) as u32;
+            c <<= 1;
+        }
+        res |= (c & 0x7F) << (count * 5);
+        if res > MAX_UTF || res < LIMITS[count] {
+            return None;
+        }
+    }
+    if strict && (res > MAX_UNICODE || (0xD800..=0xDFFF).contains(&res)) {
+        return None;
+    }
+    Some((res, count + 1))
+}
+
+/// utf8.char(...)：把每个码点编码后连接成字符串
+fn utf8_char(state: &mut ExeState) -> Result<i32, LuaError> {
+    let mut buf = Vec::new();
+    for i in 1..=state.get_top() {
+        let code = state.check_integer(i, "char")?;
+        if !(0..=MAX_UTF as i64).contains(&code) {
+            return Err(state.arg_error(i, "char", "value out of range"));
+        }
+        utf8_encode(code as u32, &mut buf);
+    }
+    state.push(Value::from(buf));
+    Ok(1)
+}
+
+/// utf8.codepoint(s [, i [, j [, lax]]])：返回 s[i..j] 中所有字符的码点
+fn utf8_codepoint(state: &mut ExeState) -> Result<i32, LuaError> {
+    let s = state.check_bytes(1, "codepoint")?;
+    let posi = pos_relative(state.opt_integer(2, "codepoint")?.unwrap_or(1), s.len());
+    let pose = pos_relative(state.opt_integer(3, "codepoint")?.unwrap_or(posi), s.len());
+    let strict = !state.arg(4).is_truthy();
+    if posi < 1 {
+        return Err(state.arg_error(2, "codepoint", "out of bounds"));
+    }
+    if pose > s.len() as i64 {
+        return Err(state.arg_error(3, "codepoint", "out of bounds"));
+    }
+    let mut n = 0;
+    let mut i = posi as usize - 1;
+    while i < pose as usize {
+        let Some((code, len)) = decode(&s, i, strict) else {
+            return Err(state.error(MSG_INVALID));
+        };
+        state.push(Value::Integer(code as i64));
+        i += len;
+        n += 1;
+    }
+    Ok(n)
+}
+
+/// utf8.len(s [, i [, j [, lax]]])：s[i..j] 中的字符数；
+/// 遇到非法序列时返回 fail 和它的位置
+fn utf8_len(state: &mut ExeState) -> Result<i32, LuaError> {
+    let s = state.check_bytes(1, "len")?;
+    let len = s.len() as i64;
+    let posi = pos_relative(state.opt_integer(2, "len")?.unwrap_or(1), s.len());
+    let posj = pos_relative(state.opt_integer(3, "len")?.unwrap_or(-1), s.len());
+    let strict = !state.arg(4).is_truthy();
+    if !(1..=len + 1).contains(&posi) {
+        return Err(state.arg_error(2, "len", "initial position out of bounds"));
+    }
+    if posj > len {
+        return Err(state.arg_error(3, "len", "final position out of bounds"));
+    }
+    let mut i = posi - 1;
+    let mut n = 0;
+    while i < posj {
+        match decode(&s, i as usize, strict) {
+            Some((_, l)) => i += l as i64,
+            None => {
+                state.push(Value::Nil);
+                state.push(Value::Integer(i + 1));
+                return Ok(2);
+            }
+        }
+        n += 1;
+    }
+    state.push(Value::Integer(n));
+    Ok(1)
+}
+
+/// utf8.offset(s, n [, i])：从位置 i 起第 n 个字符的起始位置；
+/// n 为 0 时返回 i 所在字符的起始位置
+fn utf8_offset(state: &mut ExeState) -> Result<i32, LuaError> {
+    let s = state.check_bytes(1, "offset")?;
+    let len = s.len() as i64;
+    let mut n = state.check_integer(2, "offset")?;
+    let default = if n >= 0 { 1 } else { len + 1 };
+    let posi = pos_relative(state.opt_integer(3, "offset")?.unwrap_or(default), s.len());
+    if !(1..=len + 1).contains(&posi) {
+        return Err(state.arg_error(3, "offset", "position out of bounds"));
+    }
+    let mut posi = posi as usize - 1;
+    if n == 0 {
+        while posi > 0 && is_cont(&s, posi) {
+            posi -= 1;
+        }
+    } else {
+        if is_cont(&s, posi) {
+            return Err(state.error("initial position is a continuation byte"));
+        }
+        if n < 0 {
+            while n < 0 && posi > 0 {
+                posi -= 1;
+                while posi > 0 && is_cont(&s, posi) {
+                    posi -= 1;
+                }
+                n += 1;
+            }
+        } else {
+            n -= 1;
+            while n > 0 && posi < s.len() {
+                posi += 1;
+                while is_cont(&s, posi) {
+                    posi += 1;
+                }
+                n -= 1;
+            }
+        }
+    }
+    if n == 0 {
+        state.push(Value::Integer(posi as i64 + 1));
+    } else {
+        state.push(Value::Nil);
+    }
+    Ok(1)
+}
+
+/// utf8.codes 的迭代函数：跳过当前字符的后续字节，返回下一个字符的位置和码点
+fn codes_aux(state: &mut ExeState, strict: bool) -> Result<i32, LuaError> {
+    let s = state.check_bytes(1, "for iterator")?;
+    let mut i = match state.arg(2) {
+        Value::Integer(n) if n >= 0 => n as usize,
+        _ => return Ok(0),
+    };
+    while i < s.len() && is_cont(&s, i) {
+        i += 1;
+    }
+    if i >= s.len() {
+        return Ok(0);
+    }
+    match decode(&s, i, strict) {
+        Some((code, len)) if !is_cont(&s, i + len) => {
+            state.push(Value::Integer(i as i64 + 1));
+            state.push(Value::Integer(code as i64));
+            Ok(2)
+        }
+        _ => Err(state.error(MSG_INVALID)),
+    }
+}
+
+fn codes_strict(state: &mut ExeState) -> Result<i32, LuaError> {
+    codes_aux(state, true)
+}
+
+fn codes_lax(state: &mut ExeState) -> Result<i32, LuaError> {
+    codes_aux(state, false)
+}
+
+/// utf8.codes(s [, lax])：供泛型 for 使用，依次得到 (位置, 码点)
+fn utf8_codes(state: &mut ExeState) -> Result<i32, LuaError> {
+    let s = state.check_bytes(1, "codes")?;
+    if is_cont(&s, 0) {
+        return Err(state.arg_error(1, "codes", MSG_INVALID));
+    }
+    let iter = if state.arg(2).is_truthy() { codes_lax } else { codes_strict };
+    let subject = state.arg(1);
+    state.push(Value::Function(iter));
+    state.push(subject);
+    state.push(Value::Integer(0));
+    Ok(3)
+}
+
+pub fn open_lib() -> Value {
+    let mut utf8 = Table::new(0, 6);
+    utf8.set_str("char", Value::Function(utf8_char));
+    utf8.set_str("charpattern", Value::from(CHAR_PATTERN));
+    utf8.set_str("codepoint", Value::Function(utf8_codepoint));
+    utf8.set_str("len", Value::Function(utf8_len));
+    utf8.set_str("offset", Value::Function(utf8_offset));
+    utf8.set_str("codes", Value::Function(utf8_codes));
+    Value::from(utf8)
+}
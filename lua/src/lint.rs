@@ -0,0 +1,322 @@
+// Lua 源代码静态检查
+// 在 AST 上按作用域跟踪局部变量，报告以下问题：
+// - 读取未定义的全局变量：既不是标准库的全局名字，也没有在本文件中被赋值
+// - 未使用的局部变量、局部函数与循环变量（只被赋值不算使用；以 _ 开头的名字不检查，函数参数不检查）
+// - 局部变量遮蔽外层（包括外层函数）的同名局部变量或参数
+// - 在 return、break、goto 或总是跳出的 do/if 语句之后、同一代码块中的语句永远不会执行
+
+use crate::ast::{self, Block, Exp, ExpKind, FieldKind, FuncBody, Stat, StatKind};
+use crate::lex::ParseError;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Cursor;
+
+/// Lua 5.4 标准环境中的全局名字
+const BUILTINS: &[&str] = &[
+    "_G", "_VERSION", "assert", "collectgarbage", "coroutine", "debug", "dofile", "error", "getmetatable", "io",
+    "ipairs", "load", "loadfile", "math", "next", "os", "package", "pairs", "pcall", "print", "rawequal", "rawget",
+    "rawlen", "rawset", "require", "select", "setmetatable", "string", "table", "tonumber", "tostring", "type",
+    "utf8", "warn", "xpcall",
+];
+
+/// 一条检查结果：所在行号与说明
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.msg)
+    }
+}
+
+/// 检查一段 Lua 源代码，返回按行号排序的检查结果；源代码有语法错误时返回错误
+pub fn lint(source: &[u8]) -> Result<Vec<Warning>, ParseError> {
+    lint_with_globals(source, &[])
+}
+
+/// 同 lint，globals 为宿主另外注册的全局名字，读取它们不报告
+pub fn lint_with_globals(source: &[u8], globals: &[&str]) -> Result<Vec<Warning>, ParseError> {
+    let block = ast::parse(Cursor::new(source))?;
+    let mut linter = Linter::default();
+    linter.scoped(|l| l.block(&block));
+    let Linter {
+        mut warnings,
+        global_reads,
+        global_writes,
+        ..
+    } = linter;
+    for (name, line) in global_reads {
+        if !BUILTINS.contains(&name.as_str()) && !globals.contains(&name.as_str()) && !global_writes.contains(&name) {
+            warnings.push(Warning {
+                line,
+                msg: format!("accessing undefined variable '{name}'"),
+            });
+        }
+    }
+    warnings.sort_by_key(|w| w.line);
+    Ok(warnings)
+}
+
+/// 局部变量的种类，决定未使用时的提示
+#[derive(Clone, Copy, PartialEq)]
+enum VarKind {
+    Local,
+    Function,
+    Loop,
+    /// 函数参数（包括隐含的 self）：不报告未使用
+    Param,
+}
+
+/// 作用域中的局部变量
+struct Var {
+    name: String,
+    line: usize,
+    kind: VarKind,
+    used: bool,
+}
+
+/// 检查状态
+/// - `scopes`: 由外向内的各层作用域（代码块与函数）中的局部变量
+/// - `global_reads`: 读取的全局变量及其行号，全部检查完后再与 global_writes 比较
+#[derive(Default)]
+struct Linter {
+    scopes: Vec<Vec<Var>>,
+    warnings: Vec<Warning>,
+    global_reads: Vec<(String, usize)>,
+    global_writes: HashSet<String>,
+}
+
+impl Linter {
+    fn warn(&mut self, line: usize, msg: String) {
+        self.warnings.push(Warning { line, msg });
+    }
+
+    /// 在新的作用域中执行 f，离开时报告其中未使用的局部变量
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(Vec::new());
+        f(self);
+        for var in self.scopes.pop().unwrap() {
+            if var.used || var.name.starts_with('_') {
+                continue;
+            }
+            let what = match var.kind {
+                VarKind::Local => "variable",
+                VarKind::Function => "function",
+                VarKind::Loop => "loop variable",
+                VarKind::Param => continue,
+            };
+            self.warn(var.line, format!("unused {what} '{}'", var.name));
+        }
+    }
+
+    fn find(&mut self, name: &str) -> Option<&mut Var> {
+        self.scopes.iter_mut().rev().find_map(|scope| scope.iter_mut().rev().find(|v| v.name == name))
+    }
+
+    /// 在当前作用域中声明局部变量，遮蔽同名的局部变量时报告
+    fn declare(&mut self, name: &str, line: usize, kind: VarKind) {
+        if !name.starts_with('_') {
+            if let Some(prev) = self.find(name) {
+                let prev_line = prev.line;
+                self.warn(line, format!("shadowing definition of '{name}' on line {prev_line}"));
+            }
+        }
+        self.scopes.last_mut().unwrap().push(Var {
+            name: name.to_string(),
+            line,
+            kind,
+            used: false,
+        });
+    }
+
+    /// 读取名为 name 的变量
+    fn read(&mut self, name: &str, line: usize) {
+        match self.find(name) {
+            Some(var) => var.used = true,
+            None => self.global_reads.push((name.to_string(), line)),
+        }
+    }
+
+    /// 给名为 name 的变量赋值：局部变量不算使用，全局变量记为已定义
+    fn write(&mut self, name: &str) {
+        if self.find(name).is_none() {
+            self.global_writes.insert(name.to_string());
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        let mut exited = false;
+        let mut warned = false;
+        for stat in &block.stats {
+            if let StatKind::Label(_) = stat.kind {
+                // goto 可以跳到标签处，之后的语句又可以执行
+                exited = false;
+                warned = false;
+            } else if exited && !warned {
+                // 同一段不可达的语句只报告第一条
+                self.warn(stat.span.line, "unreachable code".to_string());
+                warned = true;
+            }
+            self.stat(stat);
+            exited = exited || always_exits(stat);
+        }
+    }
+
+    fn stat(&mut self, stat: &Stat) {
+        let line = stat.span.line;
+        match &stat.kind {
+            StatKind::Local(vars, exps) => {
+                self.exps(exps);
+                for (name, _) in vars {
+                    self.declare(name, line, VarKind::Local);
+                }
+            }
+            StatKind::LocalFunction(name, func) => {
+                self.declare(name, line, VarKind::Function);
+                self.function(func, false);
+            }
+            StatKind::Function(name, func) => {
+                let first = &name.path[0];
+                if name.path.len() == 1 && name.method.is_none() {
+                    self.write(first);
+                } else {
+                    self.read(first, line);
+                }
+                self.function(func, name.method.is_some());
+            }
+            StatKind::Assign(vars, exps) => {
+                self.exps(exps);
+                for var in vars {
+                    match &var.kind {
+                        ExpKind::Name(name) => self.write(name),
+                        _ => self.exp(var),
+                    }
+                }
+            }
+            StatKind::Call(call) => self.exp(call),
+            StatKind::Do(block) => self.scoped(|l| l.block(block)),
+            StatKind::While(cond, block) => {
+                self.exp(cond);
+                self.scoped(|l| l.block(block));
+            }
+            StatKind::Repeat(block, cond) => {
+                // until 的条件可以使用循环体中的局部变量
+                self.scoped(|l| {
+                    l.block(block);
+                    l.exp(cond);
+                });
+            }
+            StatKind::If(conds, else_block) => {
+                for (cond, block) in conds {
+                    self.exp(cond);
+                    self.scoped(|l| l.block(block));
+                }
+                if let Some(block) = else_block {
+                    self.scoped(|l| l.block(block));
+                }
+            }
+            StatKind::NumericFor {
+                var,
+                start,
+                limit,
+                step,
+                body,
+            } => {
+                self.exp(start);
+                self.exp(limit);
+                if let Some(step) = step {
+                    self.exp(step);
+                }
+                self.scoped(|l| {
+                    l.declare(var, line, VarKind::Loop);
+                    l.block(body);
+                });
+            }
+            StatKind::GenericFor { vars, exps, body } => {
+                self.exps(exps);
+                self.scoped(|l| {
+                    for var in vars {
+                        l.declare(var, line, VarKind::Loop);
+                    }
+                    l.block(body);
+                });
+            }
+            StatKind::Return(exps) => self.exps(exps),
+            StatKind::Break | StatKind::Goto(_) | StatKind::Label(_) => (),
+        }
+    }
+
+    /// 函数体：参数与函数体中的局部变量在同一个作用域
+    fn function(&mut self, func: &FuncBody, has_self: bool) {
+        self.scoped(|l| {
+            let line = func.span.line;
+            if has_self {
+                l.declare("self", line, VarKind::Param);
+            }
+            for param in &func.params {
+                l.declare(param, line, VarKind::Param);
+            }
+            l.block(&func.body);
+        });
+    }
+
+    fn exps(&mut self, exps: &[Exp]) {
+        for exp in exps {
+            self.exp(exp);
+        }
+    }
+
+    fn exp(&mut self, exp: &Exp) {
+        match &exp.kind {
+            ExpKind::Nil
+            | ExpKind::True
+            | ExpKind::False
+            | ExpKind::Dots
+            | ExpKind::Integer(_)
+            | ExpKind::Float(_)
+            | ExpKind::String(_) => (),
+            ExpKind::Function(func) => self.function(func, false),
+            ExpKind::Table(fields) => {
+                for field in fields {
+                    match &field.kind {
+                        FieldKind::Positional(v) | FieldKind::Named(_, v) => self.exp(v),
+                        FieldKind::Keyed(k, v) => {
+                            self.exp(k);
+                            self.exp(v);
+                        }
+                    }
+                }
+            }
+            ExpKind::Binary(_, left, right) => {
+                self.exp(left);
+                self.exp(right);
+            }
+            ExpKind::Unary(_, operand) | ExpKind::Paren(operand) | ExpKind::Field(operand, _) => self.exp(operand),
+            ExpKind::Name(name) => self.read(name, exp.span.line),
+            ExpKind::Index(t, key) => {
+                self.exp(t);
+                self.exp(key);
+            }
+            ExpKind::Call(func, args) | ExpKind::Method(func, _, args) => {
+                self.exp(func);
+                self.exps(args);
+            }
+        }
+    }
+}
+
+/// 语句执行后是否总是跳出所在的代码块
+fn always_exits(stat: &Stat) -> bool {
+    match &stat.kind {
+        StatKind::Return(_) | StatKind::Break | StatKind::Goto(_) => true,
+        StatKind::Do(block) => block.stats.last().is_some_and(always_exits),
+        StatKind::If(conds, Some(else_block)) => {
+            conds.iter().all(|(_, b)| b.stats.last().is_some_and(always_exits))
+                && else_block.stats.last().is_some_and(always_exits)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,514 @@
+// 语言服务器：通过标准输入输出上的 JSON-RPC（LSP 协议）为编辑器提供：
+// - 诊断：打开或修改文档时发布语法错误（带位置）与静态检查的警告
+// - 跳转到定义：局部变量、参数、本文件中的全局变量，以及 require 的模块文件
+// - 悬停：变量的种类与由初始值推断的类型、函数签名、字面量的类型
+// - 文档大纲：局部变量与函数
+// - 补全：关键字、可见的局部变量、宿主通过嵌入 API 注册的全局变量与本文件中的全局变量，"name." 之后补全表的字段
+// 文档内容由客户端完整同步（textDocumentSync = Full），分析基于 analysis 模块的语义索引
+// LSP 的位置为从 0 开始的行号与 UTF-16 编码单元的列号，与源代码的字节偏移互相转换
+
+use crate::analysis::{self, DefKind, Definition, Index, Symbol};
+use crate::lex::Span;
+use crate::lint;
+use crate::value::Value as LuaValue;
+use crate::vm::ExeState;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// 补全的关键字
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in", "local", "nil",
+    "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+// LSP 的 CompletionItemKind 与 SymbolKind
+const COMPLETION_FUNCTION: u32 = 3;
+const COMPLETION_FIELD: u32 = 5;
+const COMPLETION_VARIABLE: u32 = 6;
+const COMPLETION_MODULE: u32 = 9;
+const COMPLETION_KEYWORD: u32 = 14;
+const SYMBOL_FUNCTION: u32 = 12;
+const SYMBOL_VARIABLE: u32 = 13;
+
+/// 打开的文档：内容与最近一次没有语法错误时的索引（输入到一半时仍可以补全）
+struct Document {
+    text: String,
+    index: Option<Index>,
+}
+
+/// 语言服务器的状态
+/// - `globals`: 宿主注册的全局变量名与值的类型
+/// - `members`: 值为表的全局变量的各字段名与类型
+/// - `path`: 查找 require 的模块文件的模板，与 package.path 相同
+pub struct Server {
+    globals: Vec<(String, &'static str)>,
+    members: HashMap<String, Vec<(String, &'static str)>>,
+    path: String,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl Server {
+    /// 以 state 中的全局变量（包括宿主注册的函数与模块）作为补全的候选
+    pub fn new(state: &ExeState) -> Self {
+        let mut globals = Vec::new();
+        let mut members = HashMap::new();
+        for (k, v) in table_entries(&state.globals().borrow()) {
+            if let LuaValue::Table(t) = &v {
+                let mut fields = table_entries(&t.borrow());
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                let fields = fields.into_iter().map(|(k, v)| (k, v.type_name())).collect();
+                members.insert(k.clone(), fields);
+            }
+            globals.push((k, v.type_name()));
+        }
+        globals.sort();
+        let path = match state.package().borrow().get_str("path") {
+            LuaValue::Nil => String::new(),
+            v => v.to_string(),
+        };
+        Server {
+            globals,
+            members,
+            path,
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// 读取并处理消息直到收到 exit 通知或输入结束
+    pub fn run<R: BufRead, W: Write>(&mut self, mut input: R, mut output: W) -> io::Result<()> {
+        while let Some(msg) = read_message(&mut input)? {
+            for out in self.handle(&msg) {
+                write_message(&mut output, &out)?;
+            }
+            if msg["method"] == "exit" {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// 处理一条消息，返回要发给客户端的响应与通知
+    pub fn handle(&mut self, msg: &Value) -> Vec<Value> {
+        let method = msg["method"].as_str().unwrap_or_default();
+        let params = &msg["params"];
+        let Some(id) = msg.get("id") else {
+            // 通知没有响应，文档变化时发布诊断
+            return match method {
+                "textDocument/didOpen" => {
+                    let doc = &params["textDocument"];
+                    self.update(
+                        doc["uri"].as_str().unwrap_or_default(),
+                        doc["text"].as_str().unwrap_or_default(),
+                    )
+                }
+                "textDocument/didChange" => {
+                    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                    match params["contentChanges"].as_array().and_then(|c| c.last()) {
+                        Some(change) => self.update(uri, change["text"].as_str().unwrap_or_default()),
+                        None => Vec::new(),
+                    }
+                }
+                "textDocument/didClose" => {
+                    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                    self.documents.remove(uri);
+                    vec![diagnostics(uri, Vec::new())]
+                }
+                _ => Vec::new(),
+            };
+        };
+        let result = if self.shutdown {
+            Err((-32600, String::from("server is shutting down")))
+        } else {
+            match method {
+                "initialize" => Ok(capabilities()),
+                "shutdown" => {
+                    self.shutdown = true;
+                    Ok(Value::Null)
+                }
+                "textDocument/definition" => Ok(self.definition(params)),
+                "textDocument/hover" => Ok(self.hover(params)),
+                "textDocument/documentSymbol" => Ok(self.document_symbols(params)),
+                "textDocument/completion" => Ok(self.completion(params)),
+                _ => Err((-32601, format!("method not found: {method}"))),
+            }
+        };
+        let response = match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}}),
+        };
+        vec![response]
+    }
+
+    /// 更新文档内容，返回其诊断
+    fn update(&mut self, uri: &str, text: &str) -> Vec<Value> {
+        let mut list = Vec::new();
+        let index = match analysis::index(text) {
+            Ok(index) => {
+                // 没有语法错误时静态检查不会失败；宿主注册的全局变量不算未定义
+                let globals: Vec<&str> = self.globals.iter().map(|(name, _)| name.as_str()).collect();
+                for w in lint::lint_with_globals(text.as_bytes(), &globals).unwrap_or_default() {
+                    let range = line_range(text, w.line);
+                    list.push(json!({"range": range, "severity": 2, "source": "lua", "message": w.msg}));
+                }
+                Some(index)
+            }
+            Err(e) => {
+                let range = match e.span {
+                    Some(span) => span_range(text, span),
+                    None => line_range(text, e.line),
+                };
+                list.push(json!({"range": range, "severity": 1, "source": "lua", "message": e.msg}));
+                self.documents.get_mut(uri).and_then(|d| d.index.take())
+            }
+        };
+        let text = text.to_string();
+        self.documents.insert(uri.to_string(), Document { text, index });
+        vec![diagnostics(uri, list)]
+    }
+
+    /// 请求参数中的文档与光标处的字节偏移
+    fn document<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a Document, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let doc = self.documents.get(uri)?;
+        let pos = &params["position"];
+        let offset = offset_of(
+            &doc.text,
+            pos["line"].as_u64()? as usize,
+            pos["character"].as_u64()? as usize,
+        );
+        Some((uri, doc, offset))
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let Some((uri, doc, offset)) = self.document(params) else {
+            return Value::Null;
+        };
+        let Some(index) = &doc.index else {
+            return Value::Null;
+        };
+        if let Some((module, _)) = index.require_at(offset) {
+            return match self.find_module(uri, module) {
+                Some(path) => json!({"uri": path_uri(&path), "range": range(0, 0, 0, 0)}),
+                None => Value::Null,
+            };
+        }
+        let def = match index.reference_at(offset) {
+            Some(r) => r.def.map(|d| &index.defs[d]),
+            None => index.definition_at(offset).map(|(_, d)| d),
+        };
+        match def {
+            Some(def) => json!({"uri": uri, "range": span_range(&doc.text, def.span)}),
+            None => Value::Null,
+        }
+    }
+
+    /// 按模块搜索路径查找 require 的模块文件，相对路径相对于文档所在的目录
+    fn find_module(&self, uri: &str, module: &str) -> Option<PathBuf> {
+        let dir = uri_path(uri)?.parent()?.to_path_buf();
+        let name = module.replace('.', "/");
+        self.path
+            .split(';')
+            .map(|template| dir.join(template.strip_prefix("./").unwrap_or(template).replace('?', &name)))
+            .find(|path| path.is_file())
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let Some((_, doc, offset)) = self.document(params) else {
+            return Value::Null;
+        };
+        let Some(index) = &doc.index else {
+            return Value::Null;
+        };
+        let (text, span) = if let Some(r) = index.reference_at(offset) {
+            match r.def {
+                Some(d) => (describe(&index.defs[d]), r.span),
+                None => match self.globals.iter().find(|(name, _)| *name == r.name) {
+                    Some((name, ty)) => (format!("global {name}: {ty}"), r.span),
+                    None => (format!("global {}", r.name), r.span),
+                },
+            }
+        } else if let Some((_, def)) = index.definition_at(offset) {
+            (describe(def), def.span)
+        } else if let Some(&(span, ty)) = index.literal_at(offset) {
+            (ty.to_string(), span)
+        } else {
+            return Value::Null;
+        };
+        json!({
+            "contents": {"kind": "markdown", "value": format!("```lua\n{text}\n```")},
+            "range": span_range(&doc.text, span),
+        })
+    }
+
+    fn document_symbols(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match self.documents.get(uri).and_then(|d| Some((&d.text, d.index.as_ref()?))) {
+            Some((text, index)) => symbols(text, &index.symbols),
+            None => json!([]),
+        }
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let Some((_, doc, offset)) = self.document(params) else {
+            return json!([]);
+        };
+        let before = &doc.text.as_bytes()[..offset];
+        let is_word = |c: &u8| *c == b'_' || c.is_ascii_alphanumeric();
+        let prefix_len = before.iter().rev().take_while(|c| is_word(c)).count();
+        let prefix = &doc.text[offset - prefix_len..offset];
+        let mut items = Vec::new();
+
+        // "name." 或 "name:" 之后补全全局表的字段
+        let head = &before[..offset - prefix_len];
+        if let Some(b'.' | b':') = head.last() {
+            let head = &head[..head.len() - 1];
+            let len = head.iter().rev().take_while(|c| is_word(c)).count();
+            let table = &doc.text[head.len() - len..head.len()];
+            for (name, ty) in self.members.get(table).into_iter().flatten() {
+                let kind = if *ty == "function" {
+                    COMPLETION_FUNCTION
+                } else {
+                    COMPLETION_FIELD
+                };
+                items.push((name.clone(), kind, ty.to_string()));
+            }
+        } else {
+            for keyword in KEYWORDS {
+                items.push((keyword.to_string(), COMPLETION_KEYWORD, String::from("keyword")));
+            }
+            let mut seen: Vec<&str> = Vec::new();
+            if let Some(index) = &doc.index {
+                for def in index.visible_at(offset) {
+                    let kind = if def.ty == Some("function") {
+                        COMPLETION_FUNCTION
+                    } else {
+                        COMPLETION_VARIABLE
+                    };
+                    items.push((def.name.clone(), kind, describe(def)));
+                    seen.push(&def.name);
+                }
+            }
+            for (name, ty) in &self.globals {
+                if !seen.contains(&name.as_str()) {
+                    let kind = match *ty {
+                        "function" => COMPLETION_FUNCTION,
+                        "table" => COMPLETION_MODULE,
+                        _ => COMPLETION_VARIABLE,
+                    };
+                    items.push((name.clone(), kind, format!("global {name}: {ty}")));
+                }
+            }
+        }
+        let items: Vec<Value> = items
+            .into_iter()
+            .filter(|(name, ..)| name.starts_with(prefix))
+            .map(|(label, kind, detail)| json!({"label": label, "kind": kind, "detail": detail}))
+            .collect();
+        Value::from(items)
+    }
+}
+
+/// 表中键为字符串的各项
+fn table_entries(table: &crate::value::Table) -> Vec<(String, LuaValue)> {
+    let mut entries = Vec::new();
+    let mut key = LuaValue::Nil;
+    while let Ok(Some((k, v))) = table.next(&key) {
+        if let Some(name) = k.as_str() {
+            entries.push((name.to_string(), v));
+        }
+        key = k;
+    }
+    entries
+}
+
+/// initialize 请求的结果：服务器支持的功能
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "definitionProvider": true,
+            "hoverProvider": true,
+            "documentSymbolProvider": true,
+            "completionProvider": {"triggerCharacters": ["."]},
+        },
+        "serverInfo": {"name": "lua-lsp", "version": env!("CARGO_PKG_VERSION")},
+    })
+}
+
+fn diagnostics(uri: &str, list: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": list},
+    })
+}
+
+/// 悬停与补全中变量的说明，如 "local x: integer"、"function f(a, b)"
+fn describe(def: &Definition) -> String {
+    if let Some(detail) = &def.detail {
+        return detail.clone();
+    }
+    let kind = match def.kind {
+        DefKind::Local | DefKind::LocalFunction | DefKind::Loop => "local",
+        DefKind::Param => "param",
+        DefKind::Global => "global",
+    };
+    match (&def.require, def.ty) {
+        (Some(module), _) => format!("{kind} {} = require(\"{module}\")", def.name),
+        (None, Some(ty)) => format!("{kind} {}: {ty}", def.name),
+        (None, None) => format!("{kind} {}", def.name),
+    }
+}
+
+fn symbols(text: &str, list: &[Symbol]) -> Value {
+    let list: Vec<Value> = list
+        .iter()
+        .map(|s| {
+            json!({
+                "name": s.name,
+                "kind": if s.is_function { SYMBOL_FUNCTION } else { SYMBOL_VARIABLE },
+                "range": span_range(text, s.span),
+                "selectionRange": span_range(text, s.name_span),
+                "children": symbols(text, &s.children),
+            })
+        })
+        .collect();
+    Value::from(list)
+}
+
+fn range(line: usize, character: usize, end_line: usize, end_character: usize) -> Value {
+    json!({
+        "start": {"line": line, "character": character},
+        "end": {"line": end_line, "character": end_character},
+    })
+}
+
+fn span_range(text: &str, span: Span) -> Value {
+    let (line, character) = position_of(text, span.start);
+    let (end_line, end_character) = position_of(text, span.end);
+    range(line, character, end_line, end_character)
+}
+
+/// 第 line 行（从 1 开始）的整行
+fn line_range(text: &str, line: usize) -> Value {
+    let content = text.split('\n').nth(line.saturating_sub(1)).unwrap_or_default();
+    let len = content.trim_end_matches('\r').encode_utf16().count();
+    range(line.saturating_sub(1), 0, line.saturating_sub(1), len)
+}
+
+/// 字节偏移对应的 LSP 位置：从 0 开始的行号与 UTF-16 列号
+fn position_of(text: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count(),
+        before[line_start..].encode_utf16().count(),
+    )
+}
+
+/// LSP 位置对应的字节偏移，超出行尾时为行尾
+fn offset_of(text: &str, line: usize, character: usize) -> usize {
+    let mut start = 0;
+    for _ in 0..line {
+        match text[start..].find('\n') {
+            Some(i) => start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+    let mut units = 0;
+    for (i, c) in text[start..end].char_indices() {
+        if units >= character {
+            return start + i;
+        }
+        units += c.len_utf16();
+    }
+    end
+}
+
+/// file:// URI 对应的路径，解码 %XX 转义
+fn uri_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(c)) => {
+                decoded.push(c);
+                i += 3;
+            }
+            (c, _) => {
+                decoded.push(c);
+                i += 1;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(decoded).ok()?))
+}
+
+/// 路径对应的 file:// URI，转义空格与 % 等字符
+fn path_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for c in path.to_string_lossy().chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '/' | '-' | '_' | '.' | '~' => uri.push(c),
+            _ => {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    uri.push_str(&format!("%{b:02X}"));
+                }
+            }
+        }
+    }
+    uri
+}
+
+/// 读取一条消息：Content-Length 头部、空行与 JSON 内容；输入结束时返回 None
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing Content-Length header",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// 写出一条消息
+pub fn write_message<W: Write>(output: &mut W, msg: &Value) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
@@ -1,25 +1,129 @@
+mod debugger;
+mod tools;
+
+use lua::profile::{Profile, ProfileMode, Weight};
+use lua::value::Value;
+use lua::vm::{ExeState, LuaError};
 use std::env;
 use std::fs::File;
-use std::io::BufReader;
-mod lex;
-mod value;
-mod vm;
-mod parse;
-mod bytecode;
-
+use std::io::{BufReader, BufWriter, Write};
+use std::process;
+use std::thread;
+use std::time::Duration;
 
 fn main() {
-    let args = env::args().collect::<Vec<String>>();
+    let mut args = env::args().collect::<Vec<String>>();
+    // lua fmt / lua lint / lua --dump-ast：格式化、静态检查与输出语法树，不执行脚本
+    match args.get(1).map(String::as_str) {
+        Some("fmt") => return tools::fmt(&args[2..]),
+        Some("lint") => return tools::lint(&args[2..]),
+        Some("--dump-ast") => return tools::dump_ast(&args[2..]),
+        _ => (),
+    }
+    // lua --debug <lua file>：在调试器中运行脚本
+    // lua --timeout <seconds> <lua file>：脚本运行超过指定秒数时中断
+    // lua --profile <out.folded> <lua file>：性能分析，按墙钟时间（微秒）输出折叠栈，供 flamegraph 工具使用
+    // lua --profile-opcodes <lua file>：统计各操作码的执行次数，结束时输出到标准错误
+    let mut debug = false;
+    let mut timeout = None;
+    let mut profile = None;
+    let mut profile_opcodes = false;
+    while args.len() > 1 {
+        match args[1].as_str() {
+            "--debug" => debug = true,
+            "--profile" if args.len() > 2 => profile = Some(args.remove(2)),
+            "--profile-opcodes" => profile_opcodes = true,
+            "--timeout" if args.len() > 2 => {
+                // 负数、NaN 与超出 Duration 范围的秒数（包括 inf）都不是有效的时限
+                let Some(t) = args[2].parse().ok().and_then(|t| Duration::try_from_secs_f64(t).ok()) else {
+                    eprintln!("lua: invalid timeout '{}' (non-negative seconds expected)", args[2]);
+                    eprintln!("usage: {} --timeout seconds <lua file>", args[0]);
+                    process::exit(1);
+                };
+                timeout = Some(t);
+                args.remove(1);
+            }
+            _ => break,
+        }
+        args.remove(1);
+    }
     if args.len() < 2 {
-        println!("Usage: {} <lua file>", args[0]);
+        println!(
+            "Usage: {} [--debug] [--timeout seconds] [--profile out.folded] [--profile-opcodes] <lua file>",
+            args[0]
+        );
+        println!("       {} fmt [--check] [lua file...]", args[0]);
+        println!("       {} lint [lua file...]", args[0]);
+        println!("       {} --dump-ast [lua file...]", args[0]);
         return;
     }
 
     let lua_file = &args[1];
-    let file = File::open(lua_file).unwrap();
+    let file = match File::open(lua_file) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("lua: cannot open {lua_file}: {e}");
+            process::exit(1);
+        }
+    };
     let input = BufReader::new(file);
 
-    let proto = parse::ParseProto::load(input);
-    let mut exe_state = vm::ExeState::new();
-    exe_state.execute(&proto);
-}
\ No newline at end of file
+    let mut exe_state = ExeState::new();
+    if debug {
+        debugger::attach(&mut exe_state, lua_file);
+    }
+    if let Some(t) = timeout {
+        let handle = exe_state.interrupt_handle();
+        thread::spawn(move || {
+            thread::sleep(t);
+            handle.interrupt();
+        });
+    }
+    if profile.is_some() {
+        exe_state.start_profile(ProfileMode::Full);
+    } else if profile_opcodes {
+        exe_state.start_profile(ProfileMode::Opcodes);
+    }
+    // 脚本之后的命令行参数作为代码块的 ... 传入
+    let script_args = args[2..].iter().map(|a| Value::from(a.as_str())).collect();
+    let result = exe_state
+        .load(input, lua_file)
+        .and_then(|main| exe_state.call(main, script_args));
+    // 脚本出错时同样输出分析结果
+    if let Some(p) = exe_state.stop_profile() {
+        write_profile(&p, profile.as_deref(), profile_opcodes);
+    }
+    match result {
+        Ok(_) => (),
+        Err(LuaError::Interrupted) => {
+            eprintln!("lua: timed out after {:?}", timeout.unwrap_or_default());
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("lua: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+/// 输出分析结果：折叠栈写入文件，操作码直方图写到标准错误
+fn write_profile(profile: &Profile, folded: Option<&str>, opcodes: bool) {
+    if let Some(path) = folded {
+        let written = File::create(path).and_then(|f| {
+            let mut w = BufWriter::new(f);
+            profile.write_folded(&mut w, Weight::Time)?;
+            w.flush()
+        });
+        if let Err(e) = written {
+            eprintln!("lua: cannot write {path}: {e}");
+            process::exit(1);
+        }
+    }
+    if opcodes {
+        let total: u64 = profile.opcodes().iter().map(|(_, n)| n).sum();
+        eprintln!("{:<16}{:>12}{:>8}", "opcode", "count", "%");
+        for (name, n) in profile.opcodes() {
+            eprintln!("{name:<16}{n:>12}{:>8.2}", *n as f64 * 100.0 / total as f64);
+        }
+    }
+}
@@ -1,214 +1,1428 @@
-// Lua 语法分析器（Parser）模块
-// 负责将 Token 流转换为字节码（ByteCode）序列
-// 采用递归下降解析器（Recursive Descent Parser）模式，支持以下语法构造：
-// - 赋值语句（assignment）：name = expression
-// - 局部变量声明（local statement）：local var = expression
-// - 函数调用（function call）：func(args)
-// - 表达式解析（expression）：常数、变量名、一元运算等
-// 最终生成可被虚拟机执行的字节码
-
-use crate::bytecode::ByteCode;
-use crate::lex::Lex;
-use crate::lex::Token;
+// Lua 代码生成模块
+// 由 ast 模块把源代码解析为抽象语法树，再遍历语法树为每个函数生成字节码（ByteCode）序列，支持以下语法构造：
+// - 赋值语句（assignment）：var1, var2 = exp1, exp2（变量可为局部、上值、全局或表字段）
+// - 局部变量声明（local statement）：local var = expression / local function f() end
+// - 函数定义（function）：function a.b.c() end，以及匿名函数表达式
+// - 函数调用（function call）：func(args)、func "string"、func {table}、obj:method(args)，可作用于任意前缀表达式
+// - 代码块与返回：do ... end、return explist
+// - 控制结构：if/elseif/else、while、repeat ... until、数值 for 与泛型 for、break
+// - goto 与标签：goto name / ::name::，按 Lua 5.4 的可见性规则解析（见 goto_stat 与 label_stat）；
+//   break 与 Lua 一样作为跳到循环末尾的 goto 处理
+// - 表达式（expression）：常数、变量名、字段访问、表构造器、可变参数，以及一元、二元运算
+// 表达式先生成为 ExpDesc（表达式描述），在需要时再生成把值放入寄存器的字节码
+// 每个函数生成一个 FuncProto，嵌套函数的原型保存在外层函数的 protos 中
+
+use crate::ast::{self, Attrib, BinOp, Block, Exp, ExpKind, FieldKind, FuncBody, FuncName, Stat, StatKind, UnOp};
+use crate::bytecode::{ByteCode, Instruction, FIELDS_PER_FLUSH, MAX_ARG_B, MAX_BX};
+use crate::lex::ParseError;
+use crate::sync::Rc;
+use crate::table::str_hash;
 use crate::value::Value;
-use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Seek};
+
+/// 函数可用的寄存器个数：寄存器编号与 Call、Return 中的个数+1 都要放进 8 位字段
+const MAX_REGS: usize = 255;
+/// 函数中同时有效的局部变量个数上限，与 Lua 相同
+const MAX_VARS: usize = 200;
+/// 函数的上值个数上限，上值索引放在 8 位字段中
+const MAX_UPVALUES: usize = 255;
 
-/// Lua 解析原型结构体
+/// 函数原型：编译一个函数（或整个代码块）得到的结果
+/// - `has_varargs`: 参数列表是否包含 ...
+/// - `nparam`: 固定参数个数
 /// - `constants`: 常数池，存储所有字面量常数（整数、浮点、字符串等）
-/// - `byte_codes`: 生成的字节码序列，待虚拟机执行
-/// - `locals`: 局部变量表，记录当前作用域内声明的所有变量名
-/// - `lex`: 词法分析器实例，提供 Token 流
-pub struct ParseProto<R> {
+/// - `const_hashes`: 常数池中字符串常量的哈希（其余为 0），读写全局变量时不必每次计算变量名的哈希
+/// - `upindexes`: 创建闭包时每个上值的来源
+/// - `protos`: 嵌套定义的子函数原型
+/// - `byte_codes`: 生成的（编码后的）字节码序列，待虚拟机执行
+/// - `max_stack`: 函数用到的寄存器个数，调用时按此预先分配栈空间
+/// - `source`: 代码块名（通常是文件名），用于错误信息与调试
+/// - `linedefined`/`lastlinedefined`: 函数定义的起止行号，主代码块为 0
+/// - `lineinfo`: 每条字节码对应的源代码行号
+/// - `locvars`: 局部变量的名字及其有效的字节码范围，供调试器查看局部变量
+#[derive(Default)]
+pub struct FuncProto {
+    pub has_varargs: bool,
+    pub nparam: usize,
     pub constants: Vec<Value>,
-    pub byte_codes: Vec<ByteCode>,
-    locals: Vec<String>,
-    lex: Lex<R>,
+    pub const_hashes: Vec<u32>,
+    pub upindexes: Vec<UpIndex>,
+    pub protos: Vec<Rc<FuncProto>>,
+    pub byte_codes: Vec<Instruction>,
+    pub max_stack: usize,
+    pub source: String,
+    pub linedefined: usize,
+    pub lastlinedefined: usize,
+    pub lineinfo: Vec<usize>,
+    pub locvars: Vec<LocVar>,
 }
 
-impl<R: Read> ParseProto<R> {
-    /// 从文件加载并解析 Lua 源代码，返回包含字节码的 ParseProto
-    /// 主入口函数：初始化解析器并调用 chunk() 开始递归解析
-    pub fn load(input: R) -> Self {
-        let mut proto = Self {
-            constants: Vec::new(),
-            byte_codes: Vec::new(),
-            locals: Vec::new(),
-            lex: Lex::new(input),
-        };
+/// 局部变量的调试信息：在字节码 [startpc, endpc) 范围内有效
+/// 某个位置上有效的局部变量按声明顺序依次对应寄存器 0, 1, 2...
+#[derive(Debug, Clone)]
+pub struct LocVar {
+    pub name: String,
+    pub startpc: usize,
+    pub endpc: usize,
+}
+
+/// 上值的来源
+/// - `Local(reg)`: 外层函数的局部变量（寄存器）
+/// - `Upvalue(idx)`: 外层函数自身的上值
+#[derive(Debug, Clone, Copy)]
+pub enum UpIndex {
+    Local(usize),
+    Upvalue(usize),
+}
+
+/// 表达式描述：表达式解析后尚未生成“放入寄存器”代码的中间形式
+#[derive(Debug)]
+enum ExpDesc {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(Vec<u8>),
+    /// 局部变量或临时值所在的寄存器
+    Local(usize),
+    /// 上值索引
+    Upvalue(usize),
+    /// 全局变量，变量名在常数池中的索引
+    Global(usize),
+    /// t[k]：(表寄存器, 键寄存器)
+    Index(usize, usize),
+    /// t.k：(表寄存器, 字段名常量)，常量索引不超过 255
+    IndexField(usize, usize),
+    /// t[n]，n 为 1 到 255 之间的整数常量：(表寄存器, n)
+    IndexInt(usize, usize),
+    /// 函数调用：(函数寄存器, 参数个数+1)
+    Call(usize, usize),
+    /// 可变参数 ...
+    VarArgs,
+    /// 子函数原型索引
+    Function(usize),
+}
+
+/// 代码生成期间的局部变量
+/// - `captured`: 被内层函数捕获，离开作用域时需要关闭上值
+/// - `konst`: 初值为常量的 <const> 变量，引用它时直接使用该常量
+struct Local {
+    name: String,
+    attrib: Attrib,
+    captured: bool,
+    konst: Option<Value>,
+}
 
-        proto.chunk();
+/// 标签：位置与定义处有效的局部变量个数（位于代码块末尾时为代码块开始处的个数）
+struct Label {
+    name: String,
+    pc: usize,
+    line: usize,
+    nvar: usize,
+}
+
+/// 尚未找到标签的 goto：Jump 字节码的位置与 goto 处有效的局部变量个数
+/// 离开代码块时 nvar 降为代码块开始处的个数；离开的局部变量中有需要关闭的，close 为 true
+struct Goto {
+    name: String,
+    pc: usize,
+    line: usize,
+    nvar: usize,
+    close: bool,
+}
+
+/// 进入代码块时的状态：有效的局部变量个数与标签、goto 列表的长度
+#[derive(Clone, Copy)]
+struct BlockMark {
+    nvar: usize,
+    labels: usize,
+    gotos: usize,
+}
+
+/// 每层正在生成的函数：局部变量与上值（名字, 来源）
+#[derive(Default)]
+struct Level {
+    locals: Vec<Local>,
+    upvalues: Vec<(String, UpIndex)>,
+}
+
+/// 代码生成上下文：所有嵌套函数共享的作用域层次
+struct ParseContext {
+    levels: Vec<Level>,
+    source: String,
+}
 
+/// 为一个函数生成字节码的状态，每个函数对应一个
+/// - `fp`: 正在生成的函数原型
+/// - `sp`: 第一个空闲寄存器（局部变量之上为临时值）
+/// - `ctx`: 共享的代码生成上下文
+/// - `actvars`: 当前有效的局部变量在 fp.locvars 中的索引，与 Level::locals 一一对应
+/// - `line`: 正在生成的语法结构所在的行号，记入字节码的行号表
+/// - `labels`: 当前可见的标签，即所在的各层代码块中已定义的
+/// - `gotos`: 尚未找到标签的 goto（向前跳转）
+/// - `overflow`: 生成字节码时超出的限制（寄存器、局部变量、常量个数，跳转距离等），
+///   在语句结束时作为错误报告，生成字节码的各函数因此不必都返回 Result
+pub struct ParseProto<'a> {
+    fp: FuncProto,
+    sp: usize,
+    ctx: &'a mut ParseContext,
+    actvars: Vec<usize>,
+    line: usize,
+    labels: Vec<Label>,
+    gotos: Vec<Goto>,
+    overflow: Option<ParseError>,
+}
+
+impl<'a> ParseProto<'a> {
+    /// 从输入流加载 Lua 源代码，解析为语法树后生成主函数原型
+    /// 整个代码块作为一个带可变参数的函数，source 为代码块名
+    pub fn load<R: Read + Seek>(input: R, source: &str) -> Result<FuncProto, ParseError> {
+        let block = ast::parse(input)?;
+        Self::generate(&block, source)
+    }
+
+    /// 为已解析的主代码块生成函数原型
+    pub fn generate(block: &Block, source: &str) -> Result<FuncProto, ParseError> {
+        let mut ctx = ParseContext {
+            levels: vec![Level::default()],
+            source: source.to_string(),
+        };
+        let mut proto = ParseProto::new(&mut ctx, true, Vec::new(), 0);
+        proto.block(block, block.span.end_line)?;
+        proto.check_gotos()?;
+        proto.line = block.span.end_line;
+        proto.emit(ByteCode::Return(0, 1));
+        proto.check_overflow()?;
+        Ok(proto.finish(0))
+    }
+
+    fn new(ctx: &'a mut ParseContext, has_varargs: bool, params: Vec<String>, linedefined: usize) -> Self {
+        let nparam = params.len();
+        let source = ctx.source.clone();
+        let mut proto = ParseProto {
+            fp: FuncProto {
+                has_varargs,
+                nparam,
+                max_stack: nparam,
+                source,
+                linedefined,
+                ..Default::default()
+            },
+            sp: nparam,
+            ctx,
+            actvars: Vec::new(),
+            line: linedefined,
+            labels: Vec::new(),
+            gotos: Vec::new(),
+            overflow: None,
+        };
+        proto.add_locals(params);
         proto
     }
 
-    /// 块（chunk）解析函数：递归下降解析的顶层入口
-    /// 循环读取 Token 并根据类型分发到相应的语句解析函数：
-    /// - Name Token：检查后续是否为赋值 (=) 或函数调用
-    /// - Local Token：本地变量声明
-    /// - Eos Token：文件尾，终止解析
-    fn chunk(&mut self) {
-        loop {
-            match self.lex.next() {
-                Token::Name(name) => {
-                    if self.lex.peek() == &Token::Assign {
-                        self.assignment(name);
-                    } else {
-                        self.function_call(name);
-                    }
+    /// 函数生成完毕：结束仍有效的局部变量（参数等）的范围，返回函数原型
+    fn finish(mut self, lastlinedefined: usize) -> FuncProto {
+        let end = self.fp.byte_codes.len();
+        for &i in &self.actvars {
+            self.fp.locvars[i].endpc = end;
+        }
+        self.fp.lastlinedefined = lastlinedefined;
+        self.fp.const_hashes = self.fp.constants.iter().map(|c| c.as_bytes().map_or(0, str_hash)).collect();
+        self.fp
+    }
+
+    /// 生成一条字节码，同时记录其所在的源代码行号，返回字节码的位置
+    fn emit(&mut self, code: ByteCode) -> usize {
+        let code = self.check_fits(code);
+        self.fp.byte_codes.push(code.encode());
+        self.fp.lineinfo.push(self.line);
+        self.fp.byte_codes.len() - 1
+    }
+
+    /// 回填 pc 处先前生成的字节码（如跳转距离）
+    fn patch(&mut self, pc: usize, code: ByteCode) {
+        let code = self.check_fits(code);
+        self.fp.byte_codes[pc] = code.encode();
+    }
+
+    /// 参数放不进指令字段时记下错误，以无用的指令代替
+    /// 常量与子函数过多已在加入时记下，剩下的只有跳转距离过大
+    fn check_fits(&mut self, code: ByteCode) -> ByteCode {
+        if code.fits() {
+            code
+        } else {
+            self.overflow("control structure too long".to_string());
+            ByteCode::Jump(0)
+        }
+    }
+
+    /// 记下超出的限制，只保留第一个
+    fn overflow(&mut self, msg: String) {
+        if self.overflow.is_none() {
+            self.overflow = Some(self.error(self.line, msg));
+        }
+    }
+
+    /// 超出数量限制的错误信息，格式与 Lua 相同
+    fn limit_msg(&self, what: &str, limit: usize) -> String {
+        match self.fp.linedefined {
+            0 => format!("too many {what} (limit is {limit}) in main function"),
+            line => format!("too many {what} (limit is {limit}) in function at line {line}"),
+        }
+    }
+
+    /// 报告生成字节码时超出的限制
+    fn check_overflow(&mut self) -> Result<(), ParseError> {
+        match self.overflow.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// 设置第一个空闲寄存器，同时记录函数用到的寄存器个数
+    fn set_sp(&mut self, sp: usize) {
+        if sp >= MAX_REGS {
+            self.overflow("function or expression needs too many registers".to_string());
+        }
+        self.sp = sp;
+        self.fp.max_stack = self.fp.max_stack.max(sp);
+    }
+
+    /// 声明新的局部变量，从下一条字节码开始有效
+    fn add_locals(&mut self, names: Vec<String>) {
+        if self.locals().len() + names.len() > MAX_VARS {
+            let msg = self.limit_msg("local variables", MAX_VARS);
+            self.overflow(msg);
+        }
+        let startpc = self.fp.byte_codes.len();
+        for name in names {
+            self.actvars.push(self.fp.locvars.len());
+            self.fp.locvars.push(LocVar {
+                name: name.clone(),
+                startpc,
+                endpc: startpc,
+            });
+            self.level().locals.push(Local {
+                name,
+                attrib: Attrib::Plain,
+                captured: false,
+                konst: None,
+            });
+        }
+    }
+
+    fn level(&mut self) -> &mut Level {
+        self.ctx.levels.last_mut().unwrap()
+    }
+
+    fn locals(&self) -> &Vec<Local> {
+        &self.ctx.levels.last().unwrap().locals
+    }
+
+    /// 构造 line 行的错误
+    fn error(&self, line: usize, msg: impl Into<String>) -> ParseError {
+        ParseError {
+            line,
+            msg: msg.into(),
+            span: None,
+        }
+    }
+
+    /// 代码块：其中声明的局部变量在块结束时失效，若被内层函数捕获则生成 Close 字节码
+    /// end_line 为结束代码块的 end 所在行
+    fn block(&mut self, block: &Block, end_line: usize) -> Result<(), ParseError> {
+        let nvar = self.locals().len();
+        self.block_scope(block, false)?;
+        self.line = end_line;
+        self.leave_scope(nvar);
+        Ok(())
+    }
+
+    /// 依次为代码块中的语句生成字节码，不结束其中的局部变量
+    /// 代码块中的标签在块结束后不再可见，未找到标签的 goto 移到外层代码块
+    /// repeat 为真时是 repeat 循环体，其局部变量在 until 条件中仍然有效，因此其中的标签都不算位于块末尾
+    fn block_scope(&mut self, block: &Block, repeat: bool) -> Result<(), ParseError> {
+        let mark = self.enter_block();
+        for (i, stat) in block.stats.iter().enumerate() {
+            self.set_sp(self.locals().len());
+            self.line = stat.span.line;
+            match &stat.kind {
+                StatKind::Label(name) => {
+                    // 之后只有标签的标签位于代码块末尾
+                    let last = !repeat && block.stats[i + 1..].iter().all(|s| matches!(s.kind, StatKind::Label(_)));
+                    let nvar = if last { mark.nvar } else { self.locals().len() };
+                    self.label_stat(name, stat.span.line, nvar, mark.gotos)?;
+                }
+                _ => self.stat(stat)?,
+            }
+            self.check_overflow()?;
+        }
+        self.set_sp(self.locals().len());
+        self.leave_block(mark);
+        Ok(())
+    }
+
+    fn enter_block(&self) -> BlockMark {
+        BlockMark {
+            nvar: self.locals().len(),
+            labels: self.labels.len(),
+            gotos: self.gotos.len(),
+        }
+    }
+
+    /// 离开代码块（在结束其中的局部变量之前调用）：丢弃其中的标签，
+    /// 未找到标签的 goto 将跳出代码块，其中有需要关闭的局部变量时跳转前须关闭
+    fn leave_block(&mut self, mark: BlockMark) {
+        self.labels.truncate(mark.labels);
+        let close = self.locals()[mark.nvar..]
+            .iter()
+            .any(|l| l.captured || l.attrib == Attrib::Close);
+        for goto in &mut self.gotos[mark.gotos..] {
+            if goto.nvar > mark.nvar {
+                goto.close |= close;
+                goto.nvar = mark.nvar;
+            }
+        }
+    }
+
+    /// goto name：标签已定义（向后跳转）时直接跳转，离开局部变量的作用域时先关闭它们；
+    /// 否则生成待回填的 Jump，由之后定义的标签解析
+    fn goto_stat(&mut self, name: &str, line: usize) {
+        let nvar = self.locals().len();
+        if let Some(label) = self.labels.iter().find(|l| l.name == name) {
+            let (pc, label_nvar) = (label.pc, label.nvar);
+            if nvar > label_nvar {
+                self.emit(ByteCode::Close(label_nvar as u8));
+            }
+            let jmp = pc as isize - self.fp.byte_codes.len() as isize - 1;
+            self.emit(ByteCode::Jump(jmp as i32));
+        } else {
+            let pc = self.emit(ByteCode::Jump(0));
+            self.gotos.push(Goto {
+                name: name.to_string(),
+                pc,
+                line,
+                nvar,
+                close: false,
+            });
+        }
+    }
+
+    /// ::name::：同一函数中可见的标签不能重名；解析当前代码块中（gotos 的 first 之后）跳到此标签的 goto，
+    /// goto 不能跳入局部变量的作用域（标签处有效而 goto 处无效的局部变量），位于代码块末尾的标签除外
+    fn label_stat(&mut self, name: &str, line: usize, nvar: usize, first: usize) -> Result<(), ParseError> {
+        if let Some(label) = self.labels.iter().find(|l| l.name == name) {
+            let msg = format!("label '{name}' already defined on line {}", label.line);
+            return Err(self.error(line, msg));
+        }
+        let pc = self.fp.byte_codes.len();
+        let mut close = false;
+        let mut i = first;
+        while i < self.gotos.len() {
+            if self.gotos[i].name != name {
+                i += 1;
+                continue;
+            }
+            let goto = self.gotos.remove(i);
+            if goto.nvar < nvar {
+                let local = &self.locals()[goto.nvar].name;
+                let msg = format!("<goto {name}> at line {} jumps into the scope of local '{local}'", goto.line);
+                return Err(self.error(goto.line, msg));
+            }
+            close |= goto.close;
+            let jmp = pc as isize - goto.pc as isize - 1;
+            self.patch(goto.pc, ByteCode::Jump(jmp as i32));
+        }
+        // 跳出了需要关闭的局部变量的作用域：在标签处关闭
+        if close {
+            self.emit(ByteCode::Close(nvar as u8));
+        }
+        self.labels.push(Label {
+            name: name.to_string(),
+            pc,
+            line,
+            nvar,
+        });
+        Ok(())
+    }
+
+    /// 函数结束时仍未找到标签的 goto 是错误
+    fn check_gotos(&self) -> Result<(), ParseError> {
+        match self.gotos.first() {
+            Some(goto) if goto.name == "break" => {
+                Err(self.error(goto.line, format!("break outside a loop at line {}", goto.line)))
+            }
+            Some(goto) => {
+                let msg = format!("no visible label '{}' for <goto> at line {}", goto.name, goto.line);
+                Err(self.error(goto.line, msg))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn stat(&mut self, stat: &Stat) -> Result<(), ParseError> {
+        let line = stat.span.line;
+        match &stat.kind {
+            StatKind::Local(vars, exps) => self.local(vars, exps, stat.span.end_line)?,
+            StatKind::LocalFunction(name, func) => {
+                self.add_locals(vec![name.clone()]);
+                let f = self.funcbody(func, false)?;
+                let dst = self.locals().len() - 1;
+                self.discharge(dst, f);
+            }
+            StatKind::Function(name, func) => self.function_stat(name, func, stat)?,
+            StatKind::Assign(vars, exps) => self.assignment(vars, exps)?,
+            StatKind::Call(call) => {
+                // 函数调用语句，丢弃全部返回值
+                let ExpDesc::Call(ifunc, narg_plus) = self.exp(call)? else {
+                    unreachable!()
+                };
+                self.emit(ByteCode::Call(ifunc as u8, narg_plus as u8, 1));
+            }
+            StatKind::Do(block) => self.block(block, stat.span.end_line)?,
+            StatKind::NumericFor {
+                var,
+                start,
+                limit,
+                step,
+                body,
+            } => self.for_stat(var, start, limit, step.as_ref(), body, stat.span.end_line)?,
+            StatKind::Return(exps) => self.return_stat(exps, stat.span.end_line)?,
+            StatKind::GenericFor { vars, exps, body } => {
+                self.generic_for_stat(vars, exps, body, stat.span.end_line)?
+            }
+            StatKind::If(branches, else_block) => self.if_stat(branches, else_block.as_ref())?,
+            StatKind::While(cond, body) => self.while_stat(cond, body, stat.span.end_line)?,
+            StatKind::Repeat(body, cond) => self.repeat_stat(body, cond)?,
+            // break 即跳到循环末尾的 break 标签
+            StatKind::Break => self.goto_stat("break", line),
+            StatKind::Goto(name) => self.goto_stat(name, line),
+            // 标签由 block_scope 处理
+            StatKind::Label(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// 数值 for 循环：for name = init, limit [, step] do block end
+    /// 初值、终值、步长放在连续的 3 个寄存器中（作为不可见的局部变量），循环变量在其后；
+    /// ForPrep 检查参数并在不执行循环时跳过循环体，ForLoop 更新计数并跳回循环体开头
+    fn for_stat(
+        &mut self,
+        var: &str,
+        init: &Exp,
+        limit: &Exp,
+        step: Option<&Exp>,
+        body: &Block,
+        end_line: usize,
+    ) -> Result<(), ParseError> {
+        // 跳出循环的 goto 同时离开循环变量与循环状态的作用域
+        let mark = self.enter_block();
+        let nvar = self.locals().len();
+        let base = self.sp;
+        let desc = self.exp(init)?;
+        self.discharge(base, desc);
+        let desc = self.exp(limit)?;
+        self.discharge(base + 1, desc);
+        match step {
+            Some(step) => {
+                let desc = self.exp(step)?;
+                self.discharge(base + 2, desc);
+            }
+            None => self.discharge(base + 2, ExpDesc::Integer(1)),
+        }
+        self.add_locals(vec!["(for state)".to_string(); 3]);
+
+        let iprep = self.emit(ByteCode::ForPrep(base as u8, 0));
+        // 循环变量与循环体的局部变量每次迭代都是新的，被捕获时在 ForLoop 之前关闭
+        self.add_locals(vec![var.to_string()]);
+        self.block_scope(body, false)?;
+        self.leave_block(mark);
+        self.line = end_line;
+        self.leave_scope(nvar + 3);
+        let iloop = self.fp.byte_codes.len();
+        self.emit(ByteCode::ForLoop(base as u8, (iloop - iprep) as u32));
+        self.patch(iprep, ByteCode::ForPrep(base as u8, (iloop - iprep) as u32));
+        self.leave_scope(nvar);
+        self.break_label(&mark, end_line)
+    }
+
+    /// 泛型 for 循环：for var1, var2 in explist do block end
+    /// 迭代函数、状态、控制变量与待关闭值放在连续的 4 个寄存器中，循环变量在其后；
+    /// 先跳到循环末尾的 TForCall 调用迭代函数，TForLoop 在第一个循环变量不为 nil 时跳回循环体开头
+    fn generic_for_stat(
+        &mut self,
+        vars: &[String],
+        exps: &[Exp],
+        body: &Block,
+        end_line: usize,
+    ) -> Result<(), ParseError> {
+        let mark = self.enter_block();
+        let nvar = self.locals().len();
+        let base = self.sp;
+        let (n, last) = self.explist(exps)?;
+        self.adjust_explist(base, n, last, 4);
+        self.add_locals(vec!["(for state)".to_string(); 4]);
+        // 第 4 个值是待关闭变量，循环结束（包括 break 跳出）时关闭
+        self.level().locals.last_mut().unwrap().attrib = Attrib::Close;
+        self.emit(ByteCode::Tbc((base + 3) as u8));
+        let iprep = self.emit(ByteCode::Jump(0));
+
+        let istart = self.fp.byte_codes.len();
+        self.add_locals(vars.to_vec());
+        // TForCall 用循环变量的位置调用迭代函数，至少需要 3 个寄存器
+        self.set_sp(base + 4 + vars.len().max(3));
+        self.block_scope(body, false)?;
+        self.leave_block(mark);
+        self.line = end_line;
+        self.leave_scope(nvar + 4);
+        self.patch_jump(iprep);
+        self.emit(ByteCode::TForCall(base as u8, vars.len() as u8));
+        let iloop = self.fp.byte_codes.len();
+        self.emit(ByteCode::TForLoop(base as u8, (iloop + 1 - istart) as u32));
+        self.leave_scope(nvar);
+        self.break_label(&mark, end_line)
+    }
+
+    /// if cond then block {elseif cond then block} [else block] end
+    /// 条件为假时跳到下一个分支，每个分支执行完后跳到整个语句之后
+    fn if_stat(&mut self, branches: &[(Exp, Block)], else_block: Option<&Block>) -> Result<(), ParseError> {
+        let mut jumps_to_end = Vec::new();
+        for (i, (cond, block)) in branches.iter().enumerate() {
+            self.line = cond.span.line;
+            let next = self.cond_jump(cond)?;
+            self.block(block, block.span.end_line)?;
+            if i + 1 < branches.len() || else_block.is_some() {
+                jumps_to_end.push(self.emit(ByteCode::Jump(0)));
+            }
+            if let Some(pc) = next {
+                self.patch_jump(pc);
+            }
+        }
+        if let Some(block) = else_block {
+            self.block(block, block.span.end_line)?;
+        }
+        for pc in jumps_to_end {
+            self.patch_jump(pc);
+        }
+        Ok(())
+    }
+
+    /// while cond do block end
+    fn while_stat(&mut self, cond: &Exp, body: &Block, end_line: usize) -> Result<(), ParseError> {
+        let mark = self.enter_block();
+        let istart = self.fp.byte_codes.len();
+        self.line = cond.span.line;
+        let exit = self.cond_jump(cond)?;
+        self.block(body, end_line)?;
+        self.jump_back(istart);
+        if let Some(pc) = exit {
+            self.patch_jump(pc);
+        }
+        self.break_label(&mark, end_line)
+    }
+
+    /// repeat block until cond：条件中可以引用循环体中的局部变量，
+    /// 因此循环体的作用域到条件之后才结束；跳回开头之前关闭被捕获的局部变量
+    fn repeat_stat(&mut self, body: &Block, cond: &Exp) -> Result<(), ParseError> {
+        let mark = self.enter_block();
+        let nvar = self.locals().len();
+        let istart = self.fp.byte_codes.len();
+        self.block_scope(body, true)?;
+        self.line = cond.span.line;
+        let desc = self.exp(cond)?;
+        let need_close = self.locals()[nvar..]
+            .iter()
+            .any(|l| l.captured || l.attrib == Attrib::Close);
+        match desc {
+            ExpDesc::Boolean(true) | ExpDesc::Integer(_) | ExpDesc::Float(_) | ExpDesc::String(_) => (),
+            desc => {
+                if !matches!(desc, ExpDesc::Nil | ExpDesc::Boolean(false)) {
+                    // 条件为真时跳过下面的（关闭与）跳转，结束循环
+                    let r = self.discharge_any(desc);
+                    self.emit(ByteCode::TestOrJump(r as u8, 1 + need_close as i32));
+                }
+                if need_close {
+                    self.emit(ByteCode::Close(nvar as u8));
                 }
-                Token::Local => self.local(),
-                Token::Eos => break,
-                t => panic!("unexpected token {:?}", t),
+                self.jump_back(istart);
             }
         }
+        self.leave_scope(nvar);
+        self.break_label(&mark, cond.span.end_line)
+    }
+
+    /// 条件为假时跳转：生成求值并测试 cond 的字节码，返回待回填的 Jump 的位置；
+    /// cond 是恒为真的常量时不需要跳转
+    fn cond_jump(&mut self, cond: &Exp) -> Result<Option<usize>, ParseError> {
+        let sp0 = self.sp;
+        let pc = match self.exp(cond)? {
+            ExpDesc::Boolean(true) | ExpDesc::Integer(_) | ExpDesc::Float(_) | ExpDesc::String(_) => None,
+            ExpDesc::Nil | ExpDesc::Boolean(false) => Some(self.emit(ByteCode::Jump(0))),
+            desc => {
+                // 条件为真时跳过其后的 Jump
+                let r = self.discharge_any(desc);
+                self.emit(ByteCode::TestOrJump(r as u8, 1));
+                Some(self.emit(ByteCode::Jump(0)))
+            }
+        };
+        self.set_sp(sp0);
+        Ok(pc)
+    }
+
+    /// 把 pc 处的 Jump 回填为跳到下一条要生成的字节码
+    fn patch_jump(&mut self, pc: usize) {
+        let jmp = self.fp.byte_codes.len() - pc - 1;
+        self.patch(pc, ByteCode::Jump(jmp as i32));
+    }
+
+    /// 向后跳转到 pc
+    fn jump_back(&mut self, pc: usize) {
+        let jmp = pc as isize - self.fp.byte_codes.len() as isize - 1;
+        self.emit(ByteCode::Jump(jmp as i32));
+    }
+
+    /// 循环结束处的 break 标签：解析循环中（mark 之后）的 break，之后标签不再可见
+    fn break_label(&mut self, mark: &BlockMark, line: usize) -> Result<(), ParseError> {
+        self.label_stat("break", line, mark.nvar, mark.gotos)?;
+        self.labels.pop();
+        Ok(())
+    }
+
+    /// 离开作用域：丢弃 nvar 之后声明的局部变量，若有被捕获的则关闭对应上值，
+    /// 有待关闭变量时调用它们的 __close 元方法
+    fn leave_scope(&mut self, nvar: usize) {
+        let need_close = self.locals()[nvar..]
+            .iter()
+            .any(|l| l.captured || l.attrib == Attrib::Close);
+        if need_close {
+            self.emit(ByteCode::Close(nvar as u8));
+        }
+        let end = self.fp.byte_codes.len();
+        for &i in &self.actvars[nvar..] {
+            self.fp.locvars[i].endpc = end;
+        }
+        self.actvars.truncate(nvar);
+        self.level().locals.truncate(nvar);
+        self.set_sp(nvar);
+    }
+
+    /// 本地变量声明：local var1 [attrib], var2 [attrib] = exp1, exp2
+    /// 将各表达式依次加载到寄存器（起始索引为当前 locals 长度），按变量个数调整，
+    /// 之后才把变量名添加到 locals 表（因此 local x = x 中右边的 x 是外层变量）
+    ///
+    /// 与 Lua 一样，表达式与变量个数相同且最后一个 <const> 变量的值是常量时，记下该常量供引用处直接使用；
+    /// <close> 变量生成 Tbc 字节码，由虚拟机检查其值并在离开作用域时关闭
+    fn local(&mut self, vars: &[(String, Attrib)], exps: &[Exp], end_line: usize) -> Result<(), ParseError> {
+        let nvar = vars.len();
+        let mut konst = None;
+        if exps.is_empty() {
+            self.line = end_line;
+            for _ in 0..nvar {
+                self.discharge_top(ExpDesc::Nil);
+            }
+        } else {
+            let sp0 = self.sp;
+            let (n, last) = self.explist(exps)?;
+            if n + 1 == nvar && vars[nvar - 1].1 == Attrib::Const {
+                konst = const_value(&last);
+            }
+            self.adjust_explist(sp0, n, last, nvar);
+        }
+
+        let first = self.locals().len();
+        self.add_locals(vars.iter().map(|(name, _)| name.clone()).collect());
+        let locals = &mut self.level().locals[first..];
+        for (local, (_, attrib)) in locals.iter_mut().zip(vars) {
+            local.attrib = *attrib;
+        }
+        locals[nvar - 1].konst = konst;
+        if let Some(i) = vars.iter().position(|(_, a)| *a == Attrib::Close) {
+            self.line = end_line;
+            self.emit(ByteCode::Tbc((first + i) as u8));
+        }
+        Ok(())
+    }
+
+    /// function name.field:method body：方法定义隐含第一个参数 self
+    fn function_stat(&mut self, name: &FuncName, func: &FuncBody, stat: &Stat) -> Result<(), ParseError> {
+        let sp0 = self.sp;
+        let first = &name.path[0];
+        if name.path.len() == 1 && name.method.is_none() {
+            self.check_readonly(first, stat.span.line)?;
+        }
+        let mut desc = self.simple_name(first.clone());
+        for key in name.path[1..].iter().chain(&name.method) {
+            let t = self.discharge_at(desc, sp0);
+            desc = self.field(t, key);
+        }
+        let f = self.funcbody(func, name.method.is_some())?;
+        self.line = stat.span.end_line;
+        self.assign_var(desc, f);
+        Ok(())
+    }
+
+    /// return [explist]：返回值放在从 sp 开始的连续寄存器中
+    fn return_stat(&mut self, exps: &[Exp], end_line: usize) -> Result<(), ParseError> {
+        let first = self.sp;
+        let code = if exps.is_empty() {
+            ByteCode::Return(first as u8, 1)
+        } else {
+            let (n, last) = self.explist(exps)?;
+            match self.discharge_multi(last) {
+                Some(nplus) => ByteCode::Return(first as u8, (n + nplus) as u8),
+                None => ByteCode::Return(first as u8, 0),
+            }
+        };
+        self.line = end_line;
+        self.emit(code);
+        Ok(())
+    }
+
+    /// 赋值语句：var1, var2 = exp1, exp2
+    /// 单个变量单个表达式时直接赋值（常数/全局变量有专门的字节码）；
+    /// 否则先把所有表达式求值到连续的临时寄存器，再依次赋给各变量
+    fn assignment(&mut self, targets: &[Exp], exps: &[Exp]) -> Result<(), ParseError> {
+        let mut vars = Vec::new();
+        for target in targets {
+            if let ExpKind::Name(name) = &target.kind {
+                self.check_readonly(name, target.span.line)?;
+            }
+            vars.push(self.exp(target)?);
+        }
+
+        if vars.len() == 1 {
+            let sp0 = self.sp;
+            let (n, last) = self.explist(exps)?;
+            let var = vars.pop().unwrap();
+            if n == 0 {
+                self.assign_var(var, last);
+            } else {
+                // 多个表达式：只取第一个，其余求值后丢弃
+                self.discharge_top(last);
+                self.assign_var(var, ExpDesc::Local(sp0));
+            }
+            return Ok(());
+        }
+
+        let sp0 = self.sp;
+        let (n, last) = self.explist(exps)?;
+        self.adjust_explist(sp0, n, last, vars.len());
+        for (i, var) in vars.into_iter().enumerate().rev() {
+            self.assign_var(var, ExpDesc::Local(sp0 + i));
+        }
+        Ok(())
+    }
+
+    /// 把表达式 value 赋给变量 var
+    fn assign_var(&mut self, var: ExpDesc, value: ExpDesc) {
+        match var {
+            ExpDesc::Local(i) => self.discharge(i, value),
+            ExpDesc::Upvalue(i) => {
+                let src = self.discharge_any(value);
+                self.emit(ByteCode::SetUpvalue(i as u8, src as u8));
+            }
+            ExpDesc::Global(name) => {
+                // SetGlobalConst、SetGlobalGlobal 的两个常量索引都在 8 位字段中，放不下时经由寄存器赋值
+                let code = match (const_value(&value), &value) {
+                    // from const values
+                    (Some(c), _) => {
+                        let k = self.add_const(c);
+                        (name.max(k) <= MAX_ARG_B).then_some(ByteCode::SetGlobalConst(name as u8, k as u8))
+                    }
+                    //from global var
+                    (None, &ExpDesc::Global(src)) => {
+                        (name.max(src) <= MAX_ARG_B).then_some(ByteCode::SetGlobalGlobal(name as u8, src as u8))
+                    }
+                    _ => None,
+                };
+                //from local var or other expression
+                let code = code.unwrap_or_else(|| {
+                    let src = self.discharge_any(value);
+                    ByteCode::SetGlobal(name as u32, src as u8)
+                });
+                self.emit(code);
+            }
+            ExpDesc::Index(t, k) => {
+                let v = self.discharge_any(value);
+                self.emit(ByteCode::SetTable(t as u8, k as u8, v as u8));
+            }
+            ExpDesc::IndexInt(t, n) => {
+                let v = self.discharge_any(value);
+                self.emit(ByteCode::SetInt(t as u8, n as u8, v as u8));
+            }
+            ExpDesc::IndexField(t, k) => {
+                let v = self.discharge_any(value);
+                self.emit(ByteCode::SetField(t as u8, k as u8, v as u8));
+            }
+            _ => panic!("assign to non-variable expression"),
+        }
     }
 
     /// 向常数池中添加常数，若常数已存在则返回其索引，否则添加并返回新索引
     /// 用于消除常数重复，实现常数池复用
     fn add_const(&mut self, c: Value) -> usize {
-        self.constants
-            .iter()
-            .position(|x| *x == c)
-            .unwrap_or_else(|| {
-                self.constants.push(c);
-                self.constants.len() - 1
-            })
-    }
-
-    /// 生成 LoadConst 字节码：将索引 c 处的常数加载到目标寄存器 dst
-    fn load_const(&mut self, dst: usize, c: Value) -> ByteCode {
-        ByteCode::LoadConst(dst as u8, self.add_const(c) as u16)
-    }
-
-    /// 表达式解析函数：识别并加载不同类型的表达式到目标寄存器 dst
-    /// 支持的表达式类型：
-    /// - Nil、True、False：常数 Token
-    /// - Integer、Float、String：字面量常数
-    /// - Name：变量加载
-    /// 生成相应的字节码并推入 byte_codes 序列
-    fn load_exp(&mut self, dst: usize) {
-        let code = match self.lex.next() {
-            Token::Nil => ByteCode::LoadNil(dst as u8),
-            Token::True => ByteCode::LoadBool(dst as u8, true),
-            Token::False => ByteCode::LoadBool(dst as u8, false),
-            Token::Integer(i) => {
-                if let Ok(n) = i16::try_from(i) {
-                    ByteCode::LoadInt(dst as u8, n)
-                } else {
-                    self.load_const(dst, Value::Integer(i))
+        let constants = &mut self.fp.constants;
+        // 浮点数按位比较：0.0 与 -0.0 相等但不能共用同一个常量
+        let same = |x: &Value| match (x, &c) {
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            _ => *x == c,
+        };
+        if let Some(i) = constants.iter().position(same) {
+            return i;
+        }
+        constants.push(c);
+        // LoadConst 等的常量索引在 Bx 字段中
+        if constants.len() > MAX_BX as usize + 1 {
+            let msg = self.limit_msg("constants", MAX_BX as usize + 1);
+            self.overflow(msg);
+        }
+        self.fp.constants.len() - 1
+    }
+
+    /// t.name：字段名的常量索引放不进 GetField、SetField 的 8 位字段时，
+    /// 把字段名加载到新的寄存器，作为 t[k] 访问
+    fn field(&mut self, t: usize, name: &str) -> ExpDesc {
+        let k = self.add_const(Value::from(name));
+        if k <= MAX_ARG_B {
+            ExpDesc::IndexField(t, k)
+        } else {
+            ExpDesc::Index(t, self.discharge_top(ExpDesc::String(name.as_bytes().to_vec())))
+        }
+    }
+
+    /// 表达式列表：除最后一个外都依次放入从 sp 开始的寄存器，
+    /// 返回已放入的个数与尚未处理的最后一个表达式
+    fn explist(&mut self, exps: &[Exp]) -> Result<(usize, ExpDesc), ParseError> {
+        let sp0 = self.sp;
+        let (last, init) = exps.split_last().unwrap();
+        for (n, exp) in init.iter().enumerate() {
+            let desc = self.exp(exp)?;
+            self.discharge(sp0 + n, desc);
+            self.set_sp(sp0 + n + 1);
+        }
+        let desc = self.exp(last)?;
+        self.set_sp(sp0 + init.len());
+        Ok((init.len(), desc))
+    }
+
+    /// 把 explist 的结果（从 sp0 开始已放入 n 个值，最后一个表达式为 last）调整为 want 个值
+    fn adjust_explist(&mut self, sp0: usize, n: usize, last: ExpDesc, want: usize) {
+        match last {
+            ExpDesc::Call(ifunc, narg_plus) if n < want => {
+                let nret = want - n;
+                self.emit(ByteCode::Call(ifunc as u8, narg_plus as u8, (nret + 1) as u8));
+            }
+            ExpDesc::VarArgs if n < want => {
+                let nret = want - n;
+                self.emit(ByteCode::VarArgs((sp0 + n) as u8, (nret + 1) as u8));
+            }
+            last => {
+                self.discharge(sp0 + n, last);
+                for i in n + 1..want {
+                    self.discharge(sp0 + i, ExpDesc::Nil);
                 }
             }
-            Token::Float(f) => self.load_const(dst, Value::Float(f)),
-            Token::String(s) => self.load_const(dst, Value::from(s)),
-            Token::Name(name) => self.load_var(dst, name),
-            _ => panic!("unexpected token"),
+        }
+        self.set_sp(sp0 + want);
+    }
+
+    /// 表达式：运算结果放在进入时的第一个空闲寄存器 sp0，操作数的临时寄存器随之释放
+    /// 生成的字节码记为表达式结束的行
+    fn exp(&mut self, exp: &Exp) -> Result<ExpDesc, ParseError> {
+        let sp0 = self.sp;
+        let desc = match &exp.kind {
+            ExpKind::Nil => ExpDesc::Nil,
+            ExpKind::True => ExpDesc::Boolean(true),
+            ExpKind::False => ExpDesc::Boolean(false),
+            ExpKind::Integer(i) => ExpDesc::Integer(*i),
+            ExpKind::Float(f) => ExpDesc::Float(*f),
+            ExpKind::String(s) => ExpDesc::String(s.clone()),
+            ExpKind::Dots => ExpDesc::VarArgs,
+            ExpKind::Function(func) => self.funcbody(func, false)?,
+            ExpKind::Table(_) => self.table_constructor(exp)?,
+            ExpKind::Name(_)
+            | ExpKind::Paren(_)
+            | ExpKind::Index(..)
+            | ExpKind::Field(..)
+            | ExpKind::Call(..)
+            | ExpKind::Method(..) => self.suffixed_exp(exp)?,
+            ExpKind::Unary(op, operand) => {
+                let operand = self.exp(operand)?;
+                self.line = exp.span.end_line;
+                self.unop(*op, operand, sp0)
+            }
+            ExpKind::Binary(op @ (BinOp::And | BinOp::Or), left, right) => {
+                let left = self.exp(left)?;
+                self.logic_op(*op, left, right, sp0)?
+            }
+            ExpKind::Binary(op, left, right) => {
+                let desc = self.exp(left)?;
+                let left = self.discharge_at(desc, sp0);
+                let desc = self.exp(right)?;
+                let right = self.discharge_any(desc);
+                self.line = exp.span.end_line;
+                self.binop(*op, left, right, sp0)
+            }
         };
-        self.byte_codes.push(code);
-    }
-
-    /// 本地变量声明处理：local var = expression
-    /// 流程：
-    /// 1. 读取变量名
-    /// 2. 期望 '=' Token
-    /// 3. 将表达式加载到寄存器（索引为当前 locals 长度）
-    /// 4. 将变量名添加到 locals 表
-    fn local(&mut self) {
-        let var = if let Token::Name(var) = self.lex.next() {
-            var
-        } else {
-            panic!("expected variable name")
+        self.line = exp.span.end_line;
+        Ok(desc)
+    }
+
+    /// 一元运算：数字常量的取负、常量的 not 在编译时计算，其余生成字节码
+    fn unop(&mut self, op: UnOp, operand: ExpDesc, dst: usize) -> ExpDesc {
+        match (op, operand) {
+            (UnOp::Neg, ExpDesc::Integer(i)) => ExpDesc::Integer(i.wrapping_neg()),
+            (UnOp::Neg, ExpDesc::Float(f)) => ExpDesc::Float(-f),
+            (UnOp::Not, ExpDesc::Nil | ExpDesc::Boolean(false)) => ExpDesc::Boolean(true),
+            (UnOp::Not, ExpDesc::Boolean(true) | ExpDesc::Integer(_) | ExpDesc::Float(_) | ExpDesc::String(_)) => {
+                ExpDesc::Boolean(false)
+            }
+            (_, operand) => {
+                let src = self.discharge_any(operand);
+                let (dst, src) = (dst as u8, src as u8);
+                let code = match op {
+                    UnOp::Neg => ByteCode::Neg(dst, src),
+                    UnOp::Not => ByteCode::Not(dst, src),
+                    UnOp::BitNot => ByteCode::BitNot(dst, src),
+                    UnOp::Len => ByteCode::Len(dst, src),
+                };
+                self.emit(code);
+                self.set_sp(dst as usize + 1);
+                ExpDesc::Local(dst as usize)
+            }
+        }
+    }
+
+    /// 二元运算：左右操作数已在寄存器中，结果放入 dst
+    fn binop(&mut self, op: BinOp, left: usize, right: usize, dst: usize) -> ExpDesc {
+        let (d, l, r) = (dst as u8, left as u8, right as u8);
+        let code = match op {
+            BinOp::Add => ByteCode::Add(d, l, r),
+            BinOp::Sub => ByteCode::Sub(d, l, r),
+            BinOp::Mul => ByteCode::Mul(d, l, r),
+            BinOp::Div => ByteCode::Div(d, l, r),
+            BinOp::Idiv => ByteCode::Idiv(d, l, r),
+            BinOp::Mod => ByteCode::Mod(d, l, r),
+            BinOp::Pow => ByteCode::Pow(d, l, r),
+            BinOp::BitAnd => ByteCode::BitAnd(d, l, r),
+            BinOp::BitXor => ByteCode::BitXor(d, l, r),
+            BinOp::BitOr => ByteCode::BitOr(d, l, r),
+            BinOp::ShiftL => ByteCode::ShiftL(d, l, r),
+            BinOp::ShiftR => ByteCode::ShiftR(d, l, r),
+            BinOp::Concat => ByteCode::Concat(d, l, r),
+            BinOp::Equal => ByteCode::Equal(d, l, r),
+            BinOp::NotEq => ByteCode::NotEq(d, l, r),
+            BinOp::Less => ByteCode::LesThan(d, l, r),
+            BinOp::LesEq => ByteCode::LesEq(d, l, r),
+            // a > b 即 b < a
+            BinOp::Greater => ByteCode::LesThan(d, r, l),
+            BinOp::GreEq => ByteCode::LesEq(d, r, l),
+            BinOp::And | BinOp::Or => unreachable!(),
+        };
+        self.emit(code);
+        self.set_sp(dst + 1);
+        ExpDesc::Local(dst)
+    }
+
+    /// 逻辑运算 and/or：左操作数放入 dst，按其真假决定是否跳过右操作数的求值
+    /// 跳过时 dst 中保留左操作数作为结果，否则右操作数的值覆盖 dst
+    fn logic_op(&mut self, op: BinOp, left: ExpDesc, right: &Exp, dst: usize) -> Result<ExpDesc, ParseError> {
+        self.discharge(dst, left);
+        self.set_sp(dst + 1);
+        let itest = self.fp.byte_codes.len();
+        self.emit(ByteCode::TestAndJump(dst as u8, 0));
+
+        let right = self.exp(right)?;
+        self.discharge(dst, right);
+        self.set_sp(dst + 1);
+
+        let jmp = (self.fp.byte_codes.len() - itest - 1) as i32;
+        let code = match op {
+            BinOp::And => ByteCode::TestAndJump(dst as u8, jmp),
+            _ => ByteCode::TestOrJump(dst as u8, jmp),
+        };
+        self.patch(itest, code);
+        Ok(ExpDesc::Local(dst))
+    }
+
+    /// 括号表达式 (exp) 只保留第一个值
+    fn paren_exp(&mut self, desc: ExpDesc) -> ExpDesc {
+        match desc {
+            ExpDesc::Call(..) | ExpDesc::VarArgs => ExpDesc::Local(self.discharge_top(desc)),
+            desc => desc,
+        }
+    }
+
+    /// 前缀表达式：名字或括号表达式，之后跟着一串后缀：
+    /// .name 字段访问、[exp] 索引、(args) 函数调用、:name(args) 方法调用
+    /// 链中的中间结果都放在处理后缀之前的第一个空闲寄存器 sp0，不会逐级占用新寄存器
+    fn suffixed_exp(&mut self, exp: &Exp) -> Result<ExpDesc, ParseError> {
+        // 由外向内收集后缀，直到名字或括号表达式
+        let mut chain = Vec::new();
+        let mut base = exp;
+        while let ExpKind::Field(e, _) | ExpKind::Index(e, _) | ExpKind::Call(e, _) | ExpKind::Method(e, ..) =
+            &base.kind
+        {
+            chain.push(base);
+            base = e;
+        }
+        let mut desc = match &base.kind {
+            ExpKind::Name(name) => self.simple_name(name.clone()),
+            ExpKind::Paren(inner) => {
+                let desc = self.exp(inner)?;
+                self.paren_exp(desc)
+            }
+            _ => unreachable!("prefix expression must start with a name or parentheses"),
         };
-        if self.lex.next() != Token::Assign {
-            panic!("expected assign");
-        }
-        self.load_exp(self.locals.len());
-        self.locals.push(var);
-    }
-
-    /// 函数调用处理：func(args) 或 func "string"
-    /// 流程：
-    /// 1. 加载函数变量到寄存器 ifunc
-    /// 2. 加载参数（括号表达式 或 字符串直接调用）到寄存器 iargs
-    /// 3. 生成 Call 字节码
-    fn function_call(&mut self, name: String) {
-        let ifunc = self.locals.len();
-        let iargs = ifunc + 1;
-        //function,variable
-        let code = self.load_var(ifunc, name);
-        self.byte_codes.push(code);
-        // argument,(exp) or "string"
-        match self.lex.next() {
-            Token::ParL => {
-                self.load_exp(iargs);
-                if self.lex.next() != Token::ParR {
-                    panic!("expected )")
+        self.line = base.span.end_line;
+
+        let sp0 = self.sp;
+        for suffix in chain.into_iter().rev() {
+            desc = match &suffix.kind {
+                ExpKind::Field(_, key) => {
+                    let t = self.discharge_at(desc, sp0);
+                    self.field(t, key)
+                }
+                ExpKind::Index(_, key) => {
+                    let t = self.discharge_at(desc, sp0);
+                    match self.exp(key)? {
+                        ExpDesc::Integer(n @ 1..=255) => ExpDesc::IndexInt(t, n as usize),
+                        key => ExpDesc::Index(t, self.discharge_any(key)),
+                    }
                 }
+                ExpKind::Method(_, name, args) => {
+                    // obj:name(args)：Self_ 把方法放入 sp0，对象放入 sp0+1 作为第一个参数
+                    let obj = self.discharge_at(desc, sp0);
+                    let k = self.add_const(Value::from(name.as_str()));
+                    self.set_sp(sp0 + 2);
+                    if k <= MAX_ARG_B {
+                        self.emit(ByteCode::Self_(sp0 as u8, obj as u8, k as u8));
+                    } else {
+                        // 方法名的常量索引放不进 C 字段：先放好对象，再把方法名加载到 sp0 并查找方法
+                        self.emit(ByteCode::Move(sp0 as u8 + 1, obj as u8));
+                        self.emit(ByteCode::LoadConst(sp0 as u8, k as u32));
+                        self.emit(ByteCode::GetTable(sp0 as u8, sp0 as u8 + 1, sp0 as u8));
+                    }
+                    let narg_plus = match self.call_args(args)? {
+                        0 => 0,
+                        n => n + 1,
+                    };
+                    ExpDesc::Call(sp0, narg_plus)
+                }
+                ExpKind::Call(_, args) => {
+                    self.set_sp(sp0);
+                    let ifunc = self.discharge_top(desc);
+                    let narg_plus = self.call_args(args)?;
+                    ExpDesc::Call(ifunc, narg_plus)
+                }
+                _ => unreachable!(),
+            };
+            self.line = suffix.span.end_line;
+        }
+        Ok(desc)
+    }
+
+    /// 局部变量直接使用其寄存器，其他表达式放入寄存器 dst（之上的临时值随之释放）
+    fn discharge_at(&mut self, desc: ExpDesc, dst: usize) -> usize {
+        match desc {
+            ExpDesc::Local(i) => i,
+            desc => {
+                self.set_sp(dst);
+                self.discharge_top(desc)
             }
-            Token::String(s) => {
-                let code = self.load_const(iargs, Value::from(s));
-                self.byte_codes.push(code);
+        }
+    }
+
+    /// 函数调用参数依次放在函数寄存器之后
+    /// 返回参数个数+1，最后一个参数是多返回值调用或 ... 时返回 0
+    fn call_args(&mut self, args: &[Exp]) -> Result<usize, ParseError> {
+        if args.is_empty() {
+            return Ok(1);
+        }
+        let (n, last) = self.explist(args)?;
+        Ok(match self.discharge_multi(last) {
+            Some(nplus) => n + nplus,
+            None => 0,
+        })
+    }
+
+    /// 表达式列表的最后一个表达式：调用和 ... 保留全部值并设置栈顶（返回 None），
+    /// 其他表达式放入 sp 处的寄存器（返回 Some(2)，即 1 个值 +1）
+    fn discharge_multi(&mut self, desc: ExpDesc) -> Option<usize> {
+        match desc {
+            ExpDesc::Call(ifunc, narg_plus) => {
+                self.emit(ByteCode::Call(ifunc as u8, narg_plus as u8, 0));
+                None
+            }
+            ExpDesc::VarArgs => {
+                let dst = self.sp;
+                self.emit(ByteCode::VarArgs(dst as u8, 0));
+                None
+            }
+            desc => {
+                self.discharge_top(desc);
+                Some(2)
             }
-            _ => panic!("expected ( or string"),
         }
-        self.byte_codes.push(ByteCode::Call(iargs as u8, 1));
     }
 
-    /// 获取本地变量的索引（在 locals 表中的位置）
-    /// 返回 Some(index) 若变量存在，否则返回 None
-    fn get_local(&self, name: &str) -> Option<usize> {
-        self.locals.iter().position(|x| x == name)
+    /// 变量名解析：依次查找局部变量、上值，都没有则为全局变量
+    /// 值为常量的 <const> 变量（包括外层函数的）直接替换为该常量
+    fn simple_name(&mut self, name: String) -> ExpDesc {
+        if let Some(v) = self.find_local(&name).and_then(|l| l.konst.clone()) {
+            return const_exp(v);
+        }
+        if let Some(i) = self.locals().iter().rposition(|l| l.name == name) {
+            return ExpDesc::Local(i);
+        }
+        let depth = self.ctx.levels.len() - 1;
+        if let Some(i) = self.find_upvalue(&name, depth) {
+            return ExpDesc::Upvalue(i);
+        }
+        ExpDesc::Global(self.add_const(Value::from(name)))
     }
 
-    /// 变量加载：若变量在 locals 表中则生成 Move 字节码，否则作为全局变量处理
-    fn load_var(&mut self, dst: usize, name: String) -> ByteCode {
-        if let Some(i) = self.get_local(&name) {
-            ByteCode::Move(dst as u8, i as u8)
-        } else {
-            let ic = self.add_const(Value::from(name));
-            ByteCode::LoadConst(dst as u8, ic as u16)
+    /// 由内向外在各层函数中查找名为 name 的局部变量
+    fn find_local(&self, name: &str) -> Option<&Local> {
+        self.ctx
+            .levels
+            .iter()
+            .rev()
+            .find_map(|level| level.locals.iter().rev().find(|l| l.name == name))
+    }
+
+    /// 赋值的目标是名为 name 的变量时检查它不是只读的局部变量（<const> 或 <close>）
+    fn check_readonly(&mut self, name: &str, line: usize) -> Result<(), ParseError> {
+        match self.find_local(name) {
+            Some(local) if local.attrib != Attrib::Plain => {
+                Err(self.error(line, format!("attempt to assign to const variable '{name}'")))
+            }
+            _ => Ok(()),
         }
     }
 
-    fn assignment(&mut self, var: String) {
-        self.lex.next();
-        //先判断变量是否重复，重复就覆盖
-        if let Some(i) = self.get_local(&var) {
-            self.load_exp(i);
+    /// 在第 depth 层函数中查找（必要时创建）名为 name 的上值
+    /// 若外层函数有同名局部变量则标记其被捕获，否则递归到更外层查找
+    fn find_upvalue(&mut self, name: &str, depth: usize) -> Option<usize> {
+        let levels = &mut self.ctx.levels;
+        if let Some(i) = levels[depth].upvalues.iter().position(|(v, _)| v == name) {
+            return Some(i);
+        }
+        if depth == 0 {
+            return None;
+        }
+        let parent = &mut levels[depth - 1];
+        let upindex = if let Some(i) = parent.locals.iter().rposition(|l| l.name == name) {
+            parent.locals[i].captured = true;
+            UpIndex::Local(i)
         } else {
-            //先判断全局变量，返回索引
-            let dst = self.add_const(Value::from(var)) as u8;
-            let code = match self.lex.next() {
-                // from const values
-                Token::Nil => ByteCode::SetGlobalConst(dst, self.add_const(Value::Nil) as u8),
-                Token::True => {
-                    ByteCode::SetGlobalConst(dst, self.add_const(Value::Boolean(true)) as u8)
+            UpIndex::Upvalue(self.find_upvalue(name, depth - 1)?)
+        };
+        let upvalues = &mut self.ctx.levels[depth].upvalues;
+        upvalues.push((name.to_string(), upindex));
+        Some(upvalues.len() - 1)
+    }
+
+    /// 函数体：生成子函数原型
+    /// has_self 为真时（方法定义）隐含第一个参数 self
+    fn funcbody(&mut self, func: &FuncBody, has_self: bool) -> Result<ExpDesc, ParseError> {
+        let mut params = Vec::new();
+        if has_self {
+            params.push("self".to_string());
+        }
+        params.extend(func.params.iter().cloned());
+
+        self.ctx.levels.push(Level::default());
+        let mut proto = ParseProto::new(&mut *self.ctx, func.is_vararg, params, func.span.line);
+        proto.block(&func.body, func.span.end_line)?;
+        proto.check_gotos()?;
+        proto.line = func.span.end_line;
+        proto.emit(ByteCode::Return(0, 1));
+        if proto.level().upvalues.len() > MAX_UPVALUES {
+            let msg = proto.limit_msg("upvalues", MAX_UPVALUES);
+            proto.overflow(msg);
+        }
+        proto.check_overflow()?;
+        let mut fp = proto.finish(func.span.end_line);
+        let level = self.ctx.levels.pop().unwrap();
+        fp.upindexes = level.upvalues.into_iter().map(|(_, u)| u).collect();
+
+        // Closure 的子函数原型索引在 Bx 字段中
+        if self.fp.protos.len() > MAX_BX as usize {
+            return Err(self.error(func.span.line, self.limit_msg("functions", MAX_BX as usize + 1)));
+        }
+        self.fp.protos.push(Rc::new(fp));
+        Ok(ExpDesc::Function(self.fp.protos.len() - 1))
+    }
+
+    /// 表构造器：{ exp, name = exp, [exp] = exp, ... }
+    /// 表放在新分配的寄存器中，键值先加载到其后的临时寄存器
+    /// 数组项累积在表之后的寄存器中，每满 50 个用 SetList 批量写入
+    /// 最后一项若是函数调用或 ...，展开其全部值
+    fn table_constructor(&mut self, exp: &Exp) -> Result<ExpDesc, ParseError> {
+        let ExpKind::Table(fields) = &exp.kind else {
+            unreachable!()
+        };
+        let table = self.sp;
+        self.set_sp(self.sp + 1);
+        self.line = exp.span.line;
+        let inew = self.emit(ByteCode::NewTable(table as u8, 0, 0));
+
+        let mut narray = 0;
+        let mut nmap = 0;
+        let mut pending = 0; // 尚未 SetList 的数组项个数
+        for (i, field) in fields.iter().enumerate() {
+            let sp0 = self.sp;
+            let entry = match &field.kind {
+                FieldKind::Keyed(key, value) => {
+                    let key = self.exp(key)?;
+                    let k = self.discharge_any(key);
+                    let value = self.exp(value)?;
+                    let v = self.discharge_any(value);
+                    self.emit(ByteCode::SetTable(table as u8, k as u8, v as u8));
+                    nmap += 1;
+                    None
                 }
-                Token::False => {
-                    ByteCode::SetGlobalConst(dst, self.add_const(Value::Boolean(false)) as u8)
+                FieldKind::Named(name, value) => {
+                    let key = self.field(table, name);
+                    let value = self.exp(value)?;
+                    self.assign_var(key, value);
+                    nmap += 1;
+                    None
                 }
-                Token::Integer(i) => {
-                    ByteCode::SetGlobalConst(dst, self.add_const(Value::Integer(i)) as u8)
+                FieldKind::Positional(value) => Some(self.exp(value)?),
+            };
+            self.set_sp(sp0);
+
+            if let Some(desc) = entry {
+                narray += 1;
+                if i + 1 == fields.len() && self.discharge_multi_check(&desc) {
+                    // 最后一项是多返回值：连同之前累积的项一起写到栈顶
+                    self.discharge_multi(desc);
+                    self.emit_set_list(table, 0, narray - 1 - pending);
+                    pending = 0;
+                } else {
+                    self.discharge(sp0, desc);
+                    pending += 1;
+                    self.set_sp(sp0 + 1);
                 }
-                Token::Float(f) => {
-                    ByteCode::SetGlobalConst(dst, self.add_const(Value::Float(f)) as u8)
+                if pending == FIELDS_PER_FLUSH {
+                    self.emit_set_list(table, pending, narray - pending);
+                    pending = 0;
+                    self.set_sp(table + 1);
+                }
+            }
+        }
+        self.line = exp.span.end_line;
+        if pending > 0 {
+            self.emit_set_list(table, pending, narray - pending);
+        }
+        self.patch(
+            inew,
+            ByteCode::NewTable(table as u8, narray.min(255) as u8, nmap.min(255) as u8),
+        );
+        self.set_sp(table + 1);
+        Ok(ExpDesc::Local(table))
+    }
+
+    /// 生成 SetList：把 n 个数组项（0 表示到栈顶）写到下标 offset+1 开始的位置，
+    /// offset 是 FIELDS_PER_FLUSH 的倍数，批次放不进 C 时用 ExtraArg 携带
+    fn emit_set_list(&mut self, table: usize, n: usize, offset: usize) {
+        let batch = offset / FIELDS_PER_FLUSH;
+        if batch < 255 {
+            self.emit(ByteCode::SetList(table as u8, n as u8, batch as u8));
+        } else {
+            self.emit(ByteCode::SetList(table as u8, n as u8, 255));
+            self.emit(ByteCode::ExtraArg(batch as u32));
+        }
+    }
+
+    /// 表达式是否会产生多个值（函数调用或 ...）
+    fn discharge_multi_check(&self, desc: &ExpDesc) -> bool {
+        matches!(desc, ExpDesc::Call(..) | ExpDesc::VarArgs)
+    }
+
+    /// 把表达式放入新分配的寄存器（sp），返回该寄存器
+    fn discharge_top(&mut self, desc: ExpDesc) -> usize {
+        let dst = self.sp;
+        self.discharge(dst, desc);
+        dst
+    }
+
+    /// 若表达式已在某个寄存器中则直接返回该寄存器，否则放入新寄存器
+    fn discharge_any(&mut self, desc: ExpDesc) -> usize {
+        match desc {
+            ExpDesc::Local(i) => i,
+            desc => self.discharge_top(desc),
+        }
+    }
+
+    /// 生成把表达式的值放入寄存器 dst 的字节码，并保证 sp 在 dst 之上
+    fn discharge(&mut self, dst: usize, desc: ExpDesc) {
+        let code = match desc {
+            ExpDesc::Nil => ByteCode::LoadNil(dst as u8),
+            ExpDesc::Boolean(b) => ByteCode::LoadBool(dst as u8, b),
+            ExpDesc::Integer(i) => {
+                // 小整数直接编码进 LoadInt，否则放入常数池
+                if let Ok(n) = i16::try_from(i) {
+                    ByteCode::LoadInt(dst as u8, n)
+                } else {
+                    ByteCode::LoadConst(dst as u8, self.add_const(Value::Integer(i)) as u32)
                 }
-                Token::String(s) => {
-                    ByteCode::SetGlobalConst(dst, self.add_const(Value::from(s)) as u8)
+            }
+            ExpDesc::Float(f) => ByteCode::LoadConst(dst as u8, self.add_const(Value::Float(f)) as u32),
+            ExpDesc::String(s) => ByteCode::LoadConst(dst as u8, self.add_const(Value::from(s)) as u32),
+            ExpDesc::Local(src) => {
+                if src == dst {
+                    self.set_sp(self.sp.max(dst + 1));
+                    return;
                 }
-                //from variable
-                Token::Name(var) => {
-                    if let Some(i) = self.get_local(&var) {
-                        //local var
-                        ByteCode::SetGlobal(dst, i as u8)
-                    } else {
-                        //global var
-                        ByteCode::SetGlobalGlobal(dst, self.add_const(Value::from(var)) as u8)
-                    }
+                ByteCode::Move(dst as u8, src as u8)
+            }
+            ExpDesc::Upvalue(i) => ByteCode::GetUpvalue(dst as u8, i as u8),
+            ExpDesc::Global(name) => ByteCode::GetGlobal(dst as u8, name as u32),
+            ExpDesc::Index(t, k) => ByteCode::GetTable(dst as u8, t as u8, k as u8),
+            ExpDesc::IndexInt(t, n) => ByteCode::GetInt(dst as u8, t as u8, n as u8),
+            ExpDesc::IndexField(t, k) => ByteCode::GetField(dst as u8, t as u8, k as u8),
+            ExpDesc::Call(ifunc, narg_plus) => {
+                self.emit(ByteCode::Call(ifunc as u8, narg_plus as u8, 2));
+                if ifunc == dst {
+                    self.set_sp(self.sp.max(dst + 1));
+                    return;
                 }
-                _ => panic!("unexpected token"),
-            };
-            self.byte_codes.push(code);
+                ByteCode::Move(dst as u8, ifunc as u8)
+            }
+            ExpDesc::VarArgs => ByteCode::VarArgs(dst as u8, 2),
+            ExpDesc::Function(i) => ByteCode::Closure(dst as u8, i as u32),
+        };
+        let pc = self.emit(code);
+        // 创建闭包的字节码在函数体生成完后才生成，行号记为函数定义所在行
+        if let ByteCode::Closure(_, i) = code {
+            self.fp.lineinfo[pc] = self.fp.protos[i as usize].linedefined;
         }
+        self.set_sp(self.sp.max(dst + 1));
+    }
+}
+
+/// 常量表达式的值，不是常量时返回 None
+fn const_value(desc: &ExpDesc) -> Option<Value> {
+    match desc {
+        ExpDesc::Nil => Some(Value::Nil),
+        ExpDesc::Boolean(b) => Some(Value::Boolean(*b)),
+        ExpDesc::Integer(i) => Some(Value::Integer(*i)),
+        ExpDesc::Float(f) => Some(Value::Float(*f)),
+        ExpDesc::String(s) => Some(Value::from(&s[..])),
+        _ => None,
+    }
+}
+
+/// 由常量值构造表达式，const_value 的逆过程
+fn const_exp(v: Value) -> ExpDesc {
+    match v {
+        Value::Nil => ExpDesc::Nil,
+        Value::Boolean(b) => ExpDesc::Boolean(b),
+        Value::Integer(i) => ExpDesc::Integer(i),
+        Value::Float(f) => ExpDesc::Float(f),
+        v => ExpDesc::String(v.as_bytes().unwrap().to_vec()),
     }
 }
@@ -0,0 +1,288 @@
+// 插桩式性能分析器
+// 启用后执行循环每执行一条指令调用一次 Profiler::tick：
+// - 完整模式：按调用栈（Lua 函数组成的路径）与源代码行统计指令数与墙钟时间，
+//   两条指令之间经过的时间记给前一条指令，因此内置函数的耗时算在调用它的那一行
+// - 操作码模式：只统计各操作码的执行次数，开销很小
+// 调用栈只包含 Lua 函数；由于每个栈帧至少执行一条指令，每次 tick 时与虚拟机的栈帧比较即可跟踪调用与返回
+// 结果可以输出为 flamegraph 工具使用的折叠栈格式：每行 "帧1;帧2;...;文件:行 权重"
+
+use crate::bytecode::Instruction;
+use crate::parse::FuncProto;
+use crate::sync::Rc;
+use crate::value::LuaClosure;
+use crate::vm::CallFrame;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// 分析模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileMode {
+    /// 按调用栈与行统计指令数与时间，同时统计操作码
+    Full,
+    /// 只统计操作码
+    Opcodes,
+}
+
+/// 折叠栈输出的权重
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weight {
+    /// 执行的指令数
+    Instructions,
+    /// 墙钟时间（微秒）
+    Time,
+}
+
+/// 一项统计：执行的指令数与耗时
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stat {
+    pub instructions: u64,
+    pub time: Duration,
+}
+
+impl Stat {
+    fn add(&mut self, other: &Stat) {
+        self.instructions += other.instructions;
+        self.time += other.time;
+    }
+
+    fn weight(&self, weight: Weight) -> u64 {
+        match weight {
+            Weight::Instructions => self.instructions,
+            Weight::Time => self.time.as_micros() as u64,
+        }
+    }
+}
+
+/// 调用树的节点：从主函数到某个函数的一条调用路径
+struct Node {
+    proto: Rc<FuncProto>,
+    parent: Option<usize>,
+    children: HashMap<*const FuncProto, usize>,
+    /// 该路径上在各行执行的指令数与时间
+    lines: HashMap<usize, Stat>,
+}
+
+/// 执行中的分析器，由 ExeState 持有
+pub(crate) struct Profiler {
+    mode: ProfileMode,
+    /// 各操作码的执行次数与一条样本指令（用于取名字）
+    opcodes: Vec<(u64, Option<Instruction>)>,
+    nodes: Vec<Node>,
+    roots: HashMap<*const FuncProto, usize>,
+    /// 与虚拟机栈帧对应的 (闭包地址, 节点)
+    stack: Vec<(*const LuaClosure, usize)>,
+    /// 上一条指令所在的 (节点, 行) 与执行时刻，之后经过的时间记给它
+    current: Option<(usize, usize)>,
+    last: Instant,
+}
+
+// 指针只用作标识，节点持有对应的 FuncProto 使其不被释放
+#[cfg(feature = "send")]
+unsafe impl Send for Profiler {}
+#[cfg(feature = "send")]
+unsafe impl Sync for Profiler {}
+
+impl Profiler {
+    pub(crate) fn new(mode: ProfileMode) -> Self {
+        Profiler {
+            mode,
+            opcodes: vec![(0, None); 128],
+            nodes: Vec::new(),
+            roots: HashMap::new(),
+            stack: Vec::new(),
+            current: None,
+            last: Instant::now(),
+        }
+    }
+
+    /// 即将执行 frames 最后一个栈帧的第 pc 条指令
+    pub(crate) fn tick(&mut self, frames: &[CallFrame], pc: usize) {
+        let top = &frames.last().unwrap().closure;
+        let code = top.proto.byte_codes[pc];
+        let op = &mut self.opcodes[code.op() as usize];
+        op.0 += 1;
+        op.1 = Some(code);
+        if self.mode == ProfileMode::Opcodes {
+            return;
+        }
+        let now = Instant::now();
+        self.flush(now);
+
+        // 弹出已经返回的栈帧，再压入新调用的
+        self.stack.truncate(frames.len());
+        while let Some(&(p, _)) = self.stack.last() {
+            if p == Rc::as_ptr(&frames[self.stack.len() - 1].closure) {
+                break;
+            }
+            self.stack.pop();
+        }
+        for frame in &frames[self.stack.len()..] {
+            let parent = self.stack.last().map(|&(_, n)| n);
+            let node = self.child(parent, &frame.closure.proto);
+            self.stack.push((Rc::as_ptr(&frame.closure), node));
+        }
+
+        let node = self.stack.last().unwrap().1;
+        let line = top.proto.lineinfo[pc];
+        self.nodes[node].lines.entry(line).or_default().instructions += 1;
+        self.current = Some((node, line));
+    }
+
+    /// 调用树中 parent 调用 proto 的节点，没有时新建
+    fn child(&mut self, parent: Option<usize>, proto: &Rc<FuncProto>) -> usize {
+        let key = Rc::as_ptr(proto);
+        let n = self.nodes.len();
+        let children = match parent {
+            Some(p) => &mut self.nodes[p].children,
+            None => &mut self.roots,
+        };
+        if let Some(&n) = children.get(&key) {
+            return n;
+        }
+        children.insert(key, n);
+        self.nodes.push(Node {
+            proto: proto.clone(),
+            parent,
+            children: HashMap::new(),
+            lines: HashMap::new(),
+        });
+        n
+    }
+
+    /// 把上一条指令之后经过的时间记给它
+    fn flush(&mut self, now: Instant) {
+        if let Some((node, line)) = self.current.take() {
+            let stat = self.nodes[node].lines.entry(line).or_default();
+            stat.time += now - self.last;
+        }
+        self.last = now;
+    }
+
+    /// 执行回到宿主程序：之后到下一次执行之间的时间不计入
+    pub(crate) fn pause(&mut self) {
+        self.flush(Instant::now());
+    }
+
+    /// 结束分析，返回结果
+    pub(crate) fn finish(mut self) -> Profile {
+        self.pause();
+        let mut opcodes: Vec<_> = (self.opcodes.iter())
+            .filter_map(|&(n, code)| Some((opcode_name(code?), n)))
+            .collect();
+        opcodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let nodes = self
+            .nodes
+            .into_iter()
+            .map(|n| ProfileNode {
+                label: function_label(&n.proto),
+                source: n.proto.source.clone(),
+                parent: n.parent,
+                lines: n.lines,
+            })
+            .collect();
+        Profile { nodes, opcodes }
+    }
+}
+
+/// 操作码的名字：ByteCode 的变体名
+fn opcode_name(code: Instruction) -> String {
+    let name = format!("{:?}", code.decode());
+    name.split('(').next().unwrap().to_string()
+}
+
+/// 函数在报告中的名字，与 traceback 的写法一致
+fn function_label(proto: &FuncProto) -> String {
+    if proto.linedefined == 0 {
+        format!("main chunk <{}>", proto.source)
+    } else {
+        format!("function <{}:{}>", proto.source, proto.linedefined)
+    }
+}
+
+/// 分析结果中的调用路径
+struct ProfileNode {
+    label: String,
+    source: String,
+    parent: Option<usize>,
+    lines: HashMap<usize, Stat>,
+}
+
+/// 分析结果
+pub struct Profile {
+    nodes: Vec<ProfileNode>,
+    opcodes: Vec<(String, u64)>,
+}
+
+impl Profile {
+    /// 各操作码的执行次数，按次数从多到少排列
+    pub fn opcodes(&self) -> &[(String, u64)] {
+        &self.opcodes
+    }
+
+    /// 各函数自身（不含其调用的 Lua 函数）的统计，按耗时从多到少排列
+    pub fn functions(&self) -> Vec<(String, Stat)> {
+        let mut map: HashMap<&str, Stat> = HashMap::new();
+        for node in &self.nodes {
+            let stat = map.entry(&node.label).or_default();
+            node.lines.values().for_each(|s| stat.add(s));
+        }
+        sorted(map.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    /// 各源代码行的统计，键为 "文件:行"，按耗时从多到少排列
+    pub fn lines(&self) -> Vec<(String, Stat)> {
+        let mut map: HashMap<(&str, usize), Stat> = HashMap::new();
+        for node in &self.nodes {
+            for (&line, s) in &node.lines {
+                map.entry((&node.source, line)).or_default().add(s);
+            }
+        }
+        sorted(
+            map.into_iter()
+                .map(|((src, line), v)| (format!("{src}:{line}"), v))
+                .collect(),
+        )
+    }
+
+    /// 输出折叠栈格式，权重为 0 的行省略
+    pub fn write_folded<W: Write>(&self, w: &mut W, weight: Weight) -> io::Result<()> {
+        let mut lines = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let path = self.path(i);
+            for (&line, s) in &node.lines {
+                let n = s.weight(weight);
+                if n > 0 {
+                    lines.push((format!("{path};{}:{line}", node.source), n));
+                }
+            }
+        }
+        lines.sort();
+        for (stack, n) in lines {
+            writeln!(w, "{stack} {n}")?;
+        }
+        Ok(())
+    }
+
+    /// 从主函数到节点 i 的各帧名字，以 ';' 分隔
+    fn path(&self, i: usize) -> String {
+        let mut labels = Vec::new();
+        let mut node = Some(i);
+        while let Some(i) = node {
+            labels.push(self.nodes[i].label.replace(';', ":"));
+            node = self.nodes[i].parent;
+        }
+        labels.reverse();
+        labels.join(";")
+    }
+}
+
+/// 按耗时、指令数从多到少排列，相同时按名字
+fn sorted(mut v: Vec<(String, Stat)>) -> Vec<(String, Stat)> {
+    v.sort_by(|a, b| {
+        (b.1.time, b.1.instructions)
+            .cmp(&(a.1.time, a.1.instructions))
+            .then(a.0.cmp(&b.0))
+    });
+    v
+}
@@ -0,0 +1,197 @@
+// 共享所有权与内部可变性的实现选择
+// 默认使用 std::rc::Rc 与 std::cell::RefCell；启用 send 特性时换成 Arc 与基于原子计数的 RefCell，
+// 值与解释器状态因而是 Send 的，可以在线程之间移动（每个 ExeState 仍只由一个线程使用，不加锁）
+// 两种实现的接口相同：借用冲突时 borrow/borrow_mut 都会 panic，try_borrow/try_borrow_mut 返回错误
+
+#[cfg(not(feature = "send"))]
+pub use std::cell::{Ref, RefCell, RefMut};
+#[cfg(not(feature = "send"))]
+pub use std::rc::Rc;
+
+#[cfg(feature = "send")]
+pub use atomic::{Ref, RefCell, RefMut};
+#[cfg(feature = "send")]
+pub use std::sync::Arc as Rc;
+
+/// 放入值中的 Rust 对象（闭包、用户数据）须满足的约束：启用 send 特性时为 Send + Sync，否则没有约束
+#[cfg(feature = "send")]
+pub trait MaybeSend: Send + Sync {}
+#[cfg(feature = "send")]
+impl<T: Send + Sync + ?Sized> MaybeSend for T {}
+
+#[cfg(not(feature = "send"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "send"))]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// 异步函数返回的 Future 须满足的约束：启用 send 特性时为 Send，否则没有约束
+#[cfg(feature = "send")]
+pub trait MaybeSendFuture: Send {}
+#[cfg(feature = "send")]
+impl<T: Send + ?Sized> MaybeSendFuture for T {}
+
+#[cfg(not(feature = "send"))]
+pub trait MaybeSendFuture {}
+#[cfg(not(feature = "send"))]
+impl<T: ?Sized> MaybeSendFuture for T {}
+
+#[cfg(feature = "send")]
+mod atomic {
+    use std::cell::UnsafeCell;
+    use std::marker::PhantomData;
+    use std::ops::{Deref, DerefMut};
+    use std::ptr::NonNull;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 可变借用时的借用计数
+    const WRITING: usize = usize::MAX;
+
+    /// 与 std::cell::RefCell 相同的借用规则，借用计数是原子的，因此可以跨线程共享
+    /// 值放在最后一个字段，使 RefCell<T> 可以转换为 RefCell<dyn Trait>
+    pub struct RefCell<T: ?Sized> {
+        borrow: AtomicUsize,
+        value: UnsafeCell<T>,
+    }
+
+    // SAFETY: 移到另一个线程的 RefCell 独占其中的值，与移动 T 本身相同，只要求 T: Send
+    unsafe impl<T: ?Sized + Send> Send for RefCell<T> {}
+    // SAFETY: 借用计数的修改都是原子的，同一时刻只有一个可变借用或若干共享借用（与 RwLock 相同），
+    // 通过 &RefCell 可以在其他线程得到 &T（要求 T: Sync）或 &mut T（相当于移动，要求 T: Send）
+    unsafe impl<T: ?Sized + Send + Sync> Sync for RefCell<T> {}
+
+    /// try_borrow/try_borrow_mut 遇到冲突的借用
+    #[derive(Debug)]
+    pub struct BorrowError;
+
+    impl<T> RefCell<T> {
+        pub const fn new(value: T) -> Self {
+            RefCell {
+                borrow: AtomicUsize::new(0),
+                value: UnsafeCell::new(value),
+            }
+        }
+    }
+
+    impl<T: ?Sized> RefCell<T> {
+        pub fn borrow(&self) -> Ref<'_, T> {
+            self.try_borrow().expect("already mutably borrowed")
+        }
+
+        pub fn borrow_mut(&self) -> RefMut<'_, T> {
+            self.try_borrow_mut().expect("already borrowed")
+        }
+
+        pub fn try_borrow(&self) -> Result<Ref<'_, T>, BorrowError> {
+            let mut n = self.borrow.load(Ordering::Relaxed);
+            loop {
+                if n >= WRITING - 1 {
+                    return Err(BorrowError);
+                }
+                match self.borrow.compare_exchange_weak(n, n + 1, Ordering::Acquire, Ordering::Relaxed) {
+                    Ok(_) => break,
+                    Err(cur) => n = cur,
+                }
+            }
+            Ok(Ref {
+                // SAFETY: 借用计数已加一且不是 WRITING，Ref 释放前不会有可变借用；
+                // Acquire 保证看到上一个可变借用释放前的写入
+                value: unsafe { &*self.value.get() },
+                borrow: &self.borrow,
+            })
+        }
+
+        pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowError> {
+            self.borrow
+                .compare_exchange(0, WRITING, Ordering::Acquire, Ordering::Relaxed)
+                .map_err(|_| BorrowError)?;
+            Ok(RefMut {
+                // SAFETY: UnsafeCell::get 返回的指针不为空；借用计数已从 0 改为 WRITING，RefMut 释放前没有其他借用
+                value: unsafe { NonNull::new_unchecked(self.value.get()) },
+                borrow: &self.borrow,
+                marker: PhantomData,
+            })
+        }
+    }
+
+    /// 共享借用
+    pub struct Ref<'b, T: ?Sized> {
+        value: &'b T,
+        borrow: &'b AtomicUsize,
+    }
+
+    impl<'b, T: ?Sized> Ref<'b, T> {
+        pub fn filter_map<U: ?Sized>(orig: Self, f: impl FnOnce(&T) -> Option<&U>) -> Result<Ref<'b, U>, Self> {
+            match f(orig.value) {
+                Some(value) => {
+                    let borrow = orig.borrow;
+                    std::mem::forget(orig);
+                    Ok(Ref { value, borrow })
+                }
+                None => Err(orig),
+            }
+        }
+    }
+
+    impl<T: ?Sized> Deref for Ref<'_, T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            self.value
+        }
+    }
+
+    impl<T: ?Sized> Drop for Ref<'_, T> {
+        fn drop(&mut self) {
+            self.borrow.fetch_sub(1, Ordering::Release);
+        }
+    }
+
+    /// 可变借用
+    pub struct RefMut<'b, T: ?Sized> {
+        value: NonNull<T>,
+        borrow: &'b AtomicUsize,
+        marker: PhantomData<&'b mut T>,
+    }
+
+    impl<'b, T: ?Sized> RefMut<'b, T> {
+        pub fn filter_map<U: ?Sized>(
+            mut orig: Self,
+            f: impl FnOnce(&mut T) -> Option<&mut U>,
+        ) -> Result<RefMut<'b, U>, Self> {
+            // SAFETY: orig 独占其中的值，f 返回的引用随 orig 的借用计数一起转移到新的 RefMut
+            match f(unsafe { orig.value.as_mut() }) {
+                Some(value) => {
+                    let value = NonNull::from(value);
+                    let borrow = orig.borrow;
+                    std::mem::forget(orig);
+                    Ok(RefMut {
+                        value,
+                        borrow,
+                        marker: PhantomData,
+                    })
+                }
+                None => Err(orig),
+            }
+        }
+    }
+
+    impl<T: ?Sized> Deref for RefMut<'_, T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            // SAFETY: RefMut 存在期间借用计数为 WRITING，指针指向的值没有其他借用
+            unsafe { self.value.as_ref() }
+        }
+    }
+
+    impl<T: ?Sized> DerefMut for RefMut<'_, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            // SAFETY: 同上，且 &mut self 保证同一个 RefMut 不会同时给出两个引用
+            unsafe { self.value.as_mut() }
+        }
+    }
+
+    impl<T: ?Sized> Drop for RefMut<'_, T> {
+        fn drop(&mut self) {
+            self.borrow.store(0, Ordering::Release);
+        }
+    }
+}
@@ -0,0 +1,464 @@
+// Lua 表：移植自 Lua 5.4 的 ltable.c
+// 表分为数组部分与哈希部分：
+// - 数组部分保存键为 1..=n 的整数键，可以含有 nil
+// - 哈希部分是大小为 2 的幂的节点数组，开放寻址：冲突的键用 next（相对偏移）链接成链，
+//   链上的节点都在节点数组之内，不另外分配；键不在其主位置（main position）时可以被挤走（Brent 方法）
+// 哈希部分没有空闲节点时重新计算大小（rehash）：统计所有整数键，取使数组部分超过一半被使用的最大的 2 的幂
+// 作为数组部分的大小，其余的键放入哈希部分
+// 删除键只是把值置为 nil，键留在节点中（死键），因此遍历中给已有的键赋值（包括 nil）不影响 next
+
+use crate::sync::{Rc, RefCell};
+use crate::value::{float_to_integer, Value};
+
+/// 数组部分大小的上限为 2^MAX_ABITS
+const MAX_ABITS: usize = 31;
+
+/// 哈希部分的节点；key 为 nil 表示空闲节点，val 为 nil 而 key 不为 nil 的是死键
+struct Node {
+    key: Value,
+    val: Value,
+    /// 同一条链上下一个节点的相对偏移，0 表示链尾
+    next: i32,
+}
+
+impl Node {
+    fn empty() -> Self {
+        Node {
+            key: Value::Nil,
+            val: Value::Nil,
+            next: 0,
+        }
+    }
+}
+
+/// Lua 表：数组部分、哈希部分与元表
+pub struct Table {
+    /// 数组部分：下标 i 保存键 i+1 的值
+    pub(crate) array: Vec<Value>,
+    /// 哈希部分：大小为 0 或 2 的幂
+    node: Vec<Node>,
+    /// 查找空闲节点的位置，从尾部向前移动
+    lastfree: usize,
+    pub metatable: Option<Rc<RefCell<Table>>>,
+}
+
+impl Table {
+    /// 创建新表，数组部分与哈希部分按给出的大小预先分配（NewTable 的大小提示）
+    pub fn new(narray: usize, nmap: usize) -> Self {
+        let nsize = if nmap == 0 { 0 } else { nmap.next_power_of_two() };
+        Table {
+            array: vec![Value::Nil; narray],
+            node: (0..nsize).map(|_| Node::empty()).collect(),
+            lastfree: nsize,
+            metatable: None,
+        }
+    }
+
+    /// 按键读取，键不存在时返回 nil；值为整数的浮点数键与对应的整数键相同
+    pub fn get(&self, key: &Value) -> Value {
+        match key {
+            Value::Integer(i) => self.get_int(*i),
+            Value::Float(f) => match float_to_integer(*f) {
+                Some(i) => self.get_int(i),
+                None => self.get_node(key),
+            },
+            Value::Nil => Value::Nil,
+            _ => self.get_node(key),
+        }
+    }
+
+    /// 按整数键读取
+    pub fn get_int(&self, i: i64) -> Value {
+        match self.array.get((i as usize).wrapping_sub(1)) {
+            Some(v) => v.clone(),
+            None => self.get_node(&Value::Integer(i)),
+        }
+    }
+
+    /// 按字符串键读取，方便库函数使用
+    pub fn get_str(&self, key: &str) -> Value {
+        self.get(&Value::from(key))
+    }
+
+    /// 按字符串键读取，hash 为预先算好的 str_hash(key)（如常量的哈希，见 FuncProto::const_hashes），不再计算
+    pub(crate) fn get_str_hashed(&self, key: &Value, hash: u32) -> Value {
+        match self.find_from(key, hash as usize & self.node.len().wrapping_sub(1)) {
+            Some(n) => self.node[n].val.clone(),
+            None => Value::Nil,
+        }
+    }
+
+    /// 按字符串键写入，hash 同 get_str_hashed；只有插入新键时才重新计算哈希
+    pub(crate) fn set_str_hashed(&mut self, key: Value, hash: u32, value: Value) {
+        match self.find_from(&key, hash as usize & self.node.len().wrapping_sub(1)) {
+            Some(n) => self.node[n].val = value,
+            None if value == Value::Nil => (),
+            None => self.new_key(key, value),
+        }
+    }
+
+    /// 按键写入，赋值 nil 即删除；键为 nil 或 NaN 时忽略（由调用者报错）
+    pub fn set(&mut self, key: Value, value: Value) {
+        let key = match key {
+            Value::Float(f) => match float_to_integer(f) {
+                Some(i) => return self.set_int(i, value),
+                None if f.is_nan() => return,
+                None => key,
+            },
+            Value::Integer(i) => return self.set_int(i, value),
+            Value::Nil => return,
+            key => key,
+        };
+        self.set_node(key, value);
+    }
+
+    /// 按整数键写入
+    pub fn set_int(&mut self, i: i64, value: Value) {
+        match self.array.get_mut((i as usize).wrapping_sub(1)) {
+            Some(slot) => *slot = value,
+            None => self.set_node(Value::Integer(i), value),
+        }
+    }
+
+    /// 按字符串键写入，方便库函数注册
+    pub fn set_str(&mut self, key: &str, value: Value) {
+        self.set(Value::from(key), value);
+    }
+
+    /// 表构造器的批量写入（SetList）：从数组下标 offset+1 开始依次写入，数组部分不够时先扩大
+    pub fn set_list(&mut self, offset: usize, values: Vec<Value>) {
+        let last = offset + values.len();
+        if last > self.array.len() {
+            self.resize(last, self.node.len());
+        }
+        for (slot, v) in self.array[offset..last].iter_mut().zip(values) {
+            *slot = v;
+        }
+    }
+
+    /// 遍历：返回 key 之后的下一个键值对，key 为 nil 时返回第一个，遍历结束返回 Ok(None)
+    /// 先按顺序遍历数组部分，再按节点顺序遍历哈希部分；key 不在表中时返回错误信息
+    pub fn next(&self, key: &Value) -> Result<Option<(Value, Value)>, &'static str> {
+        let start = self.traverse_index(key)?;
+        for (i, v) in self.array.iter().enumerate().skip(start) {
+            if *v != Value::Nil {
+                return Ok(Some((Value::Integer(i as i64 + 1), v.clone())));
+            }
+        }
+        let from = start.saturating_sub(self.array.len());
+        Ok(self.node[from..]
+            .iter()
+            .find(|n| n.val != Value::Nil)
+            .map(|n| (n.key.clone(), n.val.clone())))
+    }
+
+    /// 取长度（边界）：返回某个 n，使 t[n] 非 nil 且 t[n+1] 为 nil（表为空时为 0）
+    pub fn border(&self) -> usize {
+        let n = self.array.len();
+        if n > 0 && self.array[n - 1] == Value::Nil {
+            // 数组部分末尾是 nil：二分查找其中的边界
+            let (mut lo, mut hi) = (0, n);
+            while hi - lo > 1 {
+                let mid = (lo + hi) / 2;
+                if self.array[mid - 1] == Value::Nil {
+                    hi = mid;
+                } else {
+                    lo = mid;
+                }
+            }
+            return lo;
+        }
+        if self.node.is_empty() || self.get_node(&Value::Integer(n as i64 + 1)) == Value::Nil {
+            return n;
+        }
+        self.hash_search(n + 1)
+    }
+
+    /// 在哈希部分中查找边界，已知 t[j] 非 nil：键成倍增大直到遇到 nil，再二分查找
+    fn hash_search(&self, j: usize) -> usize {
+        let (mut i, mut j) = (j, j * 2);
+        while self.get_int(j as i64) != Value::Nil {
+            i = j;
+            if j > i64::MAX as usize / 2 {
+                // 溢出之前退回到线性查找（只有刻意构造的表才会走到这里）
+                let mut k = 1;
+                while self.get_int(k) != Value::Nil {
+                    k += 1;
+                }
+                return k as usize - 1;
+            }
+            j *= 2;
+        }
+        while j - i > 1 {
+            let mid = (i + j) / 2;
+            if self.get_int(mid as i64) == Value::Nil {
+                j = mid;
+            } else {
+                i = mid;
+            }
+        }
+        i
+    }
+
+    /// 数组部分与哈希部分的大小（节点数），用于测试与基准
+    pub fn sizes(&self) -> (usize, usize) {
+        (self.array.len(), self.node.len())
+    }
+
+    /// key 之后开始遍历的位置：数组部分为下标，哈希部分为数组部分大小加节点下标加 1
+    fn traverse_index(&self, key: &Value) -> Result<usize, &'static str> {
+        let key = match key {
+            Value::Nil => return Ok(0),
+            Value::Float(f) => match float_to_integer(*f) {
+                Some(i) => Value::Integer(i),
+                None => key.clone(),
+            },
+            _ => key.clone(),
+        };
+        if let Value::Integer(i) = key {
+            if i >= 1 && (i as usize) <= self.array.len() {
+                return Ok(i as usize);
+            }
+        }
+        match self.find(&key) {
+            Some(n) => Ok(self.array.len() + n + 1),
+            None => Err("invalid key to 'next'"),
+        }
+    }
+
+    /// 在哈希部分中读取
+    fn get_node(&self, key: &Value) -> Value {
+        match self.find(key) {
+            Some(n) => self.node[n].val.clone(),
+            None => Value::Nil,
+        }
+    }
+
+    /// 在哈希部分中写入，键不存在时插入新键
+    fn set_node(&mut self, key: Value, value: Value) {
+        match self.find(&key) {
+            Some(n) => self.node[n].val = value,
+            None if value == Value::Nil => (),
+            None => self.new_key(key, value),
+        }
+    }
+
+    /// 在哈希部分中查找键（包括死键），返回节点下标
+    fn find(&self, key: &Value) -> Option<usize> {
+        if self.node.is_empty() {
+            return None;
+        }
+        self.find_from(key, self.main_position(key))
+    }
+
+    /// 从主位置 n 开始沿链查找键；哈希部分为空时返回 None
+    fn find_from(&self, key: &Value, mut n: usize) -> Option<usize> {
+        if self.node.is_empty() {
+            return None;
+        }
+        loop {
+            let node = &self.node[n];
+            if node.key == *key {
+                return Some(n);
+            }
+            if node.next == 0 {
+                return None;
+            }
+            n = (n as isize + node.next as isize) as usize;
+        }
+    }
+
+    /// 键在哈希部分中的主位置：字符串与布尔值对大小取模（按位与），其余对小于大小的奇数取模，
+    /// 避免指针与整数的低位规律造成的聚集
+    fn main_position(&self, key: &Value) -> usize {
+        let mask = self.node.len() - 1;
+        let modulo = |h: u64| (h % (mask as u64 | 1)) as usize;
+        match key {
+            Value::Integer(i) => modulo(*i as u64),
+            Value::Float(f) => {
+                let bits = f.to_bits();
+                modulo(bits ^ (bits >> 32))
+            }
+            Value::ShortStr(..) | Value::MidStr(_) | Value::LongStr(_) => {
+                str_hash(key.as_bytes().unwrap()) as usize & mask
+            }
+            Value::Boolean(b) => *b as usize & mask,
+            Value::Function(f) => modulo(*f as usize as u64),
+            Value::RustClosure(c) => modulo(Rc::as_ptr(c) as *const () as usize as u64),
+            Value::LuaFunction(c) => modulo(Rc::as_ptr(c) as usize as u64),
+            Value::Table(t) => modulo(Rc::as_ptr(t) as usize as u64),
+            Value::UserData(u) => modulo(Rc::as_ptr(u) as *const () as usize as u64),
+            Value::LightUserData(p) => modulo(*p as usize as u64),
+            Value::Nil => unreachable!("nil key"),
+        }
+    }
+
+    /// 从尾部向前找一个空闲节点
+    fn free_position(&mut self) -> Option<usize> {
+        while self.lastfree > 0 {
+            self.lastfree -= 1;
+            if self.node[self.lastfree].key == Value::Nil {
+                return Some(self.lastfree);
+            }
+        }
+        None
+    }
+
+    /// 插入哈希部分中不存在的键（luaH_newkey）
+    /// 主位置被占用时：占用者不在它自己的主位置上，就把它挪到空闲节点，新键放在主位置；
+    /// 否则新键放到空闲节点，接在主位置的链上。没有空闲节点时 rehash 后重新插入
+    fn new_key(&mut self, key: Value, value: Value) {
+        if self.node.is_empty() {
+            self.rehash(&key);
+            return self.set(key, value);
+        }
+        let mut mp = self.main_position(&key);
+        if self.node[mp].val != Value::Nil {
+            let Some(f) = self.free_position() else {
+                self.rehash(&key);
+                return self.set(key, value);
+            };
+            let mut other = self.main_position(&self.node[mp].key);
+            if other != mp {
+                // 找到链上指向 mp 的前一个节点，把占用者挪到空闲节点
+                while other as isize + self.node[other].next as isize != mp as isize {
+                    other = (other as isize + self.node[other].next as isize) as usize;
+                }
+                self.node[other].next = (f as isize - other as isize) as i32;
+                let moved = std::mem::replace(&mut self.node[mp], Node::empty());
+                self.node[f] = moved;
+                if self.node[f].next != 0 {
+                    self.node[f].next += (mp as isize - f as isize) as i32;
+                }
+            } else {
+                // 占用者在自己的主位置上：新键放到空闲节点，插在 mp 之后
+                if self.node[mp].next != 0 {
+                    self.node[f].next = (mp as isize + self.node[mp].next as isize - f as isize) as i32;
+                }
+                self.node[mp].next = (f as isize - mp as isize) as i32;
+                mp = f;
+            }
+        }
+        self.node[mp].key = key;
+        self.node[mp].val = value;
+    }
+
+    /// 重新计算数组部分与哈希部分的大小，ek 是将要插入的键
+    fn rehash(&mut self, ek: &Value) {
+        // nums[i]：2^(i-1) < k <= 2^i 的整数键 k 的个数
+        let mut nums = [0usize; MAX_ABITS + 1];
+        let mut na = 0;
+        for (i, v) in self.array.iter().enumerate() {
+            if *v != Value::Nil {
+                nums[ceil_log2(i + 1)] += 1;
+                na += 1;
+            }
+        }
+        let mut total = na;
+        for n in &self.node {
+            if n.val != Value::Nil {
+                na += count_int(&n.key, &mut nums);
+                total += 1;
+            }
+        }
+        na += count_int(ek, &mut nums);
+        total += 1;
+        let (asize, na) = compute_sizes(&nums, na);
+        self.resize(asize, total - na);
+    }
+
+    /// 按新的大小重建表：数组部分缩小时多出的项移到哈希部分，原有的节点重新插入
+    fn resize(&mut self, asize: usize, hsize: usize) {
+        let nsize = if hsize == 0 { 0 } else { hsize.next_power_of_two() };
+        let old = std::mem::replace(&mut self.node, (0..nsize).map(|_| Node::empty()).collect());
+        self.lastfree = nsize;
+        if asize < self.array.len() {
+            let tail = self.array.split_off(asize);
+            for (i, v) in tail.into_iter().enumerate() {
+                if v != Value::Nil {
+                    self.set_node(Value::Integer((asize + i + 1) as i64), v);
+                }
+            }
+        } else {
+            self.array.resize(asize, Value::Nil);
+        }
+        for n in old {
+            if n.val != Value::Nil {
+                self.set(n.key, n.val);
+            }
+        }
+    }
+}
+
+/// 逐层递归的析构在释放嵌套很深的表（如重复很多次 t = {t}）时会耗尽栈：
+/// 这里把只被这张表引用的子表移出，放到工作表中逐个释放，每张子表的子表同样移出
+impl Drop for Table {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        self.take_children(&mut pending);
+        while let Some(t) = pending.pop() {
+            t.borrow_mut().take_children(&mut pending);
+        }
+    }
+}
+
+impl Table {
+    /// 把只被这张表引用的子表（值、键与元表）移到 out 中
+    fn take_children(&mut self, out: &mut Vec<Rc<RefCell<Table>>>) {
+        let values = self.array.iter_mut().chain(self.node.iter_mut().flat_map(|n| [&mut n.key, &mut n.val]));
+        for v in values {
+            if matches!(v, Value::Table(t) if Rc::strong_count(t) == 1) {
+                if let Value::Table(t) = std::mem::replace(v, Value::Nil) {
+                    out.push(t);
+                }
+            }
+        }
+        if let Some(m) = self.metatable.take_if(|m| Rc::strong_count(m) == 1) {
+            out.push(m);
+        }
+    }
+}
+
+/// 不小于 log2(x) 的最小整数，x >= 1
+fn ceil_log2(x: usize) -> usize {
+    (usize::BITS - (x - 1).leading_zeros()) as usize
+}
+
+/// 键是可以放入数组部分的整数时计入 nums，返回是否计入
+fn count_int(key: &Value, nums: &mut [usize]) -> usize {
+    match key {
+        Value::Integer(k) if *k >= 1 && *k <= 1 << MAX_ABITS => {
+            nums[ceil_log2(*k as usize)] += 1;
+            1
+        }
+        _ => 0,
+    }
+}
+
+/// 计算数组部分的最优大小：使 1..=n 中有超过一半的键存在的最大的 2 的幂 n
+/// na 为整数键的总数，返回 (数组部分大小, 将放入数组部分的键数)
+fn compute_sizes(nums: &[usize], na: usize) -> (usize, usize) {
+    let (mut a, mut optimal, mut nopt) = (0, 0, 0);
+    let mut twotoi = 1usize;
+    for &n in nums {
+        if na <= twotoi / 2 {
+            break;
+        }
+        a += n;
+        if a > twotoi / 2 {
+            optimal = twotoi;
+            nopt = a;
+        }
+        twotoi *= 2;
+    }
+    (optimal, nopt)
+}
+
+/// 字符串的哈希（luaS_hash），种子固定
+pub(crate) fn str_hash(bytes: &[u8]) -> u32 {
+    let mut h = 0x2545_f491 ^ bytes.len() as u32;
+    for &b in bytes.iter().rev() {
+        h ^= (h << 5).wrapping_add(h >> 2).wrapping_add(b as u32);
+    }
+    h
+}
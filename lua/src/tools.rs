@@ -0,0 +1,94 @@
+// lua fmt 与 lua lint 子命令
+// - lua fmt [--check] [file...]：就地格式化各文件；--check 只列出需要格式化的文件；没有文件时从标准输入读取，输出到标准输出
+// - lua lint [file...]：输出各文件的检查结果，格式为 file:line: message；没有文件时检查标准输入
+// - lua --dump-ast [file...]：输出各文件解析得到的语法树；没有文件时解析标准输入
+// 有文件需要格式化、有检查结果或有语法错误时以状态码 1 退出
+
+use lua::ast;
+use lua::fmt;
+use lua::lint;
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::process;
+
+/// 读取各文件（没有文件时读取标准输入，名字为 stdin）并依次处理，任意一个失败时以状态码 1 退出
+fn for_each_source(files: &[String], mut f: impl FnMut(&str, Vec<u8>) -> bool) {
+    let mut ok = true;
+    if files.is_empty() {
+        let mut source = Vec::new();
+        if let Err(e) = io::stdin().read_to_end(&mut source) {
+            eprintln!("lua: cannot read stdin: {e}");
+            process::exit(1);
+        }
+        ok = f("stdin", source);
+    }
+    for file in files {
+        match fs::read(file) {
+            Ok(source) => ok &= f(file, source),
+            Err(e) => {
+                eprintln!("lua: cannot open {file}: {e}");
+                ok = false;
+            }
+        }
+    }
+    if !ok {
+        process::exit(1);
+    }
+}
+
+pub fn fmt(args: &[String]) {
+    let check = args.first().is_some_and(|a| a == "--check");
+    let files = if check { &args[1..] } else { args };
+    let to_stdout = files.is_empty();
+    for_each_source(files, |name, source| {
+        let formatted = match fmt::format(&source) {
+            Ok(out) => out,
+            Err(e) => {
+                eprintln!("lua: {name}:{e}");
+                return false;
+            }
+        };
+        if check {
+            if formatted != source {
+                println!("{name}: not formatted");
+                return false;
+            }
+        } else if to_stdout {
+            io::stdout().write_all(&formatted).unwrap();
+        } else if formatted != source {
+            if let Err(e) = fs::write(name, formatted) {
+                eprintln!("lua: cannot write {name}: {e}");
+                return false;
+            }
+        }
+        true
+    });
+}
+
+pub fn lint(args: &[String]) {
+    for_each_source(args, |name, source| match lint::lint(&source) {
+        Ok(warnings) => {
+            for w in &warnings {
+                println!("{name}:{w}");
+            }
+            warnings.is_empty()
+        }
+        Err(e) => {
+            eprintln!("lua: {name}:{e}");
+            false
+        }
+    });
+}
+
+pub fn dump_ast(args: &[String]) {
+    for_each_source(args, |name, source| match ast::parse(Cursor::new(source)) {
+        Ok(block) => {
+            println!("{block:#?}");
+            true
+        }
+        Err(e) => {
+            eprintln!("lua: {name}:{e}");
+            false
+        }
+    });
+}
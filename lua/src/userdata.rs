@@ -0,0 +1,111 @@
+// 把 Rust 类型作为用户数据（userdata）交给 Lua
+// 类型实现 UserData trait，在 add_methods 中声明方法与只读字段；
+// 同一类型的所有对象共享一张元表，由 ExeState 在首次创建该类型的用户数据时生成并缓存
+// 元表的 __index 先查方法表，再查字段读取函数，因此 Lua 中可以写 obj:method(...) 与 obj.field
+
+use crate::sync::{MaybeSend, Rc, RefCell};
+use crate::value::{AnyUserData, RustClosure, Table, Value};
+use crate::vm::{ExeState, LuaError};
+use std::any::Any;
+use std::collections::HashMap;
+
+/// 可以交给 Lua 的 Rust 类型
+pub trait UserData: Any + MaybeSend + Sized {
+    /// 类型名，用于元表的 __name 与参数错误信息，默认取 Rust 类型名的最后一段
+    fn name() -> &'static str {
+        std::any::type_name::<Self>().rsplit("::").next().unwrap()
+    }
+
+    /// 声明方法与字段，默认没有
+    fn add_methods(_methods: &mut UserDataMethods<Self>) {}
+}
+
+/// 字段读取函数
+#[cfg(not(feature = "send"))]
+type FieldGetter<T> = Box<dyn Fn(&T) -> Value>;
+#[cfg(feature = "send")]
+type FieldGetter<T> = Box<dyn Fn(&T) -> Value + Send + Sync>;
+
+/// UserData::add_methods 的参数，收集某个类型的方法与字段
+pub struct UserDataMethods<T> {
+    methods: Table,
+    getters: HashMap<String, FieldGetter<T>>,
+}
+
+impl<T: UserData> UserDataMethods<T> {
+    /// 添加方法：Lua 中以 obj:name(...) 调用
+    /// f 收到可变借用的对象本身，其余参数从第 2 个开始（state.arg(2)），返回值与内置函数一样压栈
+    pub fn add_method<F>(&mut self, name: &str, f: F)
+    where
+        F: Fn(&mut ExeState, &mut T) -> Result<i32, LuaError> + MaybeSend + 'static,
+    {
+        let fname = name.to_string();
+        let method = move |state: &mut ExeState| -> Result<i32, LuaError> {
+            let ud = state.check_userdata::<T>(1, &fname)?;
+            let mut data = ud
+                .borrow_mut::<T>()
+                .ok_or_else(|| state.error(format!("{} is already borrowed", T::name())))?;
+            f(state, &mut data)
+        };
+        let method: RustClosure = RefCell::new(Box::new(method));
+        self.methods.set_str(name, Value::RustClosure(Rc::new(method)));
+    }
+
+    /// 添加只读字段：Lua 中读取 obj.name 时调用 f 计算字段值
+    pub fn add_field_getter<F>(&mut self, name: &str, f: F)
+    where
+        F: Fn(&T) -> Value + MaybeSend + 'static,
+    {
+        self.getters.insert(name.to_string(), Box::new(f));
+    }
+}
+
+/// 生成类型 T 的元表：__index 依次查方法、字段，都没有时为 nil
+pub(crate) fn metatable<T: UserData>() -> Rc<RefCell<Table>> {
+    let mut methods = UserDataMethods::<T> {
+        methods: Table::new(0, 0),
+        getters: HashMap::new(),
+    };
+    T::add_methods(&mut methods);
+    let UserDataMethods { methods, getters } = methods;
+
+    let index = move |state: &mut ExeState| -> Result<i32, LuaError> {
+        let key = state.arg(2);
+        let method = methods.get(&key);
+        if method != Value::Nil {
+            state.push(method);
+            return Ok(1);
+        }
+        let getter = key.as_str().and_then(|k| getters.get(k));
+        let value = match (state.arg(1), getter) {
+            (Value::UserData(ud), Some(getter)) => match ud.borrow::<T>() {
+                Some(data) => getter(&data),
+                None => return Err(state.error(format!("{} is already borrowed", T::name()))),
+            },
+            _ => Value::Nil,
+        };
+        state.push(value);
+        Ok(1)
+    };
+    let index: RustClosure = RefCell::new(Box::new(index));
+
+    let mut meta = Table::new(0, 2);
+    meta.set_str("__index", Value::RustClosure(Rc::new(index)));
+    meta.set_str("__name", Value::from(T::name()));
+    Rc::new(RefCell::new(meta))
+}
+
+impl ExeState {
+    /// 检查第 i 个参数是类型 T 的用户数据
+    pub fn check_userdata<T: UserData>(&self, i: usize, fname: &str) -> Result<Rc<AnyUserData>, LuaError> {
+        if let Value::UserData(ud) = self.arg(i) {
+            match ud.data.try_borrow() {
+                Ok(data) if data.is::<T>() => {}
+                Ok(_) => return Err(self.arg_type_error(i, fname, T::name())),
+                Err(_) => return Err(self.error(format!("{} is already borrowed", T::name()))),
+            }
+            return Ok(ud);
+        }
+        Err(self.arg_type_error(i, fname, T::name()))
+    }
+}
@@ -2,30 +2,72 @@
 // 支持多种值类型，包括基本类型（nil, boolean, integer, float）与字符串优化
 // 字符串采用分层存储以优化空间使用：短字符串直接存储、中等/长字符串用引用计数
 
-use crate::vm::ExeState;
-use std::collections::HashMap;
-use std::rc::Rc;
+use crate::parse::FuncProto;
+use crate::sync::{MaybeSend, Rc, Ref, RefCell, RefMut};
+use crate::vm::{ExeState, LuaError};
+use std::any::Any;
 use std::fmt;
-use std::cell::RefCell;
+use std::ffi::c_void;
 use std::hash::{Hash, Hasher};
 
+pub use crate::table::Table;
+
 /// 短字符串的最大长度（优化：直接在 Value 中存储小字符串）
 const SHORT_STR_MAX: usize = 14; //sizeof(Value) - 1(tag)-1(len)
+
+// Value 固定为 16 字节：1 字节标签加 15 字节负载，其余成员都是 8 字节的数值或瘦指针；
+// Option<Value> 利用标签中未用的取值，也是 16 字节（栈与表的读取都会返回 Option<Value>）
+const _: () = assert!(std::mem::size_of::<Value>() == 16);
+const _: () = assert!(std::mem::size_of::<Option<Value>>() == 16);
+const _: () = assert!(SHORT_STR_MAX == std::mem::size_of::<Value>() - 2);
 /// 中等字符串的最大长度
 const MID_STR_MAX: usize = 48-1;
 
+/// Rust 实现的库函数类型：参数从栈上读取，返回值压栈后返回其个数
+pub type RustFunction = fn(&mut ExeState) -> Result<i32, LuaError>;
+
+/// 带状态的 Rust 函数（闭包），如 io.lines() 返回的迭代器；启用 send 特性时须是 Send + Sync 的
+#[cfg(not(feature = "send"))]
+pub type RustClosure = RefCell<Box<dyn FnMut(&mut ExeState) -> Result<i32, LuaError>>>;
+#[cfg(feature = "send")]
+pub type RustClosure = RefCell<Box<dyn FnMut(&mut ExeState) -> Result<i32, LuaError> + Send + Sync>>;
+
+/// 用户数据中的 Rust 对象；启用 send 特性时须是 Send + Sync 的
+#[cfg(not(feature = "send"))]
+pub type DynAny = dyn Any;
+#[cfg(feature = "send")]
+pub type DynAny = dyn Any + Send + Sync;
 
-pub struct Table{
-    pub array: Vec<Value>,
-    pub map:HashMap<Value,Value>,
+/// Lua 函数（闭包）：函数原型加上创建时捕获的上值
+pub struct LuaClosure {
+    pub proto: Rc<FuncProto>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
 }
+
+/// 上值：被捕获的局部变量仍在栈上时为 Open(栈位置)，离开作用域后关闭为 Closed(值)
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+/// 用户数据：由 Rust 持有的任意对象（如文件句柄），通过元表暴露方法给 Lua
+/// 数据另外装箱，使 AnyUserData 是定长的，Value 中只需保存瘦指针 Rc<AnyUserData>
+pub struct AnyUserData {
+    pub metatable: Option<Rc<RefCell<Table>>>,
+    pub data: RefCell<Box<DynAny>>,
+}
+
 /// Lua 值类型枚举
-/// 采用分层字符串存储以平衡空间与性能
+/// 采用分层字符串存储以平衡空间与性能；大小固定为 16 字节，新增成员的负载不能超过 15 字节
 #[derive(Clone)]
 pub enum Value {
     // String(String),  // 原始方案（已弃用）
     /// 函数值：指向虚拟机内置函数的指针
-    Function(fn(&mut ExeState) -> i32),
+    Function(RustFunction),
+    /// 带捕获状态的内置函数
+    RustClosure(Rc<RustClosure>),
+    /// Lua 函数
+    LuaFunction(Rc<LuaClosure>),
     /// 布尔值
     Boolean(bool),
     /// 64 位整数
@@ -38,23 +80,89 @@ pub enum Value {
     ShortStr(u8, [u8; SHORT_STR_MAX]),
     /// 中等字符串（引用计数）
     MidStr(Rc<(u8, [u8; MID_STR_MAX])>),
-    /// 长字符串（引用计数 String）
-    LongStr(Rc<String>),
+    /// 长字符串（引用计数的字节串）
+    LongStr(Rc<Vec<u8>>),
     Table(Rc<RefCell<Table>>),
+    /// 用户数据
+    UserData(Rc<AnyUserData>),
+    /// 轻量用户数据：不透明的指针，Lua 只能传递和比较
+    LightUserData(*mut c_void),
 }
 
+// SAFETY: 轻量用户数据的指针只被传递和比较，解释器从不解引用，在哪个线程都一样；
+// 其余成员都是 Send + Sync 的，其中的内部可变性（表、上值、Rust 闭包、用户数据）都使用 sync::RefCell，
+// 启用 send 特性时它是原子计数的，下面的编译期检查保证这一点
+#[cfg(feature = "send")]
+unsafe impl Send for Value {}
+// SAFETY: 同上
+#[cfg(feature = "send")]
+unsafe impl Sync for Value {}
+
+/// 除轻量用户数据外，Value 的每种负载本身都是 Send + Sync 的；
+/// 若有负载改用了非线程安全的类型（如 std::cell::RefCell），这里编译失败，而不是被上面的 unsafe impl 掩盖
+#[cfg(feature = "send")]
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync + ?Sized>() {}
+    assert_send_sync::<RustFunction>();
+    assert_send_sync::<Rc<RustClosure>>();
+    assert_send_sync::<Rc<LuaClosure>>();
+    assert_send_sync::<Rc<(u8, [u8; MID_STR_MAX])>>();
+    assert_send_sync::<Rc<Vec<u8>>>();
+    assert_send_sync::<Rc<RefCell<Table>>>();
+    assert_send_sync::<Rc<AnyUserData>>();
+};
+
+/// 与 tostring 相同的字符串形式（不考虑 __tostring 元方法）：
+/// 字符串输出其字节内容（不是合法 UTF-8 的部分显示为替换字符），浮点数按 "%.14g" 格式化，
+/// 表、函数与用户数据显示为 "类型: 地址"
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Value::Nil => write!(f, "nil"),
             Value::Boolean(b) => write!(f, "{b}"),
             Value::Integer(i) => write!(f, "{i}"),
-            Value::Float(n) => write!(f, "{n:?}"),
-            Value::ShortStr(l,s,) => write!(f, "{l},{s:?}"),
-            Value::MidStr(s) => write!(f, "{s:?}"),
-            Value::LongStr(s) => write!(f, "{s:?}"),
-            Value::Function(_) => write!(f, "function"),
-            Value::Table(t) => write!(f, "table {:?}", Rc::as_ptr(t)),
+            Value::Float(n) => write!(f, "{}", format_float(*n)),
+            Value::ShortStr(..) | Value::MidStr(_) | Value::LongStr(_) => {
+                for chunk in self.as_bytes().unwrap().utf8_chunks() {
+                    f.write_str(chunk.valid())?;
+                    if !chunk.invalid().is_empty() {
+                        f.write_str("\u{FFFD}")?;
+                    }
+                }
+                Ok(())
+            }
+            Value::Function(func) => write!(f, "function: {:p}", *func as *const ()),
+            Value::RustClosure(c) => write!(f, "function: {:p}", Rc::as_ptr(c)),
+            Value::LuaFunction(c) => write!(f, "function: {:p}", Rc::as_ptr(c)),
+            Value::Table(t) => write!(f, "table: {:p}", Rc::as_ptr(t)),
+            Value::UserData(u) => write!(f, "userdata: {:p}", Rc::as_ptr(u)),
+            Value::LightUserData(p) => write!(f, "userdata: {p:p}"),
+        }
+    }
+}
+
+/// 显示值的内部表示：变体名与内容，字符串带引号（不是合法 UTF-8 时显示为转义的字节串）
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self {
+            Value::Nil => return write!(f, "Nil"),
+            Value::Boolean(b) => return write!(f, "Boolean({b})"),
+            Value::Integer(i) => return write!(f, "Integer({i})"),
+            Value::Float(n) => return write!(f, "Float({n:?})"),
+            Value::ShortStr(..) => "ShortStr",
+            Value::MidStr(_) => "MidStr",
+            Value::LongStr(_) => "LongStr",
+            Value::Function(func) => return write!(f, "Function({:p})", *func as *const ()),
+            Value::RustClosure(c) => return write!(f, "RustClosure({:p})", Rc::as_ptr(c)),
+            Value::LuaFunction(c) => return write!(f, "LuaFunction({:p})", Rc::as_ptr(c)),
+            Value::Table(t) => return write!(f, "Table({:p})", Rc::as_ptr(t)),
+            Value::UserData(u) => return write!(f, "UserData({:p})", Rc::as_ptr(u)),
+            Value::LightUserData(p) => return write!(f, "LightUserData({p:p})"),
+        };
+        let bytes = self.as_bytes().unwrap();
+        match std::str::from_utf8(bytes) {
+            Ok(s) => write!(f, "{kind}({s:?})"),
+            Err(_) => write!(f, "{kind}(b\"{}\")", bytes.escape_ascii()),
         }
     }
 }
@@ -66,13 +174,22 @@ impl PartialEq for Value {
             (Value::Boolean(a), Value::Boolean(b)) => *a == *b,
             (Value::Integer(a), Value::Integer(b)) => *a == *b,
             (Value::Float(a), Value::Float(b)) => *a == *b,
-            (Value::ShortStr(a,b), Value::ShortStr(c,d)) => *b == *d,
-            (Value::Function(a), Value::Function(b)) => std::ptr::eq(a, b),
+            (Value::ShortStr(a,b), Value::ShortStr(c,d)) => *a == *c && *b == *d,
+            (Value::MidStr(a), Value::MidStr(b)) => a.0 == b.0 && a.1[..a.0 as usize] == b.1[..b.0 as usize],
+            (Value::LongStr(a), Value::LongStr(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (Value::RustClosure(a), Value::RustClosure(b)) => Rc::ptr_eq(a, b),
+            (Value::LuaFunction(a), Value::LuaFunction(b)) => Rc::ptr_eq(a, b),
+            (Value::Table(a), Value::Table(b)) => Rc::ptr_eq(a, b),
+            (Value::UserData(a), Value::UserData(b)) => Rc::ptr_eq(a, b),
+            (Value::LightUserData(a), Value::LightUserData(b)) => a == b,
             _ => false,
         }
     }
 }
 
+impl Eq for Value {}
+
 impl Hash for Value {
     fn hash<H:Hasher>(&self,state:&mut H){
         match self {
@@ -93,50 +210,173 @@ impl Hash for Value {
                 let bits = f.to_bits();
                 bits.hash(state);
             },
-            Value::ShortStr(_,s) => {
+            Value::ShortStr(..) | Value::MidStr(_) | Value::LongStr(_) => {
+                // 同一字符串不论存储层级，都按字节内容计算 hash
                 4u8.hash(state);
-                s.hash(state);
+                self.as_bytes().hash(state);
             },
             Value::Function(f) => {
                 5u8.hash(state);
                 let ptr = *f as usize;
                 ptr.hash(state);
             },
+            Value::RustClosure(c) => {
+                5u8.hash(state);
+                Rc::as_ptr(c).hash(state);
+            },
+            Value::LuaFunction(c) => {
+                5u8.hash(state);
+                Rc::as_ptr(c).hash(state);
+            },
             Value::Table(t) => {
                 6u8.hash(state);
                 let ptr = Rc::as_ptr(t) as usize;
                 ptr.hash(state);
             },
-            _ => {
-                panic!("unhashable value");
-            }
+            Value::UserData(u) => {
+                7u8.hash(state);
+                Rc::as_ptr(u).hash(state);
+            },
+            Value::LightUserData(p) => {
+                7u8.hash(state);
+                p.hash(state);
+            },
         }
     }
 }
 
-impl From<String> for Value {
-    fn from(value: String) -> Self {
+impl Value {
+    /// Lua 类型名，用于错误信息与 type()
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Boolean(_) => "boolean",
+            Value::Integer(_) | Value::Float(_) => "number",
+            Value::ShortStr(..) | Value::MidStr(_) | Value::LongStr(_) => "string",
+            Value::Function(_) | Value::RustClosure(_) | Value::LuaFunction(_) => "function",
+            Value::Table(_) => "table",
+            Value::UserData(_) | Value::LightUserData(_) => "userdata",
+        }
+    }
+
+    /// 取字符串值的字节内容，非字符串返回 None
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::ShortStr(len, buf) => Some(&buf[..*len as usize]),
+            Value::MidStr(s) => Some(&s.1[..s.0 as usize]),
+            Value::LongStr(s) => Some(&s[..]),
+            _ => None,
+        }
+    }
+
+    /// 取字符串值（要求是合法 UTF-8）
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    /// Lua 的真值判断：只有 nil 和 false 为假
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+
+    /// 转换为整数：整数原样返回，浮点数须无小数部分，数字字符串先解析
+    pub fn to_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            Value::Float(f) => float_to_integer(*f),
+            _ => match self.as_str().and_then(str_to_number)? {
+                Value::Integer(i) => Some(i),
+                Value::Float(f) => float_to_integer(f),
+                _ => None,
+            },
+        }
+    }
+
+    /// 转换为浮点数：数字直接转换，数字字符串先解析
+    pub fn to_number(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            _ => match self.as_str().and_then(str_to_number)? {
+                Value::Integer(i) => Some(i as f64),
+                Value::Float(f) => Some(f),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl AnyUserData {
+    /// 以指定的元表包装一个 Rust 对象
+    pub fn new<T: Any + MaybeSend>(data: T, metatable: Option<Rc<RefCell<Table>>>) -> Rc<Self> {
+        Rc::new(AnyUserData {
+            metatable,
+            data: RefCell::new(Box::new(data)),
+        })
+    }
+
+    /// 数据是否为类型 T
+    pub fn is<T: Any>(&self) -> bool {
+        self.data.try_borrow().is_ok_and(|d| d.is::<T>())
+    }
+
+    /// 以类型 T 借用数据，类型不符或已被可变借用时返回 None
+    pub fn borrow<T: Any>(&self) -> Option<Ref<'_, T>> {
+        let data = self.data.try_borrow().ok()?;
+        Ref::filter_map(data, |d| d.downcast_ref::<T>()).ok()
+    }
+
+    /// 以类型 T 可变借用数据，类型不符或已被借用时返回 None
+    pub fn borrow_mut<T: Any>(&self) -> Option<RefMut<'_, T>> {
+        let data = self.data.try_borrow_mut().ok()?;
+        RefMut::filter_map(data, |d| d.downcast_mut::<T>()).ok()
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
         let len = value.len();
         if len <= SHORT_STR_MAX {
             let mut buf = [0u8; SHORT_STR_MAX];
-            buf[..len].copy_from_slice(value.as_bytes());
+            buf[..len].copy_from_slice(value);
             Value::ShortStr(len as u8, buf)
         }else if len <= MID_STR_MAX {
             let mut buf = [0u8; MID_STR_MAX];
-            buf[..len].copy_from_slice(value.as_bytes());
+            buf[..len].copy_from_slice(value);
             Value::MidStr(Rc::new((len as u8, buf)))
         }else {
+            Value::LongStr(Rc::new(value.to_vec()))
+        }
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        if value.len() <= MID_STR_MAX {
+            Value::from(&value[..])
+        } else {
             Value::LongStr(Rc::new(value))
         }
     }
-    
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::from(value.into_bytes())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::from(value.as_bytes())
+    }
 }
 
 impl From<f64> for Value {
     fn from(value: f64) -> Self {
         Value::Float(value)
     }
-    
+
 }
 impl From<i64> for Value {
     fn from(value: i64) -> Self {
@@ -149,8 +389,118 @@ impl From<bool> for Value {
     }
 }
 
-impl From<fn(&mut ExeState) -> i32> for Value {
-    fn from(value: fn(&mut ExeState) -> i32) -> Self {
+impl From<RustFunction> for Value {
+    fn from(value: RustFunction) -> Self {
         Value::Function(value)
     }
-}
\ No newline at end of file
+}
+
+impl From<*mut c_void> for Value {
+    fn from(value: *mut c_void) -> Self {
+        Value::LightUserData(value)
+    }
+}
+
+impl From<Table> for Value {
+    fn from(value: Table) -> Self {
+        Value::Table(Rc::new(RefCell::new(value)))
+    }
+}
+
+/// 浮点数无损转换为整数，有小数部分或超出范围时返回 None
+pub fn float_to_integer(f: f64) -> Option<i64> {
+    // i64::MAX as f64 会舍入为 2^63，因此上界用严格小于
+    if f.fract() == 0.0 && f >= i64::MIN as f64 && f < -(i64::MIN as f64) {
+        Some(f as i64)
+    } else {
+        None
+    }
+}
+
+/// 按 Lua 规则把字符串解析为数字（允许首尾空白、十六进制整数与浮点数）
+pub fn str_to_number(s: &str) -> Option<Value> {
+    // 与 C 的 isspace 一致，包括 \v
+    let s = s.trim_matches(|c| matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c'));
+    let (neg, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return hex_to_float(hex).map(|f| Value::Float(if neg { -f } else { f }));
+        }
+        // 十六进制整数按 64 位回绕
+        let n = hex.bytes().fold(0i64, |n, b| {
+            n.wrapping_mul(16).wrapping_add((b as char).to_digit(16).unwrap() as i64)
+        });
+        return Some(Value::Integer(if neg { n.wrapping_neg() } else { n }));
+    }
+    if let Ok(i) = s.parse::<i64>() {
+        return Some(Value::Integer(i));
+    }
+    // 拒绝 Rust 接受而 Lua 不接受的写法，如 "inf"、"nan"
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    s.parse::<f64>().ok().map(Value::Float)
+}
+
+/// 十六进制浮点数（已去掉 0x 前缀），如 "1.8p3"、".8"、"A"
+fn hex_to_float(s: &str) -> Option<f64> {
+    let (mantissa, exp) = match s.split_once(['p', 'P']) {
+        Some((m, e)) => (m, e.parse::<i64>().ok()?),
+        None => (s, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = || int.bytes().chain(frac.bytes());
+    if int.len() + frac.len() == 0 || !digits().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let f = digits().fold(0.0, |f, b| f * 16.0 + (b as char).to_digit(16).unwrap() as f64);
+    let exp = exp.saturating_sub(4 * frac.len() as i64).clamp(i32::MIN as i64, i32::MAX as i64);
+    Some(f * 2f64.powi(exp as i32))
+}
+
+/// 按 C 的 "%.14g" 格式化浮点数（Lua 的 LUA_NUMBER_FMT），io.write 直接使用
+pub fn format_g14(f: f64) -> String {
+    if f.is_nan() {
+        return if f.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    }
+    if f.is_infinite() {
+        return if f < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    if f == 0.0 {
+        return if f.is_sign_negative() { "-0" } else { "0" }.to_string();
+    }
+    // 先按 14 位有效数字的科学计数法舍入，得到十进制指数
+    let sci = format!("{f:.13e}");
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    if !(-4..14).contains(&exp) {
+        let mantissa = trim_fraction_zeros(mantissa);
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{mantissa}e{sign}{:02}", exp.abs())
+    } else {
+        let decimals = (13 - exp) as usize;
+        trim_fraction_zeros(&format!("{f:.decimals$}")).to_string()
+    }
+}
+
+/// 转为 Lua 字符串形式的浮点数：看起来像整数时补上 ".0"，如 1.0、-0.0
+pub fn format_float(f: f64) -> String {
+    let s = format_g14(f);
+    if s.bytes().all(|b| b.is_ascii_digit() || b == b'-') {
+        s + ".0"
+    } else {
+        s
+    }
+}
+
+/// 去掉小数部分末尾的 0（以及随之多余的小数点）
+fn trim_fraction_zeros(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
@@ -0,0 +1,295 @@
+// Lua 值与 serde 数据模型的互相转换（serde 特性）
+// 序列化：
+// - nil 与空指针轻量用户数据（json.null）为 unit，布尔、整数、浮点数对应同名类型
+// - 字符串是合法 UTF-8 时为 str，否则为字节串
+// - 表的键都是 1..n 的连续整数时为序列，否则为映射；空表与稀疏数组的处理由 SerializeOptions 决定
+// - 映射的键只能是字符串或数字，函数、用户数据与有环的表不能序列化
+// - 与 cjson 一样限制表的嵌套层数（MAX_DEPTH），避免嵌套过深时耗尽栈
+// 反序列化：序列成为数组部分从 1 开始的表，映射成为键值对表，unit/none 成为 nil（可换成其他值，如 json.null）
+
+use crate::sync::{Rc, RefCell};
+use crate::value::{format_float, Table, Value};
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Error as _, SerializeMap, SerializeSeq, Serializer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 序列化时表的最大嵌套层数，与 cjson 的默认值相同
+/// debug 构建中 serde_json 每层约占 2.5KB 的栈，达到上限需要的栈比新线程默认的 2MB 多
+const MAX_DEPTH: usize = 1000;
+
+/// 键都是正整数但不连续的表（稀疏数组）的序列化方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SparseArray {
+    /// 作为映射，键为整数
+    #[default]
+    Map,
+    /// 作为序列，空缺处填 unit（JSON 中为 null），长度为最大的键
+    Fill,
+    /// 报错
+    Error,
+}
+
+/// NaN 与无穷大的序列化方式
+/// 默认原样交给序列化器，由目标格式决定如何表示；lua 的 json.encode 默认使用 Error，见 lib_json
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NanMode {
+    /// 原样交给序列化器（serde_json 输出 null，TOML 可以表示）
+    #[default]
+    Keep,
+    /// 作为 unit
+    Null,
+    /// 报错
+    Error,
+}
+
+/// 序列化选项
+/// - `empty_as_array`: 空表作为空序列，否则作为空映射
+/// - `sparse_array`: 稀疏数组的处理
+/// - `nan`: NaN 与无穷大的处理
+/// - `sort_keys`: 映射按键排序（数字键按数值排在字符串键之前），否则按表的遍历顺序
+#[derive(Debug, Clone, Default)]
+pub struct SerializeOptions {
+    pub empty_as_array: bool,
+    pub sparse_array: SparseArray,
+    pub nan: NanMode,
+    pub sort_keys: bool,
+}
+
+/// 按指定选项序列化的值
+struct WithOptions<'a> {
+    value: &'a Value,
+    options: &'a SerializeOptions,
+    /// 正在序列化的外层表，用于发现环与限制嵌套层数
+    ancestors: &'a RefCell<Vec<*const RefCell<Table>>>,
+}
+
+impl Value {
+    /// 按指定选项序列化；直接序列化 Value 时使用默认选项
+    pub fn serialize_with<S: Serializer>(&self, options: &SerializeOptions, serializer: S) -> Result<S::Ok, S::Error> {
+        let ancestors = RefCell::new(Vec::new());
+        WithOptions {
+            value: self,
+            options,
+            ancestors: &ancestors,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_with(&SerializeOptions::default(), serializer)
+    }
+}
+
+impl WithOptions<'_> {
+    fn child<'b>(&'b self, value: &'b Value) -> WithOptions<'b> {
+        WithOptions {
+            value,
+            options: self.options,
+            ancestors: self.ancestors,
+        }
+    }
+}
+
+impl Serialize for WithOptions<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Value::Nil => serializer.serialize_unit(),
+            Value::LightUserData(p) if p.is_null() => serializer.serialize_unit(),
+            Value::Boolean(b) => serializer.serialize_bool(*b),
+            Value::Integer(i) => serializer.serialize_i64(*i),
+            Value::Float(f) if !f.is_finite() => match self.options.nan {
+                NanMode::Keep => serializer.serialize_f64(*f),
+                NanMode::Null => serializer.serialize_unit(),
+                NanMode::Error => Err(S::Error::custom("cannot serialize NaN or infinity")),
+            },
+            Value::Float(f) => serializer.serialize_f64(*f),
+            Value::ShortStr(..) | Value::MidStr(_) | Value::LongStr(_) => match self.value.as_str() {
+                Some(s) => serializer.serialize_str(s),
+                None => serializer.serialize_bytes(self.value.as_bytes().unwrap()),
+            },
+            Value::Table(t) => {
+                let ptr = Rc::as_ptr(t);
+                if self.ancestors.borrow().contains(&ptr) {
+                    return Err(S::Error::custom("cannot serialize a table with cycles"));
+                }
+                if self.ancestors.borrow().len() >= MAX_DEPTH {
+                    return Err(S::Error::custom(format!("cannot serialize, excessive nesting ({})", MAX_DEPTH + 1)));
+                }
+                self.ancestors.borrow_mut().push(ptr);
+                let result = self.serialize_table(&t.borrow(), serializer);
+                self.ancestors.borrow_mut().pop();
+                result
+            }
+            v => Err(S::Error::custom(format!("cannot serialize a {}", v.type_name()))),
+        }
+    }
+}
+
+impl WithOptions<'_> {
+    fn serialize_table<S: Serializer>(&self, t: &Table, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries = Vec::new();
+        let mut key = Value::Nil;
+        while let Some((k, v)) = t.next(&key).map_err(S::Error::custom)? {
+            entries.push((k.clone(), v));
+            key = k;
+        }
+
+        // 键都是正整数时按数组处理：连续时为序列，否则按稀疏数组的选项处理
+        let max = entries.iter().try_fold(0, |max, (k, _)| match k {
+            Value::Integer(i) if *i >= 1 => Some(max.max(*i as usize)),
+            _ => None,
+        });
+        let as_seq = match max {
+            Some(0) => self.options.empty_as_array,
+            Some(max) if max == entries.len() => true,
+            Some(_) => match self.options.sparse_array {
+                SparseArray::Map => false,
+                SparseArray::Fill => true,
+                SparseArray::Error => return Err(S::Error::custom("cannot serialize a sparse array")),
+            },
+            None => false,
+        };
+
+        if as_seq {
+            let len = max.unwrap();
+            let mut seq = serializer.serialize_seq(Some(len))?;
+            for i in 1..=len {
+                seq.serialize_element(&self.child(&t.get(&Value::Integer(i as i64))))?;
+            }
+            return seq.end();
+        }
+
+        if self.options.sort_keys {
+            entries.sort_by(|(a, _), (b, _)| key_order(a).partial_cmp(&key_order(b)).unwrap());
+        }
+        let mut map = serializer.serialize_map(Some(entries.len()))?;
+        for (k, v) in &entries {
+            map.serialize_key(&Key(k))?;
+            map.serialize_value(&self.child(v))?;
+        }
+        map.end()
+    }
+}
+
+/// 排序用的键：数字在前，按数值比较；字符串在后，按字节比较
+fn key_order(key: &Value) -> (u8, f64, &[u8]) {
+    match key {
+        Value::Integer(i) => (0, *i as f64, &[]),
+        Value::Float(f) => (0, *f, &[]),
+        _ => (1, 0.0, key.as_bytes().unwrap_or_default()),
+    }
+}
+
+/// 映射的键：字符串与整数原样，浮点数按 tostring 的形式转为字符串
+struct Key<'a>(&'a Value);
+
+impl Serialize for Key<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Integer(i) => serializer.serialize_i64(*i),
+            Value::Float(f) => serializer.serialize_str(&format_float(*f)),
+            Value::ShortStr(..) | Value::MidStr(_) | Value::LongStr(_) => match self.0.as_str() {
+                Some(s) => serializer.serialize_str(s),
+                None => serializer.serialize_bytes(self.0.as_bytes().unwrap()),
+            },
+            v => Err(S::Error::custom(format!("cannot serialize a {} key", v.type_name()))),
+        }
+    }
+}
+
+/// 反序列化为 Value，null 为 unit/none 反序列化得到的值
+pub struct ValueSeed {
+    pub null: Value,
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ValueSeed { null: Value::Nil }.deserialize(deserializer)
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for ValueSeed {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(&self)
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for &ValueSeed {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for &ValueSeed {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a value representable in Lua")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Boolean(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Integer(v))
+    }
+
+    /// 超出 i64 范围的无符号整数转为浮点数
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(i64::try_from(v).map_or(Value::Float(v as f64), Value::Integer))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(self.null.clone())
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(self.null.clone())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut table = Table::new(seq.size_hint().unwrap_or(0), 0);
+        let mut i = 0;
+        while let Some(v) = seq.next_element_seed(self)? {
+            i += 1;
+            table.set(Value::Integer(i), v);
+        }
+        Ok(Value::from(table))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut table = Table::new(0, map.size_hint().unwrap_or(0));
+        while let Some(k) = map.next_key_seed(self)? {
+            let v = map.next_value_seed(self)?;
+            if k == Value::Nil {
+                return Err(de::Error::custom("table index is nil"));
+            }
+            table.set(k, v);
+        }
+        Ok(Value::from(table))
+    }
+}
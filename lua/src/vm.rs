@@ -1,157 +1,1725 @@
 // Lua 虚拟机（Virtual Machine）模块
 // 负责执行由解析器生成的字节码
-// 维护全局变量表、运行栈、执行环境状态
+// 维护全局变量表、运行栈、调用栈帧与上值，执行环境状态
+// Lua 函数之间的调用不占用 Rust 的调用栈：每次调用压入一个栈帧，在同一个执行循环中继续执行；
+// 只有内置函数反过来调用 Lua 函数（如 require 执行模块代码）时才会嵌套一层执行循环
 
-use crate::value::Value;
+use crate::arith::{self, ArithOp};
+use crate::bytecode::{ByteCode, FIELDS_PER_FLUSH};
+use crate::lex::ParseError;
+use crate::parse::{FuncProto, ParseProto, UpIndex};
+use crate::profile::{Profile, ProfileMode, Profiler};
+use crate::sync::{MaybeSend, Rc, RefCell};
+use crate::userdata::{self, UserData};
+use crate::value::{format_float, AnyUserData, LuaClosure, RustFunction, Table, Upvalue, Value};
+use crate::{lib_base, lib_debug, lib_io, lib_os, lib_package, lib_string, lib_utf8};
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
-use crate::parse::ParseProto;
+use std::fmt;
+use std::future::Future;
+use std::io::{Read, Seek};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-/// 内置库函数：print() 的实现
-/// 输出栈上索引 1 的值（第一个参数）
-fn lib_print(state:&mut ExeState)-> i32{
-    println!("{}", state.stack[1]);
-    0
+/// Lua 运行时错误
+#[derive(Clone)]
+pub enum LuaError {
+    /// 携带错误值（通常是错误信息字符串）的运行时错误
+    Runtime(Value),
+    /// 执行被 InterruptHandle 中断，不能被 pcall/xpcall 捕获
+    Interrupted,
+}
+
+impl LuaError {
+    /// 以字符串信息构造运行时错误
+    pub fn new(msg: impl Into<String>) -> Self {
+        LuaError::Runtime(Value::from(msg.into()))
+    }
+}
+
+impl fmt::Debug for LuaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LuaError({self})")
+    }
+}
+
+impl std::error::Error for LuaError {}
+
+impl fmt::Display for LuaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LuaError::Runtime(v) => match v.as_bytes() {
+                Some(b) => write!(f, "{}", String::from_utf8_lossy(b)),
+                None => write!(f, "(error object is a {} value)", v.type_name()),
+            },
+            LuaError::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+/// 钩子事件掩码：函数调用时
+pub const MASK_CALL: u8 = 1 << 0;
+/// 钩子事件掩码：函数返回时
+pub const MASK_RETURN: u8 = 1 << 1;
+/// 钩子事件掩码：执行到新的一行时
+pub const MASK_LINE: u8 = 1 << 2;
+/// 钩子事件掩码：每执行 count 条字节码时
+pub const MASK_COUNT: u8 = 1 << 3;
+
+/// Lua 函数调用栈帧的最大层数，超过时报错 "stack overflow"（对应 C 实现中栈大小的上限 LUAI_MAXSTACK）
+const MAX_FRAMES: usize = 200_000;
+
+/// 经过 Rust 代码的嵌套调用（元方法、pcall、内置函数调用 Lua 函数等）的最大层数，超过时报错 "stack overflow"（同 C 实现的 LUAI_MAXCCALLS）
+/// 每层都要占用宿主线程的栈：release 构建约 2KB，debug 构建约 13KB，
+/// 因此 debug 构建中达到上限需要约 2.5MB 的栈，主线程（通常 8MB）足够，而新线程默认的 2MB 不够
+const MAX_RUST_CALLS: usize = 200;
+
+/// __index、__newindex 与 __call 元方法链的最大长度，超过时认为元表构成了循环（同 C 实现的 MAXTAGLOOP）
+const MAX_TAG_LOOP: usize = 2000;
+
+/// 触发钩子的事件
+/// 调用与返回事件只针对 Lua 函数，内置函数不占用调用栈帧
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookEvent {
+    Call,
+    Return,
+    /// 即将执行的行号
+    Line(usize),
+    Count,
+}
+
+/// 钩子函数：在事件发生时以当前执行状态调用，返回错误时中止脚本的执行
+#[cfg(not(feature = "send"))]
+pub type HookFn = Box<dyn FnMut(&mut ExeState, HookEvent) -> Result<(), LuaError>>;
+#[cfg(feature = "send")]
+pub type HookFn = Box<dyn FnMut(&mut ExeState, HookEvent) -> Result<(), LuaError> + Send + Sync>;
+
+/// 异步函数返回的 Future：完成时得到函数的全部返回值
+#[cfg(not(feature = "send"))]
+pub type LuaFuture = Pin<Box<dyn Future<Output = Result<Vec<Value>, LuaError>>>>;
+#[cfg(feature = "send")]
+pub type LuaFuture = Pin<Box<dyn Future<Output = Result<Vec<Value>, LuaError>> + Send>>;
+
+/// 中断脚本执行的句柄，可以交给其他线程
+/// 中断请求在下一个检查点（函数调用、循环跳回）生效，正在执行的调用以 LuaError::Interrupted 结束
+#[derive(Clone)]
+pub struct InterruptHandle {
+    flag: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    /// 请求中断；没有正在执行的脚本时，下一次执行在第一个检查点中断
+    /// 请求只作用于一次最外层调用：调用结束时仍未生效的请求被丢弃
+    pub fn interrupt(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+}
+
+/// 已设置的钩子
+/// - `lua_func`: 由 debug.sethook 设置时为对应的 Lua 函数，供 debug.gethook 返回
+struct Hook {
+    func: Rc<RefCell<HookFn>>,
+    mask: u8,
+    count: usize,
+    lua_func: Option<Value>,
+}
+
+/// 函数或调用栈帧的调试信息，对应 debug.getinfo 返回的字段
+/// - `source`: 代码块名，Lua 代码以 '@' 开头，内置函数为 "=[C]"
+/// - `short_src`: 用于信息显示的代码块名
+/// - `what`: "Lua"（普通函数）、"main"（主代码块）或 "C"（内置函数）
+/// - `currentline`: 正在执行的行号，只有调用栈上的函数才有
+/// - `linedefined`/`lastlinedefined`: 函数定义的起止行号
+#[derive(Debug, Clone)]
+pub struct DebugInfo {
+    pub source: String,
+    pub short_src: String,
+    pub what: &'static str,
+    pub currentline: Option<usize>,
+    pub linedefined: usize,
+    pub lastlinedefined: usize,
+    pub nparams: usize,
+    pub is_vararg: bool,
+    pub nups: usize,
+    pub func: Value,
+}
+
+impl DebugInfo {
+    /// 函数值本身的调试信息（不含 currentline）
+    pub fn of_function(func: &Value) -> Self {
+        match func {
+            Value::LuaFunction(f) => DebugInfo {
+                source: format!("@{}", f.proto.source),
+                short_src: f.proto.source.clone(),
+                what: if f.proto.linedefined == 0 { "main" } else { "Lua" },
+                currentline: None,
+                linedefined: f.proto.linedefined,
+                lastlinedefined: f.proto.lastlinedefined,
+                nparams: f.proto.nparam,
+                is_vararg: f.proto.has_varargs,
+                nups: f.upvalues.len(),
+                func: func.clone(),
+            },
+            _ => DebugInfo {
+                source: "=[C]".to_string(),
+                short_src: "[C]".to_string(),
+                what: "C",
+                currentline: None,
+                linedefined: 0,
+                lastlinedefined: 0,
+                nparams: 0,
+                is_vararg: true,
+                nups: 0,
+                func: func.clone(),
+            },
+        }
+    }
+}
+
+/// 调用栈帧：一次正在执行的 Lua 函数调用
+/// - `closure`: 被调用的闭包
+/// - `base`: 寄存器 0 在栈上的位置（函数本身位于 base - 1）
+/// - `pc`: 调用其他函数时保存的下一条指令位置
+/// - `varargs`: 超出固定参数个数的实参，即 ...
+/// - `want`: 调用者期望的返回值个数+1，0 表示全部
+/// - `traced_pc`: 行钩子上次检查过的指令位置，用于判断是否进入新的一行
+pub(crate) struct CallFrame {
+    pub(crate) closure: Rc<LuaClosure>,
+    base: usize,
+    pc: usize,
+    varargs: Vec<Value>,
+    want: usize,
+    traced_pc: Option<usize>,
+}
+
+impl CallFrame {
+    /// 正在执行的行号：pc 指向下一条指令，尚未开始执行时取函数定义所在行
+    fn current_line(&self) -> usize {
+        let proto = &self.closure.proto;
+        match self.pc.checked_sub(1).and_then(|pc| proto.lineinfo.get(pc)) {
+            Some(&line) => line,
+            None => proto.linedefined,
+        }
+    }
 }
 
 /// 虚拟机执行状态结构体
-/// - `globals`: 全局变量表，存储全局变量与内置函数
+/// - `globals`: 全局变量表，存储全局变量与内置函数；以变量名的字符串值为键，字节码直接用常数池中的名字查找
 /// - `stack`: 运行栈，存储临时变量、函数调用时的本地变量、参数等
+/// - `base`: 当前被调用的内置函数的第一个参数在栈上的位置
+/// - `frames`: Lua 函数调用栈帧
+/// - `open_upvalues`: 仍指向栈上局部变量的上值，离开作用域时关闭
+/// - `tbc`: 待关闭变量（local x <close>）在栈上的位置，按声明顺序排列，离开作用域或出错时逆序关闭
+/// - `package`: package 库表，require 与 register_module 使用
+/// - `userdata_metatables`: 各 Rust 类型的用户数据共享的元表
+/// - `hook`: 调试钩子，`hook_mask` 为其事件掩码（没有钩子时为 0），`hook_left` 为距下次计数事件的指令数
+/// - `in_hook`: 正在执行钩子函数，此时不再触发钩子
+/// - `rust_calls`: 正在进行的经过 Rust 代码的嵌套调用（call）的层数
+/// - `msgh`: xpcall 设置的消息处理函数，出错时在展开调用栈之前调用一次，之后清空直到 xpcall 返回
+/// - `rust_caller`: 最近一次调用来自 Rust 代码（call）而不是 Lua 代码，如 pcall(error, msg)，此时 error 不加位置
+/// - `yieldable`: 正在由 call_async 直接执行，异步函数可以挂起（经过 call 的嵌套调用中不能挂起）
+/// - `in_async`: 正在执行 call_async，用于区分不能挂起的原因
+/// - `pending`: 刚被调用的异步函数返回的 Future，由执行循环取走
+/// - `suspended`: 挂起中的异步函数调用，call_async 等待其完成后继续执行
+/// - `interrupt`: 中断请求标志，由 InterruptHandle 设置，执行循环在检查点读取并清除，最外层调用结束时也清除
+/// - `profiler`: 正在进行的性能分析
 pub struct ExeState {
-    globals: HashMap<String, Value>,
+    globals: Rc<RefCell<Table>>,
     stack: Vec<Value>,
+    base: usize,
+    frames: Vec<CallFrame>,
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    tbc: Vec<usize>,
+    package: Rc<RefCell<Table>>,
+    userdata_metatables: HashMap<TypeId, Rc<RefCell<Table>>>,
+    hook: Option<Hook>,
+    hook_mask: u8,
+    hook_left: usize,
+    in_hook: bool,
+    rust_calls: usize,
+    msgh: Option<Value>,
+    rust_caller: bool,
+    yieldable: bool,
+    in_async: bool,
+    pending: Option<LuaFuture>,
+    suspended: Option<Suspended>,
+    interrupt: Arc<AtomicBool>,
+    profiler: Option<Box<Profiler>>,
+}
+
+/// 挂起的异步函数调用：Future 完成后把结果放到 ifunc 开始的位置，want 为期望返回值个数+1
+pub(crate) struct Suspended {
+    pub(crate) ifunc: usize,
+    pub(crate) want: usize,
+    pub(crate) future: LuaFuture,
 }
 
 impl ExeState {
     /// 创建新虚拟机实例，初始化全局变量表与内置函数
     pub fn new() -> Self {
-        let mut globals = HashMap::new();
-        globals.insert("print".to_string(), Value::Function(lib_print));
-        Self {
-            globals,
+        let mut state = Self {
+            globals: Rc::new(RefCell::new(Table::new(0, 64))),
             stack: Vec::new(),
+            base: 0,
+            frames: Vec::new(),
+            open_upvalues: Vec::new(),
+            tbc: Vec::new(),
+            package: lib_package::open_lib(),
+            userdata_metatables: HashMap::new(),
+            hook: None,
+            hook_mask: 0,
+            hook_left: 0,
+            in_hook: false,
+            rust_calls: 0,
+            msgh: None,
+            rust_caller: false,
+            yieldable: false,
+            in_async: false,
+            pending: None,
+            suspended: None,
+            interrupt: Arc::new(AtomicBool::new(false)),
+            profiler: None,
+        };
+        lib_base::open_lib(&mut state);
+        state.set_global("require", Value::Function(lib_package::require));
+        state.open_module("package", Value::Table(state.package.clone()));
+        let io = lib_io::open_lib(&mut state);
+        state.open_module("io", io);
+        state.open_module("string", lib_string::open_lib());
+        state.open_module("os", lib_os::open_lib());
+        state.open_module("debug", lib_debug::open_lib());
+        state.open_module("utf8", lib_utf8::open_lib());
+        #[cfg(feature = "serde")]
+        state.register_module("json", crate::lib_json::loader);
+        state
+    }
+
+    /// 注册标准库：设为全局变量，并记入 package.loaded 使 require 可以直接取得
+    fn open_module(&mut self, name: &str, module: Value) {
+        if let Value::Table(loaded) = self.package.borrow().get_str("loaded") {
+            loaded.borrow_mut().set_str(name, module.clone());
+        }
+        self.set_global(name, module);
+    }
+
+    /// 注册 Rust 实现的模块，Lua 代码通过 require(name) 加载
+    /// loader 与 Lua 的加载函数一样接收模块名，返回（压栈）模块的值，通常是一张函数表；
+    /// 它被放入 package.preload，首次 require 时才调用，结果缓存在 package.loaded 中
+    pub fn register_module(&mut self, name: &str, loader: RustFunction) {
+        if let Value::Table(preload) = self.package.borrow().get_str("preload") {
+            preload.borrow_mut().set_str(name, Value::Function(loader));
+        }
+    }
+
+    /// 把 Rust 对象包装为用户数据，方法与字段由 T 的 UserData 实现声明
+    pub fn create_userdata<T: UserData>(&mut self, data: T) -> Value {
+        let meta = self
+            .userdata_metatables
+            .entry(TypeId::of::<T>())
+            .or_insert_with(userdata::metatable::<T>)
+            .clone();
+        Value::UserData(AnyUserData::new(data, Some(meta)))
+    }
+
+    /// 类型 T 的用户数据共享的元表，首次使用时由 init 创建；供不实现 UserData 的标准库类型（如文件句柄）使用
+    pub(crate) fn metatable_of<T: Any>(&mut self, init: fn() -> Rc<RefCell<Table>>) -> Rc<RefCell<Table>> {
+        self.userdata_metatables.entry(TypeId::of::<T>()).or_insert_with(init).clone()
+    }
+
+    /// package 库表
+    pub(crate) fn package(&self) -> Rc<RefCell<Table>> {
+        self.package.clone()
+    }
+
+    /// 全局变量表，即 _G
+    pub(crate) fn globals(&self) -> Rc<RefCell<Table>> {
+        self.globals.clone()
+    }
+
+    /// 设置全局变量，供宿主注册库函数与模块
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.borrow_mut().set_str(name, value);
+    }
+
+    /// 读取全局变量，不存在时返回 nil
+    pub fn get_global(&self, name: &str) -> Value {
+        self.globals.borrow().get_str(name)
+    }
+
+    /// 当前内置函数收到的参数个数
+    pub fn get_top(&self) -> usize {
+        self.stack.len() - self.base
+    }
+
+    /// 读取第 i 个参数（从 1 开始），超出参数个数时返回 nil
+    pub fn arg(&self, i: usize) -> Value {
+        self.stack.get(self.base + i - 1).cloned().unwrap_or(Value::Nil)
+    }
+
+    /// 压入一个返回值
+    pub fn push(&mut self, v: impl Into<Value>) {
+        self.stack.push(v.into());
+    }
+
+    /// 弹出栈顶的值
+    pub fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap_or(Value::Nil)
+    }
+
+    /// 检查第 i 个参数是字符串（数字按 Lua 规则转为字符串）
+    pub fn check_string(&self, i: usize, fname: &str) -> Result<String, LuaError> {
+        match self.arg(i) {
+            Value::Integer(n) => Ok(n.to_string()),
+            Value::Float(n) => Ok(format_float(n)),
+            v => match v.as_bytes() {
+                Some(b) => Ok(String::from_utf8_lossy(b).into_owned()),
+                None => Err(self.arg_type_error(i, fname, "string")),
+            },
+        }
+    }
+
+    /// 检查第 i 个参数是字符串并返回原始字节（数字按 Lua 规则转为字符串）
+    pub fn check_bytes(&self, i: usize, fname: &str) -> Result<Vec<u8>, LuaError> {
+        match self.arg(i) {
+            Value::Integer(n) => Ok(n.to_string().into_bytes()),
+            Value::Float(n) => Ok(format_float(n).into_bytes()),
+            v => match v.as_bytes() {
+                Some(b) => Ok(b.to_vec()),
+                None => Err(self.arg_type_error(i, fname, "string")),
+            },
+        }
+    }
+
+    /// 读取可选的字符串参数，nil 或缺省时返回 None
+    pub fn opt_string(&self, i: usize, fname: &str) -> Result<Option<String>, LuaError> {
+        match self.arg(i) {
+            Value::Nil => Ok(None),
+            _ => self.check_string(i, fname).map(Some),
+        }
+    }
+
+    /// 检查第 i 个参数是整数（或可无损转换为整数的浮点数、数字字符串）
+    pub fn check_integer(&self, i: usize, fname: &str) -> Result<i64, LuaError> {
+        let v = self.arg(i);
+        match v.to_integer() {
+            Some(n) => Ok(n),
+            None if v.to_number().is_some() => Err(self.arg_error(i, fname, "number has no integer representation")),
+            None => Err(self.arg_type_error(i, fname, "number")),
+        }
+    }
+
+    /// 读取可选的整数参数，nil 或缺省时返回 None
+    pub fn opt_integer(&self, i: usize, fname: &str) -> Result<Option<i64>, LuaError> {
+        match self.arg(i) {
+            Value::Nil => Ok(None),
+            _ => self.check_integer(i, fname).map(Some),
+        }
+    }
+
+    /// 检查第 i 个参数是数字
+    pub fn check_number(&self, i: usize, fname: &str) -> Result<f64, LuaError> {
+        self.arg(i).to_number().ok_or_else(|| self.arg_type_error(i, fname, "number"))
+    }
+
+    /// 构造带出错位置的运行时错误，如 "main.lua:3: attempt to call a nil value"
+    /// 位置为正在执行的 Lua 函数的当前行；内置函数报错时即调用它的那一行
+    pub fn error(&self, msg: impl Into<String>) -> LuaError {
+        LuaError::new(format!("{}{}", self.location(0), msg.into()))
+    }
+
+    /// 正在执行的内置函数是否由 Rust 代码（而不是 Lua 代码）直接调用
+    pub fn called_from_rust(&self) -> bool {
+        self.rust_caller
+    }
+
+    /// 第 level 层调用（0 为正在执行的 Lua 函数）的当前位置，如 "main.lua:3: "，超出调用栈时为空串
+    pub fn location(&self, level: usize) -> String {
+        match self.frame_at(level) {
+            Some(frame) => format!("{}:{}: ", frame.closure.proto.source, frame.current_line()),
+            None => String::new(),
+        }
+    }
+
+    /// 构造 "bad argument" 错误
+    pub fn arg_error(&self, i: usize, fname: &str, msg: &str) -> LuaError {
+        self.error(format!("bad argument #{i} to '{fname}' ({msg})"))
+    }
+
+    /// 构造参数类型错误，如 "bad argument #1 to 'open' (string expected, got nil)"
+    pub fn arg_type_error(&self, i: usize, fname: &str, expected: &str) -> LuaError {
+        let got = if i > self.get_top() { "no value" } else { self.arg(i).type_name() };
+        self.arg_error(i, fname, &format!("{expected} expected, got {got}"))
+    }
+
+    /// 按 Lua 语义索引值：表先查自身再查元表 __index，用户数据查元表 __index
+    /// __index 为函数时以 (t, key) 调用，取其第一个返回值；否则继续索引 __index 的值
+    pub fn index(&mut self, t: &Value, key: &Value) -> Result<Value, LuaError> {
+        let mut t = t.clone();
+        for _ in 0..MAX_TAG_LOOP {
+            let meta = match &t {
+                Value::Table(tbl) => {
+                    let tbl = tbl.borrow();
+                    let v = tbl.get(key);
+                    if v != Value::Nil {
+                        return Ok(v);
+                    }
+                    match &tbl.metatable {
+                        Some(m) => m.clone(),
+                        None => return Ok(Value::Nil),
+                    }
+                }
+                Value::UserData(u) => match &u.metatable {
+                    Some(m) => m.clone(),
+                    None => return Err(self.error("attempt to index a userdata value")),
+                },
+                _ => return Err(self.error(format!("attempt to index a {} value", t.type_name()))),
+            };
+            let handler = meta.borrow().get_str("__index");
+            match handler {
+                Value::Nil => return Ok(Value::Nil),
+                h @ (Value::Function(_) | Value::RustClosure(_) | Value::LuaFunction(_)) => {
+                    let results = self.call(h, vec![t, key.clone()])?;
+                    return Ok(results.into_iter().next().unwrap_or(Value::Nil));
+                }
+                h => t = h,
+            }
         }
+        Err(self.error("'__index' chain too long; possibly a loop"))
     }
 
-    /// 执行字节码：遍历 ParseProto 中的字节码序列并逐个执行
-    pub fn execute<R: std::io::Read>(&mut self,proto:&ParseProto<R>){
-        // 简单字节码执行循环实现说明：
-        // - 栈（stack）被用作寄存器文件：字节码中的寄存器索引直接对应 stack 的位置
-        // - 在访问某个寄存器前，保证 stack 有足够长度（不足则用 Value::Nil 扩展）
-        // - 支持的指令包括：LoadConst/LoadNil/LoadBool/LoadInt/Move/Call/GetGlobal/SetGlobal*/等
-        // - 目前只支持内置函数调用（Value::Function），以及通过全局表读写变量
+    /// 编译一段 Lua 源代码，返回代表整个代码块的函数
+    /// chunkname 用于语法错误与运行时错误信息，如 "main.lua:3: unexpected symbol near '='"
+    pub fn load<R: Read + Seek>(&mut self, input: R, chunkname: &str) -> Result<Value, LuaError> {
+        let proto = ParseProto::load(input, chunkname)
+            .map_err(|e: ParseError| LuaError::new(format!("{chunkname}:{e}")))?;
+        Ok(Value::LuaFunction(Rc::new(LuaClosure {
+            proto: Rc::new(proto),
+            upvalues: Vec::new(),
+        })))
+    }
 
-        use crate::bytecode::ByteCode;
+    /// 执行一个已解析的代码块
+    pub fn execute(&mut self, proto: FuncProto) -> Result<(), LuaError> {
+        let main = Value::LuaFunction(Rc::new(LuaClosure {
+            proto: Rc::new(proto),
+            upvalues: Vec::new(),
+        }));
+        self.call(main, Vec::new()).map(|_| ())
+    }
 
-        // 辅助：确保 stack 至少有 len 个槽
-        fn ensure_stack(state: &mut ExeState, len: usize) {
-            while state.stack.len() <= len {
-                state.stack.push(Value::Nil);
+    /// 以 args 为参数调用任意函数值并返回其全部返回值
+    /// 供宿主程序和内置函数调用 Lua 函数，出错时栈恢复原状；这样的嵌套调用超过 200 层时报错 "stack overflow"
+    pub fn call(&mut self, func: Value, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+        if self.rust_calls >= MAX_RUST_CALLS {
+            return Err(self.error("stack overflow"));
+        }
+        let (ifunc, nargs, depth) = self.push_call(func, args);
+        // 经过 Rust 代码的嵌套调用中异步函数不能挂起
+        let yieldable = std::mem::replace(&mut self.yieldable, false);
+        self.rust_calls += 1;
+        let result = self.start_call(ifunc, nargs, depth);
+        self.rust_calls -= 1;
+        self.yieldable = yieldable;
+        self.finish_call(ifunc, depth, result)
+    }
+
+    /// 把函数与参数压到栈顶，返回函数位置、参数个数与当前调用栈深度
+    pub(crate) fn push_call(&mut self, func: Value, args: Vec<Value>) -> (usize, usize, usize) {
+        let ifunc = self.stack.len();
+        let nargs = args.len();
+        self.stack.push(func);
+        self.stack.extend(args);
+        (ifunc, nargs, self.frames.len())
+    }
+
+    /// 开始执行位于 ifunc 的函数调用，直到返回或挂起在异步函数上
+    pub(crate) fn start_call(&mut self, ifunc: usize, nargs: usize, depth: usize) -> Result<(), LuaError> {
+        self.rust_caller = true;
+        match self.precall(ifunc, nargs, 0) {
+            Ok(true) => self.run(depth),
+            Ok(false) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 结束位于 ifunc 的函数调用：成功时取出全部返回值，出错时恢复调用栈
+    pub(crate) fn finish_call(
+        &mut self,
+        ifunc: usize,
+        depth: usize,
+        result: Result<(), LuaError>,
+    ) -> Result<Vec<Value>, LuaError> {
+        if let Some(profiler) = self.profiler.as_deref_mut().filter(|_| depth == 0) {
+            profiler.pause();
+        }
+        // 最外层调用结束时丢弃没有遇到检查点的中断请求，使其不影响之后的执行
+        if depth == 0 {
+            self.interrupt.store(false, Ordering::Relaxed);
+        }
+        match result {
+            Ok(()) => Ok(self.stack.split_off(ifunc)),
+            Err(e) => {
+                // 调用钩子出错时新栈帧已经压入
+                self.unwind(depth);
+                // 出错时同样关闭这次调用中的待关闭变量，__close 中的错误替换原来的错误
+                let e = self.close_tbc(ifunc, Some(e)).unwrap_err();
+                self.stack.truncate(ifunc);
+                Err(e)
             }
         }
+    }
 
-        // 辅助：从 Value 中提取字符串（仅用于全局变量名）
-        fn value_to_string(v: &Value) -> Option<String> {
-            match v {
-                Value::ShortStr(len, buf) => {
-                    let l = *len as usize;
-                    let s = std::str::from_utf8(&buf[..l]).ok()?.to_string();
-                    Some(s)
+    /// 调用位于 ifunc 的函数，参数为其后的 nargs 个值，want 为期望返回值个数+1
+    /// Lua 函数压入新栈帧并返回 true，由执行循环继续执行；
+    /// 内置函数直接执行，返回值放到 ifunc 开始的位置后返回 false
+    fn precall(&mut self, ifunc: usize, nargs: usize, want: usize) -> Result<bool, LuaError> {
+        let base = ifunc + 1;
+        self.stack.truncate(base + nargs);
+        let result = match self.stack[ifunc].clone() {
+            Value::LuaFunction(closure) => {
+                if self.frames.len() >= MAX_FRAMES {
+                    return Err(self.error("stack overflow"));
                 }
-                Value::MidStr(rc) => {
-                    let (len, buf) = &**rc;
-                    let l = *len as usize;
-                    let s = std::str::from_utf8(&buf[..l]).ok()?.to_string();
-                    Some(s)
+                let nparam = closure.proto.nparam;
+                let varargs = if closure.proto.has_varargs && nargs > nparam {
+                    self.stack.split_off(base + nparam)
+                } else {
+                    Vec::new()
+                };
+                // 实参不足时补 nil，多余的丢弃；同时按函数用到的寄存器个数预先分配栈帧
+                self.stack.resize(base + nparam, Value::Nil);
+                self.stack.resize(base + closure.proto.max_stack.max(nparam), Value::Nil);
+                self.check_interrupt()?;
+                self.frames.push(CallFrame {
+                    closure,
+                    base,
+                    pc: 0,
+                    varargs,
+                    want,
+                    traced_pc: None,
+                });
+                if self.hook_mask & MASK_CALL != 0 {
+                    self.call_hook(HookEvent::Call)?;
                 }
-                Value::LongStr(rcs) => Some((**rcs).clone()),
-                _ => None,
+                return Ok(true);
+            }
+            // 内置函数接口：被调用函数直接访问 state.stack 中的参数
+            Value::Function(f) => {
+                let saved_base = std::mem::replace(&mut self.base, base);
+                let result = f(self);
+                self.base = saved_base;
+                result
+            }
+            Value::RustClosure(c) => {
+                let saved_base = std::mem::replace(&mut self.base, base);
+                let result = (c.borrow_mut())(self);
+                self.base = saved_base;
+                result
+            }
+            // 其他值使用其 __call 元方法，原来的值作为第一个参数
+            _ => {
+                let nargs = self.call_handler(ifunc, nargs)?;
+                return self.precall(ifunc, nargs, want);
+            }
+        };
+        let nret = result? as usize;
+        // 异步函数：挂起，结果在 Future 完成后放置
+        if let Some(future) = self.pending.take() {
+            self.suspended = Some(Suspended { ifunc, want, future });
+            return Ok(false);
+        }
+        // 返回值位于栈顶，移动到 ifunc 开始的位置
+        let ret_start = self.stack.len() - nret;
+        self.place_results(ifunc, ret_start, nret, want);
+        Ok(false)
+    }
+
+    /// 把位于 ifunc 的不可调用的值换成其 __call 元方法，原来的值移到参数之前，返回新的参数个数
+    fn call_handler(&mut self, ifunc: usize, mut nargs: usize) -> Result<usize, LuaError> {
+        for _ in 0..MAX_TAG_LOOP {
+            let v = &self.stack[ifunc];
+            if matches!(v, Value::Function(_) | Value::RustClosure(_) | Value::LuaFunction(_)) {
+                return Ok(nargs);
+            }
+            let handler = self.metamethod(v, "__call");
+            if handler == Value::Nil {
+                return Err(self.error(format!("attempt to call a {} value", v.type_name())));
             }
+            self.stack.insert(ifunc, handler);
+            nargs += 1;
         }
+        Err(self.error("'__call' chain too long; possibly a loop"))
+    }
 
-        for code in &proto.byte_codes {
+    /// 把从 src 开始的 n 个返回值移动到 dst，want 为期望个数+1
+    /// 期望全部返回值时栈顶设在最后一个返回值之后，否则不足的补 nil
+    fn place_results(&mut self, dst: usize, src: usize, n: usize, want: usize) {
+        let count = if want == 0 { n } else { want - 1 };
+        ensure_stack(self, dst + count);
+        for i in 0..count {
+            self.stack[dst + i] = if i < n { self.stack[src + i].clone() } else { Value::Nil };
+        }
+        self.stack.truncate(dst + count);
+    }
+
+    /// 中断脚本执行的句柄
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle {
+            flag: self.interrupt.clone(),
+        }
+    }
+
+    /// 检查点：有中断请求时清除请求并返回中断错误
+    fn check_interrupt(&self) -> Result<(), LuaError> {
+        if self.interrupt.swap(false, Ordering::Relaxed) {
+            return Err(LuaError::Interrupted);
+        }
+        Ok(())
+    }
+
+    /// 异步函数能否挂起；不能时返回相应的错误
+    pub(crate) fn check_yieldable(&self) -> Result<(), LuaError> {
+        if self.yieldable {
+            Ok(())
+        } else if self.in_async {
+            Err(self.error("attempt to yield across a C-call boundary"))
+        } else {
+            Err(self.error("attempt to yield from outside a coroutine"))
+        }
+    }
+
+    /// 交给执行循环挂起的 Future
+    pub(crate) fn set_pending(&mut self, future: LuaFuture) {
+        self.pending = Some(future);
+    }
+
+    /// 挂起中的异步函数调用
+    pub(crate) fn suspended(&mut self) -> Option<&mut Suspended> {
+        self.suspended.as_mut()
+    }
+
+    /// 异步函数调用完成：放置其返回值，继续执行调用它的 Lua 函数直到调用栈回到 depth 层或再次挂起
+    pub(crate) fn resume_call(&mut self, values: Result<Vec<Value>, LuaError>, depth: usize) -> Result<(), LuaError> {
+        let Suspended { ifunc, want, .. } = self.suspended.take().unwrap();
+        let values = values?;
+        self.stack.truncate(ifunc);
+        let n = values.len();
+        self.stack.extend(values);
+        self.place_results(ifunc, ifunc, n, want);
+        if self.frames.len() == depth {
+            return Ok(());
+        }
+        if want != 0 {
+            let (closure, base, _) = self.current_frame();
+            ensure_stack(self, base + closure.proto.max_stack);
+        }
+        self.run(depth)
+    }
+
+    /// 放弃位于 ifunc 的调用（call_async 的 Future 未完成就被丢弃）：弹出其栈帧，不关闭待关闭变量
+    pub(crate) fn abandon_call(&mut self, ifunc: usize, depth: usize) {
+        self.suspended = None;
+        self.pending = None;
+        self.unwind(depth);
+        self.tbc.retain(|&i| i < ifunc);
+        self.stack.truncate(ifunc);
+    }
+
+    /// 进入或离开 call_async：进入时异步函数可以挂起
+    pub(crate) fn set_async(&mut self, in_async: bool) {
+        self.in_async = in_async;
+        self.yieldable = in_async;
+    }
+
+    /// 执行栈帧直到调用栈回到 depth 层，出错时弹出这期间的栈帧并关闭其上值
+    fn run(&mut self, depth: usize) -> Result<(), LuaError> {
+        let result = self.execute_frames(depth).map_err(|e| self.handle_error(e));
+        if result.is_err() {
+            self.unwind(depth);
+        }
+        result
+    }
+
+    /// 设置消息处理函数（None 为不处理），返回原来的处理函数；pcall 与 xpcall 在调用期间设置，返回后恢复
+    pub(crate) fn set_msgh(&mut self, msgh: Option<Value>) -> Option<Value> {
+        std::mem::replace(&mut self.msgh, msgh)
+    }
+
+    /// 有消息处理函数时以错误值调用它（调用栈尚未展开，处理函数可以取得出错处的回溯信息），
+    /// 以其第一个返回值作为新的错误值；处理函数只调用一次，自身出错时以这个错误作为结果，中断不经过处理
+    pub(crate) fn handle_error(&mut self, e: LuaError) -> LuaError {
+        let LuaError::Runtime(v) = e else {
+            return e;
+        };
+        match self.msgh.take() {
+            Some(h) => match self.call(h, vec![v]) {
+                Ok(rets) => LuaError::Runtime(rets.into_iter().next().unwrap_or(Value::Nil)),
+                Err(e) => e,
+            },
+            None => LuaError::Runtime(v),
+        }
+    }
+
+    /// 弹出 depth 层以上的栈帧并关闭其上值
+    fn unwind(&mut self, depth: usize) {
+        while self.frames.len() > depth {
+            let frame = self.frames.pop().unwrap();
+            self.close_upvalues(frame.base);
+        }
+    }
+
+    /// 当前栈帧的闭包、寄存器基址与指令位置
+    fn current_frame(&self) -> (Rc<LuaClosure>, usize, usize) {
+        let frame = self.frames.last().unwrap();
+        (frame.closure.clone(), frame.base, frame.pc)
+    }
+
+    /// 字节码执行循环
+    /// - 栈（stack）被用作寄存器文件：寄存器 i 对应 stack[base + i]
+    /// - 栈的长度在多返回值的调用、... 之后表示“栈顶”，供后续 Call/Return/SetList 使用
+    /// - 调用 Lua 函数时保存当前 pc 并切换到新栈帧，返回时恢复调用者的栈帧
+    fn execute_frames(&mut self, depth: usize) -> Result<(), LuaError> {
+        let (mut closure, mut base, mut pc) = self.current_frame();
+        loop {
+            let code = closure.proto.byte_codes[pc].decode();
+            pc += 1;
+            // 保存 pc 以便出错信息、钩子与调试接口取得当前行
+            self.frames.last_mut().unwrap().pc = pc;
+            if self.hook_mask & (MASK_LINE | MASK_COUNT) != 0 {
+                self.trace_exec(pc - 1)?;
+            }
+            if let Some(profiler) = self.profiler.as_deref_mut() {
+                profiler.tick(&self.frames, pc - 1);
+            }
             match code {
                 ByteCode::LoadConst(dst, idx) => {
-                    let const_idx = *idx as usize;
-                    let val = proto.constants[const_idx].clone();
-                    ensure_stack(self, *dst as usize);
-                    self.stack[*dst as usize] = val;
+                    let val = closure.proto.constants[idx as usize].clone();
+                    self.set_reg(base, dst, val);
                 }
                 ByteCode::LoadNil(dst) => {
-                    ensure_stack(self, *dst as usize);
-                    self.stack[*dst as usize] = Value::Nil;
+                    self.set_reg(base, dst, Value::Nil);
                 }
                 ByteCode::LoadBool(dst, b) => {
-                    ensure_stack(self, *dst as usize);
-                    self.stack[*dst as usize] = Value::Boolean(*b);
+                    self.set_reg(base, dst, Value::Boolean(b));
                 }
                 ByteCode::LoadInt(dst, n) => {
-                    ensure_stack(self, *dst as usize);
-                    self.stack[*dst as usize] = Value::Integer(*n as i64);
+                    self.set_reg(base, dst, Value::Integer(n as i64));
                 }
                 ByteCode::Move(dst, src) => {
-                    ensure_stack(self, *dst as usize);
-                    ensure_stack(self, *src as usize);
-                    self.stack[*dst as usize] = self.stack[*src as usize].clone();
+                    let val = self.get_reg(base, src);
+                    self.set_reg(base, dst, val);
                 }
                 ByteCode::GetGlobal(dst, cidx) => {
-                    // cidx 是常数池中保存变量名的索引
-                    let name = proto.constants[*cidx as usize].clone();
-                    if let Some(key) = value_to_string(&name) {
-                        let val = self.globals.get(&key).cloned().unwrap_or(Value::Nil);
-                        ensure_stack(self, *dst as usize);
-                        self.stack[*dst as usize] = val;
-                    } else {
-                        ensure_stack(self, *dst as usize);
-                        self.stack[*dst as usize] = Value::Nil;
-                    }
+                    // cidx 是常数池中保存变量名的索引，直接以该字符串值及其预先算好的哈希查找
+                    let val = self.get_global_const(&closure.proto, cidx);
+                    self.set_reg(base, dst, val);
                 }
                 ByteCode::SetGlobal(dst_const, src_reg) => {
                     // dst_const: 常数池中保存目标全局变量名的索引
-                    let name = proto.constants[*dst_const as usize].clone();
-                    if let Some(key) = value_to_string(&name) {
-                        ensure_stack(self, *src_reg as usize);
-                        let val = self.stack[*src_reg as usize].clone();
-                        self.globals.insert(key, val);
-                    }
+                    let val = self.get_reg(base, src_reg);
+                    self.set_global_const(&closure.proto, dst_const, val);
                 }
                 ByteCode::SetGlobalConst(dst_const, cidx) => {
-                    let name = proto.constants[*dst_const as usize].clone();
-                    if let Some(key) = value_to_string(&name) {
-                        let val = proto.constants[*cidx as usize].clone();
-                        self.globals.insert(key, val);
-                    }
+                    let val = closure.proto.constants[cidx as usize].clone();
+                    self.set_global_const(&closure.proto, dst_const.into(), val);
                 }
                 ByteCode::SetGlobalGlobal(dst_const, src_const) => {
-                    let name_dst = proto.constants[*dst_const as usize].clone();
-                    let name_src = proto.constants[*src_const as usize].clone();
-                    if let (Some(kd), Some(ks)) = (value_to_string(&name_dst), value_to_string(&name_src)) {
-                        if let Some(v) = self.globals.get(&ks) {
-                            self.globals.insert(kd, v.clone());
-                        } else {
-                            self.globals.insert(kd, Value::Nil);
+                    let val = self.get_global_const(&closure.proto, src_const.into());
+                    self.set_global_const(&closure.proto, dst_const.into(), val);
+                }
+                ByteCode::GetUpvalue(dst, idx) => {
+                    let val = match &*closure.upvalues[idx as usize].borrow() {
+                        Upvalue::Open(i) => self.stack.get(*i).cloned().unwrap_or(Value::Nil),
+                        Upvalue::Closed(v) => v.clone(),
+                    };
+                    self.set_reg(base, dst, val);
+                }
+                ByteCode::SetUpvalue(idx, src) => {
+                    let val = self.get_reg(base, src);
+                    match &mut *closure.upvalues[idx as usize].borrow_mut() {
+                        Upvalue::Open(i) => {
+                            let i = *i;
+                            ensure_stack(self, i + 1);
+                            self.stack[i] = val;
                         }
+                        Upvalue::Closed(v) => *v = val,
                     }
                 }
-                ByteCode::Call(arg_reg, _ret) => {
-                    // 约定：函数位于 arg_reg-1，参数从 arg_reg 开始
-                    let arg_index = *arg_reg as usize;
-                    if arg_index == 0 { continue; }
-                    let func_reg = arg_index - 1;
-                    ensure_stack(self, func_reg);
-                    match &self.stack[func_reg] {
-                        Value::Function(f) => {
-                            // 内置函数接口为 fn(&mut ExeState) -> i32
-                            // 被调用函数可以直接访问 state.stack 中的参数
-                            let _ret_count = f(self);
-                            // 返回值处理：当前简化为不设置返回寄存器
+                ByteCode::GetField(dst, t, kidx) => {
+                    let table = self.get_reg(base, t);
+                    let val = self.index(&table, &closure.proto.constants[kidx as usize])?;
+                    self.set_reg(base, dst, val);
+                }
+                ByteCode::GetTable(dst, t, k) => {
+                    let fast = match self.stack.get(base + k as usize) {
+                        Some(&Value::Integer(i)) => self.array_get(base + t as usize, i),
+                        _ => None,
+                    };
+                    let val = match fast {
+                        Some(val) => val,
+                        None => {
+                            let table = self.get_reg(base, t);
+                            let key = self.get_reg(base, k);
+                            self.index(&table, &key)?
+                        }
+                    };
+                    self.set_reg(base, dst, val);
+                }
+                ByteCode::GetInt(dst, t, n) => {
+                    let val = match self.array_get(base + t as usize, n as i64) {
+                        Some(val) => val,
+                        None => {
+                            let table = self.get_reg(base, t);
+                            self.index(&table, &Value::Integer(n as i64))?
                         }
-                        _ => {
-                            // 非函数值调用，忽略或报错（这里仅打印）
-                            eprintln!("attempt to call non-function value");
+                    };
+                    self.set_reg(base, dst, val);
+                }
+                ByteCode::NewTable(dst, narray, nmap) => {
+                    let table = Table::new(narray as usize, nmap as usize);
+                    self.set_reg(base, dst, Value::Table(Rc::new(RefCell::new(table))));
+                }
+                ByteCode::SetTable(t, k, v) => {
+                    let key = self.get_reg(base, k);
+                    let val = self.get_reg(base, v);
+                    let val = match key {
+                        Value::Integer(i) => self.array_set(base + t as usize, i, val),
+                        _ => Some(val),
+                    };
+                    if let Some(val) = val {
+                        self.set_table(self.get_reg(base, t), key, val)?;
+                    }
+                }
+                ByteCode::SetInt(t, n, v) => {
+                    let val = self.get_reg(base, v);
+                    if let Some(val) = self.array_set(base + t as usize, n as i64, val) {
+                        self.set_table(self.get_reg(base, t), Value::Integer(n as i64), val)?;
+                    }
+                }
+                ByteCode::SetField(t, kidx, v) => {
+                    let key = closure.proto.constants[kidx as usize].clone();
+                    let val = self.get_reg(base, v);
+                    self.set_table(self.get_reg(base, t), key, val)?;
+                }
+                ByteCode::SetList(t, n, batch) => {
+                    let batch = match batch {
+                        255 => {
+                            let ByteCode::ExtraArg(ax) = closure.proto.byte_codes[pc].decode() else {
+                                panic!("SetList without ExtraArg")
+                            };
+                            pc += 1;
+                            ax as usize
                         }
+                        b => b as usize,
+                    };
+                    let first = base + t as usize + 1;
+                    let values = if n == 0 {
+                        self.stack.split_off(first.min(self.stack.len()))
+                    } else {
+                        (0..n).map(|i| self.get_reg(base, t + 1 + i)).collect()
+                    };
+                    match self.get_reg(base, t) {
+                        Value::Table(table) => table.borrow_mut().set_list(batch * FIELDS_PER_FLUSH, values),
+                        _ => panic!("SetList on non-table"),
+                    }
+                    ensure_stack(self, base + closure.proto.max_stack);
+                }
+                ByteCode::Closure(dst, idx) => {
+                    let proto = closure.proto.protos[idx as usize].clone();
+                    let upvalues = proto
+                        .upindexes
+                        .iter()
+                        .map(|up| match *up {
+                            UpIndex::Local(i) => self.open_upvalue(base + i),
+                            UpIndex::Upvalue(i) => closure.upvalues[i].clone(),
+                        })
+                        .collect();
+                    let f = Value::LuaFunction(Rc::new(LuaClosure { proto, upvalues }));
+                    self.set_reg(base, dst, f);
+                }
+                ByteCode::Self_(dst, obj, kidx) => {
+                    let obj = self.get_reg(base, obj);
+                    let method = self.index(&obj, &closure.proto.constants[kidx as usize])?;
+                    self.set_reg(base, dst + 1, obj);
+                    self.set_reg(base, dst, method);
+                }
+                ByteCode::Jump(jmp) => {
+                    pc = (pc as isize + jmp as isize) as usize;
+                    // 向后跳转（循环、goto）与 ForLoop 一样检查中断
+                    if jmp < 0 {
+                        self.check_interrupt()?;
+                    }
+                }
+                ByteCode::TestAndJump(r, jmp) => {
+                    if !self.get_reg(base, r).is_truthy() {
+                        pc = (pc as isize + jmp as isize) as usize;
+                    }
+                }
+                ByteCode::TestOrJump(r, jmp) => {
+                    if self.get_reg(base, r).is_truthy() {
+                        pc = (pc as isize + jmp as isize) as usize;
+                    }
+                }
+                ByteCode::ForPrep(a, skip) => {
+                    if !self.for_prep(base + a as usize)? {
+                        pc += skip as usize;
+                    }
+                }
+                ByteCode::ForLoop(a, back) => {
+                    let ra = base + a as usize;
+                    let again = match (&self.stack[ra], &self.stack[ra + 1], &self.stack[ra + 2]) {
+                        // 整数循环：A+1 中是剩余的迭代次数
+                        (&Value::Integer(i), &Value::Integer(count), &Value::Integer(step)) => {
+                            (count as u64 > 0).then(|| {
+                                self.stack[ra + 1] = Value::Integer((count as u64 - 1) as i64);
+                                Value::Integer(i.wrapping_add(step))
+                            })
+                        }
+                        (&Value::Float(i), &Value::Float(limit), &Value::Float(step)) => {
+                            let i = i + step;
+                            let go_on = if step > 0.0 { i <= limit } else { limit <= i };
+                            go_on.then_some(Value::Float(i))
+                        }
+                        _ => None,
+                    };
+                    if let Some(i) = again {
+                        self.stack[ra] = i.clone();
+                        self.stack[ra + 3] = i;
+                        pc -= back as usize;
+                        self.check_interrupt()?;
+                    }
+                }
+                ByteCode::TForCall(a, nvars) => {
+                    // 把迭代函数、状态与控制变量复制到循环变量的位置，像 Call 一样调用
+                    let ra = base + a as usize;
+                    ensure_stack(self, ra + 7);
+                    for i in 0..3 {
+                        self.stack[ra + 4 + i] = self.stack[ra + i].clone();
+                    }
+                    self.rust_caller = false;
+                    if self.precall(ra + 4, 2, nvars as usize + 1)? {
+                        (closure, base, pc) = self.current_frame();
+                    } else if self.suspended.is_some() {
+                        return Ok(());
+                    } else {
+                        ensure_stack(self, base + closure.proto.max_stack);
+                    }
+                }
+                ByteCode::TForLoop(a, back) => {
+                    let ra = base + a as usize;
+                    if self.stack[ra + 4] != Value::Nil {
+                        self.stack[ra + 2] = self.stack[ra + 4].clone();
+                        pc -= back as usize;
+                        self.check_interrupt()?;
+                    }
+                }
+                ByteCode::Neg(dst, src) => {
+                    let v = self.get_reg(base, src);
+                    let r = match arith::neg(&v) {
+                        Some(r) => r,
+                        None => self.unary_metamethod("__unm", &v, "perform arithmetic on")?,
+                    };
+                    self.set_reg(base, dst, r);
+                }
+                ByteCode::Not(dst, src) => {
+                    let v = self.get_reg(base, src);
+                    self.set_reg(base, dst, Value::Boolean(!v.is_truthy()));
+                }
+                ByteCode::BitNot(dst, src) => {
+                    let v = self.get_reg(base, src);
+                    let r = match arith::bit_not(&v) {
+                        Some(r) => r,
+                        None if arith::to_arith_number(&v).is_some() => {
+                            return Err(self.error("number has no integer representation"))
+                        }
+                        None => self.unary_metamethod("__bnot", &v, "perform bitwise operation on")?,
+                    };
+                    self.set_reg(base, dst, r);
+                }
+                ByteCode::Len(dst, src) => {
+                    let v = self.get_reg(base, src);
+                    let r = self.len(&v)?;
+                    self.set_reg(base, dst, r);
+                }
+                ByteCode::Add(dst, a, b) => self.arith_op(ArithOp::Add, base, dst, a, b)?,
+                ByteCode::Sub(dst, a, b) => self.arith_op(ArithOp::Sub, base, dst, a, b)?,
+                ByteCode::Mul(dst, a, b) => self.arith_op(ArithOp::Mul, base, dst, a, b)?,
+                ByteCode::Div(dst, a, b) => self.arith_op(ArithOp::Div, base, dst, a, b)?,
+                ByteCode::Idiv(dst, a, b) => self.arith_op(ArithOp::Idiv, base, dst, a, b)?,
+                ByteCode::Mod(dst, a, b) => self.arith_op(ArithOp::Mod, base, dst, a, b)?,
+                ByteCode::Pow(dst, a, b) => self.arith_op(ArithOp::Pow, base, dst, a, b)?,
+                ByteCode::BitAnd(dst, a, b) => self.arith_op(ArithOp::BitAnd, base, dst, a, b)?,
+                ByteCode::BitXor(dst, a, b) => self.arith_op(ArithOp::BitXor, base, dst, a, b)?,
+                ByteCode::BitOr(dst, a, b) => self.arith_op(ArithOp::BitOr, base, dst, a, b)?,
+                ByteCode::ShiftL(dst, a, b) => self.arith_op(ArithOp::ShiftL, base, dst, a, b)?,
+                ByteCode::ShiftR(dst, a, b) => self.arith_op(ArithOp::ShiftR, base, dst, a, b)?,
+                ByteCode::Concat(dst, a, b) => {
+                    let a = self.get_reg(base, a);
+                    let b = self.get_reg(base, b);
+                    let r = self.concat(&a, &b)?;
+                    self.set_reg(base, dst, r);
+                }
+                ByteCode::Equal(dst, a, b) => {
+                    let a = self.get_reg(base, a);
+                    let b = self.get_reg(base, b);
+                    let r = self.equal(&a, &b)?;
+                    self.set_reg(base, dst, Value::Boolean(r));
+                }
+                ByteCode::NotEq(dst, a, b) => {
+                    let a = self.get_reg(base, a);
+                    let b = self.get_reg(base, b);
+                    let r = self.equal(&a, &b)?;
+                    self.set_reg(base, dst, Value::Boolean(!r));
+                }
+                ByteCode::LesThan(dst, a, b) => {
+                    let fast = match (self.stack.get(base + a as usize), self.stack.get(base + b as usize)) {
+                        (Some(x), Some(y)) => arith::less_than(x, y),
+                        _ => None,
+                    };
+                    let r = match fast {
+                        Some(r) => r,
+                        None => {
+                            let a = self.get_reg(base, a);
+                            let b = self.get_reg(base, b);
+                            self.compare_metamethod("__lt", &a, &b)?
+                        }
+                    };
+                    self.set_reg(base, dst, Value::Boolean(r));
+                }
+                ByteCode::LesEq(dst, a, b) => {
+                    let fast = match (self.stack.get(base + a as usize), self.stack.get(base + b as usize)) {
+                        (Some(x), Some(y)) => arith::less_equal(x, y),
+                        _ => None,
+                    };
+                    let r = match fast {
+                        Some(r) => r,
+                        None => {
+                            let a = self.get_reg(base, a);
+                            let b = self.get_reg(base, b);
+                            self.compare_metamethod("__le", &a, &b)?
+                        }
+                    };
+                    self.set_reg(base, dst, Value::Boolean(r));
+                }
+                // 由前一条指令读取并跳过
+                ByteCode::ExtraArg(_) => unreachable!("stray ExtraArg"),
+                ByteCode::Close(reg) => {
+                    self.close_upvalues(base + reg as usize);
+                    self.close_tbc(base + reg as usize, None)?;
+                }
+                ByteCode::Tbc(reg) => {
+                    let v = self.get_reg(base, reg);
+                    // nil 与 false 不需要关闭
+                    if v.is_truthy() {
+                        if self.metamethod(&v, "__close") == Value::Nil {
+                            let name = self.get_local(0, reg as i64 + 1).map_or("?".to_string(), |(n, _)| n);
+                            return Err(self.error(format!("variable '{name}' got a non-closable value")));
+                        }
+                        self.tbc.push(base + reg as usize);
+                    }
+                }
+                ByteCode::VarArgs(dst, want) => {
+                    let dst = base + dst as usize;
+                    let varargs = &self.frames.last().unwrap().varargs;
+                    if want == 0 {
+                        let varargs = varargs.clone();
+                        self.stack.resize(dst, Value::Nil);
+                        self.stack.extend(varargs);
+                    } else {
+                        let values: Vec<_> = (0..want as usize - 1)
+                            .map(|i| varargs.get(i).cloned().unwrap_or(Value::Nil))
+                            .collect();
+                        ensure_stack(self, dst + values.len());
+                        for (i, v) in values.into_iter().enumerate() {
+                            self.stack[dst + i] = v;
+                        }
+                    }
+                }
+                ByteCode::Call(func, nargs, want) => {
+                    let ifunc = base + func as usize;
+                    let nargs = if nargs == 0 {
+                        self.stack.len() - ifunc - 1
+                    } else {
+                        nargs as usize - 1
+                    };
+                    ensure_stack(self, ifunc + 1 + nargs);
+                    self.rust_caller = false;
+                    if self.precall(ifunc, nargs, want as usize)? {
+                        (closure, base, pc) = self.current_frame();
+                    } else if self.suspended.is_some() {
+                        return Ok(());
+                    } else if want != 0 {
+                        ensure_stack(self, base + closure.proto.max_stack);
+                    }
+                }
+                ByteCode::Return(first, n) => {
+                    let first = base + first as usize;
+                    let n = if n == 0 {
+                        self.stack.len() - first
+                    } else {
+                        n as usize - 1
+                    };
+                    ensure_stack(self, first + n);
+                    if self.hook_mask & MASK_RETURN != 0 {
+                        self.call_hook(HookEvent::Return)?;
+                    }
+                    self.close_upvalues(base);
+                    self.close_tbc(base, None)?;
+                    let frame = self.frames.pop().unwrap();
+                    self.place_results(base - 1, first, n, frame.want);
+                    if self.frames.len() == depth {
+                        return Ok(());
+                    }
+                    (closure, base, pc) = self.current_frame();
+                    if frame.want != 0 {
+                        ensure_stack(self, base + closure.proto.max_stack);
                     }
                 }
             }
         }
     }
-}
\ No newline at end of file
+
+    /// 数值 for 循环的准备，ra 为初值所在的栈位置；返回是否执行循环体
+    /// 初值与步长都是整数时为整数循环：终值按步长方向取整，A+1 中改存迭代次数（视为无符号数）；
+    /// 否则三者都转为浮点数
+    fn for_prep(&mut self, ra: usize) -> Result<bool, LuaError> {
+        let (init, limit, step) = (self.stack[ra].clone(), self.stack[ra + 1].clone(), self.stack[ra + 2].clone());
+        if let (Value::Integer(init), Value::Integer(step)) = (&init, &step) {
+            let (init, step) = (*init, *step);
+            if step == 0 {
+                return Err(self.error("'for' step is zero"));
+            }
+            let limit = match limit {
+                Value::Integer(l) => l,
+                Value::Float(f) => {
+                    let f = if step > 0 { f.floor() } else { f.ceil() };
+                    if f >= -(i64::MIN as f64) {
+                        // 终值超过整数范围
+                        if step < 0 {
+                            return Ok(false);
+                        }
+                        i64::MAX
+                    } else if f >= i64::MIN as f64 {
+                        f as i64
+                    } else {
+                        // 终值小于整数范围，或为 NaN
+                        if step > 0 {
+                            return Ok(false);
+                        }
+                        i64::MIN
+                    }
+                }
+                _ => return Err(self.error("'for' limit must be a number")),
+            };
+            if if step > 0 { init > limit } else { init < limit } {
+                return Ok(false);
+            }
+            let count = if step > 0 {
+                (limit as u64).wrapping_sub(init as u64) / step as u64
+            } else {
+                (init as u64).wrapping_sub(limit as u64) / ((-(step + 1)) as u64 + 1)
+            };
+            self.stack[ra + 1] = Value::Integer(count as i64);
+            self.stack[ra + 3] = Value::Integer(init);
+            return Ok(true);
+        }
+
+        let float = |v: &Value| match v {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        };
+        let Some(limit) = float(&limit) else {
+            return Err(self.error("'for' limit must be a number"));
+        };
+        let Some(step) = float(&step) else {
+            return Err(self.error("'for' step must be a number"));
+        };
+        let Some(init) = float(&init) else {
+            return Err(self.error("'for' initial value must be a number"));
+        };
+        if step == 0.0 {
+            return Err(self.error("'for' step is zero"));
+        }
+        if if step > 0.0 { limit < init } else { init < limit } {
+            return Ok(false);
+        }
+        self.stack[ra] = Value::Float(init);
+        self.stack[ra + 1] = Value::Float(limit);
+        self.stack[ra + 2] = Value::Float(step);
+        self.stack[ra + 3] = Value::Float(init);
+        Ok(true)
+    }
+
+    /// 快速读取：栈位置 it 上的表的数组部分中第 i 项，不是表、不在数组部分或为 nil（可能需要元方法）时返回 None
+    fn array_get(&self, it: usize, i: i64) -> Option<Value> {
+        match self.stack.get(it) {
+            Some(Value::Table(t)) => {
+                let t = t.borrow();
+                let v = t.array.get((i as usize).wrapping_sub(1))?;
+                (*v != Value::Nil).then(|| v.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// 快速写入：栈位置 it 上的表的数组部分中第 i 项（原值不为 nil 或表没有元表，不涉及 __newindex）
+    /// 写入成功返回 None，否则把值交还调用者走一般的路径
+    fn array_set(&mut self, it: usize, i: i64, val: Value) -> Option<Value> {
+        if let Some(Value::Table(t)) = self.stack.get(it) {
+            let mut t = t.borrow_mut();
+            let raw = t.metatable.is_none();
+            if let Some(slot) = t.array.get_mut((i as usize).wrapping_sub(1)) {
+                if *slot != Value::Nil || raw {
+                    *slot = val;
+                    return None;
+                }
+            }
+        }
+        Some(val)
+    }
+
+    /// 设置调试钩子：mask 为 MASK_* 的按位或，count 为计数事件的间隔（指令数，为 0 时不触发计数事件）
+    /// 同一时间只有一个钩子，新设置的钩子替换原有的；钩子函数执行期间不会再触发钩子
+    pub fn set_hook<F>(&mut self, mask: u8, count: usize, f: F)
+    where
+        F: FnMut(&mut ExeState, HookEvent) -> Result<(), LuaError> + MaybeSend + 'static,
+    {
+        self.install_hook(mask, count, Box::new(f), None);
+    }
+
+    /// 移除调试钩子
+    pub fn remove_hook(&mut self) {
+        self.hook = None;
+        self.hook_mask = 0;
+    }
+
+    /// 设置钩子，lua_func 为 debug.sethook 传入的 Lua 函数
+    pub(crate) fn install_hook(&mut self, mask: u8, count: usize, f: HookFn, lua_func: Option<Value>) {
+        let mask = if count == 0 { mask & !MASK_COUNT } else { mask };
+        if mask == 0 {
+            self.remove_hook();
+            return;
+        }
+        self.hook = Some(Hook {
+            func: Rc::new(RefCell::new(f)),
+            mask,
+            count,
+            lua_func,
+        });
+        self.hook_mask = mask;
+        self.hook_left = count;
+    }
+
+    /// 当前钩子的 Lua 函数、掩码与计数，供 debug.gethook 使用
+    pub(crate) fn hook_info(&self) -> Option<(Option<Value>, u8, usize)> {
+        self.hook.as_ref().map(|h| (h.lua_func.clone(), h.mask, h.count))
+    }
+
+    /// 调用钩子函数，钩子函数执行期间不再触发
+    fn call_hook(&mut self, event: HookEvent) -> Result<(), LuaError> {
+        if self.in_hook {
+            return Ok(());
+        }
+        let Some(hook) = &self.hook else {
+            return Ok(());
+        };
+        let func = hook.func.clone();
+        self.in_hook = true;
+        let result = (func.borrow_mut())(self, event);
+        self.in_hook = false;
+        result
+    }
+
+    /// 执行 pc 处的指令前触发计数与行事件
+    /// 进入新的一行、向回跳转（如循环）或刚进入函数时触发行事件
+    fn trace_exec(&mut self, pc: usize) -> Result<(), LuaError> {
+        if self.hook_mask & MASK_COUNT != 0 {
+            self.hook_left = self.hook_left.saturating_sub(1);
+            if self.hook_left == 0 {
+                self.hook_left = self.hook.as_ref().map_or(0, |h| h.count);
+                self.call_hook(HookEvent::Count)?;
+            }
+        }
+        if self.hook_mask & MASK_LINE != 0 {
+            let frame = self.frames.last_mut().unwrap();
+            let lineinfo = &frame.closure.proto.lineinfo;
+            let line = lineinfo[pc];
+            let new_line = match frame.traced_pc {
+                Some(last) => pc <= last || lineinfo[last] != line,
+                None => true,
+            };
+            frame.traced_pc = Some(pc);
+            if new_line {
+                self.call_hook(HookEvent::Line(line))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 开始性能分析，已在进行的分析被丢弃
+    pub fn start_profile(&mut self, mode: ProfileMode) {
+        self.profiler = Some(Box::new(Profiler::new(mode)));
+    }
+
+    /// 结束性能分析，返回结果；没有在进行分析时返回 None
+    pub fn stop_profile(&mut self) -> Option<Profile> {
+        self.profiler.take().map(|p| p.finish())
+    }
+
+    /// Lua 函数的调用层数
+    pub fn call_depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// 第 level 层调用栈帧，0 为正在执行的 Lua 函数
+    fn frame_at(&self, level: usize) -> Option<&CallFrame> {
+        self.frames.len().checked_sub(level + 1).map(|i| &self.frames[i])
+    }
+
+    /// 第 level 层（0 为正在执行的 Lua 函数）调用的调试信息，超出调用栈时返回 None
+    pub fn get_info(&self, level: usize) -> Option<DebugInfo> {
+        let frame = self.frame_at(level)?;
+        let mut info = DebugInfo::of_function(&Value::LuaFunction(frame.closure.clone()));
+        info.currentline = Some(frame.current_line());
+        Some(info)
+    }
+
+    /// 第 level 层调用的第 n 个局部变量的名字与值
+    /// n 从 1 开始，按当前位置上有效的局部变量的声明顺序编号；n 为负时取第 -n 个可变参数
+    pub fn get_local(&self, level: usize, n: i64) -> Option<(String, Value)> {
+        let frame = self.frame_at(level)?;
+        if n < 0 {
+            let v = frame.varargs.get((-n - 1) as usize)?;
+            return Some(("(vararg)".to_string(), v.clone()));
+        }
+        let pc = frame.pc.saturating_sub(1);
+        let (reg, var) = frame
+            .closure
+            .proto
+            .locvars
+            .iter()
+            .filter(|v| v.startpc <= pc && pc < v.endpc)
+            .enumerate()
+            .nth((n as usize).checked_sub(1)?)?;
+        let value = self.stack.get(frame.base + reg).cloned().unwrap_or(Value::Nil);
+        Some((var.name.clone(), value))
+    }
+
+    /// 从第 level 层开始的调用栈回溯信息，格式与 debug.traceback 相同
+    /// 与 C 实现一致，超过 21 层时只列出最前的 10 层与最后的 11 层，中间的层数以一行 "..." 代替
+    pub fn traceback(&self, level: usize) -> String {
+        const LEVELS1: usize = 10;
+        const LEVELS2: usize = 11;
+        let mut s = String::from("stack traceback:");
+        let skipped = self.frames.len().saturating_sub(level + LEVELS1 + LEVELS2);
+        let mut l = level;
+        while let Some(info) = self.get_info(l) {
+            if skipped > 0 && l == level + LEVELS1 {
+                s.push_str(&format!("\n\t...\t(skipping {skipped} levels)"));
+                l += skipped;
+                continue;
+            }
+            l += 1;
+            let line = info.currentline.unwrap_or(0);
+            if info.what == "main" {
+                s.push_str(&format!("\n\t{}:{line}: in main chunk", info.short_src));
+            } else {
+                s.push_str(&format!(
+                    "\n\t{}:{line}: in function <{}:{}>",
+                    info.short_src, info.short_src, info.linedefined
+                ));
+            }
+        }
+        s
+    }
+
+    /// 取值的元表中名为 event 的元方法，没有时为 nil
+    pub(crate) fn metamethod(&self, v: &Value, event: &str) -> Value {
+        let meta = match v {
+            Value::Table(t) => t.borrow().metatable.clone(),
+            Value::UserData(u) => u.metatable.clone(),
+            _ => None,
+        };
+        meta.map_or(Value::Nil, |m| m.borrow().get_str(event))
+    }
+
+    /// 以 (a, b) 调用 a 或 b（先查 a）的元方法 event，取第一个返回值；都没有时返回 None
+    fn call_metamethod(&mut self, event: &str, a: &Value, b: &Value) -> Result<Option<Value>, LuaError> {
+        let mut mm = self.metamethod(a, event);
+        if mm == Value::Nil {
+            mm = self.metamethod(b, event);
+        }
+        if mm == Value::Nil {
+            return Ok(None);
+        }
+        let results = self.call(mm, vec![a.clone(), b.clone()])?;
+        Ok(Some(results.into_iter().next().unwrap_or(Value::Nil)))
+    }
+
+    /// 一元运算的元方法（以操作数自身作为两个参数），没有时报错 "attempt to {what} a xx value"
+    fn unary_metamethod(&mut self, event: &str, v: &Value, what: &str) -> Result<Value, LuaError> {
+        match self.call_metamethod(event, v, v)? {
+            Some(r) => Ok(r),
+            None => Err(self.error(format!("attempt to {what} a {} value", v.type_name()))),
+        }
+    }
+
+    /// 执行二元算术或位运算：数字直接计算，否则查找元方法
+    fn arith_op(&mut self, op: ArithOp, base: usize, dst: u8, a: u8, b: u8) -> Result<(), LuaError> {
+        // 快速路径：两个整数或两个浮点数的加减乘
+        let fast = match (self.stack.get(base + a as usize), self.stack.get(base + b as usize)) {
+            (Some(&Value::Integer(x)), Some(&Value::Integer(y))) => match op {
+                ArithOp::Add => Some(Value::Integer(x.wrapping_add(y))),
+                ArithOp::Sub => Some(Value::Integer(x.wrapping_sub(y))),
+                ArithOp::Mul => Some(Value::Integer(x.wrapping_mul(y))),
+                _ => None,
+            },
+            (Some(&Value::Float(x)), Some(&Value::Float(y))) => match op {
+                ArithOp::Add => Some(Value::Float(x + y)),
+                ArithOp::Sub => Some(Value::Float(x - y)),
+                ArithOp::Mul => Some(Value::Float(x * y)),
+                ArithOp::Div => Some(Value::Float(x / y)),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(r) = fast {
+            self.set_reg(base, dst, r);
+            return Ok(());
+        }
+        let a = self.get_reg(base, a);
+        let b = self.get_reg(base, b);
+        let r = match arith::arith(op, &a, &b).map_err(|msg| self.error(msg))? {
+            Some(r) => r,
+            None => match self.call_metamethod(op.event(), &a, &b)? {
+                Some(r) => r,
+                None => {
+                    let msg = if !op.is_bitwise() {
+                        let bad = if arith::to_arith_number(&a).is_some() { &b } else { &a };
+                        format!("attempt to perform arithmetic on a {} value", bad.type_name())
+                    } else if arith::to_arith_number(&a).is_some() && arith::to_arith_number(&b).is_some() {
+                        // 与 C 实现一致，数字字符串先转为数字，再判断能否转为整数
+                        "number has no integer representation".to_string()
+                    } else {
+                        let bad = if arith::to_arith_number(&a).is_some() { &b } else { &a };
+                        format!("attempt to perform bitwise operation on a {} value", bad.type_name())
+                    };
+                    return Err(self.error(msg));
+                }
+            },
+        };
+        self.set_reg(base, dst, r);
+        Ok(())
+    }
+
+    /// 字符串连接：字符串与数字直接连接，否则查找 __concat 元方法
+    fn concat(&mut self, a: &Value, b: &Value) -> Result<Value, LuaError> {
+        if let (Some(x), Some(y)) = (concat_piece(a), concat_piece(b)) {
+            return Ok(Value::from([x, y].concat()));
+        }
+        match self.call_metamethod("__concat", a, b)? {
+            Some(r) => Ok(r),
+            None => {
+                let bad = if concat_piece(a).is_some() { b } else { a };
+                Err(self.error(format!("attempt to concatenate a {} value", bad.type_name())))
+            }
+        }
+    }
+
+    /// 取长度：字符串为字节数，表优先使用 __len 元方法
+    fn len(&mut self, v: &Value) -> Result<Value, LuaError> {
+        if let Some(s) = v.as_bytes() {
+            return Ok(Value::Integer(s.len() as i64));
+        }
+        if let Some(r) = self.call_metamethod("__len", v, v)? {
+            return Ok(r);
+        }
+        match v {
+            Value::Table(t) => Ok(Value::Integer(t.borrow().border() as i64)),
+            _ => Err(self.error(format!("attempt to get length of a {} value", v.type_name()))),
+        }
+    }
+
+    /// 相等比较：原始相等，或同为表（用户数据）时使用 __eq 元方法
+    fn equal(&mut self, a: &Value, b: &Value) -> Result<bool, LuaError> {
+        if arith::raw_equal(a, b) {
+            return Ok(true);
+        }
+        match (a, b) {
+            (Value::Table(_), Value::Table(_)) | (Value::UserData(_), Value::UserData(_)) => {
+                Ok(self.call_metamethod("__eq", a, b)?.is_some_and(|r| r.is_truthy()))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// 大小比较的元方法 __lt/__le，没有时报错
+    fn compare_metamethod(&mut self, event: &str, a: &Value, b: &Value) -> Result<bool, LuaError> {
+        match self.call_metamethod(event, a, b)? {
+            Some(r) => Ok(r.is_truthy()),
+            None => {
+                let (ta, tb) = (a.type_name(), b.type_name());
+                Err(self.error(if ta == tb {
+                    format!("attempt to compare two {ta} values")
+                } else {
+                    format!("attempt to compare {ta} with {tb}")
+                }))
+            }
+        }
+    }
+
+    /// 取得指向栈位置 idx 的上值，已有的打开上值直接共享
+    fn open_upvalue(&mut self, idx: usize) -> Rc<RefCell<Upvalue>> {
+        let found = self
+            .open_upvalues
+            .iter()
+            .find(|up| matches!(*up.borrow(), Upvalue::Open(i) if i == idx));
+        if let Some(up) = found {
+            return up.clone();
+        }
+        let up = Rc::new(RefCell::new(Upvalue::Open(idx)));
+        self.open_upvalues.push(up.clone());
+        up
+    }
+
+    /// 关闭所有指向栈位置 from 及以上的上值：把栈上的值移入上值自身
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|up| {
+            let mut up = up.borrow_mut();
+            match *up {
+                Upvalue::Open(i) if i >= from => {
+                    *up = Upvalue::Closed(stack.get(i).cloned().unwrap_or(Value::Nil));
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    /// 逆序关闭栈位置 from 及以上的待关闭变量：以 (值, 错误值) 调用其 __close 元方法
+    /// err 为正在传播的错误（正常离开作用域时为 None）；某个 __close 出错时其余变量改为带着这个新错误关闭，
+    /// 最后返回最终的错误
+    fn close_tbc(&mut self, from: usize, mut err: Option<LuaError>) -> Result<(), LuaError> {
+        while let Some(&i) = self.tbc.last() {
+            if i < from {
+                break;
+            }
+            self.tbc.pop();
+            let v = self.stack.get(i).cloned().unwrap_or(Value::Nil);
+            let errv = match &err {
+                Some(LuaError::Runtime(e)) => e.clone(),
+                Some(LuaError::Interrupted) => Value::from("interrupted"),
+                None => Value::Nil,
+            };
+            let mm = self.metamethod(&v, "__close");
+            // __close 中的错误替换原来的错误，但中断不能被替换
+            if let Err(e) = self.call(mm, vec![v, errv]) {
+                if !matches!(err, Some(LuaError::Interrupted)) {
+                    err = Some(e);
+                }
+            }
+        }
+        match err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// 读取名字为常量 cidx 的全局变量
+    fn get_global_const(&self, proto: &FuncProto, cidx: u32) -> Value {
+        let i = cidx as usize;
+        self.globals.borrow().get_str_hashed(&proto.constants[i], proto.const_hashes[i])
+    }
+
+    /// 写入名字为常量 cidx 的全局变量
+    fn set_global_const(&self, proto: &FuncProto, cidx: u32, val: Value) {
+        let i = cidx as usize;
+        let key = proto.constants[i].clone();
+        self.globals.borrow_mut().set_str_hashed(key, proto.const_hashes[i], val);
+    }
+
+    /// 读取寄存器，超出栈长度视为 nil
+    fn get_reg(&self, base: usize, reg: u8) -> Value {
+        self.stack.get(base + reg as usize).cloned().unwrap_or(Value::Nil)
+    }
+
+    /// 写入寄存器：进入函数（precall）与每次调用返回后栈都至少有 base + max_stack 个槽，
+    /// 而字节码只写 max_stack 以内的寄存器，因此不必检查栈长度
+    fn set_reg(&mut self, base: usize, reg: u8, v: Value) {
+        self.stack[base + reg as usize] = v;
+    }
+
+    /// 按 Lua 语义写入键值：表中没有这个键时查元表 __newindex，用户数据查元表 __newindex
+    /// __newindex 为函数时以 (t, key, val) 调用；否则继续向 __newindex 的值写入
+    fn set_table(&mut self, t: Value, key: Value, val: Value) -> Result<(), LuaError> {
+        let mut t = t;
+        for _ in 0..MAX_TAG_LOOP {
+            let handler = match &t {
+                Value::Table(table) => {
+                    let handler = {
+                        let tbl = table.borrow();
+                        match &tbl.metatable {
+                            Some(m) if tbl.get(&key) == Value::Nil => m.borrow().get_str("__newindex"),
+                            _ => Value::Nil,
+                        }
+                    };
+                    if handler == Value::Nil {
+                        match key {
+                            Value::Nil => return Err(self.error("table index is nil")),
+                            Value::Float(f) if f.is_nan() => return Err(self.error("table index is NaN")),
+                            _ => (),
+                        }
+                        table.borrow_mut().set(key, val);
+                        return Ok(());
+                    }
+                    handler
+                }
+                Value::UserData(_) => match self.metamethod(&t, "__newindex") {
+                    Value::Nil => return Err(self.error("attempt to index a userdata value")),
+                    h => h,
+                },
+                v => return Err(self.error(format!("attempt to index a {} value", v.type_name()))),
+            };
+            match handler {
+                h @ (Value::Function(_) | Value::RustClosure(_) | Value::LuaFunction(_)) => {
+                    self.call(h, vec![t, key, val])?;
+                    return Ok(());
+                }
+                h => t = h,
+            }
+        }
+        Err(self.error("'__newindex' chain too long; possibly a loop"))
+    }
+}
+
+impl Default for ExeState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 辅助：确保 stack 至少有 len 个槽
+fn ensure_stack(state: &mut ExeState, len: usize) {
+    if state.stack.len() < len {
+        state.stack.resize(len, Value::Nil);
+    }
+}
+/// 辅助：字符串连接的操作数，字符串取其内容，数字转为字符串
+fn concat_piece(v: &Value) -> Option<Vec<u8>> {
+    match v {
+        Value::Integer(i) => Some(i.to_string().into_bytes()),
+        Value::Float(f) => Some(format_float(*f).into_bytes()),
+        _ => v.as_bytes().map(|b| b.to_vec()),
+    }
+}
+
+
+// 启用 send 特性时解释器状态可以在线程之间移动
+#[cfg(feature = "send")]
+const _: () = {
+    fn assert_send<T: Send>() {}
+    let _ = assert_send::<ExeState>;
+};
@@ -0,0 +1,44 @@
+-- 在参考实现上生成 expected.txt：lua5.4 gen.lua > expected.txt
+-- 现有的 expected.txt 不是这样生成的（见 tests/arith.rs），有 lua5.4 时应重新生成
+-- 每行为 "表达式<TAB>结果"，结果是 tostring 的输出（NaN 统一写作 nan），
+-- 出错时为 "error: " 加错误消息，去掉位置和变量名（如 "(constant '3.0')"）这类与运算语义无关的部分
+
+local operands = {
+  "0", "1", "(-1)", "3", "(-7)",
+  "9007199254740993", "9223372036854775807", "(-9223372036854775807 - 1)",
+  "0.0", "(-0.0)", "1.5", "(-2.5)", "(2^53)", "(2^63)", "(1/0)",
+  '"10"', '" 0x10 "', '"3.0"',
+}
+local binops = {
+  "+", "-", "*", "/", "//", "%", "^",
+  "&", "|", "~", "<<", ">>",
+  "==", "<", "<=",
+}
+
+local function show(expr)
+  local ok, r = pcall(load("return " .. expr))
+  if not ok then
+    r = r:gsub('^%[string ".-"%]:%d+: ', ""):gsub(" %(constant '.-'%)", "")
+    return "error: " .. r
+  elseif r ~= r then
+    return "nan"
+  end
+  return tostring(r)
+end
+
+local function emit(expr)
+  print(expr .. "\t" .. show(expr))
+end
+
+for _, op in ipairs({"-", "~"}) do
+  for _, a in ipairs(operands) do
+    emit(op .. a)
+  end
+end
+for _, op in ipairs(binops) do
+  for _, a in ipairs(operands) do
+    for _, b in ipairs(operands) do
+      emit(a .. " " .. op .. " " .. b)
+    end
+  end
+end
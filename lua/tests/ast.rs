@@ -0,0 +1,123 @@
+// 语法树：格式化输出后重新解析得到相同的语法树，各节点的位置与源代码一致
+
+use lua::ast::{parse, ExpKind, StatKind};
+use lua::fmt::format;
+use std::fs;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+fn dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// 去掉语法树调试输出中的位置信息，只比较结构
+fn structure(source: &[u8]) -> String {
+    let dump = format!("{:?}", parse(Cursor::new(source)).unwrap());
+    let mut out = String::new();
+    let mut rest = dump.as_str();
+    while let Some(i) = rest.find("Span {") {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + rest[i..].find('}').unwrap() + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[test]
+fn round_trip() {
+    // 所有能解析的测试脚本，格式化后的源代码解析得到的语法树与原来的相同
+    let mut n = 0;
+    for sub in ["tests/corpus", "tests/official"] {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir().join(sub)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(fs::read_dir(path).unwrap().map(|e| e.unwrap().path()));
+            } else {
+                files.push(path);
+            }
+        }
+        for path in files.into_iter().filter(|p| p.extension().is_some_and(|e| e == "lua")) {
+            let source = fs::read(&path).unwrap();
+            let Ok(formatted) = format(&source) else {
+                continue;
+            };
+            assert!(
+                structure(&source) == structure(&formatted),
+                "{} parses differently after formatting",
+                path.display()
+            );
+            n += 1;
+        }
+    }
+    assert!(n > 20);
+}
+
+#[test]
+fn spans() {
+    let source = "local t = {}\nfunction t.f(a)\n  return a +\n    1\nend\nprint(t.f(1))\n";
+    let block = parse(Cursor::new(source)).unwrap();
+    assert_eq!(block.stats.len(), 3);
+
+    let func = &block.stats[1];
+    assert!(matches!(func.kind, StatKind::Function(..)));
+    assert_eq!((func.span.line, func.span.end_line), (2, 5));
+    assert_eq!(
+        &source[func.span.start..func.span.end],
+        "function t.f(a)\n  return a +\n    1\nend"
+    );
+
+    let StatKind::Function(_, body) = &func.kind else {
+        unreachable!()
+    };
+    let ret = &body.body.stats[0];
+    let StatKind::Return(exps) = &ret.kind else {
+        panic!("expected return")
+    };
+    assert!(matches!(exps[0].kind, ExpKind::Binary(..)));
+    assert_eq!((exps[0].span.line, exps[0].span.end_line), (3, 4));
+    assert_eq!(&source[exps[0].span.start..exps[0].span.end], "a +\n    1");
+
+    let call = &block.stats[2];
+    assert_eq!(&source[call.span.start..call.span.end], "print(t.f(1))");
+}
+
+#[test]
+fn syntax_error() {
+    let e = parse(Cursor::new("local x <foo> = 1")).unwrap_err();
+    assert_eq!(e.line, 1);
+    assert!(e.msg.contains("unknown attribute"), "{}", e.msg);
+}
+
+/// 读到第 n 个字节时出错的输入
+struct FailingReader {
+    inner: Cursor<&'static [u8]>,
+    fail_at: u64,
+}
+
+impl Read for FailingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.inner.position() >= self.fail_at {
+            return Err(io::Error::other("disk on fire"));
+        }
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for FailingReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn read_error() {
+    // 读取出错不当作输入结束，而是作为错误报告
+    let input = FailingReader {
+        inner: Cursor::new(b"local x = 1\nlocal y = 2\n"),
+        fail_at: 16,
+    };
+    let e = parse(input).unwrap_err();
+    assert_eq!(e.line, 2);
+    assert!(e.msg.contains("cannot read source: disk on fire"), "{}", e.msg);
+}
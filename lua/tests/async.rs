@@ -0,0 +1,174 @@
+// 异步函数：Lua 代码调用时挂起，等 Future 完成后继续执行
+// 用手动轮询的本地执行器与模拟的异步服务检查挂起与恢复
+
+use lua::value::Value;
+use lua::vm::{ExeState, LuaError};
+use std::future::Future;
+use std::io::Cursor;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+type Slot = Arc<Mutex<Option<String>>>;
+
+/// 模拟的异步服务：请求一直未完成，直到测试调用 complete
+#[derive(Clone, Default)]
+struct Service {
+    requests: Arc<Mutex<Vec<(String, Slot)>>>,
+}
+
+impl Service {
+    fn fetch(&self, key: String) -> Fetch {
+        let slot = Slot::default();
+        self.requests.lock().unwrap().push((key, slot.clone()));
+        Fetch { slot }
+    }
+
+    /// 完成最早的请求，返回其键
+    fn complete(&self) -> String {
+        let (key, slot) = self.requests.lock().unwrap().remove(0);
+        *slot.lock().unwrap() = Some(format!("<{key}>"));
+        key
+    }
+
+    fn pending(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+}
+
+struct Fetch {
+    slot: Slot,
+}
+
+impl Future for Fetch {
+    type Output = String;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<String> {
+        match self.slot.lock().unwrap().take() {
+            Some(v) => Poll::Ready(v),
+            None => Poll::Pending,
+        }
+    }
+}
+
+fn poll<F: Future + Unpin>(f: &mut F) -> Poll<F::Output> {
+    Pin::new(f).poll(&mut Context::from_waker(Waker::noop()))
+}
+
+/// 本地执行器：轮询到完成为止，只用于不会真正挂起的 Future
+fn block_on<F: Future + Unpin>(mut f: F) -> F::Output {
+    loop {
+        if let Poll::Ready(v) = poll(&mut f) {
+            return v;
+        }
+    }
+}
+
+fn new_state(service: &Service) -> ExeState {
+    let mut state = ExeState::new();
+    let service = service.clone();
+    let fetch = state.create_async_function(move |args| {
+        let fetch = service.fetch(args[0].as_str().unwrap().to_string());
+        async move { Ok(vec![Value::from(fetch.await)]) }
+    });
+    state.set_global("fetch", fetch);
+    let double = state.create_async_function(|args| async move {
+        match args.first() {
+            Some(&Value::Integer(i)) => Ok(vec![Value::Integer(i * 2), Value::Integer(i)]),
+            _ => Err(LuaError::new("double: integer expected")),
+        }
+    });
+    state.set_global("double", double);
+    state
+}
+
+fn load(state: &mut ExeState, source: &str) -> Value {
+    state.load(Cursor::new(source), "chunk").unwrap()
+}
+
+#[test]
+fn suspend_and_resume() {
+    let service = Service::default();
+    let mut state = new_state(&service);
+    let main = load(&mut state, "local a = fetch('x') local b = fetch('y') return a .. ',' .. b");
+    let mut call = state.call_async(main, Vec::new());
+
+    assert!(poll(&mut call).is_pending());
+    assert_eq!(service.pending(), 1);
+    // 请求完成前重复轮询仍然挂起
+    assert!(poll(&mut call).is_pending());
+    assert_eq!(service.complete(), "x");
+    assert!(poll(&mut call).is_pending());
+    assert_eq!(service.complete(), "y");
+    match poll(&mut call) {
+        Poll::Ready(r) => assert_eq!(r.unwrap(), vec![Value::from("<x>,<y>")]),
+        Poll::Pending => panic!("call should be finished"),
+    }
+    assert_eq!(service.pending(), 0);
+}
+
+#[test]
+fn nested_frames_and_results() {
+    let service = Service::default();
+    let mut state = new_state(&service);
+    // 在嵌套的 Lua 函数中挂起；返回值个数按调用处的要求调整
+    let source = "
+        local function f(n) local d = double(n) return d + 1 end
+        local t = {double(5)}
+        local a, b, c = double(7)
+        return f(1), f(2), #t, a, b, c, double(10)";
+    let main = load(&mut state, source);
+    let r = block_on(state.call_async(main, Vec::new())).unwrap();
+    let expected = [3, 5, 2, 14, 7].map(Value::Integer).into_iter();
+    let expected = expected.chain([Value::Nil, Value::Integer(20), Value::Integer(10)]);
+    assert_eq!(r, expected.collect::<Vec<_>>());
+
+    // 直接异步调用异步函数
+    let double = state.get_global("double");
+    let r = block_on(state.call_async(double, vec![Value::Integer(4)])).unwrap();
+    assert_eq!(r, vec![Value::Integer(8), Value::Integer(4)]);
+}
+
+#[test]
+fn errors() {
+    let service = Service::default();
+    let mut state = new_state(&service);
+
+    // Future 的错误在 Lua 代码中抛出
+    let main = load(&mut state, "local x = double('a') return x");
+    let e = block_on(state.call_async(main, Vec::new())).unwrap_err();
+    assert!(e.to_string().contains("double: integer expected"), "{e}");
+
+    // 经过 pcall 的嵌套调用不能挂起，错误可以被捕获
+    let main = load(&mut state, "return pcall(fetch, 'x')");
+    let r = block_on(state.call_async(main, Vec::new())).unwrap();
+    assert_eq!(r[0], Value::Boolean(false));
+    assert!(r[1].to_string().contains("attempt to yield across a C-call boundary"), "{}", r[1]);
+
+    // 同步调用中不能挂起
+    let main = load(&mut state, "return fetch('x')");
+    let e = state.call(main, Vec::new()).unwrap_err();
+    assert!(e.to_string().contains("attempt to yield from outside a coroutine"), "{e}");
+    assert_eq!(service.pending(), 0);
+}
+
+#[test]
+fn abandoned_call() {
+    let service = Service::default();
+    let mut state = new_state(&service);
+    let main = load(&mut state, "local t <close> = nil local s = fetch('x') return s");
+    let mut call = state.call_async(main, Vec::new());
+    assert!(poll(&mut call).is_pending());
+    drop(call);
+
+    // 放弃的调用不影响之后的执行
+    assert_eq!(state.call_depth(), 0);
+    let main = load(&mut state, "return 1 + 1");
+    assert_eq!(state.call(main, Vec::new()).unwrap(), vec![Value::Integer(2)]);
+    let main = load(&mut state, "return fetch('y')");
+    let mut call = state.call_async(main, Vec::new());
+    assert!(poll(&mut call).is_pending());
+    service.complete();
+    service.complete();
+    assert!(matches!(poll(&mut call), Poll::Ready(Ok(r)) if r == vec![Value::from("<y>")]));
+}
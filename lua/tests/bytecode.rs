@@ -0,0 +1,55 @@
+// 指令编码：各种格式的指令编码后能原样解码，字段取到边界值
+
+use lua::bytecode::{ByteCode, Instruction, MAX_BX, OFFSET_SBX, OFFSET_SJ};
+
+#[test]
+fn encode_decode_round_trip() {
+    let codes = [
+        ByteCode::GetGlobal(0, MAX_BX),
+        ByteCode::LoadConst(255, MAX_BX),
+        ByteCode::SetGlobal(MAX_BX, 3),
+        ByteCode::LoadNil(7),
+        ByteCode::Tbc(9),
+        ByteCode::LoadBool(3, true),
+        ByteCode::LoadBool(3, false),
+        ByteCode::LoadInt(1, i16::MIN),
+        ByteCode::LoadInt(1, i16::MAX),
+        ByteCode::Call(2, 0, 255),
+        ByteCode::TestAndJump(4, -1),
+        ByteCode::TestOrJump(4, -OFFSET_SBX),
+        ByteCode::TestOrJump(4, OFFSET_SBX + 1),
+        ByteCode::Jump(-(1 << 24) + 1),
+        ByteCode::Jump(1 << 24),
+        ByteCode::ForLoop(250, 1000),
+        ByteCode::TForCall(7, 255),
+        ByteCode::TForLoop(7, 3),
+        ByteCode::LesEq(255, 255, 255),
+        ByteCode::SetList(3, 0, 255),
+        ByteCode::ExtraArg(0),
+        ByteCode::ExtraArg((1 << 25) - 1),
+    ];
+    for code in codes {
+        assert_eq!(code.encode().decode(), code);
+    }
+}
+
+#[test]
+fn jump_format() {
+    for sj in [-(1 << 24) + 1, -1, 0, 1, 1 << 24] {
+        let i = Instruction::isj(100, sj);
+        assert_eq!((i.op(), i.sj()), (100, sj));
+    }
+}
+
+#[test]
+fn operand_ranges() {
+    assert!(ByteCode::LoadConst(0, MAX_BX).fits());
+    assert!(!ByteCode::LoadConst(0, MAX_BX + 1).fits());
+    assert!(!ByteCode::ForPrep(0, MAX_BX + 1).fits());
+    assert!(!ByteCode::TestAndJump(0, OFFSET_SBX + 2).fits());
+    assert!(!ByteCode::TestOrJump(0, -OFFSET_SBX - 1).fits());
+    assert!(ByteCode::Jump(-OFFSET_SJ).fits());
+    assert!(!ByteCode::Jump(OFFSET_SJ + 2).fits());
+    assert!(!ByteCode::ExtraArg(1 << 25).fits());
+    assert!(ByteCode::LesEq(255, 255, 255).fits());
+}
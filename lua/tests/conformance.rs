@@ -0,0 +1,204 @@
+// Lua 一致性测试
+// - corpus/<特性>/*.lua：与同名的 .out（标准输出，须完全一致）和 .err（标准错误的第一行）比较；
+//   没有 .err 的脚本须正常结束
+// - official/*.lua：参照 Lua 5.4 官方测试集整理的子集，只收入已支持的部分，用到缺少的库函数处改用 Lua 代码实现；
+//   脚本自行 assert，正常结束且最后输出 OK 即通过
+// 开头注释中含 "xfail: 原因" 的脚本是已知尚未支持的：失败不影响测试结果，通过时则要求去掉标记
+// 脚本在其所在目录中由 lua 可执行文件运行，结束时按特性打印通过率（cargo test -- --nocapture 查看）
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 单个脚本的运行时间上限
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 一个测试脚本
+/// - `feature`: 所属特性，用于汇总通过率
+/// - `xfail`: 已知失败的原因
+struct Case {
+    feature: String,
+    path: PathBuf,
+    xfail: Option<String>,
+}
+
+/// 脚本的运行结果
+struct Output {
+    success: bool,
+    stdout: String,
+    stderr: String,
+}
+
+fn tests_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+/// 目录下的 .lua 文件，按文件名排序
+fn lua_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("cannot read {}: {e}", dir.display()))
+        .map(|entry| entry.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "lua"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// 读取开头注释中的 xfail 标记
+fn xfail_reason(path: &Path) -> Option<String> {
+    let source = fs::read_to_string(path).unwrap_or_default();
+    source
+        .lines()
+        .take_while(|line| line.starts_with("--"))
+        .find_map(|line| line.split_once("xfail:").map(|(_, reason)| reason.trim().to_string()))
+}
+
+fn new_case(feature: String, path: PathBuf) -> Case {
+    let xfail = xfail_reason(&path);
+    Case { feature, path, xfail }
+}
+
+/// 在脚本所在目录中运行脚本，超时则终止
+fn run_script(path: &Path) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lua"))
+        .arg(path.file_name().unwrap())
+        .current_dir(path.parent().unwrap())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("cannot run lua");
+
+    // 在单独的线程中读取输出，避免管道写满时子进程阻塞
+    let mut out = child.stdout.take().unwrap();
+    let mut err = child.stderr.take().unwrap();
+    let out = thread::spawn(move || {
+        let mut s = Vec::new();
+        out.read_to_end(&mut s).ok();
+        String::from_utf8_lossy(&s).into_owned()
+    });
+    let err = thread::spawn(move || {
+        let mut s = Vec::new();
+        err.read_to_end(&mut s).ok();
+        String::from_utf8_lossy(&s).into_owned()
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break Some(status);
+        }
+        if start.elapsed() > TIMEOUT {
+            child.kill().ok();
+            child.wait().ok();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+    let mut stderr = err.join().unwrap();
+    if status.is_none() {
+        stderr = format!("timed out after {TIMEOUT:?}\n{stderr}");
+    }
+    Output {
+        success: status.is_some_and(|s| s.success()),
+        stdout: out.join().unwrap(),
+        stderr,
+    }
+}
+
+/// corpus 脚本的检查：与 .out/.err 比较，返回不一致之处
+fn check_corpus(path: &Path, output: &Output) -> Result<(), String> {
+    if let Ok(expected) = fs::read_to_string(path.with_extension("out")) {
+        if output.stdout != expected {
+            return Err(format!("stdout mismatch\n--- expected\n{expected}--- got\n{}", output.stdout));
+        }
+    }
+    match fs::read_to_string(path.with_extension("err")) {
+        Ok(expected) => {
+            let got = output.stderr.lines().next().unwrap_or("");
+            if output.success || got != expected.trim_end() {
+                return Err(format!("error mismatch\n--- expected\n{expected}--- got\n{}", output.stderr));
+            }
+        }
+        Err(_) if !output.success => return Err(format!("script failed\n{}", output.stderr)),
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+/// 官方测试脚本的检查：正常结束且最后一行输出为 OK
+fn check_official(_path: &Path, output: &Output) -> Result<(), String> {
+    if !output.success {
+        return Err(format!("script failed\n{}", output.stderr));
+    }
+    if output.stdout.lines().last() != Some("OK") {
+        return Err(format!("script did not print OK\n{}", output.stdout));
+    }
+    Ok(())
+}
+
+/// 运行全部脚本，打印各特性的通过率；
+/// 未标记 xfail 的脚本失败，或标记了 xfail 的脚本通过时，测试失败
+fn run_cases(title: &str, cases: Vec<Case>, check: fn(&Path, &Output) -> Result<(), String>) {
+    // 特性 -> (通过数, 总数)
+    let mut stats: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    let mut problems = Vec::new();
+    for case in &cases {
+        let result = check(&case.path, &run_script(&case.path));
+        let stat = stats.entry(case.feature.clone()).or_default();
+        stat.1 += 1;
+        let name = case.path.strip_prefix(tests_dir()).unwrap().display();
+        match (result, &case.xfail) {
+            (Ok(()), None) => stat.0 += 1,
+            (Ok(()), Some(_)) => {
+                stat.0 += 1;
+                problems.push(format!("{name}: passed but is marked xfail, remove the marker"));
+            }
+            (Err(_), Some(_)) => {}
+            (Err(msg), None) => problems.push(format!("{name}: {msg}")),
+        }
+    }
+
+    let (passed, total) = stats.values().fold((0, 0), |(p, t), (sp, st)| (p + sp, t + st));
+    println!("{title}:");
+    for (feature, (p, t)) in &stats {
+        println!("  {feature:<20} {p:>3}/{t:<3} {:>5.1}%", *p as f64 * 100.0 / *t as f64);
+    }
+    println!("  {:<20} {passed:>3}/{total:<3} {:>5.1}%", "total", passed as f64 * 100.0 / total as f64);
+
+    assert!(problems.is_empty(), "{} conformance problem(s):\n{}", problems.len(), problems.join("\n\n"));
+}
+
+#[test]
+fn corpus() {
+    let root = tests_dir().join("corpus");
+    let mut features: Vec<PathBuf> = fs::read_dir(&root)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|p| p.is_dir())
+        // json 模块只在开启 serde 特性时编译
+        .filter(|p| cfg!(feature = "serde") || !p.ends_with("json"))
+        .collect();
+    features.sort();
+    let cases = features
+        .iter()
+        .flat_map(|dir| {
+            let feature = dir.file_name().unwrap().to_string_lossy().into_owned();
+            lua_files(dir).into_iter().map(move |path| new_case(feature.clone(), path))
+        })
+        .collect();
+    run_cases("corpus", cases, check_corpus);
+}
+
+#[test]
+fn official_suite() {
+    let cases = lua_files(&tests_dir().join("official"))
+        .into_iter()
+        .map(|path| new_case(path.file_stem().unwrap().to_string_lossy().into_owned(), path))
+        .collect();
+    run_cases("official suite subset", cases, check_official);
+}
@@ -0,0 +1,33 @@
+-- 待关闭变量：离开作用域、函数返回与出错时逆序调用 __close
+local function closer(name)
+  return setmetatable({}, {__close = function(_, err) print("close", name, err) end})
+end
+do
+  local a <close> = closer("a")
+  local b <close> = closer("b")
+  local c <close> = nil
+  print("in block")
+end
+print("after block")
+
+local function f()
+  local x <close> = closer("x")
+  return "ret"
+end
+print(f())
+
+print(pcall(function()
+  local y <close> = closer("y")
+  local z <close> = closer("z")
+  error("boom")
+end))
+
+print(pcall(function()
+  local y <close> = setmetatable({}, {__close = function() error("in close") end})
+  local z <close> = closer("z2")
+end))
+
+print(pcall(function() local bad <close> = {} end))
+for i = 1, 2 do
+  local w <close> = closer("loop" .. i)
+end
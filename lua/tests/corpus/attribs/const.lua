@@ -0,0 +1,11 @@
+-- 只读变量：值为常量时在引用处直接替换为常量，内层函数不需要上值
+local K <const> = 10
+local S <const>, F <const> = "str", 0.5
+local function g() return K * 2, S .. F end
+print(g())
+print(K + 1, -K, #S)
+local T <const> = {}
+T.x = 1
+print(T.x)
+local N <const> = nil
+print(N, debug.getinfo(g, "u").nups)
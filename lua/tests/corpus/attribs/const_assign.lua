@@ -0,0 +1,4 @@
+local limit <const> = 10
+local function bump()
+  limit = limit + 1
+end
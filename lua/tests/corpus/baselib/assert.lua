@@ -0,0 +1,3 @@
+print(assert(1, "unused"))
+print(pcall(error, "boom"))
+assert(false, "assertion message")
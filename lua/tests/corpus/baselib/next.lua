@@ -0,0 +1,12 @@
+-- next 先遍历数组部分，再按插入顺序遍历哈希部分；遍历中删除字段不影响后续遍历
+local t = {10, 20, x = 1, y = 2, z = 3}
+local function walk(k, acc)
+    local nk, v = next(t, k)
+    local _ = k ~= nil and rawset(t, k, nil)
+    return nk == nil and acc or walk(nk, acc .. tostring(nk) .. "=" .. tostring(v) .. " ")
+end
+print(walk(nil, ""))
+print(next(t))
+t.w = 4
+print(next(t))
+print(pcall(next, {}, "missing"))
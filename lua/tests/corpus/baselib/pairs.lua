@@ -0,0 +1,5 @@
+local t = {10, 20, 30}
+local sum = 0
+for i, v in ipairs(t) do sum = sum + i * v end
+print(sum, select("#", 1, nil, 3), select(2, "a", "b", "c"))
+print(rawequal(t, t), rawlen(t), rawget(t, 1), next({}))
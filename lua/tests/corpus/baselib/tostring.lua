@@ -0,0 +1,6 @@
+print(1, 2.0, -0.0, 0.1, 1e15, 1e100, -1e100, 2^63, 1/0, -1/0, 3.14159265358979)
+print(tostring(nil), tostring(true), tostring(12), tostring(1.5))
+print(tonumber("10"), tonumber("0x1F"), tonumber(" 2.5 "), tonumber("1e"), tonumber(7))
+print(tonumber("z", 36), tonumber("ff", 16), tonumber("-101", 2), tonumber("8", 8), tonumber("  7  ", 10))
+local p = setmetatable({}, {__tostring = function() return "point" end})
+print(p, tostring(p))
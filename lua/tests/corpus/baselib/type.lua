@@ -0,0 +1,2 @@
+print(type(1), type("s"), type(nil), type({}), type(print), type(true))
+print(tostring(12), tostring(1.5), tonumber("0x10"), tonumber("z", 36))
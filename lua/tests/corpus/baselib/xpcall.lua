@@ -0,0 +1,21 @@
+-- 消息处理函数在出错处、调用栈展开之前调用，能看到出错的函数
+local function where(m)
+  local lines = ""
+  for level = 2, 10 do
+    local info = debug.getinfo(level, "Sl")
+    if not info then break end
+    if info.what ~= "C" then lines = lines .. " " .. info.currentline end
+  end
+  return m .. " at" .. lines
+end
+local function inner() error("boom") end
+local function outer() inner() end
+print(xpcall(outer, where))
+print(xpcall(error, function(m) return "handled: " .. m end, "direct"))
+-- 内层 pcall 捕获的错误不经过消息处理函数
+print(xpcall(function() return pcall(error, "inner") end, function(m) return "unused" end))
+
+-- assert 的默认信息带有出错位置，由内置函数直接调用时没有
+print(pcall(function() assert(false) end))
+print(pcall(assert, false))
+print(pcall(assert, nil, "custom"))
@@ -0,0 +1,4 @@
+print(1, 2.5, -3, 0x10, 1e2)
+print(nil, true, false)
+print(10 // 3, 7 % 3, 2 ^ 10, 7 / 2)
+print(3 == 3.0, 1 < 2, "a" < "b")
@@ -0,0 +1,10 @@
+local a, b, c = 1, 2
+print(a, b, c)
+local x = 1
+do
+  local x = 2
+  print(x)
+end
+print(x)
+a, b = b, a
+print(a, b)
@@ -0,0 +1,5 @@
+print("hello" .. " " .. "world")
+print('single', "tab\tsep", [[long
+string]])
+print("n" .. 1 .. 2.0)
+print(#"abc", #"")
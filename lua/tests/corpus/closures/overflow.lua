@@ -0,0 +1,13 @@
+-- 无限递归报错 "stack overflow"，可以被 pcall 捕获，之后仍能正常调用
+local function f(n) return 1 + f(n + 1) end
+print(pcall(f, 1))
+local function count(n) if n == 0 then return 0 end return count(n - 1) + 1 end
+print(count(10000))
+-- 经过 pcall 与元方法的嵌套调用同样受限
+local depth = 0
+local function g() depth = depth + 1 return pcall(g) end
+g()
+print(depth)
+local t = setmetatable({}, {})
+getmetatable(t).__index = function(t, k) return t[k] end
+print(pcall(function() return t.x end))
@@ -0,0 +1,6 @@
+local s = 0
+for i = 1, 10 do s = s + i end
+for i = 10, 1, -3 do s = s + i end
+local t = {}
+for k, v in pairs({a = 1}) do t[#t + 1] = k .. v end
+print(s, t[1])
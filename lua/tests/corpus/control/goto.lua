@@ -0,0 +1,53 @@
+-- goto 与标签：continue 写法、向后跳转构成的循环、跳出嵌套代码块，以及 Lua 5.4 的可见性规则
+for i = 1, 5 do
+  io.write(i, " ")
+  goto continue
+  io.write("never")
+  ::continue::
+end
+print()
+
+-- 每次向后跳转都离开 k 的作用域，闭包捕获的是各自的 k
+local fs = {}
+local n = 0
+local function stop(k) return k < 3 or error("stop") end
+print(pcall(function()
+  ::top::
+  local k = n
+  fs[#fs + 1] = function() return k end
+  n = n + 1
+  stop(n)
+  goto top
+end))
+print(fs[1](), fs[2](), fs[3](), #fs)
+
+-- 跳出代码块时关闭被捕获的局部变量
+do
+  local count = 10
+  fs.inc = function() count = count + 1 return count end
+  do
+    goto out
+  end
+end
+::out::
+print(fs.inc(), fs.inc())
+
+-- 代码块末尾的标签不在其中局部变量的作用域内
+do
+  goto finish
+  local x = 1
+  print(x)
+  ::finish::
+end
+
+-- 不同函数与并列代码块中的标签互不影响
+do ::dup:: end
+do ::dup:: end
+local function f() goto dup ::dup:: return "f" end
+print(f())
+
+print(load("goto nowhere"))
+print(load("do ::a:: end goto a"))
+print(load("goto f local x ::f:: print(x)"))
+print(load("::a:: do ::a:: end"))
+print(load("local function g() goto l end ::l::"))
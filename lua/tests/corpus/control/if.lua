@@ -0,0 +1,8 @@
+local x = 5
+if x > 3 then
+  print("big")
+elseif x > 1 then
+  print("medium")
+else
+  print("small")
+end
@@ -0,0 +1,62 @@
+-- repeat 条件中引用循环体的局部变量、break 跳出各种循环并关闭被捕获的变量、泛型 for 的待关闭值
+local fs = {}
+local n = 0
+repeat
+  local x = n
+  fs[#fs + 1] = function() return x end
+  n = n + 1
+until x >= 3
+print(fs[1](), fs[2](), fs[3](), fs[4]())
+
+-- break 跳出最内层循环
+local found
+for i = 1, 3 do
+  for j = 1, 3 do
+    if i * j == 4 then
+      found = i .. "," .. j
+      break
+    end
+  end
+  if found then break end
+end
+print(found)
+
+-- break 之前捕获的循环变量各自独立
+local cl = {}
+for i, v in ipairs({"a", "b", "c", "d"}) do
+  cl[i] = function() return v end
+  if i == 3 then break end
+end
+print(#cl, cl[1](), cl[2](), cl[3]())
+
+local k = 0
+while true do
+  k = k + 1
+  if k == 2 then goto continue end
+  if k > 4 then break end
+  io.write(k, " ")
+  ::continue::
+end
+print()
+
+repeat
+  local y <close> = setmetatable({}, {__close = function() print("close y") end})
+  do break end
+until false
+
+-- 泛型 for 的第 4 个值在循环结束或 break 时关闭
+local mt = {__close = function() print("closed") end}
+local function iter(_, i)
+  if i < 3 then return i + 1, i * i end
+end
+for i, sq in iter, nil, 0, setmetatable({}, mt) do
+  print(i, sq)
+end
+for i in iter, nil, 0, setmetatable({}, mt) do
+  if i == 2 then break end
+end
+
+-- 条件为常量
+while false do print("never") end
+if nil then print("never") elseif 0 then print("zero is true") end
+repeat print("once") until 1
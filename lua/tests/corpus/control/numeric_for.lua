@@ -0,0 +1,21 @@
+-- 数值 for 循环：整数与浮点数循环、负步长、循环变量的闭包、整数边界
+local s = 0
+for i = 1, 10 do s = s + i end
+print(s)
+for i = 10, 1, -3 do io.write(i, " ") end
+print()
+for i = 1, 2, 0.5 do print(i) end
+for i = 1, 0 do print("never") end
+for i = 1, 2.9 do io.write(i, " ") end
+print()
+
+local fs = {}
+for i = 1, 3 do
+    local j = i * 10
+    fs[i] = function() return i + j end
+end
+print(fs[1](), fs[2](), fs[3]())
+
+local n = 0
+for i = 9223372036854775806, 9223372036854775807 do n = n + 1 end
+print(n)
@@ -0,0 +1,7 @@
+local i, s = 0, 0
+while i < 10 do
+  i = i + 1
+  s = s + i
+end
+repeat i = i - 1 until i == 0
+print(s, i)
@@ -0,0 +1,8 @@
+local function f(a, b, ...)
+  local info = debug.getinfo(1)
+  return info.currentline, info.linedefined, info.lastlinedefined, info.nparams, info.isvararg
+end
+print(f())
+local main = debug.getinfo(1)
+print(main.what, main.currentline, main.short_src)
+print(debug.getlocal(f, 1), debug.getlocal(f, 2), debug.getlocal(f, 3))
@@ -0,0 +1,10 @@
+local lines = ""
+local function add(a, b)
+  return a + b
+end
+debug.sethook(function(event, line)
+  lines = lines .. event .. ":" .. line .. " "
+end, "l")
+add(1, 2)
+debug.sethook()
+io.write(lines, "\n")
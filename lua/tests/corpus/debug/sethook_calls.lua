@@ -0,0 +1,11 @@
+-- xfail: call and return hooks are not fired for Rust functions
+local events = ""
+local function add(a, b)
+  return a + b
+end
+debug.sethook(function(event)
+  events = events .. event .. " "
+end, "cr")
+add(1, 2)
+debug.sethook()
+io.write(events, "\n")
@@ -0,0 +1,9 @@
+-- xfail: tracebacks do not name functions or show C frames
+local function inner()
+  return debug.traceback("here")
+end
+local function outer()
+  local s = inner()
+  return s
+end
+io.write(outer(), "\n")
@@ -0,0 +1,4 @@
+-- xfail: runtime errors do not name the offending variable
+local t = {}
+
+local y = t.missing + 1
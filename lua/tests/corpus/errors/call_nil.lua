@@ -0,0 +1,3 @@
+-- xfail: runtime errors do not name the offending variable
+local x = 1
+undefined_function(x)
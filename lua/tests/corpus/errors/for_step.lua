@@ -0,0 +1,2 @@
+local n = 0
+for i = 1, 10, n do end
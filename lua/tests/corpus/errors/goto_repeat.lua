@@ -0,0 +1,2 @@
+-- repeat 循环体末尾的标签之后还有 until 条件，局部变量 y 在那里仍然有效
+repeat local x = 1; goto l; local y = 2; ::l:: until x == 1
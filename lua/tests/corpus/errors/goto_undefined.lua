@@ -0,0 +1,3 @@
+local x = 1
+goto done
+print(x)
@@ -0,0 +1,3 @@
+local t = {}
+local nan = 0/0
+t[nan] = 1
@@ -0,0 +1,3 @@
+-- xfail: runtime errors do not name the offending variable
+local a
+print(a.b)
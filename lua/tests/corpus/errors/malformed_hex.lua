@@ -0,0 +1,2 @@
+-- 0x 之后至少要有一位十六进制数字
+local x = 0x
@@ -0,0 +1,40 @@
+-- io.read 的各种格式："n"、"l"、"L"、"a" 与字节数
+local name = os.tmpname()
+local f = io.open(name, "w")
+f:write("12 3.5 0x10\nfirst line\nsecond line\nabcdefgh\nrest\nof file")
+f:close()
+
+f = io.open(name, "r")
+print(f:read("n", "n", "n"))
+print(f:read("l"))
+print(f:read("l"))
+print(f:read("L"))
+print(f:read(3), f:read(0), f:read(2))
+print(f:read("l"))
+print(f:read("a"))
+print(f:read("a"), f:read("l"), f:read(0), f:read("n"))
+f:close()
+
+f = io.open(name, "r")
+print(f:read("n"), f:read("n"), f:read("n"), f:read("n"))
+f:close()
+
+-- io.lines 的迭代函数按给定的格式读取
+local next_line = io.lines(name, 2, "l")
+print(next_line())
+print(next_line())
+print(os.remove(name))
+
+-- "r+" 模式读取之后写入，写入位置紧接着已读取的内容而不是缓冲区之后
+name = os.tmpname()
+f = io.open(name, "w")
+f:write("line one\nline two\n")
+f:close()
+f = io.open(name, "r+")
+print(f:read("l"))
+f:write("XX")
+f:close()
+f = io.open(name, "r")
+print(f:read("a"))
+f:close()
+print(os.remove(name))
@@ -0,0 +1,4 @@
+io.write("a", 1, " ", 2.5, "\n")
+local f = io.stdout
+f:write("via file\n")
+print(io.type(f), io.type(42))
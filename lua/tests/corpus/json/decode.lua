@@ -0,0 +1,28 @@
+-- json.decode：数组成为序列，对象成为表，null 默认为 nil
+local json = require("json")
+
+local v = json.decode('{"name": "lua", "list": [1, 2.5, "x", true], "nested": {"n": -7}, "big": 18446744073709551615}')
+print(v.name, #v.list, v.list[1], v.list[2], v.list[3], v.list[4])
+print(v.nested.n)
+print(v.big)
+
+-- null
+local t = json.decode('[1, null, 3]')
+print(t[1], t[2], t[3])
+t = json.decode('{"a": null, "b": 1}')
+print(t.a, t.b)
+t = json.decode('[1, null, 3]', {null = json.null})
+print(t[2] == json.null, #t)
+
+-- 标量与 Unicode 转义
+print(json.decode('"caf\\u00e9"'), json.decode("12"), json.decode("true"), json.decode("null"))
+
+-- 编码后再解码
+local config = {servers = {{host = "a", port = 80}, {host = "b", port = 8080}}, debug = false}
+local again = json.decode(json.encode(config))
+print(again.servers[2].host, again.servers[2].port, again.debug)
+
+-- 错误
+print(pcall(json.decode, "{"))
+print(pcall(json.decode, "[1] x"))
+print(pcall(json.decode, {}))
@@ -0,0 +1,36 @@
+-- json.encode：表按内容编码为数组或对象，以及各选项
+local json = require("json")
+
+print(json.encode({1, 2, "three", true, false}))
+print(json.encode({name = "lua", version = 5.4, tags = {"a", "b"}}))
+print(json.encode({}))
+print(json.encode({}, {empty_as_array = true}))
+print(json.encode("line\n\"quoted\""))
+print(json.encode(42), json.encode(0.5), json.encode(nil), json.encode(json.null))
+print(json.encode({1, json.null, 3}))
+
+-- 排序
+local t = {b = 1, a = 2, [10] = "x", [2] = "y", c = {z = 1, y = 2}}
+print(json.encode(t, {sort_keys = true}))
+
+-- 稀疏数组
+local sparse = {[1] = "a", [3] = "c"}
+print(json.encode(sparse))
+print(json.encode(sparse, {sparse_array = "fill"}))
+print(pcall(json.encode, sparse, {sparse_array = "error"}))
+print(pcall(json.encode, sparse, {sparse_array = "bogus"}))
+
+-- NaN 与无穷大
+print(pcall(json.encode, {0/0}))
+print(json.encode({1/0, -1/0}, {nan = "null"}))
+
+-- 不能编码的值
+print(pcall(json.encode, {f = print}))
+local cyclic = {}
+cyclic.self = cyclic
+print(pcall(json.encode, cyclic))
+print(pcall(json.encode, {[true] = 1}))
+
+-- 同一张表出现两次不是环
+local shared = {1}
+print(json.encode({shared, shared}))
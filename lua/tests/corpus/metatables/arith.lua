@@ -0,0 +1,10 @@
+local mt = {}
+mt.__add = function(a, b) return a.v + b.v end
+mt.__concat = function(a, b) return "cat" end
+mt.__len = function() return 99 end
+mt.__eq = function() return true end
+mt.__lt = function(a, b) return a.v < b.v end
+mt.__unm = function(a) return -a.v end
+local a = setmetatable({v = 1}, mt)
+local b = setmetatable({v = 2}, mt)
+print(a + b, a .. b, #a, a == b, a < b, -a)
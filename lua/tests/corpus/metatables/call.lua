@@ -0,0 +1,12 @@
+local c = setmetatable({}, {__call = function(self, a, b) return self, a + b end})
+local self, sum = c(1, 2)
+print(self == c, sum)
+print(select(3, pcall(c, 10, 20)))
+-- __call 的值本身也可以是带 __call 的表
+local inner = setmetatable({}, {__call = function(h, t, x) return x * 2 end})
+local outer = setmetatable({}, {__call = inner})
+print(outer(21))
+print(pcall(setmetatable({}, {}), 1))
+local loop = setmetatable({}, {})
+getmetatable(loop).__call = loop
+print(pcall(loop))
@@ -0,0 +1,5 @@
+local base = {greet = "hi"}
+local t = setmetatable({}, {__index = base})
+print(t.greet, t.other)
+local f = setmetatable({}, {__index = function(t, k) return k .. "!" end})
+print(f.abc)
@@ -0,0 +1,19 @@
+local log = {}
+local p = setmetatable({}, {__newindex = function(t, k, v) log[#log + 1] = k .. "=" .. v end})
+p.a = 1
+p[1] = 2
+print(log[1], log[2], rawget(p, "a"))
+-- 已有的键直接写入，不经过 __newindex
+rawset(p, "b", 1)
+p.b = 3
+print(p.b, #log)
+local store = {}
+local q = setmetatable({}, {__newindex = store})
+q.x = 5
+print(rawget(q, "x"), store.x)
+local r = setmetatable({}, {})
+getmetatable(r).__newindex = r
+print(pcall(function() r.z = 1 end))
+local s = setmetatable({}, {})
+getmetatable(s).__index = s
+print(pcall(function() return s.z end))
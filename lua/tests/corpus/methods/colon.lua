@@ -0,0 +1,11 @@
+local Account = {balance = 0}
+function Account.new(b)
+  return {balance = b, deposit = Account.deposit, get = Account.get}
+end
+function Account:deposit(v)
+  self.balance = self.balance + v
+end
+function Account:get() return self.balance end
+local a = Account.new(100)
+a:deposit(50)
+print(a:get(), a.balance)
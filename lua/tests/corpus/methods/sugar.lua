@@ -0,0 +1,5 @@
+local function f(t) return #t end
+local function g(s) return s .. "?" end
+print(f{1, 2, 3}, g"x", g[[y]])
+local function curry(a) return function(b) return a + b end end
+print(curry(1)(2))
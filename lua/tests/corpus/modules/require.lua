@@ -0,0 +1,4 @@
+package.path = "./?.lua"
+local m = require("mods.util")
+local again = require("mods.util")
+print(m.double(21), m == again, package.loaded["mods.util"] == m)
@@ -0,0 +1,3 @@
+print(1 + 2, 1 - 2.0, 3 * 4, 2 ^ 0.5 > 1.41)
+print(-7 // 2, -7 % 2, 7 // -2, 7 % -2, -7.5 // 2)
+print("10" + 1, "3" * "4", 10 / 4)
@@ -0,0 +1,2 @@
+print(5 & 3, 5 | 3, 5 ~ 3, ~0, 1 << 4, 256 >> 4)
+print(1 << 64, -1 >> 63, 3.0 | 0)
@@ -0,0 +1,6 @@
+-- 字符串参与算术时按 Lua 的数字语法转换
+print("10" + 1, "3.0" + 1, " 0x10 " * 2, "1e1" // 1)
+print("0x1p4" + 0, "0x.8" + 0, "0xA.8p1" + 0, tonumber("0x1P-2"))
+print(" 10\v" + 0, "\t-7\n" % 3, -"2", ~"5")
+print(tonumber("0x"), tonumber("1e"), tonumber("inf"), tonumber("0x1p"))
+print(9007199254740993 == 2^53, 9007199254740993 < 2^53 + 2, 2^63 > 9223372036854775807)
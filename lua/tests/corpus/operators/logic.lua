@@ -0,0 +1,3 @@
+print(1 and 2, nil and 1, false or "x", nil or false)
+print(not nil, not 0, 1 == 1 and "eq" or "ne")
+print(1 ~= 2, "a" .. "b" == "ab", 2 <= 2, 3 >= 4)
@@ -0,0 +1,20 @@
+-- os.time 与 os.date：UTC 格式化、日期表与往返转换
+local t = 1700000000
+print(os.date("!%Y-%m-%d %H:%M:%S", t))
+print(os.date("!%j %a %b %p %%", t))
+local d = os.date("!*t", t)
+print(d.year, d.month, d.day, d.hour, d.min, d.sec, d.wday, d.yday, d.isdst)
+print(os.date("!%Y", 0))
+
+-- 本地时间的日期表经 os.time 转换回原来的时间
+local l = os.date("*t", t)
+print(os.time(l) == t)
+print(os.time({year = 2024, month = 3, day = 1, hour = 12}) - os.time({year = 2024, month = 2, day = 28, hour = 12}))
+print(type(os.time()), type(os.clock()))
+print(os.difftime(t + 60, t))
+print(pcall(os.date, "%Ez", t))
+
+-- 年份与月份减去偏移后超出 int 范围
+print(pcall(os.time, {year = -9223372036854775807 - 1, month = 1, day = 1}))
+print(pcall(os.time, {year = 2000, month = -2147483648, day = 1}))
+print(pcall(os.time, {year = 2000, month = 1, day = 2147483648}))
@@ -0,0 +1,62 @@
+-- string.pack/unpack/packsize：Lua 5.4 的二进制打包格式
+local pack, unpack, packsize = string.pack, string.unpack, string.packsize
+
+-- 整数：字节序、大小与符号扩展
+print(pack("<i4", 1) == "\1\0\0\0", pack(">i4", 1) == "\0\0\0\1")
+print(pack("<h", -2) == "\xfe\xff", pack(">H", 0xABCD) == "\xab\xcd")
+print(pack("b", -1) == "\xff", pack("B", 255) == "\xff")
+print(pack("<i3", -2) == "\xfe\xff\xff", pack(">I5", 0x0102030405) == "\1\2\3\4\5")
+print(pack("<i12", -1) == "\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff")
+print(pack("<I10", 1) == "\1\0\0\0\0\0\0\0\0\0")
+print(unpack("<i2", "\xfe\xff"), unpack("<I2", "\xfe\xff"), unpack(">i3", "\xff\xff\xfe"))
+print(unpack("<i16", pack("<i16", -9223372036854775807 - 1)))
+print(unpack("<I9", "\xff\xff\xff\xff\xff\xff\xff\xff\0"))
+print(unpack("j", pack("j", 9223372036854775807)), unpack("J", pack("J", -1)))
+
+-- 浮点数
+print(unpack("<d", pack("<d", 1.5)), unpack(">f", pack(">f", 0.25)), unpack("n", pack("n", -3)))
+print(pack(">d", 1.0) == "\x3f\xf0\0\0\0\0\0\0", pack("<f", -2) == "\0\0\0\xc0")
+-- f 只有单精度，0.1 不能原样还原
+print(unpack("f", pack("f", 0.1)) == 0.1, unpack("d", pack("d", 0.1)) == 0.1)
+
+-- 字符串
+print(pack("z", "abc") == "abc\0", pack("<s1", "hi") == "\2hi", pack(">s2", "hi") == "\0\2hi")
+print(pack("c5", "ab") == "ab\0\0\0", pack("c0", ""), #pack("s", "xyz"))
+print(unpack("zc2s1", "hello\0ok\3abcdef"))
+print(unpack("z", "\0"), unpack("c3", "abcdef", 4))
+
+-- 对齐与填充
+print(packsize("i1i8"), packsize("!i1i8"), packsize("!4i1i8"), packsize("!2i1i2i1"))
+print(pack("!<i1i4", 1, 2) == "\1\0\0\0\2\0\0\0", pack("<i1xi1", 1, 2) == "\1\0\2")
+print(pack("!<i1Xi4i1", 1, 2) == "\1\0\0\0\2", packsize("!8bXd"), packsize("bXh"))
+print(packsize(" < > = ! i b "), packsize("c10x"), packsize("i16"), packsize("T"))
+print(unpack("!<i1i4", pack("!<i1i4", 7, -7)))
+
+-- 起始位置与返回的下一个位置
+local s = pack("<i2i2i2", 10, 20, 30)
+print(unpack("<i2", s, 3), unpack("<i2", s, -2), unpack("<i2", s, -100))
+print(unpack("", s), unpack("", s, 7))
+
+-- 错误
+print(pcall(pack, "i0", 0))
+print(pcall(pack, "i17", 0))
+print(pcall(pack, "Xi17"))
+print(pcall(pack, "i3r", 0))
+print(pcall(pack, "!4i3", 0))
+print(pcall(pack, "c", ""))
+print(pcall(pack, "X"))
+print(pcall(pack, "Xc1"))
+print(pcall(pack, "i1", 128))
+print(pcall(pack, "I1", -1))
+print(pcall(pack, "i", 1.5))
+print(pcall(pack, "i", "x"))
+print(pcall(pack, "c2", "abc"))
+print(pcall(pack, "s1", pack("c256", "")))
+print(pcall(pack, "z", "a\0b"))
+print(pcall(packsize, "s"))
+print(pcall(packsize, "z"))
+print(pcall(unpack, "i4", "abc"))
+print(pcall(unpack, "i16", "\3\3\3\3\3\3\3\3\3\3\3\3\3\3\3\3"))
+print(pcall(unpack, "z", "abc"))
+print(pcall(unpack, "s1", "\5ab"))
+print(pcall(unpack, "i1", "a", 3))
@@ -0,0 +1,6 @@
+local t = {1, 2, 3, x = "x", ["y"] = 10, [1 + 1] = "ignored"}
+print(t[1], t[2], t[3], t.x, t.y, #t)
+local n = {{1}, {2, {3}}}
+print(n[2][2][1])
+local function three() return 1, 2, 3 end
+print(#{three()}, #{three(), three()}, #{(three())})
@@ -0,0 +1,9 @@
+local t = {}
+t.a = 1
+t["b"] = 2
+t[3] = "three"
+t.a = t.a + t.b
+print(t.a, t.b, t[3], t.c)
+local obj = {inner = {value = 5}}
+obj.inner.value = obj.inner.value * 2
+print(obj.inner.value)
@@ -0,0 +1,20 @@
+-- 数组项与键的写入：整数值的浮点数键、构造器中位置项覆盖同名的键、超过一批的构造器
+local t = {}
+t[1.0] = "one"
+t[2] = "two"
+t[2^53] = "big"
+print(t[1], t[2.0], t[2^53 | 0], #t)
+local u = {[1] = 5, 7, [2] = 6}
+print(u[1], u[2], #u)
+local big = {1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95, 96, 97, 98, 99, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117, 118, 119, 120}
+print(#big, big[1], big[50], big[51], big[101], big[120])
+local function three() return 1, 2, 3 end
+local mixed = {1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95, 96, 97, 98, 99, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117, 118, 119, 120, three()}
+print(#mixed, mixed[120], mixed[121], mixed[123])
+local holes = {1, 2, nil, 4}
+holes[4] = nil
+print(#holes)
+for i = 1, 100 do t[i] = i * i end
+for i = 1, 100 do t[i] = nil end
+print(t[1], t[100], t[2^53])
+print((pcall(function() local k = 0/0; t[k] = 1 end)))
@@ -0,0 +1,11 @@
+-- 字符串是字节序列：非 UTF-8 字节在连接、比较和长度中原样保留
+local a = "\xff\xfe"
+local b = a .. "\0z"
+print(#a, #b, b == "\xff\xfe\0z")
+print("a\xff" < "b", "\xff" > "a")
+print(utf8.len(b), utf8.len(b, 3))
+local t = {}
+t["\xff"] = 1
+t["\xfe"] = 2
+print(t["\xff"], t["\xfe"])
+print(#("\200" .. "\x80"), "\u{0}" == "\0")
@@ -0,0 +1,21 @@
+-- utf8 库：编码、解码、长度与偏移
+local s = "h\u{E9}llo\u{20AC}"
+print(#s, utf8.len(s))
+print(utf8.codepoint(s, 1, -1))
+print(utf8.char(72, 233, 8364, 0x10FFFF) == "H\u{E9}\u{20AC}\u{10FFFF}")
+print(#utf8.char(0x7FFFFFFF), "\u{7FFFFFFF}" == utf8.char(0x7FFFFFFF))
+print(utf8.len("\xffabc"))
+print(utf8.len(s, 3))
+print(utf8.len(s, -3), utf8.len(s, 10))
+print(utf8.offset(s, 3), utf8.offset(s, -1), utf8.offset(s, 0, 3), utf8.offset(s, 10))
+print((pcall(utf8.codepoint, "\xed\xa0\x80")))
+print(utf8.codepoint("\xed\xa0\x80", 1, 1, true))
+print(utf8.charpattern == "[\0-\x7F\xC2-\xFD][\x80-\xBF]*")
+
+-- 手动驱动 codes 的迭代函数
+local f, t, i = utf8.codes(s)
+local function walk(p, c)
+  return p and io.write(p, ":", c, " ") and walk(f(t, p))
+end
+walk(f(t, i))
+print()
@@ -0,0 +1,7 @@
+local function f(...)
+  local a, b = ...
+  return a, b, ...
+end
+print(f(1, 2, 3))
+local function count(...) return #{...} end
+print(count(), count(nil, nil, 3))
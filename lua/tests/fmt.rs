@@ -0,0 +1,50 @@
+// lua fmt：格式化的结果与期望一致，保留全部注释，且再次格式化保持不变
+
+use lua::fmt::format;
+use std::fs;
+use std::path::Path;
+
+fn dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+#[test]
+fn fixture() {
+    let input = fs::read(dir().join("tests/fmt/input.lua")).unwrap();
+    let expected = fs::read_to_string(dir().join("tests/fmt/expected.lua")).unwrap();
+    let got = format(&input).unwrap();
+    assert_eq!(String::from_utf8(got).unwrap(), expected);
+}
+
+#[test]
+fn idempotent() {
+    // 所有能解析的测试脚本格式化后再次格式化都不变，且注释一个不少
+    let mut n = 0;
+    for sub in ["tests/corpus", "tests/official"] {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir().join(sub)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(fs::read_dir(path).unwrap().map(|e| e.unwrap().path()));
+            } else {
+                files.push(path);
+            }
+        }
+        for path in files.into_iter().filter(|p| p.extension().is_some_and(|e| e == "lua")) {
+            let source = fs::read(&path).unwrap();
+            let Ok(once) = format(&source) else { continue };
+            let twice = format(&once).unwrap();
+            assert!(once == twice, "{} changes when formatted twice", path.display());
+            let count = |s: &[u8]| s.windows(2).filter(|w| w == b"--").count();
+            assert!(count(&once) >= count(&source), "{} lost comments", path.display());
+            n += 1;
+        }
+    }
+    assert!(n > 20);
+}
+
+#[test]
+fn syntax_error() {
+    let e = format(b"local x = (1").unwrap_err();
+    assert_eq!(e.line, 1);
+}
@@ -0,0 +1,40 @@
+-- header comment
+local   x=1   -- trailing
+local t = {1,2,
+  3, a=4,["k"]=5}   -- tbl
+
+local function f(a,b,...)
+  if a then return b -- ret
+  elseif b then
+    -- inside elseif
+    print( a..b )
+  else
+    -- default case
+    x = - -1
+  end
+  for i=1,10 do end
+  for k,v in pairs(t) do print(k,v) end
+  while x<10 do x=x+1 end
+  repeat local y = 1 until y==1
+  do return end
+end
+local s = [[long
+string]] print(s, f"x", f{1})
+goto done
+::done::
+local cfg = { -- config
+  -- first
+  name = "x", -- the name
+
+  --[[ block ]] size = 3,
+  -- tail
+}
+f(function(a) return a end)
+local e = {
+}
+a = b;
+(f)()
+local neg = - -x
+local idx = t[ [[k]] ]
+local empty = function() end
+print(not a, #t, ~b, a.b.c:d(1), (f()))
@@ -0,0 +1,111 @@
+// 从其他线程中断正在执行的脚本
+
+use lua::sync::{Rc, RefCell};
+use lua::value::{RustClosure, Value};
+use lua::vm::{ExeState, LuaError};
+use std::io::Cursor;
+use std::thread;
+use std::time::Duration;
+
+fn run(state: &mut ExeState, source: &str) -> Result<Vec<Value>, LuaError> {
+    let main = state.load(Cursor::new(source), "chunk").unwrap();
+    state.call(main, Vec::new())
+}
+
+/// 在另一个线程中稍后中断
+fn interrupt_later(state: &ExeState) -> thread::JoinHandle<()> {
+    let handle = state.interrupt_handle();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    })
+}
+
+#[test]
+fn interrupt_loop() {
+    let mut state = ExeState::new();
+    let t = interrupt_later(&state);
+    let r = run(&mut state, "local n = 0 for i = 1, 1e18 do n = n + 1 end return n");
+    assert!(matches!(r, Err(LuaError::Interrupted)));
+    t.join().unwrap();
+
+    // while 循环在向后跳转处检查中断
+    let t = interrupt_later(&state);
+    let r = run(&mut state, "local n = 0 while true do n = n + 1 end");
+    assert!(matches!(r, Err(LuaError::Interrupted)));
+    t.join().unwrap();
+
+    // goto 构成的循环在向后跳转处检查中断
+    let t = interrupt_later(&state);
+    let r = run(&mut state, "local n = 0 ::top:: n = n + 1 goto top");
+    assert!(matches!(r, Err(LuaError::Interrupted)));
+    t.join().unwrap();
+
+    // 中断之后状态可以继续使用
+    assert_eq!(run(&mut state, "return 1 + 2").unwrap(), vec![Value::Integer(3)]);
+}
+
+#[test]
+fn not_catchable() {
+    let mut state = ExeState::new();
+    let t = interrupt_later(&state);
+    // pcall、xpcall 与 __close 都不能拦下中断
+    let source = "
+        local function spin() for i = 1, 1e18 do end end
+        local function handler(e) return e end
+        local t <close> = setmetatable({}, {__close = function() error('in close') end})
+        local ok = pcall(spin)
+        ok = xpcall(spin, handler)
+        caught = true";
+    let r = run(&mut state, source);
+    assert!(matches!(r, Err(LuaError::Interrupted)), "{r:?}");
+    assert_eq!(state.get_global("caught"), Value::Nil);
+    t.join().unwrap();
+}
+
+#[test]
+fn interrupt_before_run() {
+    // 没有脚本执行时请求的中断在下一次执行的第一个检查点（这里是函数调用）生效，之后不再影响
+    let mut state = ExeState::new();
+    state.interrupt_handle().interrupt();
+    assert!(matches!(run(&mut state, "local function f() end f()"), Err(LuaError::Interrupted)));
+    assert_eq!(run(&mut state, "local function f() return 1 end return f()").unwrap(), vec![Value::Integer(1)]);
+}
+
+#[test]
+fn reset_after_run() {
+    // 执行期间请求的中断直到调用结束都没有遇到检查点：请求随调用结束丢弃，不影响下一次执行
+    let mut state = ExeState::new();
+    let handle = state.interrupt_handle();
+    let request: RustClosure = RefCell::new(Box::new(move |_| {
+        handle.interrupt();
+        Ok(0)
+    }));
+    state.set_global("request", Value::RustClosure(Rc::new(request)));
+    assert_eq!(run(&mut state, "request() return 1").unwrap(), vec![Value::Integer(1)]);
+    assert_eq!(run(&mut state, "local function f() return 2 end return f()").unwrap(), vec![Value::Integer(2)]);
+}
+
+#[test]
+fn command_line_timeout() {
+    use std::process::Command;
+    let script = std::env::temp_dir().join(format!("lua_timeout_{}.lua", std::process::id()));
+    std::fs::write(&script, "while true do end").unwrap();
+    let lua = |timeout: &str| {
+        Command::new(env!("CARGO_BIN_EXE_lua")).args(["--timeout", timeout]).arg(&script).output().unwrap()
+    };
+
+    // 不能表示为时长的秒数是用法错误，而不是 panic
+    for bad in ["-1", "nan", "inf", "1e30", "soon"] {
+        let out = lua(bad);
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert_eq!(out.status.code(), Some(1), "{bad}: {stderr}");
+        assert!(stderr.contains(&format!("invalid timeout '{bad}'")), "{stderr}");
+    }
+
+    let out = lua("0.05");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(stderr.contains("timed out after 50ms"), "{stderr}");
+    std::fs::remove_file(&script).unwrap();
+}
@@ -0,0 +1,167 @@
+// 各种限制：超出指令字段范围的常量索引改用寄存器，其余超出限制的情况给出编译错误；
+// 调用层数超过限制时报错 stack overflow，很深的调用栈的回溯信息省略中间的层
+
+use lua::value::Value;
+use lua::vm::{ExeState, LuaError};
+use std::io::Cursor;
+use std::thread;
+
+fn run(source: &str) -> Result<Vec<Value>, LuaError> {
+    let mut state = ExeState::new();
+    let main = state.load(Cursor::new(source), "chunk")?;
+    state.call(main, Vec::new())
+}
+
+fn compile_error(source: &str) -> String {
+    let mut state = ExeState::new();
+    match state.load(Cursor::new(source), "chunk") {
+        Ok(_) => panic!("expected a compile error"),
+        Err(err) => err.to_string(),
+    }
+}
+
+/// 重复 n 次 f(i) 并以 sep 连接
+fn repeat(n: usize, sep: &str, f: impl Fn(usize) -> String) -> String {
+    (1..=n).map(f).collect::<Vec<_>>().join(sep)
+}
+
+#[test]
+fn many_constants() {
+    // 常量超过 255 个之后的字段名、方法名与全局变量名放不进 8 位字段
+    let source = format!(
+        "local t = {{ {} }}
+         {}
+         function t:m300() return self.f300 end
+         h = g300
+         local s = 0
+         {}
+         return s, t:m300(), h, t.f1",
+        repeat(300, ", ", |i| format!("f{i} = {i}")),
+        repeat(300, " ", |i| format!("g{i} = {i}.5 t.f{i} = t.f{i} + 1")),
+        repeat(300, " ", |i| format!("s = s + t.f{i} + g{i}")),
+    );
+    let expected = 2 * (300 * 301 / 2) as i64 + 300;
+    assert_eq!(
+        run(&source).unwrap(),
+        vec![
+            Value::Float(expected as f64 + 150.0),
+            Value::Integer(301),
+            Value::Float(300.5),
+            Value::Integer(2)
+        ]
+    );
+}
+
+#[test]
+fn too_many_locals() {
+    let locals = repeat(201, " ", |i| format!("local a{i} = {i}"));
+    let err = compile_error(&locals);
+    assert!(
+        err.contains("too many local variables (limit is 200) in main function"),
+        "{err}"
+    );
+
+    let err = compile_error(&format!("local x\nlocal function f()\n{locals}\nend"));
+    assert!(
+        err.contains("too many local variables (limit is 200) in function at line 2"),
+        "{err}"
+    );
+
+    // 离开作用域的局部变量不计入
+    let blocks = repeat(2, " ", |_| {
+        format!("do {} end", repeat(150, " ", |i| format!("local a{i} = {i}")))
+    });
+    assert!(run(&blocks).is_ok());
+}
+
+#[test]
+fn too_many_registers() {
+    let err = compile_error(&format!("print({})", repeat(300, ", ", |i| i.to_string())));
+    assert!(err.contains("function or expression needs too many registers"), "{err}");
+
+    let err = compile_error(&format!("return {}", repeat(260, ", ", |i| i.to_string())));
+    assert!(err.contains("function or expression needs too many registers"), "{err}");
+}
+
+#[test]
+fn too_many_upvalues() {
+    // 上值来自两层外层函数，每层的局部变量都不超过限制
+    let source = format!(
+        "{}\nlocal function f()\n{}\nreturn function() return {} end\nend",
+        repeat(150, " ", |i| format!("local a{i} = {i}")),
+        repeat(150, " ", |i| format!("local b{i} = {i}")),
+        repeat(150, " + ", |i| format!("a{i} + b{i}")),
+    );
+    let err = compile_error(&source);
+    assert!(
+        err.contains("too many upvalues (limit is 255) in function at line 4"),
+        "{err}"
+    );
+}
+
+#[test]
+fn control_structure_too_long() {
+    let items = |n| repeat(n, ",", |_| "1".to_string());
+
+    // and/or 的条件跳转距离在 sBx 字段中
+    let err = compile_error(&format!("local a = true\nlocal t = a and {{{}}}", items(70000)));
+    assert!(err.contains("chunk:2: control structure too long"), "{err}");
+
+    // 数值 for 与泛型 for 的跳转距离在 Bx 字段中
+    let err = compile_error(&format!("for i = 1, 2 do\nlocal t = {{{}}}\nend", items(140000)));
+    assert!(err.contains("control structure too long"), "{err}");
+    let err = compile_error(&format!(
+        "for k in pairs({{}}) do\nlocal t = {{{}}}\nend",
+        items(140000)
+    ));
+    assert!(err.contains("control structure too long"), "{err}");
+
+    // if、while 用 Jump 跳转，sJ 字段能容纳同样长的代码
+    let source = format!(
+        "local n = 0\nwhile n < 2 do\nlocal t = {{{}}}\nn = n + #t\nend\nreturn n",
+        items(70000)
+    );
+    assert_eq!(run(&source).unwrap(), vec![Value::Integer(70000)]);
+}
+
+#[test]
+fn stack_overflow() {
+    // Lua 函数之间的调用不占用宿主线程的栈，只受栈帧层数限制
+    let err = run("local function f(n) return 1 + f(n + 1) end return f(1)").unwrap_err();
+    assert!(err.to_string().contains("chunk:1: stack overflow"), "{err}");
+    assert_eq!(
+        run("local function f(n) if n == 0 then return 0 end return f(n - 1) + 1 end return f(100000)").unwrap(),
+        vec![Value::Integer(100000)]
+    );
+
+    // 经过 Rust 代码的嵌套调用（pcall、元方法）在耗尽宿主线程的栈之前报错，且可以被 pcall 捕获
+    // debug 构建中每层约占 13KB 的栈，测试线程默认的 2MB 不够，改用与主线程相同的 8MB
+    let source = "local depth = 0
+         local function g() depth = depth + 1 return pcall(g) end
+         g()
+         local t = setmetatable({}, {})
+         getmetatable(t).__index = function(t, k) return t[k] end
+         local ok, err = pcall(function() return t.x end)
+         return depth, ok, err";
+    let check = move || {
+        assert_eq!(
+            run(source).unwrap(),
+            vec![Value::Integer(200), Value::Boolean(false), Value::from("chunk:5: stack overflow")]
+        );
+    };
+    thread::Builder::new().stack_size(8 << 20).spawn(check).unwrap().join().unwrap();
+}
+
+#[test]
+fn long_traceback() {
+    // 与 C 实现一致，很深的调用栈的回溯信息只列出最前的 10 层与最后的 11 层
+    let source = "local function f(n) if n == 0 then error('deep') end f(n - 1) end
+                  local ok, tb = xpcall(f, debug.traceback, 100000)
+                  return tb";
+    let tb = run(source).unwrap().remove(0).to_string();
+    let lines: Vec<_> = tb.lines().collect();
+    assert_eq!(lines.len(), 2 + 10 + 1 + 11, "{tb}");
+    assert_eq!(lines[0], "chunk:1: deep");
+    assert_eq!(lines[12], "\t...\t(skipping 99981 levels)");
+    assert_eq!(lines[23], "\tchunk:2: in main chunk");
+}
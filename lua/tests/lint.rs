@@ -0,0 +1,81 @@
+// lua lint：未定义的全局变量、未使用的局部变量、变量遮蔽与不可达代码
+
+use lua::lint::lint;
+
+fn check(source: &str) -> Vec<String> {
+    lint(source.as_bytes()).unwrap().iter().map(|w| w.to_string()).collect()
+}
+
+#[test]
+fn globals() {
+    let source = "\
+function helper() return undefined_name end
+print(helper(), string.rep('x', 2), counter)
+counter = 1
+";
+    assert_eq!(check(source), ["1: accessing undefined variable 'undefined_name'"]);
+}
+
+#[test]
+fn unused() {
+    let source = "\
+local a, _b = 1, 2
+local used = 3
+local function f(unused_param) end
+for i = 1, 3 do print(used) end
+local assigned
+assigned = 4
+";
+    assert_eq!(
+        check(source),
+        [
+            "1: unused variable 'a'",
+            "3: unused function 'f'",
+            "4: unused loop variable 'i'",
+            "5: unused variable 'assigned'",
+        ]
+    );
+}
+
+#[test]
+fn shadowing() {
+    let source = "\
+local x = 1
+local function g(x) return x end
+do local x = 2 print(x) end
+local x = 3
+print(x, g)
+";
+    assert_eq!(
+        check(source),
+        [
+            "1: unused variable 'x'",
+            "2: shadowing definition of 'x' on line 1",
+            "3: shadowing definition of 'x' on line 1",
+            "4: shadowing definition of 'x' on line 1",
+        ]
+    );
+}
+
+#[test]
+fn unreachable() {
+    let source = "\
+local function f(n)
+  for i = 1, n do
+    break
+    print(i)
+  end
+  do if n then return 1 else do return 2 end end
+  print('after if') end
+  goto skip
+  print('skipped')
+  ::skip::
+  print('reached')
+end
+f(1)
+";
+    assert_eq!(
+        check(source),
+        ["4: unreachable code", "7: unreachable code", "9: unreachable code"]
+    );
+}
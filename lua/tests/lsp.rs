@@ -0,0 +1,288 @@
+// 语言服务器：用脚本化的 JSON-RPC 消息驱动，检查诊断、跳转到定义、悬停、文档大纲与补全
+
+#![cfg(feature = "serde")]
+
+use lua::lsp::{read_message, write_message, Server};
+use lua::value::{Table, Value as LuaValue};
+use lua::vm::{ExeState, LuaError};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+const SOURCE: &str = "local util = require(\"util\")
+local n = 42
+local function add(a, b)
+  local s = a + b
+  return s
+end
+count = add(n, 1)
+greet(util.name .. \"é\")
+";
+
+fn greet(_: &mut ExeState) -> Result<i32, LuaError> {
+    Ok(0)
+}
+
+/// 宿主注册了 greet 函数与 host 表的服务器
+fn server() -> Server {
+    let mut state = ExeState::new();
+    state.set_global("greet", LuaValue::Function(greet));
+    let mut host = Table::new(0, 2);
+    host.set_str("version", LuaValue::Integer(3));
+    host.set_str("greet", LuaValue::Function(greet));
+    state.set_global("host", LuaValue::from(host));
+    Server::new(&state)
+}
+
+/// 放有 util.lua 模块的临时目录
+fn workspace(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lua-lsp-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("util.lua"), "return {name = 'util'}\n").unwrap();
+    dir
+}
+
+fn uri(dir: &Path) -> String {
+    format!("file://{}", dir.join("main.lua").display())
+}
+
+fn open(server: &mut Server, uri: &str, text: &str) -> Value {
+    let msg = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": {"textDocument": {"uri": uri, "languageId": "lua", "version": 1, "text": text}},
+    });
+    let mut out = server.handle(&msg);
+    assert_eq!(out.len(), 1);
+    out.remove(0)
+}
+
+fn request(server: &mut Server, method: &str, uri: &str, line: u32, character: u32) -> Value {
+    let msg = json!({
+        "jsonrpc": "2.0",
+        "id": 7,
+        "method": method,
+        "params": {"textDocument": {"uri": uri}, "position": {"line": line, "character": character}},
+    });
+    let mut out = server.handle(&msg);
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0]["id"], 7);
+    out.remove(0)["result"].take()
+}
+
+fn range(line: u32, start: u32, end: u32) -> Value {
+    json!({"start": {"line": line, "character": start}, "end": {"line": line, "character": end}})
+}
+
+fn hover_text(server: &mut Server, uri: &str, line: u32, character: u32) -> String {
+    let r = request(server, "textDocument/hover", uri, line, character);
+    r["contents"]["value"].as_str().unwrap().to_string()
+}
+
+fn labels(items: &Value) -> Vec<&str> {
+    items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["label"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn stdio_session() {
+    let dir = workspace("stdio");
+    let uri = uri(&dir);
+    let mut input = Vec::new();
+    let messages = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"capabilities": {}}}),
+        json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "textDocument/didOpen",
+               "params": {"textDocument": {"uri": uri, "languageId": "lua", "version": 1, "text": "local x = = 1"}}}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "workspace/unknown", "params": {}}),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"}),
+        json!({"jsonrpc": "2.0", "id": 4, "method": "textDocument/hover", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "exit"}),
+        // exit 之后的消息不再处理
+        json!({"jsonrpc": "2.0", "id": 5, "method": "shutdown"}),
+    ];
+    for msg in &messages {
+        write_message(&mut input, msg).unwrap();
+    }
+    let mut output = Vec::new();
+    server().run(Cursor::new(input), &mut output).unwrap();
+
+    let mut output = Cursor::new(output);
+    let mut responses = Vec::new();
+    while let Some(msg) = read_message(&mut output).unwrap() {
+        responses.push(msg);
+    }
+    assert_eq!(responses.len(), 5);
+    let caps = &responses[0]["result"]["capabilities"];
+    assert_eq!(caps["textDocumentSync"], 1);
+    assert_eq!(caps["completionProvider"]["triggerCharacters"], json!(["."]));
+
+    // 语法错误带有出错的 Token 的位置
+    assert_eq!(responses[1]["method"], "textDocument/publishDiagnostics");
+    let diagnostics = &responses[1]["params"]["diagnostics"];
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"], range(0, 10, 11));
+
+    assert_eq!(responses[2]["error"]["code"], -32601);
+    assert_eq!(responses[3], json!({"jsonrpc": "2.0", "id": 3, "result": null}));
+    assert_eq!(responses[4]["error"]["code"], -32600);
+}
+
+#[test]
+fn publish_diagnostics() {
+    let mut server = server();
+    let dir = workspace("diagnostics");
+    let uri = uri(&dir);
+    let msg = open(&mut server, &uri, SOURCE);
+    assert_eq!(msg["params"]["uri"], uri);
+    assert_eq!(msg["params"]["diagnostics"], json!([]));
+
+    // 静态检查的警告为整行
+    let msg = open(&mut server, &uri, "local unused = 1\n");
+    let diagnostics = &msg["params"]["diagnostics"];
+    assert_eq!(diagnostics[0]["severity"], 2);
+    assert_eq!(diagnostics[0]["message"], "unused variable 'unused'");
+    assert_eq!(diagnostics[0]["range"], range(0, 0, 16));
+
+    // 修改后重新发布；列号按 UTF-16 计算
+    let change = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": {"textDocument": {"uri": uri, "version": 2}, "contentChanges": [{"text": "print(\"é\" 1)"}]},
+    });
+    let out = server.handle(&change);
+    let diagnostics = &out[0]["params"]["diagnostics"];
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"], range(0, 10, 11));
+
+    let close = json!({"jsonrpc": "2.0", "method": "textDocument/didClose", "params": {"textDocument": {"uri": uri}}});
+    let out = server.handle(&close);
+    assert_eq!(out[0]["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn definition() {
+    let mut server = server();
+    let dir = workspace("definition");
+    let uri = uri(&dir);
+    open(&mut server, &uri, SOURCE);
+
+    // 局部变量与参数
+    let r = request(&mut server, "textDocument/definition", &uri, 6, 13);
+    assert_eq!(r, json!({"uri": uri, "range": range(1, 6, 7)}));
+    let r = request(&mut server, "textDocument/definition", &uri, 3, 12);
+    assert_eq!(r["range"], range(2, 19, 20));
+    let r = request(&mut server, "textDocument/definition", &uri, 6, 9);
+    assert_eq!(r["range"], range(2, 15, 18));
+    let r = request(&mut server, "textDocument/definition", &uri, 7, 7);
+    assert_eq!(r["range"], range(0, 6, 10));
+
+    // require 的模块文件
+    let r = request(&mut server, "textDocument/definition", &uri, 0, 23);
+    let module = format!("file://{}", dir.join("util.lua").display());
+    assert_eq!(r, json!({"uri": module, "range": range(0, 0, 0)}));
+    let r = request(&mut server, "textDocument/definition", &uri, 0, 13);
+    assert_eq!(r, Value::Null);
+
+    // 宿主注册的全局变量没有定义
+    let r = request(&mut server, "textDocument/definition", &uri, 7, 1);
+    assert_eq!(r, Value::Null);
+}
+
+#[test]
+fn hover() {
+    let mut server = server();
+    let dir = workspace("hover");
+    let uri = uri(&dir);
+    open(&mut server, &uri, SOURCE);
+
+    assert_eq!(hover_text(&mut server, &uri, 1, 6), "```lua\nlocal n: integer\n```");
+    assert_eq!(hover_text(&mut server, &uri, 6, 13), "```lua\nlocal n: integer\n```");
+    assert_eq!(
+        hover_text(&mut server, &uri, 2, 16),
+        "```lua\nlocal function add(a, b)\n```"
+    );
+    assert_eq!(hover_text(&mut server, &uri, 3, 12), "```lua\nparam a\n```");
+    assert_eq!(
+        hover_text(&mut server, &uri, 0, 8),
+        "```lua\nlocal util = require(\"util\")\n```"
+    );
+    assert_eq!(
+        hover_text(&mut server, &uri, 7, 2),
+        "```lua\nglobal greet: function\n```"
+    );
+    // 字面量
+    assert_eq!(hover_text(&mut server, &uri, 1, 11), "```lua\ninteger\n```");
+    assert_eq!(hover_text(&mut server, &uri, 7, 20), "```lua\nstring\n```");
+    let r = request(&mut server, "textDocument/hover", &uri, 5, 1);
+    assert_eq!(r, Value::Null);
+}
+
+#[test]
+fn document_symbols() {
+    let mut server = server();
+    let dir = workspace("symbols");
+    let uri = uri(&dir);
+    open(&mut server, &uri, SOURCE);
+
+    let msg = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "textDocument/documentSymbol",
+        "params": {"textDocument": {"uri": uri}},
+    });
+    let symbols = server.handle(&msg).remove(0)["result"].take();
+    let names: Vec<_> = symbols
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["util", "n", "add"]);
+    let add = &symbols[2];
+    assert_eq!(add["kind"], 12);
+    assert_eq!(add["selectionRange"], range(2, 15, 18));
+    assert_eq!(add["range"]["end"], json!({"line": 5, "character": 3}));
+    assert_eq!(add["children"][0]["name"], "s");
+    assert_eq!(add["children"][0]["kind"], 13);
+}
+
+#[test]
+fn completion() {
+    let mut server = server();
+    let dir = workspace("completion");
+    let uri = uri(&dir);
+    open(&mut server, &uri, SOURCE);
+
+    // 宿主注册的全局变量与本文件中的全局变量
+    let items = request(&mut server, "textDocument/completion", &uri, 7, 2);
+    assert_eq!(labels(&items), ["greet"]);
+    let items = request(&mut server, "textDocument/completion", &uri, 6, 2);
+    assert_eq!(labels(&items), ["count", "collectgarbage"]);
+    // 函数中可见的局部变量与参数
+    let items = request(&mut server, "textDocument/completion", &uri, 4, 9);
+    for name in ["s", "a", "b", "add", "n", "util", "count", "return", "print", "host"] {
+        assert!(labels(&items).contains(&name), "{name} in {items}");
+    }
+    // 函数外不可见
+    let items = request(&mut server, "textDocument/completion", &uri, 6, 0);
+    assert!(!labels(&items).contains(&"s") && !labels(&items).contains(&"a"));
+
+    // 输入到一半的语法错误不影响字段补全
+    let change = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": {"textDocument": {"uri": uri, "version": 2}, "contentChanges": [{"text": "local v = host."}]},
+    });
+    server.handle(&change);
+    let items = request(&mut server, "textDocument/completion", &uri, 0, 15);
+    assert_eq!(items[0], json!({"label": "greet", "kind": 3, "detail": "function"}));
+    assert_eq!(items[1], json!({"label": "version", "kind": 5, "detail": "number"}));
+    assert_eq!(items.as_array().unwrap().len(), 2);
+}
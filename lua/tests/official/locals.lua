@@ -0,0 +1,165 @@
+-- 参照 Lua 5.4 官方测试集 locals.lua 整理的子集：局部变量作用域、<const> 与 <close>
+print('testing local variables and environments')
+
+local debug = require"debug"
+
+-- 没有 string.find，用 string.unpack 逐个位置取子串查找
+local function contains (s, m)
+  for i = 1, #s - #m + 1 do
+    if string.unpack("c" .. #m, s, i) == m then return true end
+  end
+  return false
+end
+
+-- bug in 5.1:
+
+local function f(x) x = nil; return x end
+assert(f(10) == nil)
+
+local function f() local x; return x end
+assert(f(10) == nil)
+
+local function f(x) x = nil; local y; return x, y end
+assert(f(10) == nil and select(2, f(20)) == nil)
+
+do
+  local i = 10
+  do local i = 100; assert(i==100) end
+  do local i = 1000; assert(i==1000) end
+  assert(i == 10)
+  if i ~= 10 then
+    local i = 20
+  else
+    local i = 30
+    assert(i == 30)
+  end
+end
+
+f = nil
+
+local f
+local x = 1
+
+a = nil
+load('local a = {}')()
+assert(a == nil)
+
+function f (a)
+  local _1, _2, _3, _4, _5
+  local _6, _7, _8, _9, _10
+  local x = 3
+  local b = a
+  local c,d = a,b
+  if (d == b) then
+    local x = 'q'
+    x = b
+    assert(x == 2)
+  else
+    assert(nil)
+  end
+  assert(x == 3)
+  local f = 10
+end
+
+local b=10
+local a; repeat local b; a,b=1,2; assert(a+1==b); until a+b==3
+
+assert(x == 1)
+
+f(2)
+assert(type(f) == 'function')
+
+print"testing to-be-closed variables"
+do
+  local x <const> = 10
+  local y <const> = x + 1
+  assert(y == 11)
+  local st, msg = load("local x <const> = 1; x = 2")
+  assert(not st and contains(msg, "attempt to assign to const variable 'x'"))
+end
+
+local function func2close (f, x, y)
+  local obj = setmetatable({}, {__close = f})
+  if x then
+    return x, obj, y
+  else
+    return obj
+  end
+end
+
+do
+  local a = {}
+  do
+    local b <close> = false   -- not to be closed
+    local x <close> = setmetatable({"x"}, {__close = function (self)
+                                                   a[#a + 1] = self[1] end})
+    local w, y <close>, z = func2close(function (self, err)
+                                assert(err == nil); a[#a + 1] = "y"
+                              end, 10, 20)
+    local c <close> = nil  -- not to be closed
+    a[#a + 1] = "in"
+    assert(w == 10 and z == 20)
+  end
+  a[#a + 1] = "out"
+  assert(a[1] == "in" and a[2] == "y" and a[3] == "x" and a[4] == "out")
+end
+
+do
+  local st, msg = load("local x <foo> = 1")
+  assert(not st and contains(msg, "unknown attribute 'foo'"))
+end
+
+do   -- closing variables in a return and after errors
+  local a = {}
+  local function foo (x)
+    local y <close> = func2close(function () a[#a + 1] = "y" end)
+    local z <close> = func2close(function () a[#a + 1] = "z" end)
+    if x then error("@x") end
+    return 10, 20
+  end
+  local r, s = foo(false)
+  assert(r == 10 and s == 20 and a[1] == "z" and a[2] == "y")
+  local st, msg = pcall(foo, true)
+  assert(not st and contains(msg, "@x") and a[3] == "z" and a[4] == "y")
+end
+
+do   -- errors in closing methods
+  local function foo ()
+    local x <close> =
+      func2close(function (_, msg)
+        assert(contains(msg, "@y"))
+        error("@x")
+      end)
+    local y <close> =
+      func2close(function (_, msg)
+        assert(contains(msg, "@z"))
+        error("@y")
+      end)
+    local z <close> =
+      func2close(function (_, msg)
+        assert(msg == nil)
+        error("@z")
+      end)
+    return 200
+  end
+  local st, msg = pcall(foo)
+  assert(not st and contains(msg, "@x"))
+end
+
+do   -- closing a variable when leaving a loop
+  local n = 0
+  for i = 1, 10 do
+    local x <close> = func2close(function () n = n + 1 end)
+    if i == 5 then break end
+  end
+  assert(n == 5)
+end
+
+do   -- a value without '__close' cannot be closed
+  local st, msg = load("local x <close> = {}")
+  assert(st)
+  st, msg = pcall(st)
+  assert(not st and contains(msg, "variable 'x' got a non-closable value"))
+end
+
+print'OK'
@@ -0,0 +1,132 @@
+-- 参照 Lua 5.4 官方测试集 nextvar.lua 整理的子集：表、next、pairs 与长度
+print('testing tables, next, and for')
+
+local a = {}
+
+-- make sure table has lots of space in hash part
+for i=1,100 do a[i.."+"] = true end
+for i=1,100 do a[i.."+"] = undef end
+-- fill hash part with numeric indices testing size operator
+for i=1,100 do
+  a[i] = true
+  assert(#a == i)
+end
+
+do   -- rehash moving elements from array to hash
+  local a = {}
+  for i = 1, 100 do a[i] = i end
+  for i = 1, 100 do a[i] = undef end
+  a.x = 1
+  for i = 1, 100 do assert(a[i] == nil) end
+end
+
+-- testing ipairs
+local x = 0
+for k,v in ipairs{10,20,30;x=12} do
+  x = x + 1
+  assert(k == x and v == x * 10)
+end
+
+for _ in ipairs{x=12, y=24} do assert(nil) end
+
+-- test for 'false' x ipair
+x = false
+local i = 0
+for k,v in ipairs{true,false,true,false} do
+  i = i + 1
+  x = not x
+  assert(x == v)
+end
+assert(i == 4)
+
+-- iterator function is always the same
+assert(type(ipairs{}) == 'function' and ipairs{} == ipairs{})
+
+do   -- overflow (must wrap-around)
+  local f = ipairs{}
+  local minint = 1 << 63    -- 没有 math 库
+  local k, v = f({[minint] = 10}, minint - 1)
+  assert(k == minint and v == 10)
+  k, v = f({[minint] = 10}, k)
+  assert(k == nil)
+end
+
+-- testing next x GC of deleted keys
+-- 没有 coroutine 库，在一次遍历中完成删除与检查
+do
+  local function rep (s, n)
+    local r = ""
+    for i = 1, n do r = r .. s end
+    return r
+  end
+  local t = {}
+  t[{1}] = 1    -- add several unanchored, collectable keys
+  t[{2}] = 2
+  t[rep("a", 50)] = "a"    -- long string key -> number value
+  t[rep("b", 50)] = "b"
+  t[{3}] = 3
+  t[rep("c", 10)] = "c"    -- short string key -> number value
+  t[function () return 10 end] = 10
+  local count = 7
+  for k, v in pairs(t) do
+    local k1 = next(t)    -- all previous keys were deleted
+    assert(k == k1)       -- current key is the first in the table
+    t[k] = nil
+    local expected = (type(k) == "table" and k[1] or
+                      type(k) == "function" and k() or
+                      string.unpack("c1", k))
+    assert(expected == v)
+    collectgarbage("collect")   -- collect dead keys
+    count = count - 1
+  end
+  assert(count == 0 and next(t) == nil)    -- traversed the whole table
+end
+
+local function check (t, na, nh) end
+
+-- testing size operator
+assert(#{} == 0)
+assert(#{nil} == 0)
+assert(#{nil, nil} == 0)
+assert(#{1, 2, 3, nil, nil} == 3)
+
+-- testing next
+assert(next({}) == nil)
+assert(next({}, nil) == nil)
+
+local nofind = {}
+
+a,b,c = 1,2,3
+a,b,c = nil
+
+-- next uses always the same iteraction function
+assert(next{} == next{})
+
+local function find (name)
+  local n,v
+  while 1 do
+    n,v = next(_G, n)
+    if not n then return nofind end
+    assert(_G[n] ~= undef)
+    if n == name then return v end
+  end
+end
+
+local function find1 (name)
+  for n,v in pairs(_G) do
+    if n==name then return v end
+  end
+  return nil  -- not found
+end
+
+assert(print==find("print") and print == find1("print"))
+assert(_G["print"]==find("print"))
+assert(assert==find1("assert"))
+assert(nofind==find("return"))
+assert(not find1("return"))
+_G["ret" .. "urn"] = undef
+assert(nofind==find("return"))
+_G["xxx"] = 1
+assert(xxx==find("xxx"))
+
+print'OK'
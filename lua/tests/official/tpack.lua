@@ -0,0 +1,133 @@
+-- 参照 Lua 5.4 官方测试集 tpack.lua 整理的子集：string.pack/unpack/packsize
+local pack = string.pack
+local packsize = string.packsize
+local unpack = string.unpack
+
+print "testing pack/unpack"
+
+-- 字符串库只有 pack 系列函数，rep、reverse 与 find 用 Lua 代码实现
+local function rep (s, n)
+  local r = ""
+  for i = 1, n do r = r .. s end
+  return r
+end
+
+local function reverse (s)
+  local r = ""
+  for i = #s, 1, -1 do r = r .. unpack("c1", s, i) end
+  return r
+end
+
+local function contains (s, m)
+  for i = 1, #s - #m + 1 do
+    if unpack("c" .. #m, s, i) == m then return true end
+  end
+  return false
+end
+
+-- maximum size for integers
+local NB = 16
+
+local sizeshort = packsize("h")
+local sizeint = packsize("i")
+local sizelong = packsize("l")
+local sizesize_t = packsize("T")
+local sizeLI = packsize("j")
+local sizefloat = packsize("f")
+local sizedouble = packsize("d")
+local sizenumber = packsize("n")
+local little = (pack("i2", 1) == "\1\0")
+local align = packsize("!xXi16")
+
+assert(1 <= sizeshort and sizeshort <= sizeint and sizeint <= sizelong and
+       sizefloat <= sizedouble)
+
+print("platform:")
+print("\tshort " .. sizeshort .. ", int " .. sizeint .. ", long " .. sizelong ..
+      ", size_t " .. sizesize_t .. ", float " .. sizefloat .. ", double " .. sizedouble ..
+      ",\n\tlua Integer " .. sizeLI .. ", lua Number " .. sizenumber)
+print("\t" .. (little and "little" or "big") .. " endian")
+print("\talignment: " .. align)
+
+-- check errors in arguments
+local function checkerror (msg, f, ...)
+  local status, err = pcall(f, ...)
+  assert(not status and contains(err, msg))
+end
+
+-- minimum behavior for integer formats
+assert(unpack("B", pack("B", 0xff)) == 0xff)
+assert(unpack("b", pack("b", 0x7f)) == 0x7f)
+assert(unpack("b", pack("b", -0x80)) == -0x80)
+
+assert(unpack("H", pack("H", 0xffff)) == 0xffff)
+assert(unpack("h", pack("h", 0x7fff)) == 0x7fff)
+assert(unpack("h", pack("h", -0x8000)) == -0x8000)
+
+assert(unpack("L", pack("L", 0xffffffff)) == 0xffffffff)
+assert(unpack("l", pack("l", 0x7fffffff)) == 0x7fffffff)
+assert(unpack("l", pack("l", -0x80000000)) == -0x80000000)
+
+for i = 1, NB do
+  -- small numbers with signal extension ("\xFF...")
+  local s = rep("\xff", i)
+  assert(pack("i" .. i, -1) == s)
+  assert(packsize("i" .. i) == #s)
+  assert(unpack("i" .. i, s) == -1)
+
+  -- small unsigned number ("\0...\xAA")
+  s = "\xAA" .. rep("\0", i - 1)
+  assert(pack("<I" .. i, 0xAA) == s)
+  assert(unpack("<I" .. i, s) == 0xAA)
+  assert(pack(">I" .. i, 0xAA) == reverse(s))
+  assert(unpack(">I" .. i, reverse(s)) == 0xAA)
+end
+
+checkerror("out of limits", pack, "i0", 0)
+checkerror("out of limits", pack, "i" .. NB + 1, 0)
+checkerror("out of limits", pack, "!" .. NB + 1, 0)
+checkerror("(17) out of limits [1,16]", pack, "Xi" .. NB + 1)
+checkerror("invalid format option 'r'", pack, "i3r", 0)
+checkerror("16-byte integer", unpack, "i16", rep('\3', 16))
+checkerror("not power of 2", pack, "!4i3", 0);
+checkerror("missing size", pack, "c", "")
+checkerror("variable-length format", packsize, "s")
+checkerror("variable-length format", packsize, "z")
+
+-- overflow in option size (error will be in digit after limit)
+checkerror("invalid format", packsize, "c1" .. rep("0", 40))
+
+do
+  print "testing pack/unpack of floating-point numbers"
+
+  for _, n in ipairs{0, -1.1, 1.9, 1/0, -1/0, 1e20, -1e20, 0.1, 2000.7} do
+      assert(unpack("n", pack("n", n)) == n)
+      assert(unpack("<n", pack("<n", n)) == n)
+      assert(unpack(">n", pack(">n", n)) == n)
+      assert(pack("<f", n) == reverse(pack(">f", n)))
+      assert(pack(">d", n) == reverse(pack("<d", n)))
+  end
+end
+
+print "testing pack/unpack of strings"
+do
+  local s = rep("abc", 1000)
+  assert(pack("zB", s, 247) == s .. "\0\xF7")
+  local s1, b = unpack("zB", s .. "\0\xF9")
+  assert(b == 249 and s1 == s)
+  s1 = pack("s", s)
+  assert(unpack("s", s1) == s)
+
+  checkerror("does not fit", pack, "s1", s)
+
+  checkerror("contains zeros", pack, "z", "alo\0");
+
+  checkerror("unfinished string", unpack, "zc10000000", "alo")
+
+  for i = 2, NB do
+    local s1 = pack("s" .. i, s)
+    assert(unpack("s" .. i, s1) == s and #s1 == #s + i)
+  end
+end
+
+print "OK"
@@ -0,0 +1,199 @@
+// string.pack/unpack/packsize 的往返测试：各种格式、字节序、对齐与取值边界
+
+use lua::value::Value;
+use lua::vm::ExeState;
+
+fn string_fn(state: &ExeState, name: &str) -> Value {
+    match state.get_global("string") {
+        Value::Table(t) => t.borrow().get_str(name),
+        _ => panic!("no string library"),
+    }
+}
+
+/// 打包后解包应得到原来的值，长度与 packsize（定长格式时）一致，返回打包结果
+fn round_trip(state: &mut ExeState, fmt: &str, values: Vec<Value>) -> Vec<u8> {
+    let (pack, unpack, packsize) = (
+        string_fn(state, "pack"),
+        string_fn(state, "unpack"),
+        string_fn(state, "packsize"),
+    );
+    let fmtv = Value::from(fmt);
+    let packed = state
+        .call(pack, [vec![fmtv.clone()], values.clone()].concat())
+        .unwrap()
+        .remove(0);
+    let bytes = packed.as_bytes().unwrap().to_vec();
+    let mut r = state.call(unpack, vec![fmtv.clone(), packed]).unwrap();
+    assert_eq!(r.pop(), Some(Value::Integer(bytes.len() as i64 + 1)), "{fmt}");
+    assert_eq!(r, values, "{fmt}");
+    if !fmt.contains(['s', 'z']) {
+        let size = state.call(packsize, vec![fmtv]).unwrap();
+        assert_eq!(size, vec![Value::Integer(bytes.len() as i64)], "{fmt}");
+    }
+    bytes
+}
+
+const ENDIANS: [&str; 3] = ["<", ">", "="];
+
+#[test]
+fn integers() {
+    let mut state = ExeState::new();
+    for endian in ENDIANS {
+        for size in 1..=16usize {
+            // 各大小能表示的最小、最大值及附近的值
+            let bits = (size * 8).min(64) as u32;
+            let (smin, smax) = if bits == 64 {
+                (i64::MIN, i64::MAX)
+            } else {
+                (-(1i64 << (bits - 1)), (1i64 << (bits - 1)) - 1)
+            };
+            let umax = if bits == 64 { -1 } else { (1i64 << bits) - 1 };
+            let signed = [smin, smin + 1, -1, 0, 1, smax - 1, smax];
+            let unsigned = [0, 1, umax / 2, umax - 1, umax];
+            for n in signed {
+                let bytes = round_trip(&mut state, &format!("{endian}i{size}"), vec![Value::Integer(n)]);
+                // 超过 8 字节的部分是符号扩展
+                let ext = if n < 0 { 0xFF } else { 0 };
+                let high: Vec<u8> = match endian {
+                    ">" => bytes[..size.saturating_sub(8)].to_vec(),
+                    _ => bytes[size.min(8)..].to_vec(),
+                };
+                assert!(high.iter().all(|&b| b == ext), "i{size} {n}");
+            }
+            for n in unsigned {
+                round_trip(&mut state, &format!("{endian}I{size}"), vec![Value::Integer(n)]);
+            }
+        }
+        // 固定大小的选项
+        let fixed = [
+            ("b", -128),
+            ("B", 255),
+            ("h", -32768),
+            ("H", 65535),
+            ("l", i64::MIN),
+            ("L", -1),
+            ("j", i64::MAX),
+            ("J", -1),
+            ("T", 1 << 40),
+        ];
+        for (opt, n) in fixed {
+            round_trip(&mut state, &format!("{endian}{opt}"), vec![Value::Integer(n)]);
+        }
+    }
+
+    // 字节序
+    assert_eq!(
+        round_trip(&mut state, "<i4", vec![Value::Integer(0x01020304)]),
+        [4, 3, 2, 1]
+    );
+    assert_eq!(
+        round_trip(&mut state, ">i4", vec![Value::Integer(0x01020304)]),
+        [1, 2, 3, 4]
+    );
+    assert_eq!(
+        round_trip(&mut state, "<i3>i3", vec![Value::Integer(-2), Value::Integer(-2)]),
+        [0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE]
+    );
+}
+
+#[test]
+fn floats() {
+    let mut state = ExeState::new();
+    let doubles = [
+        0.0,
+        -0.0,
+        1.5,
+        -1.1,
+        1e300,
+        -1e-300,
+        f64::MIN_POSITIVE,
+        f64::MAX,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+    ];
+    let singles = [0.0, 0.25, -1.5, 3.0e38, 1.0e-40, f64::INFINITY];
+    for endian in ENDIANS {
+        for x in doubles {
+            round_trip(&mut state, &format!("{endian}d"), vec![Value::Float(x)]);
+            round_trip(&mut state, &format!("{endian}n"), vec![Value::Float(x)]);
+        }
+        for x in singles {
+            // 能用单精度精确表示的值
+            let x = x as f32 as f64;
+            round_trip(&mut state, &format!("{endian}f"), vec![Value::Float(x)]);
+        }
+    }
+    assert_eq!(
+        round_trip(&mut state, ">d", vec![Value::Float(1.0)]),
+        [0x3F, 0xF0, 0, 0, 0, 0, 0, 0]
+    );
+    assert_eq!(round_trip(&mut state, "<f", vec![Value::Float(-2.0)]), [0, 0, 0, 0xC0]);
+}
+
+#[test]
+fn strings() {
+    let mut state = ExeState::new();
+    let samples: Vec<Vec<u8>> = vec![
+        b"".to_vec(),
+        b"a".to_vec(),
+        b"hello".to_vec(),
+        vec![0xFF, 0xFE, 0x80],
+        vec![b'x'; 300],
+    ];
+    for endian in ENDIANS {
+        for s in &samples {
+            for size in 1..=16usize {
+                if size == 1 && s.len() > 255 {
+                    continue;
+                }
+                let bytes = round_trip(&mut state, &format!("{endian}s{size}"), vec![Value::from(s.as_slice())]);
+                assert_eq!(bytes.len(), size + s.len());
+            }
+            round_trip(&mut state, &format!("{endian}s"), vec![Value::from(s.as_slice())]);
+            round_trip(&mut state, "z", vec![Value::from(s.as_slice())]);
+            round_trip(&mut state, &format!("c{}", s.len()), vec![Value::from(s.as_slice())]);
+        }
+    }
+    // 字符串中可以有 '\0'，c 选项按给定长度补零
+    round_trip(&mut state, "s2", vec![Value::from(&b"a\0b"[..])]);
+    let bytes = round_trip(&mut state, "c4", vec![Value::from(&b"ab\0\0"[..])]);
+    assert_eq!(bytes, b"ab\0\0");
+}
+
+#[test]
+fn alignment() {
+    let mut state = ExeState::new();
+    let values = || {
+        vec![
+            Value::Integer(1),
+            Value::Integer(-2),
+            Value::Integer(3),
+            Value::Float(0.5),
+            Value::Integer(5),
+        ]
+    };
+    for align in ["", "!", "!1", "!2", "!4", "!8", "!16"] {
+        for endian in ENDIANS {
+            let fmt = format!("{align}{endian}bi4hdj");
+            round_trip(&mut state, &fmt, values());
+            round_trip(&mut state, &format!("{fmt}Xi8xXh"), values());
+            round_trip(
+                &mut state,
+                &format!("{align}{endian}Bs2zi16"),
+                vec![
+                    Value::Integer(7),
+                    Value::from("ab"),
+                    Value::from("cd"),
+                    Value::Integer(-9),
+                ],
+            );
+        }
+    }
+    // 填充位置
+    let bytes = round_trip(&mut state, "!<bi4", vec![Value::Integer(1), Value::Integer(2)]);
+    assert_eq!(bytes, [1, 0, 0, 0, 2, 0, 0, 0]);
+    let bytes = round_trip(&mut state, "!4<bi8", vec![Value::Integer(1), Value::Integer(2)]);
+    assert_eq!(bytes.len(), 12);
+    let bytes = round_trip(&mut state, "!<bXi4b", vec![Value::Integer(1), Value::Integer(2)]);
+    assert_eq!(bytes, [1, 0, 0, 0, 2]);
+}
@@ -0,0 +1,126 @@
+// 性能分析：按调用栈与行统计指令数与时间，输出折叠栈与操作码直方图
+
+use lua::profile::{ProfileMode, Weight};
+use lua::value::Value;
+use lua::vm::{ExeState, LuaError};
+use std::io::Cursor;
+use std::time::Duration;
+
+const SOURCE: &str = "local function inner(n)
+  local s = 0
+  for i = 1, n do s = s + i end
+  return s
+end
+local function outer()
+  return inner(100) + inner(10)
+end
+local function fail() error('x') end
+local ok = pcall(fail)
+sleep()
+return outer()
+";
+
+fn sleep(_: &mut ExeState) -> Result<i32, LuaError> {
+    std::thread::sleep(Duration::from_millis(30));
+    Ok(0)
+}
+
+fn profiled(mode: ProfileMode) -> lua::profile::Profile {
+    let mut state = ExeState::new();
+    state.set_global("sleep", Value::Function(sleep));
+    let main = state.load(Cursor::new(SOURCE), "chunk").unwrap();
+    state.start_profile(mode);
+    let r = state.call(main, Vec::new()).unwrap();
+    assert_eq!(r, vec![Value::Integer(5050 + 55)]);
+    state.stop_profile().unwrap()
+}
+
+fn folded(profile: &lua::profile::Profile, weight: Weight) -> Vec<(String, u64)> {
+    let mut out = Vec::new();
+    profile.write_folded(&mut out, weight).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    out.lines()
+        .map(|l| {
+            let (stack, n) = l.rsplit_once(' ').unwrap();
+            (stack.to_string(), n.parse().unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn stacks_and_lines() {
+    let profile = profiled(ProfileMode::Full);
+    let stacks = folded(&profile, Weight::Instructions);
+    let weight = |stack: &str| stacks.iter().find(|(s, _)| s == stack).map(|&(_, n)| n);
+
+    // 循环体所在行在 outer 调用 inner 的路径上，执行了两次调用共 110 次迭代
+    let loop_line = weight("main chunk <chunk>;function <chunk:6>;function <chunk:1>;chunk:3").unwrap();
+    assert!(loop_line >= 110 * 2, "{stacks:?}");
+    assert!(weight("main chunk <chunk>;function <chunk:6>;chunk:7").is_some());
+    // pcall 中出错返回后，之后的指令仍算在主函数上
+    assert!(weight("main chunk <chunk>;function <chunk:9>;chunk:9").is_some());
+    assert!(weight("main chunk <chunk>;chunk:12").is_some());
+    assert!(
+        stacks.iter().all(|(s, _)| s.starts_with("main chunk <chunk>;")),
+        "{stacks:?}"
+    );
+
+    // 各函数与各行的指令数之和等于执行的指令总数
+    let total: u64 = profile.opcodes().iter().map(|(_, n)| n).sum();
+    assert_eq!(stacks.iter().map(|(_, n)| n).sum::<u64>(), total);
+    assert_eq!(
+        profile.functions().iter().map(|(_, s)| s.instructions).sum::<u64>(),
+        total
+    );
+    assert_eq!(profile.lines().iter().map(|(_, s)| s.instructions).sum::<u64>(), total);
+    let functions = profile.functions();
+    let inner = functions.iter().find(|(f, _)| f == "function <chunk:1>").unwrap();
+    assert_eq!(
+        inner.1.instructions,
+        weight("main chunk <chunk>;function <chunk:6>;function <chunk:1>;chunk:2").unwrap()
+            + loop_line
+            + weight("main chunk <chunk>;function <chunk:6>;function <chunk:1>;chunk:4").unwrap()
+    );
+}
+
+#[test]
+fn wall_time() {
+    let profile = profiled(ProfileMode::Full);
+    // 内置函数的耗时算在调用它的那一行
+    let lines = profile.lines();
+    assert_eq!(lines[0].0, "chunk:11");
+    assert!(lines[0].1.time >= Duration::from_millis(30));
+    let stacks = folded(&profile, Weight::Time);
+    let (stack, us) = stacks.iter().max_by_key(|(_, n)| n).unwrap();
+    assert_eq!(stack, "main chunk <chunk>;chunk:11");
+    assert!(*us >= 30_000);
+}
+
+#[test]
+fn opcode_histogram() {
+    let full = profiled(ProfileMode::Full);
+    let opcodes = profiled(ProfileMode::Opcodes);
+    // 两种模式的操作码统计相同；只统计操作码时没有调用栈
+    assert_eq!(full.opcodes(), opcodes.opcodes());
+    assert!(opcodes.functions().is_empty());
+    let count = |name: &str| opcodes.opcodes().iter().find(|(n, _)| n == name).map(|&(_, c)| c);
+    assert_eq!(count("ForLoop"), Some(110));
+    assert_eq!(count("ForPrep"), Some(2));
+    assert!(opcodes.opcodes().windows(2).all(|w| w[0].1 >= w[1].1));
+}
+
+#[test]
+fn not_running() {
+    let mut state = ExeState::new();
+    assert!(state.stop_profile().is_none());
+    // 两次执行之间的时间不计入
+    state.start_profile(ProfileMode::Full);
+    let main = state.load(Cursor::new("return 1"), "chunk").unwrap();
+    state.call(main.clone(), Vec::new()).unwrap();
+    std::thread::sleep(Duration::from_millis(30));
+    state.call(main, Vec::new()).unwrap();
+    let profile = state.stop_profile().unwrap();
+    let (_, stat) = &profile.lines()[0];
+    assert!(stat.time < Duration::from_millis(30));
+    assert_eq!(stat.instructions, profile.opcodes().iter().map(|(_, n)| n).sum::<u64>());
+}
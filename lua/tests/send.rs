@@ -0,0 +1,95 @@
+// 多个解释器状态相互独立；启用 send 特性时可以在线程之间移动
+
+use lua::value::Value;
+use lua::vm::ExeState;
+use std::io::Cursor;
+
+fn run(state: &mut ExeState, source: &str) -> Vec<Value> {
+    let main = state.load(Cursor::new(source), "chunk").unwrap();
+    state.call(main, Vec::new()).unwrap()
+}
+
+#[test]
+fn independent_states() {
+    // 全局变量与标准库表（包括文件句柄的元表）各自独立
+    let mut a = ExeState::new();
+    let mut b = ExeState::new();
+    run(&mut a, "x = 1; os = nil; getmetatable(io.stdout).__index.write = nil");
+    let r = run(&mut b, "return x, type(os), type(io.stdout.write)");
+    assert_eq!(r, vec![Value::Nil, Value::from("table"), Value::from("function")]);
+    assert_eq!(run(&mut a, "return x, type(io.stdout.write)"), vec![Value::Integer(1), Value::from("nil")]);
+}
+
+#[cfg(feature = "send")]
+#[test]
+fn states_on_threads() {
+    fn assert_send<T: Send>() {}
+    assert_send::<ExeState>();
+    assert_send::<Value>();
+
+    // 每个线程创建自己的状态并运行
+    let workers: Vec<_> = (0..8)
+        .map(|i| {
+            std::thread::spawn(move || {
+                let mut state = ExeState::new();
+                let source = format!("local t = {{}} for k = 1, 1000 do t[k] = k * {i} end return #t, t[1000]");
+                run(&mut state, &source)
+            })
+        })
+        .collect();
+    for (i, w) in workers.into_iter().enumerate() {
+        assert_eq!(w.join().unwrap(), vec![Value::Integer(1000), Value::Integer(1000 * i as i64)]);
+    }
+
+    // 状态与其中的值在一个线程创建，移到另一个线程继续使用
+    let mut state = ExeState::new();
+    let counter = run(&mut state, "local n = 0 return function() n = n + 1 return n end").remove(0);
+    let handle = std::thread::spawn(move || {
+        state.call(counter.clone(), Vec::new()).unwrap();
+        let r = state.call(counter, Vec::new()).unwrap();
+        (state, r)
+    });
+    let (mut state, r) = handle.join().unwrap();
+    assert_eq!(r, vec![Value::Integer(2)]);
+    assert_eq!(run(&mut state, "return 'back'"), vec![Value::from("back")]);
+}
+
+#[cfg(feature = "send")]
+#[test]
+fn refcell_borrow_flag_across_threads() {
+    use lua::sync::{Rc, RefCell};
+    use std::thread;
+
+    // 多个线程同时争用同一个 RefCell：可变借用期间不能有其他借用，
+    // 否则非原子的“读-让出-写”会丢失更新，共享借用也会看到值在两次读取之间改变
+    let cell = Rc::new(RefCell::new(0u64));
+    let workers: Vec<_> = (0..8)
+        .map(|i| {
+            let cell = cell.clone();
+            thread::spawn(move || {
+                let mut writes = 0;
+                for n in 0..20000 {
+                    if (n + i) % 3 == 0 {
+                        if let Ok(mut v) = cell.try_borrow_mut() {
+                            let old = *v;
+                            thread::yield_now();
+                            *v = old + 1;
+                            writes += 1;
+                        }
+                    } else if let Ok(v) = cell.try_borrow() {
+                        let first = *v;
+                        thread::yield_now();
+                        assert_eq!(*v, first, "value changed during a shared borrow");
+                        assert!(cell.try_borrow_mut().is_err());
+                    }
+                }
+                writes
+            })
+        })
+        .collect();
+    let writes: u64 = workers.into_iter().map(|w| w.join().unwrap()).sum();
+    assert!(writes > 0);
+    assert_eq!(*cell.borrow(), writes);
+    // 所有借用都已释放
+    assert!(cell.try_borrow_mut().is_ok());
+}
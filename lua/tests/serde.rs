@@ -0,0 +1,161 @@
+// Value 与 serde 互转：Lua 写的配置序列化为 JSON/TOML，JSON/TOML 反序列化为 Lua 表
+#![cfg(feature = "serde")]
+
+use lua::value::{Table, Value};
+use lua::value_serde::{NanMode, SerializeOptions, SparseArray};
+use lua::vm::ExeState;
+use std::io::Cursor;
+use std::thread;
+
+/// 执行 Lua 代码，返回其第一个返回值
+fn eval(source: &str) -> Value {
+    let mut state = ExeState::new();
+    let main = state.load(Cursor::new(source), "config").unwrap();
+    state.call(main, Vec::new()).unwrap().remove(0)
+}
+
+fn to_json(v: &Value, options: &SerializeOptions) -> String {
+    let mut out = Vec::new();
+    v.serialize_with(options, &mut serde_json::Serializer::new(&mut out)).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn lua_config_to_json() {
+    let config = eval(
+        r#"
+        local port = 8000
+        return {
+            name = "service",
+            listen = {"0.0.0.0", port + 80},
+            limits = {cpu = 0.5, memory = 512},
+            debug = false,
+        }
+        "#,
+    );
+    let json: serde_json::Value = serde_json::to_value(&config).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "name": "service",
+            "listen": ["0.0.0.0", 8080],
+            "limits": {"cpu": 0.5, "memory": 512},
+            "debug": false,
+        })
+    );
+}
+
+#[test]
+fn json_to_lua() {
+    let v: Value = serde_json::from_str(r#"{"a": [1, 2.5, "x"], "b": {"c": null}, "d": true}"#).unwrap();
+    let Value::Table(t) = v else { panic!("expected a table") };
+    let t = t.borrow();
+    let Value::Table(a) = t.get_str("a") else { panic!("expected an array") };
+    let a = a.borrow();
+    assert_eq!(a.border(), 3);
+    assert_eq!(a.get(&Value::Integer(1)), Value::Integer(1));
+    assert_eq!(a.get(&Value::Integer(2)), Value::Float(2.5));
+    assert_eq!(a.get(&Value::Integer(3)), Value::from("x"));
+    assert_eq!(t.get_str("d"), Value::Boolean(true));
+    // null 成为 nil，即表中没有这个键
+    let Value::Table(b) = t.get_str("b") else { panic!("expected a table") };
+    assert_eq!(b.borrow().next(&Value::Nil), Ok(None));
+}
+
+#[test]
+fn options() {
+    let sparse = eval("return {[1] = 'a', [3] = 'c'}");
+    let default = SerializeOptions::default();
+    assert_eq!(to_json(&sparse, &default), r#"{"1":"a","3":"c"}"#);
+    let fill = SerializeOptions {
+        sparse_array: SparseArray::Fill,
+        ..Default::default()
+    };
+    assert_eq!(to_json(&sparse, &fill), r#"["a",null,"c"]"#);
+    let error = SerializeOptions {
+        sparse_array: SparseArray::Error,
+        ..Default::default()
+    };
+    assert!(serde_json::to_string(&sparse).is_ok());
+    let mut out = Vec::new();
+    assert!(sparse.serialize_with(&error, &mut serde_json::Serializer::new(&mut out)).is_err());
+
+    let empty = Value::from(Table::new(0, 0));
+    assert_eq!(to_json(&empty, &default), "{}");
+    let as_array = SerializeOptions {
+        empty_as_array: true,
+        ..Default::default()
+    };
+    assert_eq!(to_json(&empty, &as_array), "[]");
+
+    let sorted = SerializeOptions {
+        sort_keys: true,
+        ..Default::default()
+    };
+    let t = eval("return {z = 1, a = 2, m = 3, [5] = 4}");
+    assert_eq!(to_json(&t, &sorted), r#"{"5":4,"a":2,"m":3,"z":1}"#);
+
+    let nan = Value::Float(f64::NAN);
+    assert_eq!(to_json(&nan, &default), "null");
+    let null = SerializeOptions {
+        nan: NanMode::Null,
+        ..Default::default()
+    };
+    assert_eq!(to_json(&nan, &null), "null");
+    let error = SerializeOptions {
+        nan: NanMode::Error,
+        ..Default::default()
+    };
+    assert!(nan.serialize_with(&error, &mut serde_json::Serializer::new(&mut Vec::new())).is_err());
+}
+
+#[test]
+fn unserializable() {
+    let err = serde_json::to_string(&eval("return {f = print}")).unwrap_err();
+    assert_eq!(err.to_string(), "cannot serialize a function");
+    let err = serde_json::to_string(&eval("local t = {} t[1] = t return t")).unwrap_err();
+    assert_eq!(err.to_string(), "cannot serialize a table with cycles");
+
+}
+
+#[test]
+fn excessive_nesting() {
+    // 与 cjson 一样限制嵌套层数，嵌套很深的表报错而不是耗尽栈
+    // debug 构建中达到上限需要的栈比测试线程默认的 2MB 多，改用与主线程相同的 8MB
+    let check = || {
+        let deep = eval("local t = {} for i = 1, 200000 do t = {t} end return t");
+        let err = serde_json::to_string(&deep).unwrap_err();
+        assert_eq!(err.to_string(), "cannot serialize, excessive nesting (1001)");
+        let nested = eval("local t = {} for i = 1, 999 do t = {t} end return t");
+        assert_eq!(serde_json::to_string(&nested).unwrap().len(), 2000);
+    };
+    thread::Builder::new().stack_size(8 << 20).spawn(check).unwrap().join().unwrap();
+}
+
+#[test]
+fn toml() {
+    let config = eval(
+        r#"
+        return {
+            title = "example",
+            owner = {name = "someone", age = 42},
+            ports = {8001, 8002},
+        }
+        "#,
+    );
+    let text = toml::to_string(&config).unwrap();
+    let back: Value = toml::from_str(&text).unwrap();
+    assert_eq!(
+        serde_json::to_value(&back).unwrap(),
+        serde_json::json!({
+            "title": "example",
+            "owner": {"name": "someone", "age": 42},
+            "ports": [8001, 8002],
+        })
+    );
+
+    let v: Value = toml::from_str("[server]\nhost = \"localhost\"\nport = 80\n").unwrap();
+    let Value::Table(t) = v else { panic!("expected a table") };
+    let Value::Table(server) = t.borrow().get_str("server") else { panic!("expected a table") };
+    assert_eq!(server.borrow().get_str("port"), Value::Integer(80));
+}
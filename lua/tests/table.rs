@@ -0,0 +1,168 @@
+// 表：数组部分的大小计算与整数键的迁移、边界、遍历中删除键、浮点数键的规范化，
+// 以及与 HashMap 模型的随机对照、嵌套很深的表的释放
+
+use lua::value::{Table, Value};
+use lua::vm::ExeState;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+fn int(i: i64) -> Value {
+    Value::Integer(i)
+}
+
+/// 遍历整张表，返回所有键值对
+fn entries(t: &Table) -> Vec<(Value, Value)> {
+    let mut all = Vec::new();
+    let mut key = Value::Nil;
+    while let Some((k, v)) = t.next(&key).unwrap() {
+        all.push((k.clone(), v));
+        key = k;
+    }
+    all
+}
+
+#[test]
+fn size_hints() {
+    assert_eq!(Table::new(0, 0).sizes(), (0, 0));
+    assert_eq!(Table::new(4, 3).sizes(), (4, 4));
+    // 预分配的数组部分中的 nil 不影响边界
+    let mut t = Table::new(8, 0);
+    t.set(int(1), int(10));
+    t.set(int(2), int(20));
+    assert_eq!(t.border(), 2);
+    assert_eq!(t.sizes(), (8, 0));
+}
+
+#[test]
+fn array_migration() {
+    // 倒序插入的整数键起初在哈希部分，rehash 时移入数组部分
+    let mut t = Table::new(0, 0);
+    for i in (1..=100).rev() {
+        t.set(int(i), int(i * 2));
+    }
+    assert_eq!(t.border(), 100);
+    assert_eq!(t.sizes().0, 128);
+    for i in 1..=100 {
+        assert_eq!(t.get(&int(i)), int(i * 2));
+    }
+    // 稀疏的整数键不进入数组部分：只有 1、2、4 使数组部分超过一半被使用
+    let mut t = Table::new(0, 0);
+    for i in 0..20 {
+        t.set(int(1 << i), Value::Boolean(true));
+    }
+    assert_eq!(t.sizes().0, 4);
+    assert_eq!(t.border(), 4);
+    // 数组部分之后接着哈希部分中的连续整数键
+    let mut t = Table::new(2, 0);
+    for i in 1..=10 {
+        t.set(int(i), int(i));
+    }
+    t.set(Value::from("x"), int(0));
+    assert_eq!(t.border(), 10);
+}
+
+#[test]
+fn float_keys() {
+    let mut t = Table::new(0, 0);
+    t.set(Value::Float(3.0), Value::from("three"));
+    t.set(Value::Float(0.5), Value::from("half"));
+    t.set(Value::Float(-0.0), Value::from("zero"));
+    assert_eq!(t.get(&int(3)), Value::from("three"));
+    assert_eq!(t.get(&int(0)), Value::from("zero"));
+    assert_eq!(t.get(&Value::Float(0.5)), Value::from("half"));
+    // NaN 不能作为键，读取得到 nil
+    t.set(Value::Float(f64::NAN), int(1));
+    assert_eq!(t.get(&Value::Float(f64::NAN)), Value::Nil);
+    // 遍历得到的是整数键，以浮点数键继续遍历也可以
+    let keys: Vec<Value> = entries(&t).into_iter().map(|(k, _)| k).collect();
+    assert!(keys.contains(&int(3)) && keys.contains(&int(0)));
+    assert!(t.next(&Value::Float(3.0)).is_ok());
+    assert_eq!(t.next(&Value::from("missing")), Err("invalid key to 'next'"));
+}
+
+#[test]
+fn delete_while_traversing() {
+    let mut t = Table::new(0, 0);
+    for i in 1..=30 {
+        t.set(int(i), int(i));
+        t.set(Value::from(format!("k{i}")), int(i));
+    }
+    t.set(int(1000), int(0));
+    // 遍历中把当前键赋值为 nil 不影响后续的 next
+    let mut key = Value::Nil;
+    let mut seen = 0;
+    while let Some((k, _)) = t.next(&key).unwrap() {
+        t.set(k.clone(), Value::Nil);
+        key = k;
+        seen += 1;
+    }
+    assert_eq!(seen, 61);
+    assert_eq!(t.next(&Value::Nil), Ok(None));
+    assert_eq!(t.border(), 0);
+}
+
+#[test]
+fn random_against_hashmap() {
+    // 线性同余生成器，结果可重现
+    let mut seed: u64 = 12345;
+    let mut rand = move |n: u64| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) % n
+    };
+    let mut t = Table::new(0, 0);
+    // 模型以键的内部表示为键（Value 含有内部可变的成员，不宜直接作为 HashMap 的键）
+    let mut model: HashMap<String, Value> = HashMap::new();
+    for step in 0..20000 {
+        let key = match rand(4) {
+            0 => int(rand(300) as i64 - 20),
+            1 => Value::from(format!("s{}", rand(200))),
+            2 => Value::Float(rand(100) as f64 + 0.25),
+            _ => int(rand(64) as i64 + 1),
+        };
+        if rand(3) == 0 {
+            t.set(key.clone(), Value::Nil);
+            model.remove(&format!("{key:?}"));
+        } else {
+            t.set(key.clone(), int(step));
+            model.insert(format!("{key:?}"), int(step));
+        }
+        assert_eq!(
+            t.get(&key),
+            model.get(&format!("{key:?}")).cloned().unwrap_or(Value::Nil)
+        );
+        if step % 1000 == 0 {
+            let all = entries(&t);
+            assert_eq!(all.len(), model.len());
+            for (k, v) in all {
+                assert_eq!(model.get(&format!("{k:?}")), Some(&v));
+            }
+            let n = t.border();
+            assert!(n == 0 || t.get(&int(n as i64)) != Value::Nil);
+            assert_eq!(t.get(&int(n as i64 + 1)), Value::Nil);
+        }
+    }
+}
+
+#[test]
+fn constructors() {
+    // 超过 255 批的构造器用 ExtraArg 携带批次
+    let items = vec!["7"; 13000].join(", ");
+    let source = format!("local t = {{{items}, 8}}\nreturn #t, t[12750], t[12751], t[13001]");
+    let mut state = ExeState::new();
+    let main = state.load(Cursor::new(source), "constructors").unwrap();
+    let results = state.call(main, Vec::new()).unwrap();
+    assert_eq!(results, [int(13001), int(7), int(7), int(8)]);
+}
+
+#[test]
+fn drop_deeply_nested() {
+    // 嵌套很深的表（值、键与元表）释放时不逐层递归
+    let mut state = ExeState::new();
+    let source = "local t, k, m = {}, {}, {}
+                  for i = 1, 200000 do t = {t} k = {[k] = true} m = setmetatable({}, m) end
+                  return t, k, m";
+    let main = state.load(Cursor::new(source), "nested").unwrap();
+    let results = state.call(main, Vec::new()).unwrap();
+    assert_eq!(results.len(), 3);
+    drop(results);
+}
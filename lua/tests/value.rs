@@ -0,0 +1,48 @@
+// 值的显示：Display 与 tostring 一致，Debug 显示内部表示
+
+use lua::value::{Table, Value};
+
+#[test]
+fn display() {
+    assert_eq!(Value::Nil.to_string(), "nil");
+    assert_eq!(Value::Boolean(false).to_string(), "false");
+    assert_eq!(Value::Integer(-7).to_string(), "-7");
+    assert_eq!(Value::Float(1.0).to_string(), "1.0");
+    assert_eq!(Value::Float(1e100).to_string(), "1e+100");
+    assert_eq!(Value::Float(f64::INFINITY).to_string(), "inf");
+    assert_eq!(Value::Float(0.1).to_string(), "0.1");
+    // 短、中、长字符串都输出原始内容
+    for s in ["hi", "a string of medium length, 30+", &"long ".repeat(20)] {
+        assert_eq!(Value::from(s).to_string(), s);
+    }
+    // 不是合法 UTF-8 的字节显示为替换字符
+    let bytes = Value::from(&b"a\xffb"[..]);
+    assert_eq!(bytes.to_string(), "a\u{FFFD}b");
+    assert_eq!(format!("{bytes:?}"), "ShortStr(b\"a\\xffb\")");
+    let t = Value::from(Table::new(0, 0));
+    assert!(t.to_string().starts_with("table: 0x"));
+}
+
+#[test]
+fn debug() {
+    assert_eq!(format!("{:?}", Value::Nil), "Nil");
+    assert_eq!(format!("{:?}", Value::Float(2.0)), "Float(2.0)");
+    assert_eq!(format!("{:?}", Value::from("hi")), "ShortStr(\"hi\")");
+    assert_eq!(format!("{:?}", Value::from("x".repeat(20))), format!("MidStr(\"{}\")", "x".repeat(20)));
+    assert_eq!(format!("{:?}", Value::from("y".repeat(60))), format!("LongStr(\"{}\")", "y".repeat(60)));
+    assert!(format!("{:?}", Value::from(Table::new(0, 0))).starts_with("Table(0x"));
+}
+
+#[test]
+fn bytes() {
+    // 任意字节都能原样存取，各长度的字符串按内容比较
+    for len in [0, 3, 14, 15, 40, 48, 49, 200] {
+        let data: Vec<u8> = (0..len).map(|i| (i * 37 % 256) as u8).collect();
+        let v = Value::from(data.clone());
+        assert_eq!(v.as_bytes(), Some(&data[..]));
+        assert_eq!(v, Value::from(&data[..]));
+        assert_eq!(v.type_name(), "string");
+    }
+    assert_eq!(Value::from(&b"\xff"[..]).as_str(), None);
+    assert_eq!(Value::from("h\u{e9}"), Value::from(vec![b'h', 0xc3, 0xa9]));
+}